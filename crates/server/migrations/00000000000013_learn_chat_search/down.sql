@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS idx_learn_chats_title_trgm;
+DROP INDEX IF EXISTS idx_learn_chats_title_fts;
+DROP INDEX IF EXISTS idx_learn_chat_turns_content_zh_trgm;
+DROP INDEX IF EXISTS idx_learn_chat_turns_content_zh_fts;
+DROP INDEX IF EXISTS idx_learn_chat_turns_content_en_fts;
@@ -0,0 +1,21 @@
+-- ============================================================================
+-- FULL-TEXT SEARCH OVER CHAT HISTORY
+-- ============================================================================
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- Expression indexes; queries must use the exact same expressions
+-- (see routing/learn/chat_search.rs) for the planner to pick them up.
+CREATE INDEX IF NOT EXISTS idx_learn_chat_turns_content_en_fts
+    ON learn_chat_turns USING GIN (to_tsvector('english', content_en));
+CREATE INDEX IF NOT EXISTS idx_learn_chat_turns_content_zh_fts
+    ON learn_chat_turns USING GIN (to_tsvector('simple', content_zh));
+-- The default parser keeps runs of CJK characters as one token, so Chinese
+-- substrings are matched through trigrams instead.
+CREATE INDEX IF NOT EXISTS idx_learn_chat_turns_content_zh_trgm
+    ON learn_chat_turns USING GIN (content_zh gin_trgm_ops);
+
+CREATE INDEX IF NOT EXISTS idx_learn_chats_title_fts
+    ON learn_chats USING GIN (to_tsvector('simple', title));
+CREATE INDEX IF NOT EXISTS idx_learn_chats_title_trgm
+    ON learn_chats USING GIN (title gin_trgm_ops);
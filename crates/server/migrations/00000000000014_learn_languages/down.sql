@@ -0,0 +1,10 @@
+ALTER TABLE learn_chat_issues DROP COLUMN IF EXISTS descriptions;
+
+ALTER TABLE learn_chat_turns
+    DROP COLUMN IF EXISTS contents,
+    DROP COLUMN IF EXISTS native_lang,
+    DROP COLUMN IF EXISTS target_lang;
+
+ALTER TABLE archive_user_profiles
+    DROP COLUMN IF EXISTS target_lang,
+    DROP COLUMN IF EXISTS native_lang;
@@ -0,0 +1,33 @@
+-- ============================================================================
+-- PER-USER NATIVE / TARGET LANGUAGES
+-- ============================================================================
+
+-- Language codes are ISO 639-1 ('en', 'zh', 'ja', 'fr', ...), see services/language.rs
+ALTER TABLE archive_user_profiles
+    ADD COLUMN IF NOT EXISTS native_lang TEXT NOT NULL DEFAULT 'zh',
+    ADD COLUMN IF NOT EXISTS target_lang TEXT NOT NULL DEFAULT 'en';
+
+-- Chat turns remember the language pair they were recorded with and keep their
+-- text keyed by language code, e.g. {"ja": "...", "en": "..."}.
+-- content_en / content_zh are still filled when the pair contains English or
+-- Chinese so existing clients and the chat search indexes keep working.
+ALTER TABLE learn_chat_turns
+    ADD COLUMN IF NOT EXISTS target_lang TEXT NOT NULL DEFAULT 'en',
+    ADD COLUMN IF NOT EXISTS native_lang TEXT NOT NULL DEFAULT 'zh',
+    ADD COLUMN IF NOT EXISTS contents JSONB NOT NULL DEFAULT '{}'::jsonb;
+
+UPDATE learn_chat_turns
+SET contents = jsonb_strip_nulls(jsonb_build_object(
+    'en', NULLIF(content_en, ''),
+    'zh', NULLIF(content_zh, '')
+));
+
+-- Issue explanations keyed by language code, same as learn_chat_turns.contents
+ALTER TABLE learn_chat_issues
+    ADD COLUMN IF NOT EXISTS descriptions JSONB NOT NULL DEFAULT '{}'::jsonb;
+
+UPDATE learn_chat_issues
+SET descriptions = jsonb_strip_nulls(jsonb_build_object(
+    'en', NULLIF(description_en, ''),
+    'zh', NULLIF(description_zh, '')
+));
@@ -0,0 +1,10 @@
+DROP TABLE IF EXISTS learn_review_logs;
+
+ALTER TABLE learn_issue_words
+    DROP COLUMN IF EXISTS correct_count,
+    DROP COLUMN IF EXISTS mastery_level,
+    DROP COLUMN IF EXISTS ease_factor;
+
+ALTER TABLE learn_vocabularies
+    DROP COLUMN IF EXISTS ease_factor,
+    DROP COLUMN IF EXISTS review_interval_days;
@@ -0,0 +1,29 @@
+-- ============================================================================
+-- SPACED-REPETITION SCHEDULING
+-- ============================================================================
+
+-- Scheduling state, see learn/scheduler.rs. An interval of 0 means the item is
+-- new or was forgotten and is being relearned.
+ALTER TABLE learn_vocabularies
+    ADD COLUMN IF NOT EXISTS review_interval_days INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS ease_factor REAL NOT NULL DEFAULT 2.5;
+
+ALTER TABLE learn_issue_words
+    ADD COLUMN IF NOT EXISTS ease_factor REAL NOT NULL DEFAULT 2.5,
+    ADD COLUMN IF NOT EXISTS mastery_level INTEGER NOT NULL DEFAULT 1 CHECK(mastery_level BETWEEN 1 AND 5),
+    ADD COLUMN IF NOT EXISTS correct_count INTEGER NOT NULL DEFAULT 0;
+
+-- Table: learn_review_logs - One row per graded review
+CREATE TABLE IF NOT EXISTS learn_review_logs (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    item_type TEXT NOT NULL CHECK(item_type IN ('vocabulary', 'issue_word')),
+    item_id BIGINT NOT NULL,
+    grade TEXT NOT NULL CHECK(grade IN ('again', 'hard', 'good', 'easy')),
+    interval_before INTEGER NOT NULL,
+    interval_after INTEGER NOT NULL,
+    ease_after REAL NOT NULL,
+    reviewed_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS idx_learn_review_logs_user ON learn_review_logs(user_id, reviewed_at);
+CREATE INDEX IF NOT EXISTS idx_learn_review_logs_item ON learn_review_logs(item_type, item_id);
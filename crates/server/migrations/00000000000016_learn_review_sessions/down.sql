@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS learn_review_session_items;
+DROP TABLE IF EXISTS learn_review_sessions;
@@ -0,0 +1,41 @@
+-- ============================================================================
+-- DAILY REVIEW SESSIONS
+-- ============================================================================
+
+-- Table: learn_review_sessions - A generated set of review exercises
+CREATE TABLE IF NOT EXISTS learn_review_sessions (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    session_date DATE NOT NULL,
+    item_count INTEGER NOT NULL DEFAULT 0,
+    answered_count INTEGER NOT NULL DEFAULT 0,
+    correct_count INTEGER NOT NULL DEFAULT 0,
+    status TEXT NOT NULL DEFAULT 'active' CHECK(status IN ('active', 'completed')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    completed_at TIMESTAMPTZ
+);
+CREATE INDEX IF NOT EXISTS idx_learn_review_sessions_user ON learn_review_sessions(user_id, session_date);
+
+-- Table: learn_review_session_items - One exercise of a review session
+CREATE TABLE IF NOT EXISTS learn_review_session_items (
+    id BIGSERIAL PRIMARY KEY,
+    session_id BIGINT NOT NULL REFERENCES learn_review_sessions(id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL,
+    position INTEGER NOT NULL,
+    item_type TEXT NOT NULL CHECK(item_type IN ('vocabulary', 'issue_word', 'chat_issue')),
+    item_id BIGINT NOT NULL,
+    exercise_type TEXT NOT NULL CHECK(exercise_type IN ('recognition', 'recall', 'cloze', 'listening')),
+    word TEXT NOT NULL,
+    prompt TEXT NOT NULL,
+    hint TEXT,
+    choices JSONB,
+    audio_url TEXT,
+    expected_answer TEXT NOT NULL,
+    user_answer TEXT,
+    is_correct BOOLEAN,
+    grade TEXT CHECK(grade IN ('again', 'hard', 'good', 'easy')),
+    answered_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE(session_id, position)
+);
+CREATE INDEX IF NOT EXISTS idx_learn_review_session_items_user ON learn_review_session_items(user_id, answered_at);
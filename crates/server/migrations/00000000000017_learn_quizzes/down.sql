@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS learn_quiz_questions;
+DROP TABLE IF EXISTS learn_quizzes;
@@ -0,0 +1,36 @@
+-- ============================================================================
+-- DICTIONARY QUIZZES
+-- ============================================================================
+
+-- Table: learn_quizzes - A quiz generated from dictionary data
+CREATE TABLE IF NOT EXISTS learn_quizzes (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    question_count INTEGER NOT NULL DEFAULT 0,
+    answered_count INTEGER NOT NULL DEFAULT 0,
+    correct_count INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    completed_at TIMESTAMPTZ
+);
+CREATE INDEX IF NOT EXISTS idx_learn_quizzes_user ON learn_quizzes(user_id, created_at DESC);
+
+-- Table: learn_quiz_questions - One question of a quiz
+-- answer holds the accepted answers: a list of strings, or for matching
+-- questions an object mapping each left item to its right item
+CREATE TABLE IF NOT EXISTS learn_quiz_questions (
+    id BIGSERIAL PRIMARY KEY,
+    quiz_id BIGINT NOT NULL REFERENCES learn_quizzes(id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL,
+    position INTEGER NOT NULL,
+    question_type TEXT NOT NULL CHECK(question_type IN ('definition_choice', 'cloze', 'matching', 'form_drill')),
+    word_id BIGINT,
+    prompt TEXT NOT NULL,
+    hint TEXT,
+    choices JSONB,
+    answer JSONB NOT NULL,
+    user_answer JSONB,
+    is_correct BOOLEAN,
+    answered_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE(quiz_id, position)
+);
@@ -0,0 +1,11 @@
+DROP INDEX IF EXISTS idx_learn_quiz_questions_user_answered;
+DROP INDEX IF EXISTS idx_learn_vocabularies_user_seen;
+DROP INDEX IF EXISTS idx_learn_read_practices_user_created;
+DROP INDEX IF EXISTS idx_learn_write_practices_user_created;
+
+ALTER TABLE learn_daily_stats DROP COLUMN IF EXISTS corrected_at;
+ALTER TABLE learn_daily_stats DROP COLUMN IF EXISTS corrected_by;
+ALTER TABLE learn_daily_stats DROP COLUMN IF EXISTS last_activity_at;
+ALTER TABLE learn_daily_stats DROP COLUMN IF EXISTS active_seconds;
+
+ALTER TABLE archive_user_profiles DROP COLUMN IF EXISTS timezone;
@@ -0,0 +1,15 @@
+-- Day boundaries of statistics, streaks and goals follow the user's time zone
+ALTER TABLE archive_user_profiles ADD COLUMN IF NOT EXISTS timezone TEXT NOT NULL DEFAULT 'UTC';
+
+-- Daily statistics are derived from activity on the server.
+-- active_seconds and last_activity_at track study time between events,
+-- corrected_* mark rows an admin fixed by hand, which reconciliation leaves alone.
+ALTER TABLE learn_daily_stats ADD COLUMN IF NOT EXISTS active_seconds INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE learn_daily_stats ADD COLUMN IF NOT EXISTS last_activity_at TIMESTAMPTZ;
+ALTER TABLE learn_daily_stats ADD COLUMN IF NOT EXISTS corrected_by BIGINT;
+ALTER TABLE learn_daily_stats ADD COLUMN IF NOT EXISTS corrected_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_learn_write_practices_user_created ON learn_write_practices(user_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_learn_read_practices_user_created ON learn_read_practices(user_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_learn_vocabularies_user_seen ON learn_vocabularies(user_id, first_seen_at);
+CREATE INDEX IF NOT EXISTS idx_learn_quiz_questions_user_answered ON learn_quiz_questions(user_id, answered_at);
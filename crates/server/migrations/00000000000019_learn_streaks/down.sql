@@ -0,0 +1,5 @@
+DROP TABLE IF EXISTS learn_streak_freezes;
+
+DROP INDEX IF EXISTS idx_archive_user_profiles_streak_active;
+
+ALTER TABLE archive_user_profiles DROP COLUMN IF EXISTS streak_freezes;
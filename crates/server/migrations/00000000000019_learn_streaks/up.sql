@@ -0,0 +1,15 @@
+-- Streak freezes are earned by keeping a streak and spent on missed days
+ALTER TABLE archive_user_profiles ADD COLUMN IF NOT EXISTS streak_freezes INTEGER NOT NULL DEFAULT 0;
+
+-- Streaks still running are checked at every local midnight
+CREATE INDEX IF NOT EXISTS idx_archive_user_profiles_streak_active
+    ON archive_user_profiles(last_activity_date) WHERE current_streak_days > 0;
+
+-- Table: learn_streak_freezes - Missed days covered by a streak freeze
+CREATE TABLE IF NOT EXISTS learn_streak_freezes (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    frozen_date DATE NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE(user_id, frozen_date)
+);
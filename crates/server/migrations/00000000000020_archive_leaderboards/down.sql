@@ -0,0 +1,7 @@
+DROP TABLE IF EXISTS archive_league_members;
+DROP TABLE IF EXISTS archive_league_cohorts;
+
+DROP INDEX IF EXISTS idx_archive_user_xp_history_created;
+
+ALTER TABLE archive_user_profiles DROP COLUMN IF EXISTS league_tier;
+ALTER TABLE archive_user_profiles DROP COLUMN IF EXISTS leaderboard_opt_out;
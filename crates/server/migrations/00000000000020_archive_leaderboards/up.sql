@@ -0,0 +1,33 @@
+-- Learners can hide themselves from leaderboards and leagues
+ALTER TABLE archive_user_profiles ADD COLUMN IF NOT EXISTS leaderboard_opt_out BOOLEAN NOT NULL DEFAULT false;
+-- League tier the learner joins next week, 1 is the lowest
+ALTER TABLE archive_user_profiles ADD COLUMN IF NOT EXISTS league_tier INTEGER NOT NULL DEFAULT 1;
+
+-- Weekly boards sum XP earned since the start of the week
+CREATE INDEX IF NOT EXISTS idx_archive_user_xp_history_created
+    ON archive_user_xp_history(created_at, user_id) INCLUDE (xp_amount);
+
+-- Table: archive_league_cohorts - A group of learners of one tier competing for a week
+CREATE TABLE IF NOT EXISTS archive_league_cohorts (
+    id BIGSERIAL PRIMARY KEY,
+    week_start DATE NOT NULL,                               -- Monday (UTC) the week starts on
+    tier INTEGER NOT NULL,
+    member_count INTEGER NOT NULL DEFAULT 0,
+    finalized_at TIMESTAMPTZ,                               -- Set once promotions and demotions were applied
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS idx_archive_league_cohorts_week ON archive_league_cohorts(week_start, tier);
+
+-- Table: archive_league_members - Membership of a learner in the cohort of a week
+CREATE TABLE IF NOT EXISTS archive_league_members (
+    id BIGSERIAL PRIMARY KEY,
+    cohort_id BIGINT NOT NULL REFERENCES archive_league_cohorts(id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL,
+    week_start DATE NOT NULL,
+    weekly_xp INTEGER NOT NULL DEFAULT 0,                   -- Final XP of the week, filled when finalized
+    final_position INTEGER,
+    outcome TEXT CHECK(outcome IN ('promoted', 'demoted', 'stayed')),
+    joined_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE(user_id, week_start)
+);
+CREATE INDEX IF NOT EXISTS idx_archive_league_members_cohort ON archive_league_members(cohort_id);
@@ -0,0 +1,5 @@
+DROP TABLE IF EXISTS learn_placement_items;
+DROP TABLE IF EXISTS learn_placement_tests;
+
+ALTER TABLE archive_user_profiles DROP COLUMN IF EXISTS placed_at;
+ALTER TABLE archive_user_profiles DROP COLUMN IF EXISTS placement_level;
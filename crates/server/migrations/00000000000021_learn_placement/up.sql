@@ -0,0 +1,42 @@
+-- ============================================================================
+-- PLACEMENT TESTS
+-- ============================================================================
+
+-- Level estimated by the placement test, on the 1-10 scale of asset difficulties
+ALTER TABLE archive_user_profiles ADD COLUMN IF NOT EXISTS placement_level SMALLINT CHECK(placement_level BETWEEN 1 AND 10);
+ALTER TABLE archive_user_profiles ADD COLUMN IF NOT EXISTS placed_at TIMESTAMPTZ;
+
+-- Table: learn_placement_tests - An adaptive test estimating a learner's level
+CREATE TABLE IF NOT EXISTS learn_placement_tests (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    item_count INTEGER NOT NULL,                            -- Items the test will ask
+    answered_count INTEGER NOT NULL DEFAULT 0,
+    correct_count INTEGER NOT NULL DEFAULT 0,
+    estimate REAL NOT NULL,                                 -- Current level estimate
+    step REAL NOT NULL,                                     -- How far the next answer moves the estimate
+    final_level SMALLINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    completed_at TIMESTAMPTZ
+);
+CREATE INDEX IF NOT EXISTS idx_learn_placement_tests_user ON learn_placement_tests(user_id, created_at DESC);
+
+-- Table: learn_placement_items - One item of a placement test, generated when it is reached
+-- answer holds the accepted answers as a list of strings
+CREATE TABLE IF NOT EXISTS learn_placement_items (
+    id BIGSERIAL PRIMARY KEY,
+    test_id BIGINT NOT NULL REFERENCES learn_placement_tests(id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL,
+    position INTEGER NOT NULL,
+    item_type TEXT NOT NULL CHECK(item_type IN ('vocabulary', 'grammar', 'reading', 'speaking')),
+    level SMALLINT NOT NULL,
+    source_id BIGINT,                                       -- dict_words id or asset_read_sentences id
+    prompt TEXT NOT NULL,
+    choices JSONB,
+    answer JSONB NOT NULL,
+    user_answer JSONB,
+    is_correct BOOLEAN,                                     -- Absent for skipped items
+    answered_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    UNIQUE(test_id, position)
+);
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS learn_goals;
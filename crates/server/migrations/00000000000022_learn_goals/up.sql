@@ -0,0 +1,23 @@
+-- ============================================================================
+-- LEARNING GOALS
+-- ============================================================================
+
+-- Table: learn_goals - A goal the learner set and the plan is derived from
+-- target_value is minutes per day (daily_minutes, exam), new words per week
+-- (weekly_words) or unused (word_list, where the list size is the target)
+CREATE TABLE IF NOT EXISTS learn_goals (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    goal_type TEXT NOT NULL CHECK(goal_type IN ('daily_minutes', 'weekly_words', 'word_list', 'exam')),
+    title TEXT NOT NULL,
+    target_value INTEGER,
+    category_id BIGINT REFERENCES taxon_categories(id) ON DELETE SET NULL,  -- Word list to master
+    baseline_mastered INTEGER NOT NULL DEFAULT 0,           -- List words already mastered when the goal was set
+    starts_on DATE NOT NULL,                                -- Local date the goal was set
+    target_date DATE,                                       -- Deadline or exam date
+    is_active BOOLEAN NOT NULL DEFAULT true,
+    achieved_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS idx_learn_goals_user ON learn_goals(user_id, is_active);
@@ -0,0 +1,4 @@
+DROP INDEX IF EXISTS idx_dict_forms_form;
+
+ALTER TABLE learn_vocabularies
+    DROP COLUMN IF EXISTS context;
@@ -0,0 +1,10 @@
+-- ============================================================================
+-- VOCABULARY CONTEXT
+-- ============================================================================
+
+-- Sentence the learner met the word in, e.g. when added from a pasted text
+ALTER TABLE learn_vocabularies
+    ADD COLUMN IF NOT EXISTS context TEXT;
+
+-- Inflected forms are looked up to lemmatize the words of a text
+CREATE INDEX IF NOT EXISTS idx_dict_forms_form ON dict_forms(form);
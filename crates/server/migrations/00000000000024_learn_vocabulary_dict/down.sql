@@ -0,0 +1,6 @@
+DROP INDEX IF EXISTS idx_learn_vocabularies_unenriched;
+DROP INDEX IF EXISTS idx_learn_vocabularies_word;
+
+ALTER TABLE learn_vocabularies
+    DROP COLUMN IF EXISTS enriched_at,
+    DROP COLUMN IF EXISTS word_id;
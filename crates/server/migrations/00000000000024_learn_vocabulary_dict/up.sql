@@ -0,0 +1,12 @@
+-- ============================================================================
+-- VOCABULARY DICTIONARY LINKS
+-- ============================================================================
+
+-- Dictionary entry of the word's lemma, see learn/enrich.rs. enriched_at is
+-- set once the lookup ran, found or not, so the backfill job skips the row.
+ALTER TABLE learn_vocabularies
+    ADD COLUMN IF NOT EXISTS word_id BIGINT REFERENCES dict_words(id) ON DELETE SET NULL,
+    ADD COLUMN IF NOT EXISTS enriched_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_learn_vocabularies_word ON learn_vocabularies(word_id);
+CREATE INDEX IF NOT EXISTS idx_learn_vocabularies_unenriched ON learn_vocabularies(id) WHERE enriched_at IS NULL;
@@ -0,0 +1,9 @@
+ALTER TABLE learn_write_practices
+    DROP COLUMN IF EXISTS answered_at,
+    DROP COLUMN IF EXISTS diff,
+    DROP COLUMN IF EXISTS accuracy,
+    DROP COLUMN IF EXISTS error_kind,
+    DROP COLUMN IF EXISTS user_answer,
+    DROP COLUMN IF EXISTS audio_path,
+    DROP COLUMN IF EXISTS expected_text,
+    DROP COLUMN IF EXISTS mode;
@@ -0,0 +1,15 @@
+-- ============================================================================
+-- DICTATION
+-- ============================================================================
+
+-- Server-graded dictation is stored as a write practice, see learn/dictation.rs.
+-- Practices the client grades itself leave these columns empty.
+ALTER TABLE learn_write_practices
+    ADD COLUMN IF NOT EXISTS mode TEXT CHECK(mode IN ('word', 'sentence')),  -- What was dictated
+    ADD COLUMN IF NOT EXISTS expected_text TEXT,                             -- Word or sentence played
+    ADD COLUMN IF NOT EXISTS audio_path TEXT,                                -- Pronunciation URL or generated speech path
+    ADD COLUMN IF NOT EXISTS user_answer TEXT,
+    ADD COLUMN IF NOT EXISTS error_kind TEXT CHECK(error_kind IN ('correct', 'typo', 'wrong_form', 'wrong_word')),
+    ADD COLUMN IF NOT EXISTS accuracy INTEGER CHECK(accuracy BETWEEN 0 AND 100),
+    ADD COLUMN IF NOT EXISTS diff JSONB,                                     -- Character diff and word errors
+    ADD COLUMN IF NOT EXISTS answered_at TIMESTAMPTZ;
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS learn_shadowing_practices;
+DROP TABLE IF EXISTS asset_audio_timings;
@@ -0,0 +1,33 @@
+-- ============================================================================
+-- SHADOWING
+-- ============================================================================
+
+-- Table: asset_audio_timings - Word timings of reference audio, transcribed once
+CREATE TABLE IF NOT EXISTS asset_audio_timings (
+    id BIGSERIAL PRIMARY KEY,
+    audio_path TEXT NOT NULL UNIQUE,                   -- Path relative to space_path
+    transcript TEXT NOT NULL,
+    words JSONB NOT NULL,                              -- [{word, start_ms, end_ms}]
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Table: learn_shadowing_practices - A recording repeating a script turn or read
+-- sentence, compared with the reference audio (see learn/shadowing.rs)
+CREATE TABLE IF NOT EXISTS learn_shadowing_practices (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    source_type TEXT NOT NULL CHECK(source_type IN ('script_turn', 'read_sentence')),
+    source_id BIGINT NOT NULL,
+    reference_text TEXT NOT NULL,
+    user_audio_path TEXT,
+    transcript TEXT NOT NULL,
+    accuracy_score INTEGER NOT NULL CHECK(accuracy_score BETWEEN 0 AND 100),  -- Reference words said
+    rhythm_score INTEGER NOT NULL CHECK(rhythm_score BETWEEN 0 AND 100),      -- Relative word durations
+    pause_score INTEGER NOT NULL CHECK(pause_score BETWEEN 0 AND 100),        -- Pauses in the same places
+    speed_ratio REAL,                                   -- Learner duration / reference duration
+    overall_score INTEGER NOT NULL CHECK(overall_score BETWEEN 0 AND 100),
+    details JSONB NOT NULL,                             -- Word and pause comparison
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS idx_learn_shadowing_practices_user ON learn_shadowing_practices(user_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_learn_shadowing_practices_source ON learn_shadowing_practices(user_id, source_type, source_id);
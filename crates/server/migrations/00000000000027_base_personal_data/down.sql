@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS base_account_deletions;
+DROP TABLE IF EXISTS base_data_exports;
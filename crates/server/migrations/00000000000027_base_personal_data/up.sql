@@ -0,0 +1,35 @@
+-- ============================================================================
+-- PERSONAL DATA EXPORT AND ACCOUNT DELETION
+-- ============================================================================
+
+-- Table: base_data_exports - A ZIP archive of everything stored about a user
+-- Built by a background job; the file is removed once expires_at passes
+CREATE TABLE IF NOT EXISTS base_data_exports (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending' CHECK(status IN ('pending', 'ready', 'failed', 'expired')),
+    file_path TEXT,                                         -- Relative to the space path
+    file_size BIGINT,
+    error TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    finished_at TIMESTAMPTZ,
+    expires_at TIMESTAMPTZ
+);
+CREATE INDEX IF NOT EXISTS idx_base_data_exports_user ON base_data_exports(user_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_base_data_exports_status ON base_data_exports(status);
+
+-- Table: base_account_deletions - A requested account deletion
+-- Kept after the account is gone as the record of what was removed
+CREATE TABLE IF NOT EXISTS base_account_deletions (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    requested_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    scheduled_for TIMESTAMPTZ NOT NULL,                     -- End of the grace period
+    cancelled_at TIMESTAMPTZ,
+    completed_at TIMESTAMPTZ,
+    removed JSONB                                           -- Rows per table and files removed
+);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_base_account_deletions_pending
+    ON base_account_deletions(user_id) WHERE cancelled_at IS NULL AND completed_at IS NULL;
+CREATE INDEX IF NOT EXISTS idx_base_account_deletions_scheduled
+    ON base_account_deletions(scheduled_for) WHERE cancelled_at IS NULL AND completed_at IS NULL;
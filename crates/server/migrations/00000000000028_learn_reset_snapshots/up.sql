@@ -0,0 +1,21 @@
+-- ============================================================================
+-- UNDOABLE RESETS
+-- ============================================================================
+
+-- Table: learn_reset_snapshots - Rows removed by a reset, kept so it can be undone
+-- Restoring puts the rows back and drops the snapshot; once expires_at passes a
+-- background job drops it with the audio files its rows pointed at
+CREATE TABLE IF NOT EXISTS learn_reset_snapshots (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    kind TEXT NOT NULL CHECK(kind IN (
+        'issue_words', 'chats', 'write_practices', 'read_practices',
+        'vocabulary', 'daily_stats', 'achievements', 'suggestions'
+    )),
+    rows JSONB NOT NULL,                                    -- {table: [row, ...]}
+    counts JSONB NOT NULL,                                  -- {table: row count}
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    expires_at TIMESTAMPTZ NOT NULL                         -- End of the restore window
+);
+CREATE INDEX IF NOT EXISTS idx_learn_reset_snapshots_user ON learn_reset_snapshots(user_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_learn_reset_snapshots_expires ON learn_reset_snapshots(expires_at);
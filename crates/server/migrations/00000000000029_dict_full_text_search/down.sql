@@ -0,0 +1,7 @@
+DROP INDEX IF EXISTS idx_dict_translations_trgm;
+DROP INDEX IF EXISTS idx_dict_sentences_trgm;
+DROP INDEX IF EXISTS idx_dict_definitions_trgm;
+ALTER TABLE dict_translations DROP COLUMN IF EXISTS search_vector;
+ALTER TABLE dict_sentences DROP COLUMN IF EXISTS search_vector;
+ALTER TABLE dict_definitions DROP COLUMN IF EXISTS search_vector;
+DROP FUNCTION IF EXISTS dict_search_config(TEXT);
@@ -0,0 +1,48 @@
+-- ============================================================================
+-- FULL-TEXT SEARCH OVER THE DICTIONARY
+-- ============================================================================
+
+-- Text search configuration for an ISO 639-1 language code. Languages without
+-- a stemmer, Chinese, Japanese and Korean among them, use 'simple'. Queries
+-- build their tsquery with the same function (see routing/dict/search.rs).
+CREATE OR REPLACE FUNCTION dict_search_config(language TEXT) RETURNS regconfig
+    LANGUAGE sql IMMUTABLE PARALLEL SAFE AS $$
+    SELECT CASE language
+        WHEN 'en' THEN 'english'
+        WHEN 'fr' THEN 'french'
+        WHEN 'de' THEN 'german'
+        WHEN 'es' THEN 'spanish'
+        WHEN 'it' THEN 'italian'
+        WHEN 'pt' THEN 'portuguese'
+        WHEN 'ru' THEN 'russian'
+        ELSE 'simple'
+    END::regconfig
+$$;
+
+ALTER TABLE dict_definitions
+    ADD COLUMN IF NOT EXISTS search_vector tsvector
+    GENERATED ALWAYS AS (to_tsvector(dict_search_config(language), definition)) STORED;
+CREATE INDEX IF NOT EXISTS idx_dict_definitions_search
+    ON dict_definitions USING GIN (search_vector);
+
+ALTER TABLE dict_sentences
+    ADD COLUMN IF NOT EXISTS search_vector tsvector
+    GENERATED ALWAYS AS (to_tsvector(dict_search_config(language), sentence)) STORED;
+CREATE INDEX IF NOT EXISTS idx_dict_sentences_search
+    ON dict_sentences USING GIN (search_vector);
+
+ALTER TABLE dict_translations
+    ADD COLUMN IF NOT EXISTS search_vector tsvector
+    GENERATED ALWAYS AS (to_tsvector(dict_search_config(language), translation)) STORED;
+CREATE INDEX IF NOT EXISTS idx_dict_translations_search
+    ON dict_translations USING GIN (search_vector);
+
+-- The default parser keeps runs of CJK characters as one token, so those
+-- languages are also matched as substrings through trigrams.
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+CREATE INDEX IF NOT EXISTS idx_dict_definitions_trgm
+    ON dict_definitions USING GIN (definition gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS idx_dict_sentences_trgm
+    ON dict_sentences USING GIN (sentence gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS idx_dict_translations_trgm
+    ON dict_translations USING GIN (translation gin_trgm_ops);
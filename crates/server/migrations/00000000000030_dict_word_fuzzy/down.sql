@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_dict_words_word_lower_prefix;
+DROP INDEX IF EXISTS idx_dict_words_word_lower_trgm;
@@ -0,0 +1,13 @@
+-- ============================================================================
+-- TYPO-TOLERANT WORD LOOKUP
+-- ============================================================================
+
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- Candidates for "did you mean" suggestions (see learn/spelling.rs): words
+-- sharing trigrams with the misspelling, and words starting with the same
+-- letter, which catches short words with too few trigrams in common
+CREATE INDEX IF NOT EXISTS idx_dict_words_word_lower_trgm
+    ON dict_words USING GIN (word_lower gin_trgm_ops);
+CREATE INDEX IF NOT EXISTS idx_dict_words_word_lower_prefix
+    ON dict_words (word_lower text_pattern_ops);
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_dict_words_prefix2_frequency;
+DROP INDEX IF EXISTS idx_dict_words_prefix1_frequency;
@@ -0,0 +1,14 @@
+-- ============================================================================
+-- DICTIONARY AUTOCOMPLETE
+-- ============================================================================
+
+-- Completions are the most frequent words starting with what was typed (see
+-- learn/autocomplete.rs). One and two letter prefixes match too many words
+-- to sort on every keystroke, so they read these indexes in the order
+-- completions are ranked and stop after the limit; longer prefixes use
+-- idx_dict_words_word_lower_prefix. The query must spell out left(word_lower, 1)
+-- or left(word_lower, 2) with the literal length to match these expressions.
+CREATE INDEX IF NOT EXISTS idx_dict_words_prefix1_frequency
+    ON dict_words (left(word_lower, 1), frequency DESC NULLS LAST, word_lower);
+CREATE INDEX IF NOT EXISTS idx_dict_words_prefix2_frequency
+    ON dict_words (left(word_lower, 2), frequency DESC NULLS LAST, word_lower);
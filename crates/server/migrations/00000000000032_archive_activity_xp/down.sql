@@ -0,0 +1,4 @@
+DELETE FROM archive_user_xp_history WHERE source_type IN ('quiz', 'chat', 'practice');
+ALTER TABLE archive_user_xp_history DROP CONSTRAINT IF EXISTS archive_user_xp_history_source_type_check;
+ALTER TABLE archive_user_xp_history ADD CONSTRAINT archive_user_xp_history_source_type_check
+    CHECK (source_type IN ('achievement', 'session', 'daily_bonus', 'streak', 'review', 'special'));
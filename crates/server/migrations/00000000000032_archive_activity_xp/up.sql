@@ -0,0 +1,6 @@
+-- Chat turns, reviews, quiz answers and practices earn XP as they are
+-- recorded (see learn/stats.rs), so the leaderboards rank learners by what
+-- they did rather than by unlocked achievements alone
+ALTER TABLE archive_user_xp_history DROP CONSTRAINT IF EXISTS archive_user_xp_history_source_type_check;
+ALTER TABLE archive_user_xp_history ADD CONSTRAINT archive_user_xp_history_source_type_check
+    CHECK (source_type IN ('achievement', 'session', 'daily_bonus', 'streak', 'review', 'quiz', 'chat', 'practice', 'special'));
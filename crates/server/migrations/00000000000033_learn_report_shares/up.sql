@@ -0,0 +1,18 @@
+-- ============================================================================
+-- SHARED REPORTS
+-- ============================================================================
+
+-- Table: learn_report_shares - Links showing a progress report without signing in
+-- Only the SHA-256 of the token is stored; the dates are resolved when the
+-- link is created, so a shared month does not move on
+CREATE TABLE IF NOT EXISTS learn_report_shares (
+    id BIGSERIAL PRIMARY KEY,
+    user_id BIGINT NOT NULL,
+    token_hash TEXT NOT NULL UNIQUE,
+    from_date DATE NOT NULL,
+    to_date DATE NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+CREATE INDEX IF NOT EXISTS idx_learn_report_shares_user ON learn_report_shares(user_id, created_at DESC);
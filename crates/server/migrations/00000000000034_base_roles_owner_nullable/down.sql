@@ -0,0 +1,2 @@
+DELETE FROM base_roles WHERE owner_id IS NULL;
+ALTER TABLE base_roles ALTER COLUMN owner_id SET NOT NULL;
@@ -0,0 +1,3 @@
+-- Roles outlive the account that created them: deleting an account sets
+-- owner_id to NULL like the created_by and updated_by columns
+ALTER TABLE base_roles ALTER COLUMN owner_id DROP NOT NULL;
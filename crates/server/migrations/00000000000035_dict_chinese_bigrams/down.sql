@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS idx_dict_definitions_zh_bigrams;
+DROP INDEX IF EXISTS idx_dict_translations_zh_bigrams;
+DROP FUNCTION IF EXISTS dict_bigrams(TEXT);
@@ -0,0 +1,23 @@
+-- ============================================================================
+-- CHINESE REVERSE LOOKUP
+-- ============================================================================
+
+-- Reverse lookup (see learn/reverse_lookup.rs) fetches Chinese texts sharing
+-- two consecutive characters with the query. Trigram indexes cannot serve
+-- such short patterns, so Chinese texts are indexed by their one and two
+-- character substrings and searched with dict_bigrams(text) && grams.
+CREATE OR REPLACE FUNCTION dict_bigrams(value TEXT) RETURNS TEXT[]
+    LANGUAGE sql IMMUTABLE STRICT PARALLEL SAFE
+AS $$
+    SELECT array_agg(DISTINCT gram)
+      FROM generate_series(1, char_length(value)) AS i,
+           LATERAL (VALUES (substr(value, i, 1)), (substr(value, i, 2))) AS grams(gram)
+$$;
+
+-- The predicates repeat the query's language filter so the planner can use them
+CREATE INDEX IF NOT EXISTS idx_dict_translations_zh_bigrams
+    ON dict_translations USING GIN (dict_bigrams(translation))
+    WHERE language LIKE 'zh%';
+CREATE INDEX IF NOT EXISTS idx_dict_definitions_zh_bigrams
+    ON dict_definitions USING GIN (dict_bigrams(definition))
+    WHERE language LIKE 'zh%';
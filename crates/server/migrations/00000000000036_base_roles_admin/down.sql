@@ -0,0 +1,3 @@
+DELETE FROM base_role_users
+WHERE role_id IN (SELECT id FROM base_roles WHERE code = 'admin');
+DELETE FROM base_roles WHERE code = 'admin';
@@ -0,0 +1,7 @@
+-- The role require_admin looks for. Nobody holds it at first; make a user an
+-- admin with
+--   INSERT INTO base_role_users (role_id, user_id)
+--   SELECT id, <user id> FROM base_roles WHERE code = 'admin';
+INSERT INTO base_roles (code, name, kind, description)
+VALUES ('admin', 'Administrator', 'system', 'Can correct daily stats of any user')
+ON CONFLICT (code) DO NOTHING;
@@ -0,0 +1,11 @@
+DROP INDEX IF EXISTS idx_learn_chat_turns_contents_trgm;
+DROP INDEX IF EXISTS idx_learn_chat_turns_contents_fts;
+DROP INDEX IF EXISTS idx_learn_chat_turns_contents_en_fts;
+DROP FUNCTION IF EXISTS chat_contents_text(JSONB);
+
+CREATE INDEX IF NOT EXISTS idx_learn_chat_turns_content_en_fts
+    ON learn_chat_turns USING GIN (to_tsvector('english', content_en));
+CREATE INDEX IF NOT EXISTS idx_learn_chat_turns_content_zh_fts
+    ON learn_chat_turns USING GIN (to_tsvector('simple', content_zh));
+CREATE INDEX IF NOT EXISTS idx_learn_chat_turns_content_zh_trgm
+    ON learn_chat_turns USING GIN (content_zh gin_trgm_ops);
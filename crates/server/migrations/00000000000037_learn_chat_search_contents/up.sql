@@ -0,0 +1,23 @@
+-- ============================================================================
+-- CHAT SEARCH OVER EVERY LANGUAGE
+-- ============================================================================
+
+-- Chat search (see routing/learn/chat_search.rs) matches the text of every
+-- language in contents instead of the legacy content_en and content_zh
+-- columns. to_tsvector(config, jsonb) reads the string values only.
+CREATE OR REPLACE FUNCTION chat_contents_text(contents JSONB) RETURNS TEXT
+    LANGUAGE sql IMMUTABLE STRICT PARALLEL SAFE
+AS $$
+    SELECT string_agg(value, E'\n' ORDER BY key) FROM jsonb_each_text(contents)
+$$;
+
+DROP INDEX IF EXISTS idx_learn_chat_turns_content_zh_trgm;
+DROP INDEX IF EXISTS idx_learn_chat_turns_content_zh_fts;
+DROP INDEX IF EXISTS idx_learn_chat_turns_content_en_fts;
+
+CREATE INDEX IF NOT EXISTS idx_learn_chat_turns_contents_en_fts
+    ON learn_chat_turns USING GIN (to_tsvector('english', contents));
+CREATE INDEX IF NOT EXISTS idx_learn_chat_turns_contents_fts
+    ON learn_chat_turns USING GIN (to_tsvector('simple', contents));
+CREATE INDEX IF NOT EXISTS idx_learn_chat_turns_contents_trgm
+    ON learn_chat_turns USING GIN (chat_contents_text(contents) gin_trgm_ops);
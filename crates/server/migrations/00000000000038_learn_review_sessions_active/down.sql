@@ -0,0 +1 @@
+DROP INDEX IF EXISTS idx_learn_review_sessions_active;
@@ -0,0 +1,19 @@
+-- ============================================================================
+-- ONE ACTIVE REVIEW SESSION PER DAY
+-- ============================================================================
+
+-- Concurrent requests could each create today's session (see
+-- learn/session.rs). Keep the newest of duplicates active, complete the rest.
+UPDATE learn_review_sessions s
+SET status = 'completed', completed_at = now()
+WHERE s.status = 'active'
+  AND EXISTS (
+      SELECT 1 FROM learn_review_sessions newer
+      WHERE newer.user_id = s.user_id
+        AND newer.session_date = s.session_date
+        AND newer.status = 'active'
+        AND newer.id > s.id
+  );
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_learn_review_sessions_active
+    ON learn_review_sessions(user_id, session_date) WHERE status = 'active';
@@ -1,3 +1,4 @@
+use std::str::FromStr;
 use std::sync::OnceLock;
 use std::time::Duration;
 
@@ -8,6 +9,10 @@ pub struct AppConfig {
     pub jwt_ttl: Duration,
     pub database: DbConfig,
     pub space_path: String,
+    pub scheduler: SchedulerConfig,
+    pub streak: StreakConfig,
+    pub account: AccountConfig,
+    pub report: ReportConfig,
 }
 
 #[derive(Clone, Debug)]
@@ -37,6 +42,183 @@ impl Default for DbConfig {
     }
 }
 
+/// Parameters of the spaced-repetition scheduler (SM-2 as used by Anki)
+#[derive(Clone, Debug)]
+pub struct SchedulerConfig {
+    /// Ease factor of items that were never reviewed
+    pub initial_ease: f32,
+    /// Ease never drops below this
+    pub min_ease: f32,
+    /// Ease lost when an item is forgotten
+    pub again_ease_penalty: f32,
+    /// Ease lost on a "hard" answer
+    pub hard_ease_penalty: f32,
+    /// Ease gained on an "easy" answer
+    pub easy_ease_bonus: f32,
+    /// Forgotten items come back after this many minutes
+    pub again_delay_minutes: i64,
+    /// First interval after a "good" answer on a new or forgotten item
+    pub graduating_interval_days: i32,
+    /// First interval after an "easy" answer on a new or forgotten item
+    pub easy_interval_days: i32,
+    /// Interval multiplier for "hard" answers
+    pub hard_interval_factor: f32,
+    /// Extra interval multiplier for "easy" answers
+    pub easy_bonus: f32,
+    /// Global multiplier applied to every computed interval
+    pub interval_modifier: f32,
+    pub max_interval_days: i32,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            initial_ease: 2.5,
+            min_ease: 1.3,
+            again_ease_penalty: 0.2,
+            hard_ease_penalty: 0.15,
+            easy_ease_bonus: 0.15,
+            again_delay_minutes: 10,
+            graduating_interval_days: 1,
+            easy_interval_days: 4,
+            hard_interval_factor: 1.2,
+            easy_bonus: 1.3,
+            interval_modifier: 1.0,
+            max_interval_days: 365,
+        }
+    }
+}
+
+/// Value of the environment variable `name`, `default` when unset or invalid
+fn env<T: FromStr>(name: &str, default: T) -> T {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+impl SchedulerConfig {
+    /// Defaults overridden by `SRS_*` environment variables
+    fn from_env() -> Self {
+        let d = Self::default();
+        Self {
+            initial_ease: env("SRS_INITIAL_EASE", d.initial_ease),
+            min_ease: env("SRS_MIN_EASE", d.min_ease),
+            again_ease_penalty: env("SRS_AGAIN_EASE_PENALTY", d.again_ease_penalty),
+            hard_ease_penalty: env("SRS_HARD_EASE_PENALTY", d.hard_ease_penalty),
+            easy_ease_bonus: env("SRS_EASY_EASE_BONUS", d.easy_ease_bonus),
+            again_delay_minutes: env("SRS_AGAIN_DELAY_MINUTES", d.again_delay_minutes),
+            graduating_interval_days: env(
+                "SRS_GRADUATING_INTERVAL_DAYS",
+                d.graduating_interval_days,
+            ),
+            easy_interval_days: env("SRS_EASY_INTERVAL_DAYS", d.easy_interval_days),
+            hard_interval_factor: env("SRS_HARD_INTERVAL_FACTOR", d.hard_interval_factor),
+            easy_bonus: env("SRS_EASY_BONUS", d.easy_bonus),
+            interval_modifier: env("SRS_INTERVAL_MODIFIER", d.interval_modifier),
+            max_interval_days: env("SRS_MAX_INTERVAL_DAYS", d.max_interval_days),
+        }
+    }
+}
+
+/// Rules of daily learning streaks
+#[derive(Clone, Debug)]
+pub struct StreakConfig {
+    /// Minutes of study needed for a day to count
+    pub min_minutes: i32,
+    /// A streak freeze is earned every time the streak reaches a multiple of
+    /// this many days
+    pub freeze_every_days: i32,
+    /// Streak freezes a user can hold at once
+    pub max_freezes: i32,
+}
+
+impl Default for StreakConfig {
+    fn default() -> Self {
+        Self {
+            min_minutes: 5,
+            freeze_every_days: 7,
+            max_freezes: 2,
+        }
+    }
+}
+
+impl StreakConfig {
+    /// Defaults overridden by `STREAK_*` environment variables
+    fn from_env() -> Self {
+        let d = Self::default();
+        Self {
+            min_minutes: env("STREAK_MIN_MINUTES", d.min_minutes),
+            freeze_every_days: env("STREAK_FREEZE_EVERY_DAYS", d.freeze_every_days),
+            max_freezes: env("STREAK_MAX_FREEZES", d.max_freezes),
+        }
+    }
+}
+
+/// Personal data export, account deletion and undoable resets
+#[derive(Clone, Debug)]
+pub struct AccountConfig {
+    /// Days between a deletion request and the removal of the account, during
+    /// which the request can be cancelled
+    pub deletion_grace_days: i64,
+    /// Days an export archive stays available for download
+    pub export_ttl_days: i64,
+    /// Days a reset of learning data can be restored before it is purged
+    pub reset_restore_days: i64,
+}
+
+impl Default for AccountConfig {
+    fn default() -> Self {
+        Self {
+            deletion_grace_days: 14,
+            export_ttl_days: 7,
+            reset_restore_days: 30,
+        }
+    }
+}
+
+impl AccountConfig {
+    /// Defaults overridden by `ACCOUNT_*` environment variables
+    fn from_env() -> Self {
+        let d = Self::default();
+        Self {
+            deletion_grace_days: env("ACCOUNT_DELETION_GRACE_DAYS", d.deletion_grace_days),
+            export_ttl_days: env("ACCOUNT_EXPORT_TTL_DAYS", d.export_ttl_days),
+            reset_restore_days: env("ACCOUNT_RESET_RESTORE_DAYS", d.reset_restore_days),
+        }
+    }
+}
+
+/// Progress reports
+#[derive(Clone, Debug)]
+pub struct ReportConfig {
+    /// TrueType font embedded in PDF reports; it must cover Chinese, such as
+    /// Noto Sans SC with TrueType outlines
+    pub font_path: String,
+    /// Days a shared report link stays valid
+    pub share_days: i64,
+}
+
+impl Default for ReportConfig {
+    fn default() -> Self {
+        Self {
+            font_path: "./fonts/NotoSansSC-Regular.ttf".into(),
+            share_days: 7,
+        }
+    }
+}
+
+impl ReportConfig {
+    /// Defaults overridden by `REPORT_*` environment variables
+    fn from_env() -> Self {
+        let d = Self::default();
+        Self {
+            font_path: env("REPORT_FONT_PATH", d.font_path),
+            share_days: env("REPORT_SHARE_DAYS", d.share_days),
+        }
+    }
+}
+
 pub static APP_CONFIG: OnceLock<AppConfig> = OnceLock::new();
 impl AppConfig {
     pub fn init() {
@@ -63,6 +245,10 @@ impl AppConfig {
                 jwt_secret,
                 jwt_ttl: Duration::from_secs(jwt_ttl_seconds),
                 space_path: std::env::var("SPACE_PATH").unwrap_or_else(|_| "./space".into()),
+                scheduler: SchedulerConfig::from_env(),
+                streak: StreakConfig::from_env(),
+                account: AccountConfig::from_env(),
+                report: ReportConfig::from_env(),
             })
             .expect("config should be set once");
     }
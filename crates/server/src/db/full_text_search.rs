@@ -206,6 +206,10 @@ mod functions {
         #[sql_name = "ts_headline"]
         fn ts_headline_with_search_config(config: RegConfig, x: Text, y: TsQuery) -> Text;
     }
+    define_sql_function! {
+        #[sql_name = "ts_headline"]
+        fn ts_headline_with_search_config_and_options(config: RegConfig, x: Text, y: TsQuery, options: Text) -> Text;
+    }
     define_sql_function!(fn ts_rank(x: TsVector, y: TsQuery) -> Float);
     define_sql_function!(fn ts_rank_cd(x: TsVector, y: TsQuery) -> Float);
     define_sql_function! {
@@ -293,6 +297,147 @@ pub use self::dsl::*;
 pub use self::functions::*;
 pub use self::types::*;
 
+/// Escape `%`, `_` and `\` so user input is matched literally by `ILIKE`
+pub fn escape_like(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for c in input.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Marks the start of a match in a headline made with [`HEADLINE_OPTIONS`]
+const MATCH_START: char = '\u{2}';
+/// Marks the end of a match in a headline made with [`HEADLINE_OPTIONS`]
+const MATCH_END: char = '\u{3}';
+/// `ts_headline` options that mark matches with control characters, so
+/// [`headline_html`] can escape the text before adding the markup
+pub const HEADLINE_OPTIONS: &str = "StartSel=\u{2}, StopSel=\u{3}";
+
+/// HTML of a headline made with [`HEADLINE_OPTIONS`], matches wrapped in
+/// `<b>` and `</b>`; `None` when it marks no match
+///
+/// The text is escaped, so user text never turns into markup.
+pub fn headline_html(headline: &str) -> Option<String> {
+    if !headline.contains(MATCH_START) {
+        return None;
+    }
+    let mut html = String::with_capacity(headline.len());
+    for c in headline.chars() {
+        match c {
+            MATCH_START => html.push_str("<b>"),
+            MATCH_END => html.push_str("</b>"),
+            _ => push_escaped(&mut html, c),
+        }
+    }
+    Some(html)
+}
+
+/// Cut a short window around the first occurrence of `needle` and wrap it like
+/// [`headline_html`] does
+///
+/// The needle is found ignoring case, as `ILIKE` matches it, and the text keeps
+/// its own casing. The text is escaped.
+pub fn highlight_substring(text: &str, needle: &str) -> String {
+    const CONTEXT_CHARS: usize = 20;
+
+    let Some((start, end)) = find_ignore_case(text, needle) else {
+        return escape_html(&text.chars().take(CONTEXT_CHARS * 3).collect::<String>());
+    };
+    let before: String = {
+        let chars: Vec<char> = text[..start].chars().collect();
+        let skip = chars.len().saturating_sub(CONTEXT_CHARS);
+        chars[skip..].iter().collect()
+    };
+    let after: String = text[end..].chars().take(CONTEXT_CHARS).collect();
+    format!(
+        "{}<b>{}</b>{}",
+        escape_html(&before),
+        escape_html(&text[start..end]),
+        escape_html(&after)
+    )
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        push_escaped(&mut escaped, c);
+    }
+    escaped
+}
+
+fn push_escaped(out: &mut String, c: char) {
+    match c {
+        '&' => out.push_str("&amp;"),
+        '<' => out.push_str("&lt;"),
+        '>' => out.push_str("&gt;"),
+        '"' => out.push_str("&quot;"),
+        '\'' => out.push_str("&#39;"),
+        _ => out.push(c),
+    }
+}
+
+/// Byte range of the first occurrence of `needle` in `text`, ignoring case
+fn find_ignore_case(text: &str, needle: &str) -> Option<(usize, usize)> {
+    let same = |a: char, b: char| a == b || a.to_lowercase().eq(b.to_lowercase());
+    let needle: Vec<char> = needle.chars().collect();
+    if needle.is_empty() {
+        return None;
+    }
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    chars.windows(needle.len()).find_map(|window| {
+        window
+            .iter()
+            .zip(&needle)
+            .all(|(&(_, a), &b)| same(a, b))
+            .then(|| {
+                let (last, c) = window[window.len() - 1];
+                (window[0].0, last + c.len_utf8())
+            })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_like_wildcards() {
+        assert_eq!(escape_like("50%_a\\b"), "50\\%\\_a\\\\b");
+    }
+
+    #[test]
+    fn highlights_ignoring_case() {
+        assert_eq!(
+            highlight_substring("Trip to Paris", "paris"),
+            "Trip to <b>Paris</b>"
+        );
+        assert_eq!(
+            highlight_substring("去北京旅行", "北京"),
+            "去<b>北京</b>旅行"
+        );
+        assert_eq!(highlight_substring("Café", "CAFÉ"), "<b>Café</b>");
+        assert_eq!(highlight_substring("no match", "xyz"), "no match");
+    }
+
+    #[test]
+    fn escapes_text_around_matches() {
+        assert_eq!(
+            highlight_substring("<img src=x> & Paris", "paris"),
+            "&lt;img src=x&gt; &amp; <b>Paris</b>"
+        );
+        assert_eq!(highlight_substring("<i>", "xyz"), "&lt;i&gt;");
+        assert_eq!(
+            headline_html("a \u{2}trip\u{3} to \"<Paris>\""),
+            Some("a <b>trip</b> to &quot;&lt;Paris&gt;&quot;".to_owned())
+        );
+        assert_eq!(headline_html("a trip & more"), None);
+    }
+}
+
 // mod tests {
 //     use diesel::dsl::sql;
 
@@ -22,6 +22,30 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    archive_league_cohorts (id) {
+        id -> Int8,
+        week_start -> Date,
+        tier -> Int4,
+        member_count -> Int4,
+        finalized_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    archive_league_members (id) {
+        id -> Int8,
+        cohort_id -> Int8,
+        user_id -> Int8,
+        week_start -> Date,
+        weekly_xp -> Int4,
+        final_position -> Nullable<Int4>,
+        outcome -> Nullable<Text>,
+        joined_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     archive_rank_definitions (id) {
         id -> Int8,
@@ -66,6 +90,14 @@ diesel::table! {
         total_sessions -> Int4,
         joined_at -> Timestamptz,
         updated_at -> Timestamptz,
+        native_lang -> Text,
+        target_lang -> Text,
+        timezone -> Text,
+        streak_freezes -> Int4,
+        leaderboard_opt_out -> Bool,
+        league_tier -> Int4,
+        placement_level -> Nullable<Int2>,
+        placed_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -81,6 +113,16 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    asset_audio_timings (id) {
+        id -> Int8,
+        audio_path -> Text,
+        transcript -> Text,
+        words -> Jsonb,
+        created_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     asset_context_categories (id) {
         id -> Int8,
@@ -207,6 +249,32 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    base_account_deletions (id) {
+        id -> Int8,
+        user_id -> Int8,
+        requested_at -> Timestamptz,
+        scheduled_for -> Timestamptz,
+        cancelled_at -> Nullable<Timestamptz>,
+        completed_at -> Nullable<Timestamptz>,
+        removed -> Nullable<Jsonb>,
+    }
+}
+
+diesel::table! {
+    base_data_exports (id) {
+        id -> Int8,
+        user_id -> Int8,
+        status -> Text,
+        file_path -> Nullable<Text>,
+        file_size -> Nullable<Int8>,
+        error -> Nullable<Text>,
+        created_at -> Timestamptz,
+        finished_at -> Nullable<Timestamptz>,
+        expires_at -> Nullable<Timestamptz>,
+    }
+}
+
 diesel::table! {
     base_passwords (id) {
         id -> Int8,
@@ -239,7 +307,7 @@ diesel::table! {
         code -> Text,
         name -> Text,
         kind -> Text,
-        owner_id -> Int8,
+        owner_id -> Nullable<Int8>,
         description -> Nullable<Text>,
         updated_by -> Nullable<Int8>,
         updated_at -> Timestamptz,
@@ -551,6 +619,7 @@ diesel::table! {
         description_zh -> Nullable<Text>,
         severity -> Nullable<Text>,
         created_at -> Timestamptz,
+        descriptions -> Jsonb,
     }
 }
 
@@ -571,6 +640,9 @@ diesel::table! {
         status -> Text,
         error -> Nullable<Text>,
         created_at -> Timestamptz,
+        target_lang -> Text,
+        native_lang -> Text,
+        contents -> Jsonb,
     }
 }
 
@@ -597,6 +669,28 @@ diesel::table! {
         errors_corrected -> Nullable<Int4>,
         new_words_learned -> Nullable<Int4>,
         review_words_count -> Nullable<Int4>,
+        active_seconds -> Int4,
+        last_activity_at -> Nullable<Timestamptz>,
+        corrected_by -> Nullable<Int8>,
+        corrected_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    learn_goals (id) {
+        id -> Int8,
+        user_id -> Int8,
+        goal_type -> Text,
+        title -> Text,
+        target_value -> Nullable<Int4>,
+        category_id -> Nullable<Int8>,
+        baseline_mastered -> Int4,
+        starts_on -> Date,
+        target_date -> Nullable<Date>,
+        is_active -> Bool,
+        achieved_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+        updated_at -> Timestamptz,
     }
 }
 
@@ -616,6 +710,43 @@ diesel::table! {
         context -> Nullable<Text>,
         audio_timestamp -> Nullable<Int4>,
         created_at -> Timestamptz,
+        ease_factor -> Float4,
+        mastery_level -> Int4,
+        correct_count -> Int4,
+    }
+}
+
+diesel::table! {
+    learn_placement_items (id) {
+        id -> Int8,
+        test_id -> Int8,
+        user_id -> Int8,
+        position -> Int4,
+        item_type -> Text,
+        level -> Int2,
+        source_id -> Nullable<Int8>,
+        prompt -> Text,
+        choices -> Nullable<Jsonb>,
+        answer -> Jsonb,
+        user_answer -> Nullable<Jsonb>,
+        is_correct -> Nullable<Bool>,
+        answered_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    learn_placement_tests (id) {
+        id -> Int8,
+        user_id -> Int8,
+        item_count -> Int4,
+        answered_count -> Int4,
+        correct_count -> Int4,
+        estimate -> Float4,
+        step -> Float4,
+        final_level -> Nullable<Int2>,
+        created_at -> Timestamptz,
+        completed_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -630,6 +761,37 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    learn_quiz_questions (id) {
+        id -> Int8,
+        quiz_id -> Int8,
+        user_id -> Int8,
+        position -> Int4,
+        question_type -> Text,
+        word_id -> Nullable<Int8>,
+        prompt -> Text,
+        hint -> Nullable<Text>,
+        choices -> Nullable<Jsonb>,
+        answer -> Jsonb,
+        user_answer -> Nullable<Jsonb>,
+        is_correct -> Nullable<Bool>,
+        answered_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    learn_quizzes (id) {
+        id -> Int8,
+        user_id -> Int8,
+        question_count -> Int4,
+        answered_count -> Int4,
+        correct_count -> Int4,
+        created_at -> Timestamptz,
+        completed_at -> Nullable<Timestamptz>,
+    }
+}
+
 diesel::table! {
     learn_read_practices (id) {
         id -> Int8,
@@ -665,6 +827,82 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    learn_report_shares (id) {
+        id -> Int8,
+        user_id -> Int8,
+        token_hash -> Text,
+        from_date -> Date,
+        to_date -> Date,
+        expires_at -> Timestamptz,
+        revoked_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    learn_reset_snapshots (id) {
+        id -> Int8,
+        user_id -> Int8,
+        kind -> Text,
+        rows -> Jsonb,
+        counts -> Jsonb,
+        created_at -> Timestamptz,
+        expires_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    learn_review_logs (id) {
+        id -> Int8,
+        user_id -> Int8,
+        item_type -> Text,
+        item_id -> Int8,
+        grade -> Text,
+        interval_before -> Int4,
+        interval_after -> Int4,
+        ease_after -> Float4,
+        reviewed_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    learn_review_session_items (id) {
+        id -> Int8,
+        session_id -> Int8,
+        user_id -> Int8,
+        position -> Int4,
+        item_type -> Text,
+        item_id -> Int8,
+        exercise_type -> Text,
+        word -> Text,
+        prompt -> Text,
+        hint -> Nullable<Text>,
+        choices -> Nullable<Jsonb>,
+        audio_url -> Nullable<Text>,
+        expected_answer -> Text,
+        user_answer -> Nullable<Text>,
+        is_correct -> Nullable<Bool>,
+        grade -> Nullable<Text>,
+        answered_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    learn_review_sessions (id) {
+        id -> Int8,
+        user_id -> Int8,
+        session_date -> Date,
+        item_count -> Int4,
+        answered_count -> Int4,
+        correct_count -> Int4,
+        status -> Text,
+        created_at -> Timestamptz,
+        completed_at -> Nullable<Timestamptz>,
+    }
+}
+
 diesel::table! {
     learn_script_progress (id) {
         id -> Int8,
@@ -681,6 +919,34 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    learn_shadowing_practices (id) {
+        id -> Int8,
+        user_id -> Int8,
+        source_type -> Text,
+        source_id -> Int8,
+        reference_text -> Text,
+        user_audio_path -> Nullable<Text>,
+        transcript -> Text,
+        accuracy_score -> Int4,
+        rhythm_score -> Int4,
+        pause_score -> Int4,
+        speed_ratio -> Nullable<Float4>,
+        overall_score -> Int4,
+        details -> Jsonb,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    learn_streak_freezes (id) {
+        id -> Int8,
+        user_id -> Int8,
+        frozen_date -> Date,
+        created_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     learn_suggestions (id) {
         id -> Int8,
@@ -704,6 +970,11 @@ diesel::table! {
         practice_count -> Nullable<Int4>,
         correct_count -> Nullable<Int4>,
         next_review_at -> Nullable<Timestamptz>,
+        review_interval_days -> Int4,
+        ease_factor -> Float4,
+        context -> Nullable<Text>,
+        word_id -> Nullable<Int8>,
+        enriched_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -716,7 +987,14 @@ diesel::table! {
         success_level -> Nullable<Int4>,
         notes -> Nullable<Text>,
         updated_at -> Timestamptz,
-        created_at -> Timestamptz,
+        created_at -> Timestamptz,        mode -> Nullable<Text>,
+        expected_text -> Nullable<Text>,
+        audio_path -> Nullable<Text>,
+        user_answer -> Nullable<Text>,
+        error_kind -> Nullable<Text>,
+        accuracy -> Nullable<Int4>,
+        diff -> Nullable<Jsonb>,
+        answered_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -767,10 +1045,13 @@ diesel::table! {
 
 diesel::allow_tables_to_appear_in_same_query!(
     archive_achievement_definitions,
+    archive_league_cohorts,
+    archive_league_members,
     archive_rank_definitions,
     archive_user_achievements,
     archive_user_profiles,
     archive_user_xp_history,
+    asset_audio_timings,
     asset_context_categories,
     asset_contexts,
     asset_read_sentences,
@@ -780,6 +1061,8 @@ diesel::allow_tables_to_appear_in_same_query!(
     asset_stages,
     asset_word_sentences,
     auth_codes,
+    base_account_deletions,
+    base_data_exports,
     base_passwords,
     base_role_permissions,
     base_role_users,
@@ -808,11 +1091,23 @@ diesel::allow_tables_to_appear_in_same_query!(
     learn_chat_turns,
     learn_chats,
     learn_daily_stats,
+    learn_goals,
     learn_issue_words,
+    learn_placement_items,
+    learn_placement_tests,
     learn_practices,
+    learn_quiz_questions,
+    learn_quizzes,
     learn_read_practices,
     learn_read_progress,
+    learn_report_shares,
+    learn_reset_snapshots,
+    learn_review_logs,
+    learn_review_session_items,
+    learn_review_sessions,
     learn_script_progress,
+    learn_shadowing_practices,
+    learn_streak_freezes,
     learn_suggestions,
     learn_vocabularies,
     learn_write_practices,
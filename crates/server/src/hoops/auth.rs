@@ -1,7 +1,11 @@
+use diesel::prelude::*;
 use salvo::http::header;
 use salvo::prelude::*;
 
-use crate::{AppConfig, AppResult};
+use crate::db::schema::*;
+use crate::db::with_conn;
+use crate::diesel_exists;
+use crate::{AppConfig, AppResult, DepotExt};
 
 #[handler]
 pub async fn require_auth(
@@ -27,3 +31,22 @@ pub async fn require_auth(
     depot.insert("user_id", user_id);
     Ok(())
 }
+
+/// Only lets users with the `admin` role through, runs after `require_auth`
+#[handler]
+pub async fn require_admin(depot: &mut Depot) -> AppResult<()> {
+    let user_id = depot.user_id()?;
+    let is_admin = with_conn(move |conn| {
+        let query = base_role_users::table
+            .inner_join(base_roles::table.on(base_roles::id.eq(base_role_users::role_id)))
+            .filter(base_role_users::user_id.eq(user_id))
+            .filter(base_roles::code.eq("admin"));
+        diesel_exists!(query, conn)
+    })
+    .await
+    .map_err(|_| StatusError::internal_server_error().brief("failed to check permissions"))?;
+    if !is_admin {
+        return Err(StatusError::forbidden().brief("admin only").into());
+    }
+    Ok(())
+}
@@ -0,0 +1,151 @@
+//! Background jobs started with the server
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::Utc;
+
+use crate::config::AppConfig;
+use crate::db::with_conn;
+use crate::learn::{enrich, league, reset, stats, streak};
+use crate::user::personal_data;
+
+const MINUTE: Duration = Duration::from_secs(60);
+const HOUR: Duration = Duration::from_secs(60 * 60);
+
+/// Spawn all background jobs on the current runtime
+pub fn start() {
+    tokio::spawn(reconcile_daily_stats());
+    tokio::spawn(settle_streaks());
+    tokio::spawn(finalize_leagues());
+    tokio::spawn(enrich_vocabulary());
+    tokio::spawn(export_personal_data());
+    tokio::spawn(delete_accounts());
+    tokio::spawn(purge_resets());
+}
+
+/// Recompute the previous day's statistics of users at their local night
+async fn reconcile_daily_stats() {
+    let mut ticker = tokio::time::interval(HOUR);
+    loop {
+        ticker.tick().await;
+        match with_conn(|conn| stats::reconcile_due_users(conn, Utc::now())).await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("Reconciled daily stats of {} users", count),
+            Err(e) => tracing::error!("Failed to reconcile daily stats: {}", e),
+        }
+    }
+}
+
+/// Freeze or break streaks that missed the day that just ended locally
+async fn settle_streaks() {
+    let mut ticker = tokio::time::interval(HOUR);
+    loop {
+        ticker.tick().await;
+        match with_conn(|conn| streak::settle_due_streaks(conn, Utc::now())).await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("Settled streaks of {} users", count),
+            Err(e) => tracing::error!("Failed to settle streaks: {}", e),
+        }
+    }
+}
+
+/// Promote and demote league members once their week is over
+async fn finalize_leagues() {
+    let mut ticker = tokio::time::interval(HOUR);
+    loop {
+        ticker.tick().await;
+        match with_conn(|conn| league::finalize_past_weeks(conn, Utc::now())).await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("Finalized {} league cohorts", count),
+            Err(e) => tracing::error!("Failed to finalize leagues: {}", e),
+        }
+    }
+}
+
+/// Link vocabulary rows added before enrichment existed to the dictionary
+async fn enrich_vocabulary() {
+    let mut ticker = tokio::time::interval(HOUR);
+    loop {
+        ticker.tick().await;
+        let mut total = 0;
+        loop {
+            match with_conn(|conn| enrich::backfill(conn, Utc::now())).await {
+                Ok(0) => break,
+                Ok(count) => total += count,
+                Err(e) => {
+                    tracing::error!("Failed to enrich vocabulary: {}", e);
+                    break;
+                }
+            }
+        }
+        if total > 0 {
+            tracing::info!("Enriched {} vocabulary words", total);
+        }
+    }
+}
+
+/// Build requested data exports and remove expired archives
+async fn export_personal_data() {
+    let mut ticker = tokio::time::interval(MINUTE);
+    loop {
+        ticker.tick().await;
+        let result = with_conn(|conn| {
+            let config = AppConfig::get();
+            let space_path = PathBuf::from(&config.space_path);
+            let now = Utc::now();
+            let built = personal_data::process_exports(
+                conn,
+                &space_path,
+                config.account.export_ttl_days,
+                now,
+            )?;
+            let expired = personal_data::expire_exports(conn, &space_path, now)?;
+            Ok((built, expired))
+        })
+        .await;
+        match result {
+            Ok((0, 0)) => {}
+            Ok((built, expired)) => {
+                tracing::info!("Built {} data exports, expired {}", built, expired)
+            }
+            Err(e) => tracing::error!("Failed to process data exports: {}", e),
+        }
+    }
+}
+
+/// Delete accounts whose deletion grace period is over
+async fn delete_accounts() {
+    let mut ticker = tokio::time::interval(HOUR);
+    loop {
+        ticker.tick().await;
+        let result = with_conn(|conn| {
+            let space_path = PathBuf::from(&AppConfig::get().space_path);
+            personal_data::purge_due_accounts(conn, &space_path, Utc::now())
+        })
+        .await;
+        match result {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("Deleted {} accounts", count),
+            Err(e) => tracing::error!("Failed to delete accounts: {}", e),
+        }
+    }
+}
+
+/// Drop reset snapshots past their restore window, with their audio files
+async fn purge_resets() {
+    let mut ticker = tokio::time::interval(HOUR);
+    loop {
+        ticker.tick().await;
+        let result = with_conn(|conn| {
+            let space_path = PathBuf::from(&AppConfig::get().space_path);
+            reset::purge_expired(conn, &space_path, Utc::now())
+        })
+        .await;
+        match result {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("Purged {} reset snapshots", count),
+            Err(e) => tracing::error!("Failed to purge reset snapshots: {}", e),
+        }
+    }
+}
@@ -0,0 +1,27 @@
+//! Learning logic shared by the learn routes and background jobs
+
+pub mod achievement;
+pub mod audio;
+pub mod autocomplete;
+pub mod dictation;
+pub mod enrich;
+pub mod extract;
+pub mod goal;
+pub mod inflection;
+pub mod leaderboard;
+pub mod league;
+pub mod local_time;
+pub mod phoneme;
+pub mod placement;
+pub mod quiz;
+pub mod reading;
+pub mod report;
+pub mod reset;
+pub mod reverse_lookup;
+pub mod review;
+pub mod scheduler;
+pub mod session;
+pub mod shadowing;
+pub mod spelling;
+pub mod stats;
+pub mod streak;
@@ -0,0 +1,537 @@
+//! Achievement evaluation
+//!
+//! Learning events refresh the totals on the learner's profile, then every
+//! active rule in `archive_achievement_definitions` is checked against them.
+//! Rules that are met for the first time are completed, their XP reward is
+//! added to the history and the profile's XP and rank are recalculated.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Datelike, Days, NaiveDate, TimeZone, Timelike, Utc, Weekday};
+use diesel::dsl::{count_distinct, sum};
+use diesel::prelude::*;
+
+use super::local_time::parse_timezone;
+use crate::db::schema::*;
+use crate::diesel_exists;
+use crate::models::achievement::*;
+
+/// Vocabulary at this mastery level or above counts as mastered
+pub const MASTERED_LEVEL: i32 = 4;
+/// Local hour before which a session counts for `early_bird`
+const EARLY_BIRD_BEFORE_HOUR: u32 = 7;
+/// Local hour from which a session counts for `night_owl`
+const NIGHT_OWL_FROM_HOUR: u32 = 23;
+
+/// Something the learner did that may unlock achievements
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AchievementEvent {
+    /// A chat turn, review, practice or quiz answer
+    Studied,
+    /// A reading practice was scored, 0-100
+    ReadingScored { score: i32 },
+}
+
+/// Evaluate every active achievement of `user_id` after `event`
+///
+/// Returns the badges unlocked by this call, in definition order.
+pub fn evaluate(
+    conn: &mut PgConnection,
+    user_id: i64,
+    event: AchievementEvent,
+    at: DateTime<Utc>,
+) -> QueryResult<Vec<AchievementBadge>> {
+    conn.transaction(|conn| {
+        // The profile row lock serializes evaluations of the same user, so a
+        // reward cannot be granted twice by concurrent requests
+        let profile = refresh_profile(conn, user_id, at)?;
+
+        let definitions = archive_achievement_definitions::table
+            .filter(archive_achievement_definitions::is_active.eq(true))
+            .order((
+                archive_achievement_definitions::sort_order.asc(),
+                archive_achievement_definitions::id.asc(),
+            ))
+            .load::<AchievementDefinition>(conn)?;
+        let mut records: HashMap<i64, UserAchievementRecord> = archive_user_achievements::table
+            .filter(archive_user_achievements::user_id.eq(user_id))
+            .load::<UserAchievementRecord>(conn)?
+            .into_iter()
+            .map(|record| (record.achievement_id, record))
+            .collect();
+
+        let mut unlocked = Vec::new();
+        for definition in definitions {
+            let record = records.remove(&definition.id);
+            if record.as_ref().is_some_and(|r| r.is_completed) {
+                continue;
+            }
+            let previous = record.as_ref().map(|r| r.progress).unwrap_or(0);
+            let Some(progress) = rule_progress(conn, &definition, &profile, event, at, previous)?
+            else {
+                continue;
+            };
+            let Some((progress, completed)) = advance(
+                definition.requirement_value,
+                record.as_ref().map(|r| r.progress),
+                progress,
+            ) else {
+                continue;
+            };
+
+            let completed_at = completed.then_some(at);
+            match record {
+                Some(record) => {
+                    diesel::update(archive_user_achievements::table.find(record.id))
+                        .set(&UpdateUserAchievement {
+                            progress: Some(progress),
+                            is_completed: Some(completed),
+                            completed_at,
+                            notified_at: None,
+                        })
+                        .execute(conn)?;
+                }
+                None => {
+                    diesel::insert_into(archive_user_achievements::table)
+                        .values(&NewUserAchievement {
+                            user_id,
+                            achievement_id: definition.id,
+                            progress,
+                            is_completed: completed,
+                            completed_at,
+                        })
+                        .execute(conn)?;
+                }
+            }
+            if !completed {
+                continue;
+            }
+
+            if definition.xp_reward > 0 {
+                diesel::insert_into(archive_user_xp_history::table)
+                    .values(&NewUserXpHistory {
+                        user_id,
+                        xp_amount: definition.xp_reward,
+                        source_type: "achievement".to_owned(),
+                        source_id: Some(definition.id),
+                        description: Some(format!("Unlocked {}", definition.name_en)),
+                    })
+                    .execute(conn)?;
+            }
+            unlocked.push(badge(definition, completed_at));
+        }
+
+        if !unlocked.is_empty() {
+            recalculate_xp(conn, user_id, at)?;
+        }
+        Ok(unlocked)
+    })
+}
+
+/// Evaluate like [`evaluate`] for a response that shows the unlocked badges
+/// right away, so they are not reported again as pending
+pub fn evaluate_and_notify(
+    conn: &mut PgConnection,
+    user_id: i64,
+    event: AchievementEvent,
+    at: DateTime<Utc>,
+) -> QueryResult<Vec<AchievementBadge>> {
+    let unlocked = evaluate(conn, user_id, event, at)?;
+    let codes: Vec<String> = unlocked.iter().map(|b| b.code.clone()).collect();
+    acknowledge(conn, user_id, &codes, at)?;
+    Ok(unlocked)
+}
+
+/// Add `entry` to the XP history and recalculate the profile's XP and rank
+///
+/// Returns the new total.
+pub fn award_xp(
+    conn: &mut PgConnection,
+    entry: &NewUserXpHistory,
+    at: DateTime<Utc>,
+) -> QueryResult<i32> {
+    diesel::insert_into(archive_user_profiles::table)
+        .values(&NewUserProfile {
+            user_id: entry.user_id,
+        })
+        .on_conflict(archive_user_profiles::user_id)
+        .do_nothing()
+        .execute(conn)?;
+    diesel::insert_into(archive_user_xp_history::table)
+        .values(entry)
+        .execute(conn)?;
+    recalculate_xp(conn, entry.user_id, at)
+}
+
+/// Recompute the total XP of `user_id` from the XP history and move the
+/// profile to the matching rank
+///
+/// Returns the new total.
+pub fn recalculate_xp(
+    conn: &mut PgConnection,
+    user_id: i64,
+    at: DateTime<Utc>,
+) -> QueryResult<i32> {
+    let total_xp = archive_user_xp_history::table
+        .filter(archive_user_xp_history::user_id.eq(user_id))
+        .select(sum(archive_user_xp_history::xp_amount))
+        .first::<Option<i64>>(conn)?
+        .unwrap_or(0)
+        .clamp(0, i32::MAX as i64) as i32;
+    let rank_id = archive_rank_definitions::table
+        .filter(archive_rank_definitions::min_xp.le(total_xp))
+        .order(archive_rank_definitions::min_xp.desc())
+        .select(archive_rank_definitions::id)
+        .first::<i64>(conn)
+        .optional()?;
+
+    diesel::update(archive_user_profiles::table)
+        .filter(archive_user_profiles::user_id.eq(user_id))
+        .set((
+            archive_user_profiles::total_xp.eq(total_xp),
+            archive_user_profiles::current_rank_id.eq(rank_id),
+            archive_user_profiles::updated_at.eq(at),
+        ))
+        .execute(conn)?;
+    Ok(total_xp)
+}
+
+/// Unlocked badges of `user_id` the client has not celebrated yet
+pub fn pending_badges(conn: &mut PgConnection, user_id: i64) -> QueryResult<Vec<AchievementBadge>> {
+    let rows =
+        archive_user_achievements::table
+            .inner_join(archive_achievement_definitions::table.on(
+                archive_achievement_definitions::id.eq(archive_user_achievements::achievement_id),
+            ))
+            .filter(archive_user_achievements::user_id.eq(user_id))
+            .filter(archive_user_achievements::is_completed.eq(true))
+            .filter(archive_user_achievements::notified_at.is_null())
+            .order(archive_user_achievements::completed_at.asc())
+            .select((
+                archive_user_achievements::completed_at,
+                archive_achievement_definitions::all_columns,
+            ))
+            .load::<(Option<DateTime<Utc>>, AchievementDefinition)>(conn)?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(completed_at, definition)| badge(definition, completed_at))
+        .collect())
+}
+
+/// Mark unlocked badges as shown to the learner
+///
+/// Returns the number of badges marked.
+pub fn acknowledge(
+    conn: &mut PgConnection,
+    user_id: i64,
+    codes: &[String],
+    at: DateTime<Utc>,
+) -> QueryResult<usize> {
+    if codes.is_empty() {
+        return Ok(0);
+    }
+    let achievement_ids = archive_achievement_definitions::table
+        .filter(archive_achievement_definitions::code.eq_any(codes))
+        .select(archive_achievement_definitions::id);
+    diesel::update(archive_user_achievements::table)
+        .filter(archive_user_achievements::user_id.eq(user_id))
+        .filter(archive_user_achievements::achievement_id.eq_any(achievement_ids))
+        .filter(archive_user_achievements::is_completed.eq(true))
+        .filter(archive_user_achievements::notified_at.is_null())
+        .set(archive_user_achievements::notified_at.eq(Some(at)))
+        .execute(conn)
+}
+
+fn badge(
+    definition: AchievementDefinition,
+    completed_at: Option<DateTime<Utc>>,
+) -> AchievementBadge {
+    AchievementBadge {
+        code: definition.code,
+        name_en: definition.name_en,
+        name_zh: definition.name_zh,
+        icon: definition.icon,
+        rarity: definition.rarity,
+        completed_at,
+    }
+}
+
+/// Update the totals achievement rules refer to and return the locked profile
+fn refresh_profile(
+    conn: &mut PgConnection,
+    user_id: i64,
+    at: DateTime<Utc>,
+) -> QueryResult<UserProfile> {
+    diesel::insert_into(archive_user_profiles::table)
+        .values(&NewUserProfile { user_id })
+        .on_conflict(archive_user_profiles::user_id)
+        .do_nothing()
+        .execute(conn)?;
+    archive_user_profiles::table
+        .filter(archive_user_profiles::user_id.eq(user_id))
+        .for_update()
+        .first::<UserProfile>(conn)?;
+
+    let conversations: i64 = learn_chat_turns::table
+        .filter(learn_chat_turns::user_id.eq(user_id))
+        .filter(learn_chat_turns::speaker.eq("user"))
+        .select(count_distinct(learn_chat_turns::chat_id))
+        .first(conn)?;
+    let words_mastered: i64 = learn_vocabularies::table
+        .filter(learn_vocabularies::user_id.eq(user_id))
+        .filter(learn_vocabularies::mastery_level.ge(MASTERED_LEVEL))
+        .count()
+        .get_result(conn)?;
+    let (minutes, sessions) = learn_daily_stats::table
+        .filter(learn_daily_stats::user_id.eq(user_id))
+        .select((
+            sum(learn_daily_stats::minutes_studied),
+            sum(learn_daily_stats::sessions_completed),
+        ))
+        .first::<(Option<i64>, Option<i64>)>(conn)?;
+
+    diesel::update(archive_user_profiles::table)
+        .filter(archive_user_profiles::user_id.eq(user_id))
+        .set(&UpdateUserProfile {
+            total_stages: Some(conversations as i32),
+            total_words_mastered: Some(words_mastered as i32),
+            total_study_minutes: Some(minutes.unwrap_or(0) as i32),
+            total_sessions: Some(sessions.unwrap_or(0) as i32),
+            updated_at: Some(at),
+            ..Default::default()
+        })
+        .get_result::<UserProfile>(conn)
+}
+
+/// Progress of one rule, `None` if the event says nothing about it
+fn rule_progress(
+    conn: &mut PgConnection,
+    definition: &AchievementDefinition,
+    profile: &UserProfile,
+    event: AchievementEvent,
+    at: DateTime<Utc>,
+    previous: i32,
+) -> QueryResult<Option<i32>> {
+    match definition.requirement_type.as_str() {
+        "special" => special_progress(conn, &definition.code, profile, at),
+        _ => Ok(measured_progress(definition, profile, event, previous)),
+    }
+}
+
+/// Progress of a rule measured by a profile total or a score
+fn measured_progress(
+    definition: &AchievementDefinition,
+    profile: &UserProfile,
+    event: AchievementEvent,
+    previous: i32,
+) -> Option<i32> {
+    match definition.requirement_type.as_str() {
+        "count" | "streak" | "time" => definition
+            .requirement_field
+            .as_deref()
+            .and_then(|field| profile_field(profile, field)),
+        "score" => match event {
+            AchievementEvent::ReadingScored { score } => Some(score.max(previous)),
+            AchievementEvent::Studied => None,
+        },
+        _ => None,
+    }
+}
+
+/// Stored progress and completion of a rule now at `progress`, `None` when
+/// there is nothing to store
+fn advance(requirement: i32, previous: Option<i32>, progress: i32) -> Option<(i32, bool)> {
+    let completed = progress >= requirement;
+    let progress = progress.min(requirement);
+    match previous {
+        Some(previous) if progress == previous && !completed => None,
+        None if progress == 0 => None,
+        _ => Some((progress, completed)),
+    }
+}
+
+/// Value of a profile total named by `requirement_field`
+fn profile_field(profile: &UserProfile, field: &str) -> Option<i32> {
+    match field {
+        "total_stages" => Some(profile.total_stages),
+        "total_words_mastered" => Some(profile.total_words_mastered),
+        "current_streak_days" => Some(profile.current_streak_days),
+        "longest_streak_days" => Some(profile.longest_streak_days),
+        "total_study_minutes" => Some(profile.total_study_minutes),
+        "total_sessions" => Some(profile.total_sessions),
+        "total_xp" => Some(profile.total_xp),
+        _ => None,
+    }
+}
+
+/// Rules without a field, checked against the local time of the activity
+fn special_progress(
+    conn: &mut PgConnection,
+    code: &str,
+    profile: &UserProfile,
+    at: DateTime<Utc>,
+) -> QueryResult<Option<i32>> {
+    let Some(tz) = parse_timezone(&profile.timezone) else {
+        return Ok(None);
+    };
+    let met = special_met(code, at.with_timezone(&tz), |day| {
+        diesel_exists!(
+            learn_daily_stats::table
+                .filter(learn_daily_stats::user_id.eq(profile.user_id))
+                .filter(learn_daily_stats::stat_date.eq(day))
+                .filter(learn_daily_stats::active_seconds.gt(0)),
+            conn
+        )
+    })?;
+    Ok(met.then_some(1))
+}
+
+/// Whether the special rule `code` is met by activity at `local`, asking
+/// `studied_on` about other days; unknown codes are never met
+fn special_met<Tz: TimeZone>(
+    code: &str,
+    local: DateTime<Tz>,
+    studied_on: impl FnOnce(NaiveDate) -> QueryResult<bool>,
+) -> QueryResult<bool> {
+    let met = match code {
+        "early_bird" => local.hour() < EARLY_BIRD_BEFORE_HOUR,
+        "night_owl" => local.hour() >= NIGHT_OWL_FROM_HOUR,
+        "weekend_warrior" => {
+            let today = local.date_naive();
+            // Studying today already makes the Sunday count, check Saturday
+            today.weekday() == Weekday::Sun && studied_on(today - Days::new(1))?
+        }
+        _ => false,
+    };
+    Ok(met)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::Tz;
+
+    use super::*;
+
+    fn definition(
+        requirement_type: &str,
+        field: Option<&str>,
+        value: i32,
+    ) -> AchievementDefinition {
+        AchievementDefinition {
+            id: 1,
+            code: "rule".to_owned(),
+            name_en: "Rule".to_owned(),
+            name_zh: "规则".to_owned(),
+            description_en: None,
+            description_zh: None,
+            icon: None,
+            category: "learning".to_owned(),
+            rarity: "common".to_owned(),
+            xp_reward: 10,
+            requirement_type: requirement_type.to_owned(),
+            requirement_value: value,
+            requirement_field: field.map(str::to_owned),
+            is_hidden: None,
+            is_active: Some(true),
+            sort_order: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    fn profile() -> UserProfile {
+        UserProfile {
+            id: 1,
+            user_id: 1,
+            total_xp: 0,
+            current_rank_id: None,
+            current_streak_days: 0,
+            longest_streak_days: 0,
+            last_activity_date: None,
+            total_study_minutes: 0,
+            total_words_mastered: 0,
+            total_stages: 0,
+            total_sessions: 0,
+            joined_at: Utc::now(),
+            updated_at: Utc::now(),
+            native_lang: "en".to_owned(),
+            target_lang: "zh".to_owned(),
+            timezone: "UTC".to_owned(),
+            streak_freezes: 0,
+            leaderboard_opt_out: false,
+            league_tier: 1,
+            placement_level: None,
+            placed_at: None,
+        }
+    }
+
+    #[test]
+    fn threshold_rules_complete_at_the_requirement() {
+        let rule = definition("count", Some("total_words_mastered"), 100);
+        let mut learner = profile();
+        learner.total_words_mastered = 40;
+        let progress = measured_progress(&rule, &learner, AchievementEvent::Studied, 0);
+        assert_eq!(progress, Some(40));
+        assert_eq!(advance(100, None, 40), Some((40, false)));
+        assert_eq!(advance(100, Some(40), 40), None);
+        assert_eq!(advance(100, Some(40), 130), Some((100, true)));
+        assert_eq!(advance(100, None, 0), None);
+
+        let unknown = definition("count", Some("friends"), 1);
+        assert_eq!(
+            measured_progress(&unknown, &learner, AchievementEvent::Studied, 0),
+            None
+        );
+    }
+
+    #[test]
+    fn streak_rules_follow_the_current_streak() {
+        let rule = definition("streak", Some("current_streak_days"), 7);
+        let mut learner = profile();
+        learner.current_streak_days = 3;
+        let progress = measured_progress(&rule, &learner, AchievementEvent::Studied, 5);
+        // A broken streak lowers the progress, a stored 5 becomes 3
+        assert_eq!(progress, Some(3));
+        assert_eq!(advance(7, Some(5), 3), Some((3, false)));
+        learner.current_streak_days = 7;
+        let progress = measured_progress(&rule, &learner, AchievementEvent::Studied, 3);
+        assert_eq!(advance(7, Some(3), progress.unwrap()), Some((7, true)));
+    }
+
+    #[test]
+    fn score_rules_keep_the_best_score() {
+        let rule = definition("score", None, 90);
+        let learner = profile();
+        let scored = |score| AchievementEvent::ReadingScored { score };
+        assert_eq!(measured_progress(&rule, &learner, scored(70), 80), Some(80));
+        assert_eq!(measured_progress(&rule, &learner, scored(95), 80), Some(95));
+        assert_eq!(
+            measured_progress(&rule, &learner, AchievementEvent::Studied, 80),
+            None
+        );
+    }
+
+    #[test]
+    fn special_rules_use_the_local_time() {
+        let at = |day, hour| Utc.with_ymd_and_hms(2024, 6, day, hour, 30, 0).unwrap();
+        let never = |_: NaiveDate| -> QueryResult<bool> { panic!("no other day is needed") };
+        assert!(special_met("early_bird", at(3, 6), never).unwrap());
+        assert!(!special_met("early_bird", at(3, 7), never).unwrap());
+        assert!(special_met("night_owl", at(3, 23), never).unwrap());
+        assert!(!special_met("night_owl", at(3, 22), never).unwrap());
+        assert!(!special_met("speedrun", at(3, 6), never).unwrap());
+
+        // 2024-06-09 is a Sunday, 2024-06-08 the Saturday before it
+        let saturday = NaiveDate::from_ymd_opt(2024, 6, 8).unwrap();
+        let studied = |day: NaiveDate| Ok(day == saturday);
+        assert!(special_met("weekend_warrior", at(9, 12), studied).unwrap());
+        assert!(!special_met("weekend_warrior", at(9, 12), |_| Ok(false)).unwrap());
+        assert!(!special_met("weekend_warrior", at(8, 12), never).unwrap());
+
+        let shanghai: Tz = "Asia/Shanghai".parse().unwrap();
+        // 22:30 UTC on Saturday is 06:30 on Sunday in Shanghai
+        let local = at(8, 22).with_timezone(&shanghai);
+        assert!(special_met("early_bird", local, never).unwrap());
+        assert!(special_met("weekend_warrior", local, studied).unwrap());
+    }
+}
@@ -0,0 +1,50 @@
+//! Learner audio stored under `space_path`
+//!
+//! Recordings and generated speech live in `learn/audios/{user_id}/` and
+//! are referenced from the database by their path relative to `space_path`,
+//! which is also the path they are served from.
+
+use std::path::PathBuf;
+
+use chrono::Utc;
+
+use crate::config::AppConfig;
+
+/// Get the audio storage directory for a user
+pub fn audio_dir(user_id: i64) -> PathBuf {
+    PathBuf::from(&AppConfig::get().space_path)
+        .join("learn/audios")
+        .join(user_id.to_string())
+}
+
+/// Save audio data to file and return the relative path
+pub async fn save_audio_file(
+    user_id: i64,
+    audio_data: &[u8],
+    prefix: &str,
+    format: &str,
+) -> Option<String> {
+    let audio_dir: PathBuf = audio_dir(user_id);
+
+    // Create directory if it doesn't exist
+    if let Err(e) = tokio::fs::create_dir_all(&audio_dir).await {
+        tracing::error!("Failed to create audio directory: {:?}", e);
+        return None;
+    }
+
+    // Generate unique filename with timestamp
+    let timestamp = Utc::now().format("%Y%m%d_%H%M%S_%3f");
+    let filename = format!("{}_{}.{}", prefix, timestamp, format);
+    let file_path = audio_dir.join(&filename);
+
+    // Write audio data to file
+    if let Err(e) = tokio::fs::write(&file_path, audio_data).await {
+        tracing::error!("Failed to save audio file: {:?}", e);
+        return None;
+    }
+
+    // Return relative path for database storage
+    let relative_path = format!("learn/audios/{}/{}", user_id, filename);
+    tracing::info!("Saved audio file: {}", relative_path);
+    Some(relative_path)
+}
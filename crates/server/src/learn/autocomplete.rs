@@ -0,0 +1,134 @@
+//! Completions of a partly typed word
+//!
+//! Answered from indexes on every keystroke. One and two letter prefixes
+//! match too many words to sort, so they read their own indexes (see the
+//! `dict_word_autocomplete` migration) in the order completions are ranked,
+//! most frequent first, and the database stops after `limit` rows. Longer
+//! prefixes match few enough words to sort: they are found through the
+//! `text_pattern_ops` index on `word_lower`, or its trigram index when the
+//! plan is generic. Each completion carries a short gloss, Chinese when the
+//! dictionary has one, else the start of the first English definition.
+
+use std::collections::HashMap;
+
+use diesel::dsl::sql;
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+use salvo::oapi::ToSchema;
+use serde::Serialize;
+
+use super::enrich::chinese_glosses;
+use super::extract::truncate;
+use crate::db::full_text_search::escape_like;
+use crate::db::schema::*;
+
+/// Longest gloss returned, in characters
+const GLOSS_CHARS: usize = 40;
+
+/// A dictionary word starting with what was typed
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Completion {
+    pub word_id: i64,
+    pub word: String,
+    /// 0-100, higher is more common
+    pub frequency: Option<i16>,
+    /// Short meaning, Chinese when available
+    pub gloss: Option<String>,
+}
+
+/// Index a prefix is looked up with
+#[derive(Debug, PartialEq, Eq)]
+enum Lookup {
+    /// `left(word_lower, n) = prefix`, for prefixes of one or two characters
+    Leading(u8),
+    /// `word_lower LIKE pattern`
+    Like(String),
+}
+
+fn lookup_for(prefix: &str) -> Lookup {
+    match prefix.chars().count() {
+        n @ (1 | 2) => Lookup::Leading(n as u8),
+        _ => Lookup::Like(format!("{}%", escape_like(prefix))),
+    }
+}
+
+/// The most frequent words starting with `prefix`, with a gloss each
+pub fn complete(conn: &mut PgConnection, prefix: &str, limit: i64) -> QueryResult<Vec<Completion>> {
+    let prefix = prefix.trim().to_lowercase();
+    if prefix.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut query = dict_words::table
+        .filter(
+            dict_words::is_active
+                .eq(true)
+                .or(dict_words::is_active.is_null()),
+        )
+        .into_boxed();
+    query = match lookup_for(&prefix) {
+        // `n` is written into the SQL rather than bound, a parameter would
+        // keep generic plans from matching the expression indexes
+        Lookup::Leading(n) => {
+            query.filter(sql::<Text>(&format!("left(word_lower, {n})")).eq(prefix))
+        }
+        Lookup::Like(pattern) => query.filter(dict_words::word_lower.like(pattern)),
+    };
+    let words = query
+        .order((
+            dict_words::frequency.desc().nulls_last(),
+            dict_words::word_lower.asc(),
+        ))
+        .limit(limit)
+        .select((dict_words::id, dict_words::word, dict_words::frequency))
+        .load::<(i64, String, Option<i16>)>(conn)?;
+
+    let word_ids: Vec<i64> = words.iter().map(|(id, _, _)| *id).collect();
+    let mut glosses = chinese_glosses(conn, &word_ids)?;
+    let missing: Vec<i64> = word_ids
+        .iter()
+        .copied()
+        .filter(|id| !glosses.contains_key(id))
+        .collect();
+    if !missing.is_empty() {
+        let mut english: HashMap<i64, String> = HashMap::new();
+        for (word_id, definition) in dict_definitions::table
+            .filter(dict_definitions::word_id.eq_any(&missing))
+            .filter(dict_definitions::language.eq("en"))
+            .order((
+                dict_definitions::definition_order.asc().nulls_last(),
+                dict_definitions::id.asc(),
+            ))
+            .select((dict_definitions::word_id, dict_definitions::definition))
+            .load::<(i64, String)>(conn)?
+        {
+            english.entry(word_id).or_insert(definition);
+        }
+        glosses.extend(english);
+    }
+
+    Ok(words
+        .into_iter()
+        .map(|(word_id, word, frequency)| Completion {
+            word_id,
+            word,
+            frequency,
+            gloss: glosses
+                .remove(&word_id)
+                .map(|gloss| truncate(gloss.trim(), GLOSS_CHARS)),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_prefixes_use_leading_indexes() {
+        assert_eq!(lookup_for("a"), Lookup::Leading(1));
+        assert_eq!(lookup_for("中国"), Lookup::Leading(2));
+        assert_eq!(lookup_for("app"), Lookup::Like("app%".to_owned()));
+        assert_eq!(lookup_for("50%"), Lookup::Like("50\\%%".to_owned()));
+    }
+}
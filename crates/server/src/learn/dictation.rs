@@ -0,0 +1,691 @@
+//! Dictation graded by the server
+//!
+//! A word, or an example sentence using it, is played to the learner who
+//! types what they heard. The answer is compared character by character with
+//! the expected text and every mistaken word is classified as a typo (a small
+//! slip in the right word), a wrong form (another inflection of it) or a wrong
+//! word, which is also what a slip that spells another dictionary word is.
+//!
+//! Dictations are stored as write practices and feed the word's review
+//! schedule when it is in the learner's vocabulary.
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use salvo::oapi::ToSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::achievement::{self, AchievementEvent};
+use super::phoneme::align;
+use super::review::{ReviewItemType, ReviewResult, grade_item};
+use super::scheduler::ReviewGrade;
+use super::session::normalize_answer;
+use super::stats::{self, Activity, PracticeItem};
+use crate::config::SchedulerConfig;
+use crate::db::schema::*;
+use crate::models::achievement::AchievementBadge;
+use crate::models::learn::*;
+
+/// Endings that turn a word into another form of it
+const INFLECTIONS: &[&str] = &["s", "es", "d", "ed", "ing", "er", "est", "ly", "ies", "ied"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DictationMode {
+    /// The word alone
+    Word,
+    /// An example sentence using the word
+    Sentence,
+}
+
+impl DictationMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DictationMode::Word => "word",
+            DictationMode::Sentence => "sentence",
+        }
+    }
+}
+
+/// Mistake in a word, from least to most severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    Correct,
+    /// The right word with a slip of a letter or two
+    Typo,
+    /// Another form of the right word ("ran" for "run")
+    WrongForm,
+    /// A different or missing word
+    WrongWord,
+}
+
+impl ErrorKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorKind::Correct => "correct",
+            ErrorKind::Typo => "typo",
+            ErrorKind::WrongForm => "wrong_form",
+            ErrorKind::WrongWord => "wrong_word",
+        }
+    }
+
+    fn review_grade(self) -> ReviewGrade {
+        match self {
+            ErrorKind::Correct => ReviewGrade::Good,
+            ErrorKind::Typo | ErrorKind::WrongForm => ReviewGrade::Hard,
+            ErrorKind::WrongWord => ReviewGrade::Again,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffOp {
+    Equal,
+    /// Typed but not expected
+    Insert,
+    /// Expected but not typed
+    Delete,
+    Replace,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DiffSegment {
+    pub op: DiffOp,
+    pub expected: String,
+    pub actual: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct WordError {
+    /// Absent for an extra word
+    pub expected: Option<String>,
+    /// Absent for a missing word
+    pub actual: Option<String>,
+    pub kind: ErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Grading {
+    /// The most severe mistake
+    pub kind: ErrorKind,
+    /// 1 to 5 as for other write practices
+    pub success_level: i32,
+    /// Share of matching characters, 0-100
+    pub accuracy: i32,
+    pub diff: Vec<DiffSegment>,
+    pub word_errors: Vec<WordError>,
+}
+
+/// What will be dictated, before the audio is ready
+#[derive(Debug, Clone)]
+pub struct DictationTarget {
+    pub word_id: i64,
+    pub mode: DictationMode,
+    pub text: String,
+    /// Stored pronunciation of the word, used instead of speech synthesis
+    pub pronunciation_url: Option<String>,
+    /// Chinese gloss of the word
+    pub hint: Option<String>,
+}
+
+/// A dictation as shown before it is answered, without the expected text
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DictationView {
+    pub id: i64,
+    pub practice_id: String,
+    pub mode: DictationMode,
+    pub audio_path: Option<String>,
+    pub hint: Option<String>,
+    /// Letters in a word or words in a sentence
+    pub length: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DictationResult {
+    pub practice: WritePractice,
+    pub grading: Grading,
+    /// New schedule of the word when it is in the vocabulary
+    pub review: Option<ReviewResult>,
+    pub unlocked: Vec<AchievementBadge>,
+}
+
+/// Pick what to dictate: the given word, or the most overdue vocabulary word
+/// that has a dictionary entry
+///
+/// Sentence mode falls back to the word alone when it has no example.
+pub fn prepare(
+    conn: &mut PgConnection,
+    user_id: i64,
+    word_id: Option<i64>,
+    mode: DictationMode,
+) -> QueryResult<Option<DictationTarget>> {
+    let word_id = match word_id {
+        Some(word_id) => word_id,
+        None => {
+            let due = learn_vocabularies::table
+                .filter(learn_vocabularies::user_id.eq(user_id))
+                .filter(learn_vocabularies::word_id.is_not_null())
+                .order((
+                    learn_vocabularies::next_review_at.asc().nulls_first(),
+                    learn_vocabularies::id.asc(),
+                ))
+                .select(learn_vocabularies::word_id)
+                .first::<Option<i64>>(conn)
+                .optional()?
+                .flatten();
+            let Some(word_id) = due else {
+                return Ok(None);
+            };
+            word_id
+        }
+    };
+    let Some(word) = dict_words::table
+        .find(word_id)
+        .select(dict_words::word)
+        .first::<String>(conn)
+        .optional()?
+    else {
+        return Ok(None);
+    };
+
+    let hint = learn_vocabularies::table
+        .filter(learn_vocabularies::user_id.eq(user_id))
+        .filter(learn_vocabularies::word_id.eq(word_id))
+        .select(learn_vocabularies::word_zh)
+        .first::<Option<String>>(conn)
+        .optional()?
+        .flatten();
+
+    let sentence = match mode {
+        DictationMode::Sentence => dict_word_sentences::table
+            .inner_join(
+                dict_sentences::table.on(dict_sentences::id.eq(dict_word_sentences::sentence_id)),
+            )
+            .filter(dict_word_sentences::word_id.eq(word_id))
+            .filter(dict_sentences::language.eq("en"))
+            .order(dict_word_sentences::priority_order.desc().nulls_last())
+            .select(dict_sentences::sentence)
+            .first::<String>(conn)
+            .optional()?,
+        DictationMode::Word => None,
+    };
+    if let Some(sentence) = sentence {
+        return Ok(Some(DictationTarget {
+            word_id,
+            mode: DictationMode::Sentence,
+            text: sentence,
+            pronunciation_url: None,
+            hint,
+        }));
+    }
+
+    let pronunciation_url = dict_pronunciations::table
+        .filter(dict_pronunciations::word_id.eq(word_id))
+        .filter(
+            dict_pronunciations::audio_url
+                .is_not_null()
+                .or(dict_pronunciations::audio_path.is_not_null()),
+        )
+        .order((
+            dict_pronunciations::is_primary.desc().nulls_last(),
+            dict_pronunciations::id.asc(),
+        ))
+        .select((
+            dict_pronunciations::audio_url,
+            dict_pronunciations::audio_path,
+        ))
+        .first::<(Option<String>, Option<String>)>(conn)
+        .optional()?
+        .and_then(|(url, path)| url.or(path));
+    Ok(Some(DictationTarget {
+        word_id,
+        mode: DictationMode::Word,
+        text: word,
+        pronunciation_url,
+        hint,
+    }))
+}
+
+/// Store a dictation waiting for its answer
+pub fn create(
+    conn: &mut PgConnection,
+    user_id: i64,
+    practice_id: String,
+    target: DictationTarget,
+    audio_path: String,
+) -> QueryResult<DictationView> {
+    let practice = diesel::insert_into(learn_write_practices::table)
+        .values(&NewDictation {
+            user_id,
+            word_id: target.word_id,
+            practice_id,
+            mode: target.mode.as_str().to_owned(),
+            expected_text: target.text.clone(),
+            audio_path: Some(audio_path),
+        })
+        .get_result::<WritePractice>(conn)?;
+    Ok(DictationView {
+        id: practice.id,
+        practice_id: practice.practice_id,
+        mode: target.mode,
+        audio_path: practice.audio_path,
+        hint: target.hint,
+        length: match target.mode {
+            DictationMode::Word => target.text.chars().filter(|c| c.is_alphabetic()).count(),
+            DictationMode::Sentence => normalize_answer(&target.text).split(' ').count(),
+        },
+    })
+}
+
+/// Grade the answer to a dictation and update the word's schedule
+///
+/// Returns `NotFound` if there is no such dictation of the user. Answering
+/// twice returns the stored grading.
+pub fn answer(
+    conn: &mut PgConnection,
+    user_id: i64,
+    practice_id: i64,
+    answer: &str,
+    now: DateTime<Utc>,
+    config: &SchedulerConfig,
+) -> QueryResult<DictationResult> {
+    conn.transaction(|conn| {
+        let practice = learn_write_practices::table
+            .filter(learn_write_practices::id.eq(practice_id))
+            .filter(learn_write_practices::user_id.eq(user_id))
+            .filter(learn_write_practices::expected_text.is_not_null())
+            .for_update()
+            .first::<WritePractice>(conn)?;
+        if practice.answered_at.is_some()
+            && let Some(grading) = practice
+                .diff
+                .clone()
+                .and_then(|diff| serde_json::from_value::<Grading>(diff).ok())
+        {
+            return Ok(DictationResult {
+                practice,
+                grading,
+                review: None,
+                unlocked: Vec::new(),
+            });
+        }
+
+        let mut forms: Vec<String> = dict_forms::table
+            .filter(dict_forms::word_id.eq(practice.word_id))
+            .select(dict_forms::form)
+            .load::<String>(conn)?;
+        forms.extend(
+            dict_words::table
+                .find(practice.word_id)
+                .select(dict_words::word)
+                .first::<String>(conn)
+                .optional()?,
+        );
+        let typed: Vec<String> = normalize_answer(answer)
+            .split(' ')
+            .filter(|w| !w.is_empty())
+            .map(str::to_owned)
+            .collect();
+        let words: HashSet<String> = dict_words::table
+            .filter(dict_words::word_lower.eq_any(&typed))
+            .select(dict_words::word_lower)
+            .load::<String>(conn)?
+            .into_iter()
+            .collect();
+        let expected = practice.expected_text.clone().unwrap_or_default();
+        let grading = grade(&expected, answer, &forms, &words);
+
+        let practice = diesel::update(learn_write_practices::table.find(practice.id))
+            .set((
+                learn_write_practices::success_level.eq(Some(grading.success_level)),
+                learn_write_practices::user_answer.eq(Some(answer)),
+                learn_write_practices::error_kind.eq(Some(grading.kind.as_str())),
+                learn_write_practices::accuracy.eq(Some(grading.accuracy)),
+                learn_write_practices::diff.eq(Some(json!(grading))),
+                learn_write_practices::answered_at.eq(Some(now)),
+                learn_write_practices::updated_at.eq(now),
+            ))
+            .get_result::<WritePractice>(conn)?;
+
+        let vocabulary_id = learn_vocabularies::table
+            .filter(learn_vocabularies::user_id.eq(user_id))
+            .filter(learn_vocabularies::word_id.eq(practice.word_id))
+            .select(learn_vocabularies::id)
+            .first::<i64>(conn)
+            .optional()?;
+        let review = match vocabulary_id {
+            Some(id) => Some(grade_item(
+                conn,
+                user_id,
+                ReviewItemType::Vocabulary,
+                id,
+                grading.kind.review_grade(),
+                now,
+                config,
+            )?),
+            None => None,
+        };
+        let item = PracticeItem::Dictation {
+            word_id: practice.word_id,
+        };
+        stats::record_activity(conn, user_id, Activity::Practice { item: Some(item) }, now)?;
+        let unlocked =
+            achievement::evaluate_and_notify(conn, user_id, AchievementEvent::Studied, now)?;
+
+        Ok(DictationResult {
+            practice,
+            grading,
+            review,
+            unlocked,
+        })
+    })
+}
+
+/// Grade a typed answer against the expected word or sentence
+///
+/// `forms` are the other forms of the dictated word, recognized as wrong
+/// forms even when irregular. `words` are the typed words found in the
+/// dictionary, which are never taken for typos.
+pub fn grade(expected: &str, answer: &str, forms: &[String], words: &HashSet<String>) -> Grading {
+    let expected = normalize_answer(expected);
+    let answer = normalize_answer(answer);
+    let forms: Vec<String> = forms.iter().map(|f| normalize_answer(f)).collect();
+
+    let expected_words: Vec<&str> = expected.split(' ').filter(|w| !w.is_empty()).collect();
+    let answer_words: Vec<&str> = answer.split(' ').filter(|w| !w.is_empty()).collect();
+    let word_errors: Vec<WordError> = align(&expected_words, &answer_words)
+        .into_iter()
+        .filter_map(|(e, a)| {
+            let e = e.map(|e| expected_words[e]);
+            let a = a.map(|a| answer_words[a]);
+            let kind = match (e, a) {
+                (Some(e), Some(a)) => classify_word(e, a, &forms, words),
+                _ => ErrorKind::WrongWord,
+            };
+            (kind != ErrorKind::Correct).then(|| WordError {
+                expected: e.map(str::to_owned),
+                actual: a.map(str::to_owned),
+                kind,
+            })
+        })
+        .collect();
+
+    let kind = word_errors
+        .iter()
+        .map(|e| e.kind)
+        .max()
+        .unwrap_or(ErrorKind::Correct);
+    let diff = char_diff(&expected, &answer);
+    let equal: usize = diff
+        .iter()
+        .filter(|s| s.op == DiffOp::Equal)
+        .map(|s| s.expected.chars().count())
+        .sum();
+    let longest = expected.chars().count().max(answer.chars().count());
+    let accuracy = if longest == 0 {
+        100
+    } else {
+        (equal * 100 / longest) as i32
+    };
+    let success_level = match kind {
+        ErrorKind::Correct => 5,
+        ErrorKind::Typo => 4,
+        ErrorKind::WrongForm => 3,
+        ErrorKind::WrongWord if accuracy >= 70 => 2,
+        ErrorKind::WrongWord => 1,
+    };
+
+    Grading {
+        kind,
+        success_level,
+        accuracy,
+        diff,
+        word_errors,
+    }
+}
+
+/// Classify a typed word against the expected one
+///
+/// A typed word in `words` is a real word, so it is wrong even when it is a
+/// letter away from the expected one ("mouse" for "house").
+fn classify_word(
+    expected: &str,
+    actual: &str,
+    forms: &[String],
+    words: &HashSet<String>,
+) -> ErrorKind {
+    if expected == actual {
+        return ErrorKind::Correct;
+    }
+    let listed_forms = forms.iter().any(|f| f == expected) && forms.iter().any(|f| f == actual);
+    if listed_forms || adds_inflection(expected, actual) || adds_inflection(actual, expected) {
+        return ErrorKind::WrongForm;
+    }
+    let length = expected.chars().count();
+    let max_typos = match length {
+        0..=4 => 1,
+        5..=8 => 2,
+        _ => 3,
+    };
+    if !words.contains(actual) && edit_distance(expected, actual) <= max_typos {
+        return ErrorKind::Typo;
+    }
+    if !stems(expected).is_disjoint(&stems(actual)) {
+        return ErrorKind::WrongForm;
+    }
+    ErrorKind::WrongWord
+}
+
+/// Whether `inflected` is `base` with an inflectional ending
+fn adds_inflection(base: &str, inflected: &str) -> bool {
+    inflected
+        .strip_prefix(base)
+        .is_some_and(|ending| INFLECTIONS.contains(&ending))
+}
+
+/// Possible stems of a word: the word itself and what remains without an
+/// inflectional ending, allowing for a dropped "e", "y" turned into "i"
+/// and doubled final consonants
+fn stems(word: &str) -> HashSet<String> {
+    let mut stems = HashSet::from([word.to_owned()]);
+    for ending in INFLECTIONS {
+        let Some(stem) = word.strip_suffix(ending) else {
+            continue;
+        };
+        if stem.chars().count() < 2 {
+            continue;
+        }
+        stems.insert(stem.to_owned());
+        stems.insert(format!("{stem}e"));
+        if ending.starts_with('i') {
+            stems.insert(format!("{stem}y"));
+        }
+        let mut chars = stem.chars().rev();
+        if let (Some(last), Some(before)) = (chars.next(), chars.next())
+            && last == before
+        {
+            stems.insert(stem[..stem.len() - last.len_utf8()].to_owned());
+        }
+    }
+    stems
+}
+
+/// Edit distance counting an adjacent transposition as one edit
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for j in 0..=b.len() {
+        d[0][j] = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// Character diff of the answer against the expected text, runs of the same
+/// operation merged
+fn char_diff(expected: &str, actual: &str) -> Vec<DiffSegment> {
+    let expected: Vec<char> = expected.chars().collect();
+    let actual: Vec<char> = actual.chars().collect();
+    let mut segments: Vec<DiffSegment> = Vec::new();
+    for (e, a) in align(&expected, &actual) {
+        let e = e.map(|e| expected[e]);
+        let a = a.map(|a| actual[a]);
+        let op = match (e, a) {
+            (Some(e), Some(a)) if e == a => DiffOp::Equal,
+            (Some(_), Some(_)) => DiffOp::Replace,
+            (Some(_), None) => DiffOp::Delete,
+            (None, _) => DiffOp::Insert,
+        };
+        let segment = match segments.last_mut() {
+            Some(last) if last.op == op => last,
+            _ => {
+                segments.push(DiffSegment {
+                    op,
+                    expected: String::new(),
+                    actual: String::new(),
+                });
+                segments.last_mut().expect("just pushed")
+            }
+        };
+        segment.expected.extend(e);
+        segment.actual.extend(a);
+    }
+    segments
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forms(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    fn known(words: &[&str]) -> HashSet<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn classifies_word_mistakes() {
+        let run = forms(&["run", "ran", "runs", "running"]);
+        let none = HashSet::new();
+        assert_eq!(
+            classify_word("running", "running", &run, &none),
+            ErrorKind::Correct
+        );
+        assert_eq!(
+            classify_word("running", "runing", &run, &none),
+            ErrorKind::Typo
+        );
+        assert_eq!(
+            classify_word("receive", "recieve", &[], &none),
+            ErrorKind::Typo
+        );
+        assert_eq!(
+            classify_word("run", "ran", &run, &none),
+            ErrorKind::WrongForm
+        );
+        assert_eq!(
+            classify_word("walk", "walks", &[], &none),
+            ErrorKind::WrongForm
+        );
+        assert_eq!(
+            classify_word("walked", "walking", &[], &none),
+            ErrorKind::WrongForm
+        );
+        assert_eq!(
+            classify_word("studied", "studying", &[], &none),
+            ErrorKind::WrongForm
+        );
+        assert_eq!(classify_word("house", "hause", &[], &none), ErrorKind::Typo);
+        assert_eq!(
+            classify_word("house", "mouse", &[], &known(&["mouse"])),
+            ErrorKind::WrongWord
+        );
+        assert_eq!(
+            classify_word("house", "garden", &[], &none),
+            ErrorKind::WrongWord
+        );
+    }
+
+    #[test]
+    fn diffs_characters() {
+        let replaced = char_diff("cat", "cut");
+        assert_eq!(replaced[1].op, DiffOp::Replace);
+
+        let diff = char_diff("receive", "recieve");
+        let ops: Vec<(DiffOp, &str, &str)> = diff
+            .iter()
+            .map(|s| (s.op, s.expected.as_str(), s.actual.as_str()))
+            .collect();
+        assert_eq!(
+            ops,
+            vec![
+                (DiffOp::Equal, "rec", "rec"),
+                (DiffOp::Insert, "", "i"),
+                (DiffOp::Equal, "e", "e"),
+                (DiffOp::Delete, "i", ""),
+                (DiffOp::Equal, "ve", "ve"),
+            ]
+        );
+    }
+
+    #[test]
+    fn grades_sentences_by_the_worst_mistake() {
+        let grading = grade(
+            "She runs every morning.",
+            "she run every mornin",
+            &[],
+            &known(&["she", "run", "every"]),
+        );
+        assert_eq!(grading.kind, ErrorKind::WrongForm);
+        assert_eq!(grading.success_level, 3);
+        assert_eq!(
+            grading.word_errors,
+            vec![
+                WordError {
+                    expected: Some("runs".to_owned()),
+                    actual: Some("run".to_owned()),
+                    kind: ErrorKind::WrongForm,
+                },
+                WordError {
+                    expected: Some("morning".to_owned()),
+                    actual: Some("mornin".to_owned()),
+                    kind: ErrorKind::Typo,
+                },
+            ]
+        );
+
+        let missing = grade(
+            "She runs every morning",
+            "She runs morning",
+            &[],
+            &HashSet::new(),
+        );
+        assert_eq!(missing.kind, ErrorKind::WrongWord);
+        assert_eq!(missing.word_errors[0].expected.as_deref(), Some("every"));
+        assert_eq!(missing.word_errors[0].actual, None);
+
+        let perfect = grade("Hello, world!", "hello world", &[], &HashSet::new());
+        assert_eq!(perfect.kind, ErrorKind::Correct);
+        assert_eq!((perfect.success_level, perfect.accuracy), (5, 100));
+    }
+}
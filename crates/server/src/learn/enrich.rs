@@ -0,0 +1,259 @@
+//! Dictionary data for vocabulary entries
+//!
+//! A vocabulary word is linked to the dictionary entry of its lemma, found the
+//! same way as for pasted texts (headword or inflected form), and gets a
+//! Chinese gloss when the learner gave none. Words added through the API are
+//! enriched right away; the background job catches up on older rows.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use salvo::oapi::ToSchema;
+use serde::Serialize;
+
+use super::extract::lemmatize;
+use crate::db::schema::*;
+
+/// Rows enriched per batch of the backfill job
+pub const BACKFILL_BATCH: i64 = 500;
+/// Chinese senses joined into one gloss
+const GLOSS_SENSES: usize = 3;
+
+/// Pronunciation, meaning and usage of a dictionary entry
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct WordDetails {
+    pub ipa: Option<String>,
+    pub audio_url: Option<String>,
+    pub definition: Option<String>,
+    pub example: Option<String>,
+}
+
+/// Link the given vocabulary rows to the dictionary and fill missing glosses
+///
+/// Returns the number of rows linked to an entry.
+pub fn enrich_vocabulary(
+    conn: &mut PgConnection,
+    vocabulary_ids: &[i64],
+    now: DateTime<Utc>,
+) -> QueryResult<usize> {
+    let rows = learn_vocabularies::table
+        .filter(learn_vocabularies::id.eq_any(vocabulary_ids))
+        .select((
+            learn_vocabularies::id,
+            learn_vocabularies::word,
+            learn_vocabularies::word_zh,
+        ))
+        .load::<(i64, String, Option<String>)>(conn)?;
+    if rows.is_empty() {
+        return Ok(0);
+    }
+
+    let mut words: Vec<String> = rows
+        .iter()
+        .map(|(_, word, _)| word.to_lowercase())
+        .collect();
+    words.sort();
+    words.dedup();
+    let lemmas = lemmatize(conn, &words)?;
+    let mut word_ids: Vec<i64> = lemmas.values().map(|(_, word_id, ..)| *word_id).collect();
+    word_ids.sort_unstable();
+    word_ids.dedup();
+    let glosses = chinese_glosses(conn, &word_ids)?;
+
+    let mut linked = 0;
+    for (id, word, word_zh) in rows {
+        let word_id = lemmas
+            .get(&word.to_lowercase())
+            .map(|(_, word_id, ..)| *word_id);
+        let word_zh = word_zh
+            .filter(|w| !w.trim().is_empty())
+            .or_else(|| word_id.and_then(|w| glosses.get(&w).cloned()));
+        diesel::update(learn_vocabularies::table.find(id))
+            .set((
+                learn_vocabularies::word_id.eq(word_id),
+                learn_vocabularies::word_zh.eq(word_zh),
+                learn_vocabularies::enriched_at.eq(Some(now)),
+            ))
+            .execute(conn)?;
+        linked += usize::from(word_id.is_some());
+    }
+    Ok(linked)
+}
+
+/// Enrich one batch of rows that were never looked up
+///
+/// Returns the number of rows processed, zero once all are done.
+pub fn backfill(conn: &mut PgConnection, now: DateTime<Utc>) -> QueryResult<usize> {
+    let ids = learn_vocabularies::table
+        .filter(learn_vocabularies::enriched_at.is_null())
+        .order(learn_vocabularies::id.asc())
+        .limit(BACKFILL_BATCH)
+        .select(learn_vocabularies::id)
+        .load::<i64>(conn)?;
+    if ids.is_empty() {
+        return Ok(0);
+    }
+    conn.transaction(|conn| enrich_vocabulary(conn, &ids, now))?;
+    Ok(ids.len())
+}
+
+/// IPA, audio, primary English definition and an example of each entry
+pub fn word_details(
+    conn: &mut PgConnection,
+    word_ids: &[i64],
+) -> QueryResult<HashMap<i64, WordDetails>> {
+    let mut details: HashMap<i64, WordDetails> = HashMap::new();
+    if word_ids.is_empty() {
+        return Ok(details);
+    }
+
+    let pronunciations = dict_pronunciations::table
+        .filter(dict_pronunciations::word_id.eq_any(word_ids))
+        .order((
+            dict_pronunciations::is_primary.desc().nulls_last(),
+            dict_pronunciations::id.asc(),
+        ))
+        .select((
+            dict_pronunciations::word_id,
+            dict_pronunciations::ipa,
+            dict_pronunciations::audio_url,
+            dict_pronunciations::audio_path,
+        ))
+        .load::<(i64, String, Option<String>, Option<String>)>(conn)?;
+    for (word_id, ipa, audio_url, audio_path) in pronunciations {
+        let entry = details.entry(word_id).or_default();
+        if entry.ipa.is_none() {
+            entry.ipa = Some(ipa);
+            entry.audio_url = audio_url.or(audio_path);
+        }
+    }
+
+    let definitions = dict_definitions::table
+        .filter(dict_definitions::word_id.eq_any(word_ids))
+        .filter(dict_definitions::language.eq("en"))
+        .order((
+            dict_definitions::is_primary.desc().nulls_last(),
+            dict_definitions::definition_order.asc().nulls_last(),
+            dict_definitions::id.asc(),
+        ))
+        .select((dict_definitions::word_id, dict_definitions::definition))
+        .load::<(i64, String)>(conn)?;
+    for (word_id, definition) in definitions {
+        details
+            .entry(word_id)
+            .or_default()
+            .definition
+            .get_or_insert(definition);
+    }
+
+    let examples = dict_word_sentences::table
+        .inner_join(
+            dict_sentences::table.on(dict_sentences::id.eq(dict_word_sentences::sentence_id)),
+        )
+        .filter(dict_word_sentences::word_id.eq_any(word_ids))
+        .filter(dict_sentences::language.eq("en"))
+        .order((
+            dict_word_sentences::priority_order.desc().nulls_last(),
+            dict_word_sentences::id.asc(),
+        ))
+        .select((dict_word_sentences::word_id, dict_sentences::sentence))
+        .load::<(i64, String)>(conn)?;
+    for (word_id, sentence) in examples {
+        details
+            .entry(word_id)
+            .or_default()
+            .example
+            .get_or_insert(sentence);
+    }
+
+    Ok(details)
+}
+
+/// Chinese gloss of each entry
+///
+/// Translations of the word come first, then translations of its English
+/// definitions, then definitions written in Chinese.
+pub(super) fn chinese_glosses(
+    conn: &mut PgConnection,
+    word_ids: &[i64],
+) -> QueryResult<HashMap<i64, String>> {
+    let mut senses: HashMap<i64, Vec<String>> = HashMap::new();
+    if word_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let word_translations = dict_translations::table
+        .filter(dict_translations::origin_entity.eq("word"))
+        .filter(dict_translations::origin_id.eq_any(word_ids))
+        .filter(dict_translations::language.like("zh%"))
+        .order(dict_translations::id.asc())
+        .select((dict_translations::origin_id, dict_translations::translation))
+        .load::<(i64, String)>(conn)?;
+    let definition_translations = dict_translations::table
+        .inner_join(
+            dict_definitions::table.on(dict_definitions::id.eq(dict_translations::origin_id)),
+        )
+        .filter(dict_translations::origin_entity.eq("definition"))
+        .filter(dict_definitions::word_id.eq_any(word_ids))
+        .filter(dict_translations::language.like("zh%"))
+        .order((
+            dict_definitions::definition_order.asc().nulls_last(),
+            dict_translations::id.asc(),
+        ))
+        .select((dict_definitions::word_id, dict_translations::translation))
+        .load::<(i64, String)>(conn)?;
+    let definitions = dict_definitions::table
+        .filter(dict_definitions::word_id.eq_any(word_ids))
+        .filter(dict_definitions::language.like("zh%"))
+        .order((
+            dict_definitions::definition_order.asc().nulls_last(),
+            dict_definitions::id.asc(),
+        ))
+        .select((dict_definitions::word_id, dict_definitions::definition))
+        .load::<(i64, String)>(conn)?;
+
+    for source in [word_translations, definition_translations, definitions] {
+        let mut found: HashMap<i64, Vec<String>> = HashMap::new();
+        for (word_id, sense) in source {
+            found.entry(word_id).or_default().push(sense);
+        }
+        for (word_id, found) in found {
+            senses.entry(word_id).or_insert(found);
+        }
+    }
+    Ok(senses
+        .into_iter()
+        .filter_map(|(word_id, senses)| gloss(&senses).map(|gloss| (word_id, gloss)))
+        .collect())
+}
+
+/// Join the first distinct senses into one gloss
+fn gloss(senses: &[String]) -> Option<String> {
+    let mut picked: Vec<&str> = Vec::new();
+    for sense in senses {
+        let sense = sense.trim();
+        if !sense.is_empty() && !picked.contains(&sense) {
+            picked.push(sense);
+        }
+        if picked.len() == GLOSS_SENSES {
+            break;
+        }
+    }
+    (!picked.is_empty()).then(|| picked.join("；"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn joins_distinct_senses() {
+        let senses: Vec<String> = ["跑", " 跑 ", "", "经营", "运行", "竞选"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(gloss(&senses).as_deref(), Some("跑；经营；运行"));
+        assert_eq!(gloss(&[]), None);
+    }
+}
@@ -0,0 +1,407 @@
+//! Unknown vocabulary in a pasted text
+//!
+//! The text is split into sentences and word tokens. Each token is brought
+//! back to its dictionary lemma, either because it is a headword itself or an
+//! inflected form the lookup lemmatizer resolves, and compared with the
+//! learner's vocabulary. Function words are left out since nobody studies "the".
+
+use std::collections::HashMap;
+
+use diesel::prelude::*;
+use salvo::oapi::ToSchema;
+use serde::Serialize;
+
+use super::achievement::MASTERED_LEVEL;
+use super::inflection;
+use crate::db::schema::*;
+
+/// Longest context sentence kept with a word
+pub const MAX_CONTEXT_CHARS: usize = 300;
+
+/// Frequent function words never worth listing
+const STOP_WORDS: &[&str] = &[
+    "a", "about", "after", "all", "also", "am", "an", "and", "any", "are", "as", "at", "be",
+    "because", "been", "before", "being", "but", "by", "can", "could", "did", "do", "does", "for",
+    "from", "had", "has", "have", "he", "her", "hers", "him", "his", "how", "i", "if", "in",
+    "into", "is", "it", "its", "me", "my", "no", "not", "of", "on", "or", "our", "ours", "she",
+    "so", "than", "that", "the", "their", "them", "then", "there", "these", "they", "this",
+    "those", "to", "up", "us", "was", "we", "were", "what", "when", "where", "which", "who",
+    "whom", "why", "will", "with", "would", "you", "your", "yours",
+];
+
+/// Contractions whose stem is not the part before the apostrophe
+const IRREGULAR_CONTRACTIONS: &[(&str, &str)] = &[
+    ("can't", "can"),
+    ("won't", "will"),
+    ("shan't", "shall"),
+    ("ain't", "be"),
+];
+
+/// Clitics dropped from the end of a token
+const CLITICS: &[&str] = &["n't", "'s", "'re", "'ve", "'ll", "'d", "'m"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WordStatus {
+    /// Not in the learner's vocabulary
+    New,
+    /// In the vocabulary below the mastered level
+    Learning,
+    /// In the vocabulary and mastered
+    Known,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ExtractedWord {
+    pub lemma: String,
+    /// Dictionary entry of the lemma, absent for words the dictionary lacks
+    pub word_id: Option<i64>,
+    pub status: WordStatus,
+    /// Forms of the word as they appear in the text
+    pub forms: Vec<String>,
+    pub occurrences: i32,
+    /// Dictionary frequency score (0-100, higher is more common)
+    pub frequency: Option<i16>,
+    pub difficulty: Option<i16>,
+    pub vocabulary_id: Option<i64>,
+    pub mastery_level: Option<i32>,
+    /// First sentence the word appears in
+    pub context: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TextVocabulary {
+    /// Word tokens in the text, function words excluded
+    pub total_tokens: i32,
+    pub known_count: i32,
+    pub learning_count: i32,
+    pub new_count: i32,
+    /// Share of the tokens that are known words, 0-100
+    pub known_coverage: i32,
+    /// New words first, then learning and known ones, each by frequency
+    pub words: Vec<ExtractedWord>,
+}
+
+/// A word token with the sentence it belongs to
+#[derive(Debug, Clone, PartialEq)]
+struct Token {
+    /// Lowercased form as written, without clitics
+    form: String,
+    sentence: usize,
+}
+
+/// Lemma, dictionary id, frequency and difficulty of a dictionary entry
+pub(super) type Entry = (String, i64, Option<i16>, Option<i16>);
+
+/// Classify the words of a text against the learner's vocabulary
+pub fn extract(conn: &mut PgConnection, user_id: i64, text: &str) -> QueryResult<TextVocabulary> {
+    let sentences = split_sentences(text);
+    let tokens = tokenize(&sentences);
+    let mut forms: Vec<String> = tokens.iter().map(|t| t.form.clone()).collect();
+    forms.sort();
+    forms.dedup();
+
+    let lemmas = lemmatize(conn, &forms)?;
+
+    // A vocabulary entry may hold the lemma or the form the learner looked up
+    let mut lookup: Vec<String> = lemmas.values().map(|(lemma, ..)| lemma.clone()).collect();
+    lookup.extend(forms.iter().cloned());
+    let vocabulary: HashMap<String, (i64, Option<i32>)> = learn_vocabularies::table
+        .filter(learn_vocabularies::user_id.eq(user_id))
+        .filter(learn_vocabularies::word.eq_any(&lookup))
+        .select((
+            learn_vocabularies::word,
+            learn_vocabularies::id,
+            learn_vocabularies::mastery_level,
+        ))
+        .load::<(String, i64, Option<i32>)>(conn)?
+        .into_iter()
+        .map(|(word, id, mastery)| (word, (id, mastery)))
+        .collect();
+
+    let mut words: Vec<ExtractedWord> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for token in &tokens {
+        let (lemma, word_id, frequency, difficulty) = match lemmas.get(&token.form) {
+            Some((lemma, word_id, frequency, difficulty)) => {
+                (lemma.clone(), Some(*word_id), *frequency, *difficulty)
+            }
+            None => (token.form.clone(), None, None, None),
+        };
+        let i = *index.entry(lemma.clone()).or_insert_with(|| {
+            words.push(ExtractedWord {
+                lemma,
+                word_id,
+                status: WordStatus::New,
+                forms: Vec::new(),
+                occurrences: 0,
+                frequency,
+                difficulty,
+                vocabulary_id: None,
+                mastery_level: None,
+                context: truncate(&sentences[token.sentence], MAX_CONTEXT_CHARS),
+            });
+            words.len() - 1
+        });
+        let word = &mut words[i];
+        word.occurrences += 1;
+        if !word.forms.contains(&token.form) {
+            word.forms.push(token.form.clone());
+        }
+    }
+
+    for word in &mut words {
+        let entry = std::iter::once(&word.lemma)
+            .chain(&word.forms)
+            .filter_map(|w| vocabulary.get(w))
+            .max_by_key(|(_, mastery)| mastery.unwrap_or(0));
+        if let Some(&(id, mastery)) = entry {
+            word.vocabulary_id = Some(id);
+            word.mastery_level = mastery;
+            word.status = status(Some(mastery.unwrap_or(0)));
+        }
+    }
+    rank(&mut words);
+
+    let count = |status: WordStatus| words.iter().filter(|w| w.status == status).count() as i32;
+    let known_tokens: i32 = words
+        .iter()
+        .filter(|w| w.status == WordStatus::Known)
+        .map(|w| w.occurrences)
+        .sum();
+    let total_tokens = tokens.len() as i32;
+    Ok(TextVocabulary {
+        total_tokens,
+        known_count: count(WordStatus::Known),
+        learning_count: count(WordStatus::Learning),
+        new_count: count(WordStatus::New),
+        known_coverage: if total_tokens == 0 {
+            0
+        } else {
+            known_tokens * 100 / total_tokens
+        },
+        words,
+    })
+}
+
+/// Dictionary entry of each form that has one
+///
+/// A headword stands for itself unless the dictionary marks it as a non-lemma
+/// and it resolves to another word ("saw" stays "saw", "went" is "go"). Other
+/// forms go through [`inflection::resolve`], the lemmatizer of lookups.
+pub(super) fn lemmatize(
+    conn: &mut PgConnection,
+    forms: &[String],
+) -> QueryResult<HashMap<String, Entry>> {
+    let headwords: HashMap<String, (Entry, bool)> = dict_words::table
+        .filter(dict_words::word_lower.eq_any(forms))
+        .filter(
+            dict_words::is_active
+                .eq(true)
+                .or(dict_words::is_active.is_null()),
+        )
+        .order(dict_words::frequency.desc().nulls_last())
+        .select((
+            dict_words::word_lower,
+            dict_words::id,
+            dict_words::frequency,
+            dict_words::difficulty,
+            dict_words::is_lemma,
+        ))
+        .load::<(String, i64, Option<i16>, Option<i16>, Option<bool>)>(conn)?
+        .into_iter()
+        .rev()
+        .map(|(word, id, frequency, difficulty, is_lemma)| {
+            let lemma = is_lemma != Some(false);
+            (word.clone(), ((word, id, frequency, difficulty), lemma))
+        })
+        .collect();
+
+    let mut lemmas = HashMap::new();
+    for form in forms {
+        let entry = match headwords.get(form) {
+            Some((entry, true)) => Some(entry.clone()),
+            headword => match inflection::resolve(conn, form)? {
+                Some((lemma, _)) => Some((
+                    lemma.word_lower,
+                    lemma.id,
+                    lemma.frequency,
+                    lemma.difficulty,
+                )),
+                None => headword.map(|(entry, _)| entry.clone()),
+            },
+        };
+        if let Some(entry) = entry {
+            lemmas.insert(form.clone(), entry);
+        }
+    }
+    Ok(lemmas)
+}
+
+fn status(mastery_level: Option<i32>) -> WordStatus {
+    match mastery_level {
+        None => WordStatus::New,
+        Some(level) if level >= MASTERED_LEVEL => WordStatus::Known,
+        Some(_) => WordStatus::Learning,
+    }
+}
+
+/// New words first; within a status the most repeated, then the most common
+fn rank(words: &mut [ExtractedWord]) {
+    words.sort_by(|a, b| {
+        a.status
+            .cmp(&b.status)
+            .then(b.occurrences.cmp(&a.occurrences))
+            .then(b.frequency.unwrap_or(0).cmp(&a.frequency.unwrap_or(0)))
+            .then(a.lemma.cmp(&b.lemma))
+    });
+}
+
+/// Split on sentence punctuation and line breaks
+fn split_sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut current = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\n' || c == '\r' {
+            push_sentence(&mut sentences, &mut current);
+            continue;
+        }
+        current.push(c);
+        if matches!(c, '.' | '!' | '?' | '。' | '！' | '？')
+            && chars.peek().is_none_or(|next| next.is_whitespace())
+        {
+            push_sentence(&mut sentences, &mut current);
+        }
+    }
+    push_sentence(&mut sentences, &mut current);
+    sentences
+}
+
+fn push_sentence(sentences: &mut Vec<String>, current: &mut String) {
+    let sentence = current.split_whitespace().collect::<Vec<_>>().join(" ");
+    if !sentence.is_empty() {
+        sentences.push(sentence);
+    }
+    current.clear();
+}
+
+/// English word tokens of each sentence, leaving out function words, single
+/// letters and anything with digits
+fn tokenize(sentences: &[String]) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for (i, sentence) in sentences.iter().enumerate() {
+        let normalized = sentence.replace(['’', '‘'], "'");
+        for raw in normalized.split(|c: char| !(c.is_ascii_alphanumeric() || c == '\'')) {
+            if raw.contains(|c: char| c.is_ascii_digit()) {
+                continue;
+            }
+            let Some(form) = normalize_token(raw) else {
+                continue;
+            };
+            if form.len() > 1 && !STOP_WORDS.contains(&form.as_str()) {
+                tokens.push(Token { form, sentence: i });
+            }
+        }
+    }
+    tokens
+}
+
+/// Lowercase a token and drop quotes and clitics ("Don't" is "do")
+fn normalize_token(raw: &str) -> Option<String> {
+    let token = raw.trim_matches('\'').to_ascii_lowercase();
+    if token.is_empty() {
+        return None;
+    }
+    if let Some((_, stem)) = IRREGULAR_CONTRACTIONS.iter().find(|(c, _)| *c == token) {
+        return Some((*stem).to_owned());
+    }
+    let stem = CLITICS
+        .iter()
+        .find_map(|clitic| token.strip_suffix(clitic))
+        .unwrap_or(&token);
+    let stem = stem.trim_matches('\'');
+    (!stem.is_empty() && !stem.contains('\'')).then(|| stem.to_owned())
+}
+
+pub fn truncate(text: &str, max_chars: usize) -> String {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_sentences() {
+        let sentences = split_sentences("She arrived at 3.30.  He was late!\nNo title\n\nOK? Yes");
+        assert_eq!(
+            sentences,
+            vec![
+                "She arrived at 3.30.",
+                "He was late!",
+                "No title",
+                "OK?",
+                "Yes"
+            ]
+        );
+    }
+
+    #[test]
+    fn tokenizes_without_function_words_and_clitics() {
+        let sentences = vec![
+            "The children’s toys weren't there, and they can't find it.".to_owned(),
+            "A well-known author, 3rd time.".to_owned(),
+        ];
+        let forms: Vec<(String, usize)> = tokenize(&sentences)
+            .into_iter()
+            .map(|t| (t.form, t.sentence))
+            .collect();
+        assert_eq!(
+            forms,
+            vec![
+                ("children".to_owned(), 0),
+                ("toys".to_owned(), 0),
+                ("find".to_owned(), 0),
+                ("well".to_owned(), 1),
+                ("known".to_owned(), 1),
+                ("author".to_owned(), 1),
+                ("time".to_owned(), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn ranks_new_words_by_repetition_then_frequency() {
+        let word = |lemma: &str, status, occurrences, frequency| ExtractedWord {
+            lemma: lemma.to_owned(),
+            word_id: None,
+            status,
+            forms: vec![lemma.to_owned()],
+            occurrences,
+            frequency,
+            difficulty: None,
+            vocabulary_id: None,
+            mastery_level: None,
+            context: String::new(),
+        };
+        let mut words = vec![
+            word("known", WordStatus::Known, 5, Some(90)),
+            word("rare", WordStatus::New, 1, Some(10)),
+            word("common", WordStatus::New, 1, Some(80)),
+            word("repeated", WordStatus::New, 3, None),
+            word("studying", WordStatus::Learning, 2, Some(50)),
+        ];
+        rank(&mut words);
+        let order: Vec<&str> = words.iter().map(|w| w.lemma.as_str()).collect();
+        assert_eq!(
+            order,
+            vec!["repeated", "common", "rare", "studying", "known"]
+        );
+        assert_eq!(status(Some(MASTERED_LEVEL)), WordStatus::Known);
+        assert_eq!(status(Some(1)), WordStatus::Learning);
+    }
+}
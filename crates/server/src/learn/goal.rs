@@ -0,0 +1,647 @@
+//! Learning goals and the study plan derived from them
+//!
+//! Progress is measured against the daily statistics and vocabulary mastery
+//! in the learner's local days. Recurring goals (minutes a day, words a week)
+//! compare what was done with what the days already over called for, so a
+//! goal is never behind just because today is not finished yet. Goals with a
+//! deadline spread the remaining words evenly over the days left.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use diesel::dsl::count_distinct;
+use diesel::prelude::*;
+use salvo::oapi::ToSchema;
+use serde::{Deserialize, Serialize};
+
+use super::achievement::MASTERED_LEVEL;
+use super::leaderboard::week_start;
+use super::local_time::{local_date, user_timezone};
+use crate::db::schema::*;
+use crate::models::learn::*;
+
+/// Past days averaged to judge a minutes goal
+const PACE_DAYS: u64 = 7;
+/// Minutes a day planned for an exam when the learner gives none
+pub const DEFAULT_EXAM_MINUTES: i32 = 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GoalType {
+    /// Study a number of minutes every day
+    DailyMinutes,
+    /// Learn a number of new words every week
+    WeeklyWords,
+    /// Master the words of a category, optionally by a date and with minutes a day
+    WordList,
+    /// Prepare for an exam date with daily study and optionally a word list
+    Exam,
+}
+
+impl GoalType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GoalType::DailyMinutes => "daily_minutes",
+            GoalType::WeeklyWords => "weekly_words",
+            GoalType::WordList => "word_list",
+            GoalType::Exam => "exam",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        [
+            GoalType::DailyMinutes,
+            GoalType::WeeklyWords,
+            GoalType::WordList,
+            GoalType::Exam,
+        ]
+        .into_iter()
+        .find(|t| t.as_str() == value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum GoalStatus {
+    OnTrack,
+    Behind,
+    /// The word list is mastered
+    Achieved,
+    /// The deadline or exam date has passed
+    Expired,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MinutesProgress {
+    pub today: i32,
+    /// Average of the past days since the goal was set, absent on its first day
+    pub recent_average: Option<f32>,
+    pub target: i32,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WordsProgress {
+    /// New words this week (weekly_words) or list words mastered
+    pub current: i32,
+    /// What is needed by today to stay on track
+    pub expected: i32,
+    pub target: i32,
+}
+
+/// What to do each day from today on
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GoalPlan {
+    pub minutes_per_day: Option<i32>,
+    pub words_per_day: Option<i32>,
+    pub words_per_week: Option<i32>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct GoalProgress {
+    pub goal: Goal,
+    pub status: GoalStatus,
+    /// Days left including today, for goals with a date
+    pub days_left: Option<i64>,
+    pub minutes: Option<MinutesProgress>,
+    pub words: Option<WordsProgress>,
+    pub plan: GoalPlan,
+}
+
+/// Everything needed to create a goal, validated by the caller
+pub struct GoalSpec {
+    pub goal_type: GoalType,
+    pub title: Option<String>,
+    pub target_value: Option<i32>,
+    pub category_id: Option<i64>,
+    pub target_date: Option<NaiveDate>,
+}
+
+/// An all-day calendar entry of the study plan
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlanEvent {
+    pub uid: String,
+    pub summary: String,
+    pub date: NaiveDate,
+    /// Recurrence rule, e.g. `FREQ=DAILY;UNTIL=20250301`
+    pub rrule: Option<String>,
+}
+
+/// Store a goal, counting the list words already mastered as its baseline
+pub fn create_goal(
+    conn: &mut PgConnection,
+    user_id: i64,
+    spec: GoalSpec,
+    now: DateTime<Utc>,
+) -> QueryResult<GoalProgress> {
+    let today = local_date(user_timezone(conn, user_id)?, now);
+    let baseline_mastered = match spec.category_id {
+        Some(category_id) => mastered_in_category(conn, user_id, category_id)?,
+        None => 0,
+    };
+    let target_value = match spec.goal_type {
+        GoalType::Exam => Some(spec.target_value.unwrap_or(DEFAULT_EXAM_MINUTES)),
+        _ => spec.target_value,
+    };
+    let title = match spec.title.filter(|t| !t.trim().is_empty()) {
+        Some(title) => title.trim().to_owned(),
+        None => default_title(conn, &spec, target_value)?,
+    };
+    let goal = diesel::insert_into(learn_goals::table)
+        .values(&NewGoal {
+            user_id,
+            goal_type: spec.goal_type.as_str().to_owned(),
+            title,
+            target_value,
+            category_id: spec.category_id,
+            baseline_mastered,
+            starts_on: today,
+            target_date: spec.target_date,
+        })
+        .get_result::<Goal>(conn)?;
+    goal_progress(conn, goal, today, now)
+}
+
+/// Goals of the user with their progress, active ones only unless `all`
+pub fn list_goals(
+    conn: &mut PgConnection,
+    user_id: i64,
+    all: bool,
+    now: DateTime<Utc>,
+) -> QueryResult<Vec<GoalProgress>> {
+    let today = local_date(user_timezone(conn, user_id)?, now);
+    let mut query = learn_goals::table
+        .filter(learn_goals::user_id.eq(user_id))
+        .order(learn_goals::created_at.asc())
+        .into_boxed();
+    if !all {
+        query = query.filter(learn_goals::is_active.eq(true));
+    }
+    query
+        .load::<Goal>(conn)?
+        .into_iter()
+        .map(|goal| goal_progress(conn, goal, today, now))
+        .collect()
+}
+
+/// Apply changes to a goal of the user, `None` if there is no such goal
+pub fn update_goal(
+    conn: &mut PgConnection,
+    user_id: i64,
+    goal_id: i64,
+    changes: UpdateGoal,
+    now: DateTime<Utc>,
+) -> QueryResult<Option<GoalProgress>> {
+    let Some(goal) = diesel::update(
+        learn_goals::table
+            .filter(learn_goals::id.eq(goal_id))
+            .filter(learn_goals::user_id.eq(user_id)),
+    )
+    .set(&UpdateGoal {
+        updated_at: Some(now),
+        ..changes
+    })
+    .get_result::<Goal>(conn)
+    .optional()?
+    else {
+        return Ok(None);
+    };
+    let today = local_date(user_timezone(conn, user_id)?, now);
+    goal_progress(conn, goal, today, now).map(Some)
+}
+
+/// Calendar entries for the active goals of the user that are still running
+pub fn plan_events(
+    conn: &mut PgConnection,
+    user_id: i64,
+    now: DateTime<Utc>,
+) -> QueryResult<Vec<PlanEvent>> {
+    let today = local_date(user_timezone(conn, user_id)?, now);
+    Ok(list_goals(conn, user_id, false, now)?
+        .iter()
+        .flat_map(|progress| goal_events(progress, today))
+        .collect())
+}
+
+fn goal_progress(
+    conn: &mut PgConnection,
+    goal: Goal,
+    today: NaiveDate,
+    now: DateTime<Utc>,
+) -> QueryResult<GoalProgress> {
+    let goal_type = GoalType::parse(&goal.goal_type).unwrap_or(GoalType::DailyMinutes);
+    let days_left = goal
+        .target_date
+        .map(|date| ((date - today).num_days() + 1).max(0));
+
+    let minutes = match (goal_type, goal.target_value) {
+        (GoalType::DailyMinutes | GoalType::WordList | GoalType::Exam, Some(target)) => {
+            let from = goal.starts_on.max(today - Days::new(PACE_DAYS));
+            let totals = daily_totals(conn, goal.user_id, from, today)?;
+            Some(minutes_progress(&totals, from, today, target))
+        }
+        _ => None,
+    };
+    let words = match (goal_type, goal.category_id) {
+        (GoalType::WeeklyWords, _) => {
+            let target = goal.target_value.unwrap_or(0);
+            let from = goal.starts_on.max(week_start(today));
+            let totals = daily_totals(conn, goal.user_id, from, today)?;
+            let current = totals.values().map(|(_, words)| words).sum();
+            let days_over = (today - from).num_days() as i32;
+            Some(WordsProgress {
+                current,
+                expected: target * days_over / 7,
+                target,
+            })
+        }
+        (GoalType::WordList | GoalType::Exam, Some(category_id)) => {
+            let target = category_size(conn, category_id)?;
+            let current = mastered_in_category(conn, goal.user_id, category_id)?;
+            let expected = match goal.target_date {
+                Some(target_date) => expected_on_pace(
+                    goal.baseline_mastered,
+                    target,
+                    goal.starts_on,
+                    target_date,
+                    today,
+                ),
+                None => current,
+            };
+            Some(WordsProgress {
+                current,
+                expected,
+                target,
+            })
+        }
+        _ => None,
+    };
+
+    let list_done = matches!(goal_type, GoalType::WordList | GoalType::Exam)
+        && words
+            .as_ref()
+            .is_some_and(|w| w.target > 0 && w.current >= w.target);
+    let status = if list_done {
+        GoalStatus::Achieved
+    } else if goal.target_date.is_some_and(|date| date < today) {
+        GoalStatus::Expired
+    } else if minutes.as_ref().is_some_and(|m| !minutes_on_track(m))
+        || words.as_ref().is_some_and(|w| w.current < w.expected)
+    {
+        GoalStatus::Behind
+    } else {
+        GoalStatus::OnTrack
+    };
+
+    let goal = if list_done && goal.achieved_at.is_none() {
+        diesel::update(learn_goals::table.find(goal.id))
+            .set((
+                learn_goals::achieved_at.eq(Some(now)),
+                learn_goals::updated_at.eq(now),
+            ))
+            .get_result::<Goal>(conn)?
+    } else {
+        goal
+    };
+
+    let plan = GoalPlan {
+        minutes_per_day: minutes.as_ref().map(|m| m.target),
+        words_per_day: match (goal_type, &words, days_left) {
+            (GoalType::WordList | GoalType::Exam, Some(words), Some(days_left))
+                if days_left > 0 =>
+            {
+                Some(words_per_day(words.target - words.current, days_left))
+            }
+            _ => None,
+        },
+        words_per_week: (goal_type == GoalType::WeeklyWords)
+            .then(|| goal.target_value.unwrap_or(0)),
+    };
+
+    Ok(GoalProgress {
+        goal,
+        status,
+        days_left,
+        minutes,
+        words,
+        plan,
+    })
+}
+
+/// Minutes studied and new words learned per local day from `from` to `to`
+fn daily_totals(
+    conn: &mut PgConnection,
+    user_id: i64,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> QueryResult<BTreeMap<NaiveDate, (i32, i32)>> {
+    Ok(learn_daily_stats::table
+        .filter(learn_daily_stats::user_id.eq(user_id))
+        .filter(learn_daily_stats::stat_date.between(from, to))
+        .select((
+            learn_daily_stats::stat_date,
+            learn_daily_stats::minutes_studied,
+            learn_daily_stats::new_words_learned,
+        ))
+        .load::<(NaiveDate, Option<i32>, Option<i32>)>(conn)?
+        .into_iter()
+        .map(|(date, minutes, words)| (date, (minutes.unwrap_or(0), words.unwrap_or(0))))
+        .collect())
+}
+
+fn category_size(conn: &mut PgConnection, category_id: i64) -> QueryResult<i32> {
+    dict_word_categories::table
+        .filter(dict_word_categories::category_id.eq(category_id))
+        .select(count_distinct(dict_word_categories::word_id))
+        .first::<i64>(conn)
+        .map(|count| count as i32)
+}
+
+fn mastered_in_category(
+    conn: &mut PgConnection,
+    user_id: i64,
+    category_id: i64,
+) -> QueryResult<i32> {
+    let words = dict_word_categories::table
+        .filter(dict_word_categories::category_id.eq(category_id))
+        .select(dict_word_categories::word_id.nullable());
+    learn_vocabularies::table
+        .filter(learn_vocabularies::user_id.eq(user_id))
+        .filter(learn_vocabularies::mastery_level.ge(MASTERED_LEVEL))
+        .filter(learn_vocabularies::word_id.eq_any(words))
+        .select(count_distinct(learn_vocabularies::word_id))
+        .first::<i64>(conn)
+        .map(|count| count as i32)
+}
+
+fn default_title(
+    conn: &mut PgConnection,
+    spec: &GoalSpec,
+    target_value: Option<i32>,
+) -> QueryResult<String> {
+    let category = match spec.category_id {
+        Some(category_id) => taxon_categories::table
+            .find(category_id)
+            .select(taxon_categories::name_en)
+            .first::<String>(conn)
+            .optional()?,
+        None => None,
+    };
+    let value = target_value.unwrap_or(0);
+    Ok(match spec.goal_type {
+        GoalType::DailyMinutes => format!("{value} minutes a day"),
+        GoalType::WeeklyWords => format!("{value} new words a week"),
+        GoalType::WordList => match spec.target_date {
+            Some(date) => format!("Master {} by {date}", category.unwrap_or_default()),
+            None => format!("Master {}", category.unwrap_or_default()),
+        },
+        GoalType::Exam => match (category, spec.target_date) {
+            (Some(category), Some(date)) => format!("{category} exam on {date}"),
+            (_, Some(date)) => format!("Exam on {date}"),
+            _ => "Exam".to_owned(),
+        },
+    })
+}
+
+fn minutes_progress(
+    totals: &BTreeMap<NaiveDate, (i32, i32)>,
+    from: NaiveDate,
+    today: NaiveDate,
+    target: i32,
+) -> MinutesProgress {
+    let past_days = (today - from).num_days();
+    let past_minutes: i32 = totals
+        .range(from..today)
+        .map(|(_, (minutes, _))| minutes)
+        .sum();
+    MinutesProgress {
+        today: totals.get(&today).map(|(minutes, _)| *minutes).unwrap_or(0),
+        recent_average: (past_days > 0).then(|| past_minutes as f32 / past_days as f32),
+        target,
+    }
+}
+
+/// On track when today's target is already met or the past days averaged it
+fn minutes_on_track(minutes: &MinutesProgress) -> bool {
+    minutes.today >= minutes.target
+        || minutes
+            .recent_average
+            .is_none_or(|average| average >= minutes.target as f32)
+}
+
+/// Words that should be mastered by the start of `today` when going from
+/// `baseline` on `starts_on` to `target` by the end of `target_date`
+fn expected_on_pace(
+    baseline: i32,
+    target: i32,
+    starts_on: NaiveDate,
+    target_date: NaiveDate,
+    today: NaiveDate,
+) -> i32 {
+    let total_days = (target_date - starts_on).num_days() + 1;
+    let days_over = (today - starts_on).num_days().clamp(0, total_days);
+    if total_days <= 0 {
+        return target;
+    }
+    baseline + ((target - baseline).max(0) as i64 * days_over / total_days) as i32
+}
+
+fn words_per_day(remaining: i32, days_left: i64) -> i32 {
+    if remaining <= 0 {
+        return 0;
+    }
+    (remaining as i64).div_ceil(days_left) as i32
+}
+
+fn goal_events(progress: &GoalProgress, today: NaiveDate) -> Vec<PlanEvent> {
+    let goal = &progress.goal;
+    if matches!(progress.status, GoalStatus::Achieved | GoalStatus::Expired) {
+        return Vec::new();
+    }
+    let until = |date: NaiveDate| format!("UNTIL={}", date.format("%Y%m%d"));
+    let mut events = Vec::new();
+
+    if let Some(minutes) = progress.plan.minutes_per_day {
+        // Study time for an exam stops the day before it
+        let last_day = match GoalType::parse(&goal.goal_type) {
+            Some(GoalType::Exam) => goal.target_date.map(|d| d - Days::new(1)),
+            _ => goal.target_date,
+        };
+        if last_day.is_none_or(|d| d >= today) {
+            events.push(PlanEvent {
+                uid: format!("goal-{}-minutes", goal.id),
+                summary: format!("Study {minutes} minutes · {}", goal.title),
+                date: today,
+                rrule: Some(match last_day {
+                    Some(last_day) => format!("FREQ=DAILY;{}", until(last_day)),
+                    None => "FREQ=DAILY".to_owned(),
+                }),
+            });
+        }
+    }
+    if let Some(words) = progress.plan.words_per_week {
+        events.push(PlanEvent {
+            uid: format!("goal-{}-weekly-words", goal.id),
+            summary: format!("Learn {words} new words this week · {}", goal.title),
+            date: week_start(today),
+            rrule: Some("FREQ=WEEKLY;BYDAY=MO".to_owned()),
+        });
+    }
+    if let (Some(words), Some(target_date)) = (progress.plan.words_per_day, goal.target_date)
+        && words > 0
+    {
+        events.push(PlanEvent {
+            uid: format!("goal-{}-daily-words", goal.id),
+            summary: format!("Master {words} words · {}", goal.title),
+            date: today,
+            rrule: Some(format!("FREQ=DAILY;{}", until(target_date))),
+        });
+    }
+    if GoalType::parse(&goal.goal_type) == Some(GoalType::Exam)
+        && let Some(exam_date) = goal.target_date
+    {
+        events.push(PlanEvent {
+            uid: format!("goal-{}-exam", goal.id),
+            summary: goal.title.clone(),
+            date: exam_date,
+            rrule: None,
+        });
+    }
+    events
+}
+
+/// Render plan events as an iCalendar file
+pub fn to_ics(events: &[PlanEvent], now: DateTime<Utc>) -> String {
+    let stamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_owned(),
+        "VERSION:2.0".to_owned(),
+        "PRODID:-//colang//study plan//EN".to_owned(),
+        "CALSCALE:GREGORIAN".to_owned(),
+    ];
+    for event in events {
+        lines.push("BEGIN:VEVENT".to_owned());
+        lines.push(format!("UID:{}@colang", event.uid));
+        lines.push(format!("DTSTAMP:{stamp}"));
+        lines.push(format!(
+            "DTSTART;VALUE=DATE:{}",
+            event.date.format("%Y%m%d")
+        ));
+        lines.push(format!(
+            "DTEND;VALUE=DATE:{}",
+            (event.date + Days::new(1)).format("%Y%m%d")
+        ));
+        if let Some(rrule) = &event.rrule {
+            lines.push(format!("RRULE:{rrule}"));
+        }
+        lines.push(format!("SUMMARY:{}", escape_text(&event.summary)));
+        lines.push("TRANSP:TRANSPARENT".to_owned());
+        lines.push("END:VEVENT".to_owned());
+    }
+    lines.push("END:VCALENDAR".to_owned());
+
+    let mut ics = String::new();
+    for line in lines {
+        ics.push_str(&fold_line(&line));
+        ics.push_str("\r\n");
+    }
+    ics
+}
+
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Fold a content line into lines of at most 75 octets
+fn fold_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, month, day).unwrap()
+    }
+
+    #[test]
+    fn pace_is_linear_to_the_deadline() {
+        // 10 days to go from 0 to 100 words
+        assert_eq!(
+            expected_on_pace(0, 100, date(1, 1), date(1, 10), date(1, 1)),
+            0
+        );
+        assert_eq!(
+            expected_on_pace(0, 100, date(1, 1), date(1, 10), date(1, 6)),
+            50
+        );
+        assert_eq!(
+            expected_on_pace(20, 100, date(1, 1), date(1, 10), date(1, 6)),
+            60
+        );
+        assert_eq!(
+            expected_on_pace(0, 100, date(1, 1), date(1, 10), date(2, 1)),
+            100
+        );
+        assert_eq!(words_per_day(45, 10), 5);
+        assert_eq!(words_per_day(0, 10), 0);
+    }
+
+    #[test]
+    fn minutes_are_judged_on_finished_days() {
+        let totals = BTreeMap::from([
+            (date(1, 1), (20, 0)),
+            (date(1, 2), (10, 0)),
+            (date(1, 3), (0, 0)),
+        ]);
+        let progress = minutes_progress(&totals, date(1, 1), date(1, 3), 15);
+        assert_eq!(progress.recent_average, Some(15.0));
+        assert!(minutes_on_track(&progress));
+
+        let progress = minutes_progress(&totals, date(1, 2), date(1, 3), 15);
+        assert!(!minutes_on_track(&progress));
+        let first_day = minutes_progress(&totals, date(1, 3), date(1, 3), 15);
+        assert_eq!(first_day.recent_average, None);
+        assert!(minutes_on_track(&first_day));
+    }
+
+    #[test]
+    fn renders_icalendar() {
+        let now = DateTime::parse_from_rfc3339("2025-01-02T08:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let events = vec![PlanEvent {
+            uid: "goal-1-minutes".to_owned(),
+            summary: "Study 15 minutes · Daily, please; thanks".to_owned(),
+            date: date(1, 2),
+            rrule: Some("FREQ=DAILY".to_owned()),
+        }];
+        let ics = to_ics(&events, now);
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(ics.contains("DTSTART;VALUE=DATE:20250102\r\n"));
+        assert!(ics.contains("DTEND;VALUE=DATE:20250103\r\n"));
+        assert!(ics.contains("SUMMARY:Study 15 minutes · Daily\\, please\\; thanks\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+    }
+
+    #[test]
+    fn folds_long_lines() {
+        let line = format!("SUMMARY:{}", "ü".repeat(60));
+        let folded = fold_line(&line);
+        assert!(folded.split("\r\n").all(|l| l.len() <= 75));
+        assert_eq!(folded.replace("\r\n ", ""), line);
+    }
+}
@@ -0,0 +1,256 @@
+//! Inflected forms brought back to their dictionary entry
+//!
+//! `dict_forms` lists the forms of a lemma, irregular ones included, and is
+//! searched by form first ("went" is the past tense of "go"). Regular forms
+//! are often not listed, so English suffixes are stripped next ("studies",
+//! "stopped", "happier") and the candidates checked against the headwords.
+//! A rule only applies to a headword of a fitting part of speech, so "runner"
+//! is not taken for a comparative of the verb "run".
+
+use diesel::prelude::*;
+
+use crate::db::schema::*;
+use crate::models::dict::{MatchedForm, Word};
+
+/// Suffix rule: ending removed, ending added back, resulting form type
+struct Rule {
+    suffix: &'static str,
+    replacement: &'static str,
+    form_type: &'static str,
+    /// The stem ends in a doubled consonant to drop ("stopped" -> "stop")
+    doubled: bool,
+}
+
+const fn rule(suffix: &'static str, replacement: &'static str, form_type: &'static str) -> Rule {
+    Rule {
+        suffix,
+        replacement,
+        form_type,
+        doubled: false,
+    }
+}
+
+const fn doubled(suffix: &'static str, form_type: &'static str) -> Rule {
+    Rule {
+        suffix,
+        replacement: "",
+        form_type,
+        doubled: true,
+    }
+}
+
+/// Most specific endings first, so "studies" tries "study" before "studie"
+const RULES: &[Rule] = &[
+    rule("ies", "y", "plural"),
+    rule("es", "", "plural"),
+    rule("s", "", "plural"),
+    rule("ied", "y", "past"),
+    doubled("ed", "past"),
+    rule("ed", "", "past"),
+    rule("ed", "e", "past"),
+    rule("ying", "ie", "present_participle"),
+    doubled("ing", "present_participle"),
+    rule("ing", "", "present_participle"),
+    rule("ing", "e", "present_participle"),
+    rule("ier", "y", "comparative"),
+    doubled("er", "comparative"),
+    rule("er", "", "comparative"),
+    rule("er", "e", "comparative"),
+    rule("iest", "y", "superlative"),
+    doubled("est", "superlative"),
+    rule("est", "", "superlative"),
+    rule("est", "e", "superlative"),
+    rule("ily", "y", "adverbial"),
+    rule("ly", "", "adverbial"),
+];
+
+/// Shortest lemma a rule may leave
+const MIN_LEMMA_CHARS: usize = 2;
+
+/// Lemmas `word` could be a regular inflection of, most likely first
+fn strip_suffixes(word: &str) -> Vec<(String, &'static str)> {
+    if !word.chars().all(|c| c.is_ascii_lowercase()) {
+        return Vec::new();
+    }
+    let mut candidates: Vec<(String, &'static str)> = Vec::new();
+    for rule in RULES {
+        let Some(stem) = word.strip_suffix(rule.suffix) else {
+            continue;
+        };
+        if rule.suffix == "s" && stem.ends_with('s') {
+            // "glass" is not a plural of "glas"
+            continue;
+        }
+        let stem = if rule.doubled {
+            let bytes = stem.as_bytes();
+            let n = bytes.len();
+            let doubled_consonant =
+                n >= 2 && bytes[n - 1] == bytes[n - 2] && !b"aeiouywx".contains(&bytes[n - 1]);
+            if !doubled_consonant {
+                continue;
+            }
+            &stem[..n - 1]
+        } else {
+            stem
+        };
+        let lemma = format!("{stem}{}", rule.replacement);
+        if lemma.len() < MIN_LEMMA_CHARS {
+            continue;
+        }
+        if !candidates.iter().any(|(known, _)| *known == lemma) {
+            candidates.push((lemma, rule.form_type));
+        }
+    }
+    candidates
+}
+
+/// Whether a suffix rule may produce a form of a headword with `word_type`
+fn fits(form_type: &str, word_type: Option<&str>) -> bool {
+    let Some(word_type) = word_type else {
+        return true;
+    };
+    match form_type {
+        // Nouns take plurals, verbs the third person present with the same ending
+        "plural" => matches!(word_type, "noun" | "verb"),
+        "past" | "present_participle" => word_type == "verb",
+        "comparative" | "superlative" => matches!(word_type, "adjective" | "adverb"),
+        "adverbial" => word_type == "adjective",
+        _ => false,
+    }
+}
+
+/// "past tense of go"
+fn describe(form_type: Option<&str>, lemma: &str) -> String {
+    let kind = match form_type {
+        Some("plural") => "plural",
+        Some("singular") => "singular",
+        Some("past") => "past tense",
+        Some("present") => "present tense",
+        Some("future") => "future tense",
+        Some("present_participle") => "present participle",
+        Some("past_participle") => "past participle",
+        Some("comparative") => "comparative",
+        Some("superlative") => "superlative",
+        Some("adverbial") => "adverb",
+        Some("nominalization") => "noun",
+        _ => "form",
+    };
+    format!("{kind} of {lemma}")
+}
+
+/// Dictionary entry `word` is an inflected form of, with how it matched
+///
+/// `word` is expected in lower case, as `dict_forms` and `word_lower` are.
+pub fn resolve(conn: &mut PgConnection, word: &str) -> QueryResult<Option<(Word, MatchedForm)>> {
+    let listed = dict_forms::table
+        .inner_join(dict_words::table.on(dict_words::id.eq(dict_forms::word_id)))
+        .filter(dict_forms::form.eq(word))
+        .filter(
+            dict_words::is_active
+                .eq(true)
+                .or(dict_words::is_active.is_null()),
+        )
+        .order((
+            dict_forms::is_irregular.desc().nulls_last(),
+            dict_words::frequency.desc().nulls_last(),
+        ))
+        .select((dict_words::all_columns, dict_forms::form_type))
+        .first::<(Word, Option<String>)>(conn)
+        .optional()?;
+    if let Some((lemma, form_type)) = listed {
+        let matched = MatchedForm {
+            form: word.to_owned(),
+            description: describe(form_type.as_deref(), &lemma.word),
+            form_type,
+            lemma: lemma.word.clone(),
+            guessed: false,
+        };
+        return Ok(Some((lemma, matched)));
+    }
+
+    let candidates = strip_suffixes(word);
+    if candidates.is_empty() {
+        return Ok(None);
+    }
+    let lemmas: Vec<&str> = candidates.iter().map(|(lemma, _)| lemma.as_str()).collect();
+    let headwords = dict_words::table
+        .filter(dict_words::word_lower.eq_any(&lemmas))
+        .filter(
+            dict_words::is_active
+                .eq(true)
+                .or(dict_words::is_active.is_null()),
+        )
+        .order(dict_words::frequency.desc().nulls_last())
+        .load::<Word>(conn)?;
+
+    // Rule order first, then lemmas over non-lemma headwords, then frequency
+    let best = candidates.iter().find_map(|(lemma, form_type)| {
+        let fitting = headwords.iter().filter(|headword| {
+            headword.word_lower == *lemma && fits(form_type, headword.word_type.as_deref())
+        });
+        let mut fitting: Vec<&Word> = fitting.collect();
+        fitting.sort_by_key(|headword| headword.is_lemma == Some(false));
+        fitting
+            .first()
+            .map(|headword| ((*headword).clone(), *form_type))
+    });
+    Ok(best.map(|(lemma, form_type)| {
+        // Verbs take -s in the present, the "plural" rule only names nouns
+        let form_type = if form_type == "plural" && lemma.word_type.as_deref() == Some("verb") {
+            "present"
+        } else {
+            form_type
+        };
+        let matched = MatchedForm {
+            form: word.to_owned(),
+            form_type: Some(form_type.to_owned()),
+            lemma: lemma.word.clone(),
+            description: describe(Some(form_type), &lemma.word),
+            guessed: true,
+        };
+        (lemma, matched)
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lemmas(word: &str) -> Vec<String> {
+        strip_suffixes(word)
+            .into_iter()
+            .map(|(lemma, _)| lemma)
+            .collect()
+    }
+
+    #[test]
+    fn strips_regular_endings() {
+        assert_eq!(lemmas("studies")[0], "study");
+        assert_eq!(lemmas("stopped")[0], "stop");
+        assert!(lemmas("liked").contains(&"like".to_owned()));
+        assert!(lemmas("making").contains(&"make".to_owned()));
+        assert_eq!(lemmas("running")[0], "run");
+        assert_eq!(lemmas("lying")[0], "lie");
+        assert_eq!(lemmas("happier")[0], "happy");
+        assert_eq!(lemmas("biggest")[0], "big");
+        assert_eq!(lemmas("happily")[0], "happy");
+        assert!(lemmas("glass").is_empty());
+        assert!(lemmas("is").is_empty());
+        assert!(lemmas("café").is_empty());
+    }
+
+    #[test]
+    fn rules_fit_parts_of_speech() {
+        assert!(fits("comparative", Some("adjective")));
+        assert!(!fits("comparative", Some("verb")));
+        assert!(fits("plural", Some("verb")));
+        assert!(fits("past", None));
+    }
+
+    #[test]
+    fn describes_forms() {
+        assert_eq!(describe(Some("past"), "go"), "past tense of go");
+        assert_eq!(describe(Some("plural"), "child"), "plural of child");
+        assert_eq!(describe(None, "be"), "form of be");
+    }
+}
@@ -0,0 +1,389 @@
+//! XP leaderboards
+//!
+//! Chat turns, reviews, quiz answers and practices add XP to the history as
+//! they are recorded (see [`Activity::xp`](super::stats::Activity::xp)), and
+//! achievements add their rewards.
+//! The weekly board sums XP history since Monday 00:00 UTC, the all-time
+//! board ranks profiles by total XP. Learners who opted out are left out.
+//! The top of each board is cached for a short while since every learner
+//! sees the same one; only their own position is computed per request.
+
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+
+use chrono::{DateTime, Datelike, Days, NaiveDate, NaiveTime, Utc};
+use diesel::dsl::sum;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Timestamptz};
+use salvo::oapi::ToSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::db::schema::*;
+
+/// Entries kept per board, the most a client can ask for
+pub const BOARD_SIZE: i64 = 100;
+/// How long a computed board is served from the cache
+const CACHE_SECONDS: i64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaderboardPeriod {
+    Weekly,
+    AllTime,
+}
+
+impl LeaderboardPeriod {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "weekly" => Some(LeaderboardPeriod::Weekly),
+            "all_time" => Some(LeaderboardPeriod::AllTime),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct LeaderboardEntry {
+    /// 1-based, learners with equal XP share a position
+    pub position: i64,
+    pub user_id: i64,
+    pub display_name: String,
+    pub avatar: Option<String>,
+    pub xp: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Leaderboard {
+    pub period: LeaderboardPeriod,
+    /// Start of the counted period, absent for the all-time board
+    pub since: Option<DateTime<Utc>>,
+    pub entries: Vec<LeaderboardEntry>,
+    /// The requesting learner, absent if they opted out
+    pub me: Option<LeaderboardEntry>,
+    pub generated_at: DateTime<Utc>,
+}
+
+struct CachedBoard {
+    since: Option<DateTime<Utc>>,
+    generated_at: DateTime<Utc>,
+    entries: Arc<Vec<LeaderboardEntry>>,
+}
+
+static CACHE: LazyLock<Mutex<HashMap<LeaderboardPeriod, CachedBoard>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Monday of the week `date` is in
+pub fn week_start(date: NaiveDate) -> NaiveDate {
+    date - Days::new(u64::from(date.weekday().num_days_from_monday()))
+}
+
+/// Start of the current leaderboard week, Monday 00:00 UTC
+pub fn week_start_at(now: DateTime<Utc>) -> DateTime<Utc> {
+    week_start(now.date_naive())
+        .and_time(NaiveTime::MIN)
+        .and_utc()
+}
+
+/// Drop cached boards, e.g. after a learner changed their opt-out
+pub fn invalidate() {
+    CACHE.lock().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+/// Top `limit` learners of `period` and the position of `user_id`
+pub fn leaderboard(
+    conn: &mut PgConnection,
+    user_id: i64,
+    period: LeaderboardPeriod,
+    limit: i64,
+    now: DateTime<Utc>,
+) -> QueryResult<Leaderboard> {
+    let since = match period {
+        LeaderboardPeriod::Weekly => Some(week_start_at(now)),
+        LeaderboardPeriod::AllTime => None,
+    };
+    let (entries, generated_at) = top_entries(conn, period, since, now)?;
+
+    let opted_out = archive_user_profiles::table
+        .filter(archive_user_profiles::user_id.eq(user_id))
+        .select(archive_user_profiles::leaderboard_opt_out)
+        .first::<bool>(conn)
+        .optional()?
+        .unwrap_or(false);
+    let me = if opted_out {
+        None
+    } else if let Some(entry) = entries.iter().find(|e| e.user_id == user_id) {
+        Some(entry.clone())
+    } else {
+        Some(own_entry(conn, user_id, since, now)?)
+    };
+
+    Ok(Leaderboard {
+        period,
+        since,
+        entries: entries
+            .iter()
+            .take(limit.clamp(1, BOARD_SIZE) as usize)
+            .cloned()
+            .collect(),
+        me,
+        generated_at,
+    })
+}
+
+/// XP each of `user_ids` earned from `since` until before `until`
+pub fn xp_between(
+    conn: &mut PgConnection,
+    user_ids: &[i64],
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+) -> QueryResult<HashMap<i64, i64>> {
+    Ok(archive_user_xp_history::table
+        .filter(archive_user_xp_history::user_id.eq_any(user_ids))
+        .filter(archive_user_xp_history::created_at.ge(since))
+        .filter(archive_user_xp_history::created_at.lt(until))
+        .group_by(archive_user_xp_history::user_id)
+        .select((
+            archive_user_xp_history::user_id,
+            sum(archive_user_xp_history::xp_amount),
+        ))
+        .load::<(i64, Option<i64>)>(conn)?
+        .into_iter()
+        .map(|(user_id, xp)| (user_id, xp.unwrap_or(0)))
+        .collect())
+}
+
+/// Name and avatar shown for each user
+pub fn display_names(
+    conn: &mut PgConnection,
+    user_ids: &[i64],
+) -> QueryResult<HashMap<i64, (String, Option<String>)>> {
+    Ok(base_users::table
+        .filter(base_users::id.eq_any(user_ids))
+        .select((
+            base_users::id,
+            base_users::name,
+            base_users::display_name,
+            base_users::avatar,
+        ))
+        .load::<(i64, String, Option<String>, Option<String>)>(conn)?
+        .into_iter()
+        .map(|(id, name, display_name, avatar)| (id, (display_name.unwrap_or(name), avatar)))
+        .collect())
+}
+
+/// 1-based positions of XP totals sorted best first, equal totals share one
+fn positions(xps: &[i64]) -> Vec<i64> {
+    let mut positions = Vec::with_capacity(xps.len());
+    for (index, xp) in xps.iter().enumerate() {
+        let position = match positions.last() {
+            Some(&previous) if xps[index - 1] == *xp => previous,
+            _ => index as i64 + 1,
+        };
+        positions.push(position);
+    }
+    positions
+}
+
+/// Entries of XP totals, best first and lower user ids first on ties
+fn board_entries(
+    mut totals: Vec<(i64, i64)>,
+    mut names: HashMap<i64, (String, Option<String>)>,
+) -> Vec<LeaderboardEntry> {
+    totals.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    let xps: Vec<i64> = totals.iter().map(|(_, xp)| *xp).collect();
+    totals
+        .into_iter()
+        .zip(positions(&xps))
+        .map(|((user_id, xp), position)| {
+            let (display_name, avatar) = names.remove(&user_id).unwrap_or_default();
+            LeaderboardEntry {
+                position,
+                user_id,
+                display_name,
+                avatar,
+                xp,
+            }
+        })
+        .collect()
+}
+
+fn top_entries(
+    conn: &mut PgConnection,
+    period: LeaderboardPeriod,
+    since: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> QueryResult<(Arc<Vec<LeaderboardEntry>>, DateTime<Utc>)> {
+    if let Some(cached) = CACHE
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&period)
+        .filter(|c| c.since == since && (now - c.generated_at).num_seconds() < CACHE_SECONDS)
+    {
+        return Ok((cached.entries.clone(), cached.generated_at));
+    }
+
+    let rows: Vec<(i64, i64)> = match since {
+        Some(since) => archive_user_xp_history::table
+            .inner_join(
+                archive_user_profiles::table
+                    .on(archive_user_profiles::user_id.eq(archive_user_xp_history::user_id)),
+            )
+            .filter(archive_user_xp_history::created_at.ge(since))
+            .filter(archive_user_profiles::leaderboard_opt_out.eq(false))
+            .group_by(archive_user_xp_history::user_id)
+            .select((
+                archive_user_xp_history::user_id,
+                sum(archive_user_xp_history::xp_amount),
+            ))
+            .order((
+                sum(archive_user_xp_history::xp_amount).desc(),
+                archive_user_xp_history::user_id.asc(),
+            ))
+            .limit(BOARD_SIZE)
+            .load::<(i64, Option<i64>)>(conn)?
+            .into_iter()
+            .map(|(user_id, xp)| (user_id, xp.unwrap_or(0)))
+            .filter(|(_, xp)| *xp > 0)
+            .collect(),
+        None => archive_user_profiles::table
+            .filter(archive_user_profiles::leaderboard_opt_out.eq(false))
+            .filter(archive_user_profiles::total_xp.gt(0))
+            .order((
+                archive_user_profiles::total_xp.desc(),
+                archive_user_profiles::user_id.asc(),
+            ))
+            .select((
+                archive_user_profiles::user_id,
+                archive_user_profiles::total_xp,
+            ))
+            .limit(BOARD_SIZE)
+            .load::<(i64, i32)>(conn)?
+            .into_iter()
+            .map(|(user_id, xp)| (user_id, i64::from(xp)))
+            .collect(),
+    };
+
+    let user_ids: Vec<i64> = rows.iter().map(|(user_id, _)| *user_id).collect();
+    let names = display_names(conn, &user_ids)?;
+    let entries = Arc::new(board_entries(rows, names));
+    CACHE.lock().unwrap_or_else(|e| e.into_inner()).insert(
+        period,
+        CachedBoard {
+            since,
+            generated_at: now,
+            entries: entries.clone(),
+        },
+    );
+    Ok((entries, now))
+}
+
+#[derive(QueryableByName)]
+struct Ahead {
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+/// Entry of a learner who is not in the cached top of the board
+fn own_entry(
+    conn: &mut PgConnection,
+    user_id: i64,
+    since: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> QueryResult<LeaderboardEntry> {
+    let (xp, ahead) = match since {
+        Some(since) => {
+            let xp = xp_between(conn, &[user_id], since, now)?
+                .remove(&user_id)
+                .unwrap_or(0);
+            let ahead = diesel::sql_query(
+                "SELECT COUNT(*) AS count FROM ( \
+                     SELECT h.user_id FROM archive_user_xp_history h \
+                     JOIN archive_user_profiles p ON p.user_id = h.user_id \
+                     WHERE h.created_at >= $1 AND NOT p.leaderboard_opt_out \
+                     GROUP BY h.user_id HAVING SUM(h.xp_amount) > $2 \
+                 ) ahead",
+            )
+            .bind::<Timestamptz, _>(since)
+            .bind::<BigInt, _>(xp)
+            .get_result::<Ahead>(conn)?
+            .count;
+            (xp, ahead)
+        }
+        None => {
+            let xp = archive_user_profiles::table
+                .filter(archive_user_profiles::user_id.eq(user_id))
+                .select(archive_user_profiles::total_xp)
+                .first::<i32>(conn)
+                .optional()?
+                .unwrap_or(0);
+            let ahead = archive_user_profiles::table
+                .filter(archive_user_profiles::leaderboard_opt_out.eq(false))
+                .filter(archive_user_profiles::total_xp.gt(xp))
+                .count()
+                .get_result::<i64>(conn)?;
+            (i64::from(xp), ahead)
+        }
+    };
+    let (display_name, avatar) = display_names(conn, &[user_id])?
+        .remove(&user_id)
+        .unwrap_or_default();
+    Ok(LeaderboardEntry {
+        position: ahead + 1,
+        user_id,
+        display_name,
+        avatar,
+        xp,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::learn::review::ReviewItemType;
+    use crate::learn::stats::{Activity, PracticeItem};
+
+    #[test]
+    fn weeks_start_on_monday() {
+        let monday = NaiveDate::from_ymd_opt(2024, 3, 11).unwrap();
+        assert_eq!(week_start(monday), monday);
+        assert_eq!(
+            week_start(NaiveDate::from_ymd_opt(2024, 3, 17).unwrap()),
+            monday
+        );
+        assert_eq!(
+            week_start(NaiveDate::from_ymd_opt(2024, 3, 18).unwrap()),
+            NaiveDate::from_ymd_opt(2024, 3, 18).unwrap()
+        );
+    }
+
+    #[test]
+    fn ranking_follows_activity() {
+        let review = Activity::Review {
+            item_type: ReviewItemType::Vocabulary,
+            item_id: 1,
+            correct: true,
+            due: true,
+        };
+        let chat = Activity::ChatTurn { chat_id: 1 };
+        let dictation = Activity::Practice {
+            item: Some(PracticeItem::Dictation { word_id: 1 }),
+        };
+        let learners: [(i64, Vec<Activity>); 4] = [
+            (1, vec![chat; 3]),
+            (2, vec![review; 10]),
+            (3, vec![dictation, Activity::QuizAnswer]),
+            (4, vec![dictation, chat]),
+        ];
+        let totals: Vec<(i64, i64)> = learners
+            .iter()
+            .map(|(user_id, done)| (*user_id, done.iter().map(|a| i64::from(a.xp())).sum()))
+            .collect();
+        let names = HashMap::from([(3, ("Mei".to_owned(), None))]);
+
+        let entries = board_entries(totals, names);
+        let ranked: Vec<(i64, i64)> = entries.iter().map(|e| (e.user_id, e.position)).collect();
+        assert_eq!(ranked, [(2, 1), (3, 2), (4, 2), (1, 4)]);
+        assert_eq!(entries[1].display_name, "Mei");
+        assert_eq!(entries[0].display_name, "");
+    }
+}
@@ -0,0 +1,358 @@
+//! Weekly leagues
+//!
+//! Learners of the same tier are grouped into cohorts of up to
+//! [`LEAGUE_SIZE`] when they first open their league in a week. When the week
+//! is over the top of each cohort moves up a tier and the bottom moves down.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Days, NaiveDate, NaiveTime, Utc};
+use diesel::prelude::*;
+use salvo::oapi::ToSchema;
+use serde::Serialize;
+
+use super::leaderboard::{display_names, week_start, xp_between};
+use crate::db::schema::*;
+use crate::models::achievement::*;
+
+/// Learners per cohort
+pub const LEAGUE_SIZE: i32 = 30;
+/// Learners promoted at the end of a week
+pub const PROMOTE_COUNT: usize = 5;
+/// Learners demoted at the end of a week
+pub const DEMOTE_COUNT: usize = 5;
+/// Tier names from the lowest up, tier 1 is `bronze`
+pub const TIERS: &[&str] = &["bronze", "silver", "gold", "platinum", "diamond"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LeagueOutcome {
+    Promoted,
+    Demoted,
+    Stayed,
+}
+
+impl LeagueOutcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LeagueOutcome::Promoted => "promoted",
+            LeagueOutcome::Demoted => "demoted",
+            LeagueOutcome::Stayed => "stayed",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LeagueStanding {
+    pub position: i32,
+    pub user_id: i64,
+    pub display_name: String,
+    pub avatar: Option<String>,
+    pub weekly_xp: i64,
+    /// Where the learner ends up if the week ended now
+    pub outcome: LeagueOutcome,
+    pub is_me: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LeagueView {
+    pub tier: i32,
+    pub tier_name: String,
+    pub week_start: NaiveDate,
+    pub ends_at: DateTime<Utc>,
+    pub standings: Vec<LeagueStanding>,
+}
+
+/// Name of a tier, clamped to the known tiers
+pub fn tier_name(tier: i32) -> &'static str {
+    TIERS[(tier.clamp(1, TIERS.len() as i32) - 1) as usize]
+}
+
+/// Outcome of each position of a cohort of `tier`, given the weekly XP of
+/// its members from first to last
+///
+/// Only learners who earned XP move up, and nobody is both in the promotion
+/// and the demotion zone of a small cohort.
+pub fn outcomes(tier: i32, weekly_xp: &[i64]) -> Vec<LeagueOutcome> {
+    let count = weekly_xp.len();
+    let demote_from = count.saturating_sub(DEMOTE_COUNT).max(PROMOTE_COUNT);
+    weekly_xp
+        .iter()
+        .enumerate()
+        .map(|(index, xp)| {
+            if index < PROMOTE_COUNT && *xp > 0 && tier < TIERS.len() as i32 {
+                LeagueOutcome::Promoted
+            } else if index >= demote_from && tier > 1 {
+                LeagueOutcome::Demoted
+            } else {
+                LeagueOutcome::Stayed
+            }
+        })
+        .collect()
+}
+
+/// League of `user_id` this week, joining a cohort of their tier first
+///
+/// Returns `None` for learners who opted out of leaderboards.
+pub fn current_league(
+    conn: &mut PgConnection,
+    user_id: i64,
+    now: DateTime<Utc>,
+) -> QueryResult<Option<LeagueView>> {
+    let week = week_start(now.date_naive());
+    let Some(member) = join(conn, user_id, week)? else {
+        return Ok(None);
+    };
+    let cohort = archive_league_cohorts::table
+        .find(member.cohort_id)
+        .first::<LeagueCohort>(conn)?;
+
+    let members = competing_members(conn, cohort.id)?;
+    let user_ids: Vec<i64> = members.iter().map(|(id, _)| *id).collect();
+    let start = week.and_time(NaiveTime::MIN).and_utc();
+    let ends_at = (week + Days::new(7)).and_time(NaiveTime::MIN).and_utc();
+    let xp = xp_between(conn, &user_ids, start, ends_at)?;
+    let mut names = display_names(conn, &user_ids)?;
+
+    let ranked = rank_members(members, &xp);
+    let weekly_xp: Vec<i64> = ranked.iter().map(|(_, xp)| *xp).collect();
+    let standings = ranked
+        .into_iter()
+        .zip(outcomes(cohort.tier, &weekly_xp))
+        .enumerate()
+        .map(|(index, ((member_id, weekly_xp), outcome))| {
+            let (display_name, avatar) = names.remove(&member_id).unwrap_or_default();
+            LeagueStanding {
+                position: index as i32 + 1,
+                user_id: member_id,
+                display_name,
+                avatar,
+                weekly_xp,
+                outcome,
+                is_me: member_id == user_id,
+            }
+        })
+        .collect();
+
+    Ok(Some(LeagueView {
+        tier: cohort.tier,
+        tier_name: tier_name(cohort.tier).to_owned(),
+        week_start: week,
+        ends_at,
+        standings,
+    }))
+}
+
+/// Apply promotions and demotions of every cohort of a past week
+///
+/// Meant to run every hour; returns the number of cohorts finalized.
+pub fn finalize_past_weeks(conn: &mut PgConnection, now: DateTime<Utc>) -> QueryResult<usize> {
+    let week = week_start(now.date_naive());
+    let cohort_ids = archive_league_cohorts::table
+        .filter(archive_league_cohorts::week_start.lt(week))
+        .filter(archive_league_cohorts::finalized_at.is_null())
+        .select(archive_league_cohorts::id)
+        .load::<i64>(conn)?;
+
+    let mut finalized = 0;
+    for cohort_id in cohort_ids {
+        let done = conn.transaction(|conn| {
+            let cohort = archive_league_cohorts::table
+                .find(cohort_id)
+                .for_update()
+                .first::<LeagueCohort>(conn)?;
+            if cohort.finalized_at.is_some() {
+                return Ok(false);
+            }
+            finalize(conn, &cohort, now)?;
+            Ok::<_, diesel::result::Error>(true)
+        })?;
+        if done {
+            finalized += 1;
+        }
+    }
+    Ok(finalized)
+}
+
+fn finalize(conn: &mut PgConnection, cohort: &LeagueCohort, now: DateTime<Utc>) -> QueryResult<()> {
+    // Learners who opted out keep their tier and get no outcome
+    let members = competing_members(conn, cohort.id)?;
+    let user_ids: Vec<i64> = members.iter().map(|(id, _)| *id).collect();
+    let start = cohort.week_start.and_time(NaiveTime::MIN).and_utc();
+    let end = (cohort.week_start + Days::new(7))
+        .and_time(NaiveTime::MIN)
+        .and_utc();
+    let xp = xp_between(conn, &user_ids, start, end)?;
+
+    let ranked = rank_members(members, &xp);
+    let weekly_xp: Vec<i64> = ranked.iter().map(|(_, xp)| *xp).collect();
+    for (index, ((user_id, weekly_xp), outcome)) in ranked
+        .into_iter()
+        .zip(outcomes(cohort.tier, &weekly_xp))
+        .enumerate()
+    {
+        diesel::update(archive_league_members::table)
+            .filter(archive_league_members::cohort_id.eq(cohort.id))
+            .filter(archive_league_members::user_id.eq(user_id))
+            .set((
+                archive_league_members::weekly_xp.eq(weekly_xp.min(i64::from(i32::MAX)) as i32),
+                archive_league_members::final_position.eq(Some(index as i32 + 1)),
+                archive_league_members::outcome.eq(Some(outcome.as_str())),
+            ))
+            .execute(conn)?;
+        let tier = match outcome {
+            LeagueOutcome::Promoted => cohort.tier + 1,
+            LeagueOutcome::Demoted => cohort.tier - 1,
+            LeagueOutcome::Stayed => continue,
+        };
+        diesel::update(archive_user_profiles::table)
+            .filter(archive_user_profiles::user_id.eq(user_id))
+            .set((
+                archive_user_profiles::league_tier.eq(tier),
+                archive_user_profiles::updated_at.eq(now),
+            ))
+            .execute(conn)?;
+    }
+
+    diesel::update(archive_league_cohorts::table.find(cohort.id))
+        .set(archive_league_cohorts::finalized_at.eq(Some(now)))
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Members of a cohort that take part in its ranking, with their join time;
+/// learners who opted out of leaderboards are left out
+fn competing_members(
+    conn: &mut PgConnection,
+    cohort_id: i64,
+) -> QueryResult<Vec<(i64, DateTime<Utc>)>> {
+    archive_league_members::table
+        .inner_join(
+            archive_user_profiles::table
+                .on(archive_user_profiles::user_id.eq(archive_league_members::user_id)),
+        )
+        .filter(archive_league_members::cohort_id.eq(cohort_id))
+        .filter(archive_user_profiles::leaderboard_opt_out.eq(false))
+        .select((
+            archive_league_members::user_id,
+            archive_league_members::joined_at,
+        ))
+        .load(conn)
+}
+
+/// Members by weekly XP, earlier joiners first on ties
+fn rank_members(mut members: Vec<(i64, DateTime<Utc>)>, xp: &HashMap<i64, i64>) -> Vec<(i64, i64)> {
+    members.sort_by_key(|(user_id, joined_at)| {
+        (
+            std::cmp::Reverse(xp.get(user_id).copied().unwrap_or(0)),
+            *joined_at,
+        )
+    });
+    members
+        .into_iter()
+        .map(|(user_id, _)| (user_id, xp.get(&user_id).copied().unwrap_or(0)))
+        .collect()
+}
+
+/// Membership of `user_id` in `week`, joining the fullest open cohort of
+/// their tier or a new one
+fn join(
+    conn: &mut PgConnection,
+    user_id: i64,
+    week: NaiveDate,
+) -> QueryResult<Option<LeagueMember>> {
+    conn.transaction(|conn| {
+        diesel::insert_into(archive_user_profiles::table)
+            .values(&NewUserProfile { user_id })
+            .on_conflict(archive_user_profiles::user_id)
+            .do_nothing()
+            .execute(conn)?;
+        // Locking the profile keeps a learner from joining twice at once
+        let profile = archive_user_profiles::table
+            .filter(archive_user_profiles::user_id.eq(user_id))
+            .for_update()
+            .first::<UserProfile>(conn)?;
+        if profile.leaderboard_opt_out {
+            return Ok(None);
+        }
+        if let Some(member) = archive_league_members::table
+            .filter(archive_league_members::user_id.eq(user_id))
+            .filter(archive_league_members::week_start.eq(week))
+            .first::<LeagueMember>(conn)
+            .optional()?
+        {
+            return Ok(Some(member));
+        }
+
+        let tier = profile.league_tier.clamp(1, TIERS.len() as i32);
+        let open = archive_league_cohorts::table
+            .filter(archive_league_cohorts::week_start.eq(week))
+            .filter(archive_league_cohorts::tier.eq(tier))
+            .filter(archive_league_cohorts::member_count.lt(LEAGUE_SIZE))
+            .order((
+                archive_league_cohorts::member_count.desc(),
+                archive_league_cohorts::id.asc(),
+            ))
+            .for_update()
+            .first::<LeagueCohort>(conn)
+            .optional()?;
+        let cohort = match open {
+            Some(cohort) => cohort,
+            None => diesel::insert_into(archive_league_cohorts::table)
+                .values(&NewLeagueCohort {
+                    week_start: week,
+                    tier,
+                })
+                .get_result::<LeagueCohort>(conn)?,
+        };
+        diesel::update(archive_league_cohorts::table.find(cohort.id))
+            .set(archive_league_cohorts::member_count.eq(cohort.member_count + 1))
+            .execute(conn)?;
+        diesel::insert_into(archive_league_members::table)
+            .values(&NewLeagueMember {
+                cohort_id: cohort.id,
+                user_id,
+                week_start: week,
+            })
+            .get_result::<LeagueMember>(conn)
+            .map(Some)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_moves_up_and_bottom_moves_down() {
+        let xp: Vec<i64> = (0..30).rev().collect();
+        let result = outcomes(2, &xp);
+        assert!(result[..5].iter().all(|o| *o == LeagueOutcome::Promoted));
+        assert!(result[5..25].iter().all(|o| *o == LeagueOutcome::Stayed));
+        // The last one has no XP, and is demoted like the rest of the bottom
+        assert!(result[25..].iter().all(|o| *o == LeagueOutcome::Demoted));
+    }
+
+    #[test]
+    fn small_cohorts_do_not_overlap() {
+        let result = outcomes(2, &[30, 20, 10, 0]);
+        assert_eq!(
+            result,
+            vec![
+                LeagueOutcome::Promoted,
+                LeagueOutcome::Promoted,
+                LeagueOutcome::Promoted,
+                LeagueOutcome::Stayed,
+            ]
+        );
+    }
+
+    #[test]
+    fn tiers_are_bounded() {
+        let xp = [10; 12];
+        assert!(!outcomes(TIERS.len() as i32, &xp).contains(&LeagueOutcome::Promoted));
+        assert!(!outcomes(1, &xp).contains(&LeagueOutcome::Demoted));
+        assert_eq!(tier_name(0), "bronze");
+        assert_eq!(tier_name(99), "diamond");
+    }
+}
@@ -0,0 +1,101 @@
+//! Day boundaries in the learner's time zone
+//!
+//! Statistics, streaks and goals count calendar days as the learner sees
+//! them, so "today" depends on the time zone stored on their profile.
+
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use diesel::prelude::*;
+
+use crate::db::schema::*;
+
+/// Time zone of a user, UTC when they have no profile or an unknown zone
+pub fn user_timezone(conn: &mut PgConnection, user_id: i64) -> QueryResult<Tz> {
+    let timezone = archive_user_profiles::table
+        .filter(archive_user_profiles::user_id.eq(user_id))
+        .select(archive_user_profiles::timezone)
+        .first::<String>(conn)
+        .optional()?;
+    Ok(timezone
+        .and_then(|tz| parse_timezone(&tz))
+        .unwrap_or(Tz::UTC))
+}
+
+/// Parse an IANA time zone name such as "Asia/Shanghai"
+pub fn parse_timezone(name: &str) -> Option<Tz> {
+    name.parse::<Tz>().ok()
+}
+
+/// Calendar date of `at` in `tz`
+pub fn local_date(tz: Tz, at: DateTime<Utc>) -> NaiveDate {
+    at.with_timezone(&tz).date_naive()
+}
+
+/// Start and end (exclusive) of a local day in UTC
+pub fn day_bounds(tz: Tz, date: NaiveDate) -> (DateTime<Utc>, DateTime<Utc>) {
+    (
+        day_start(tz, date),
+        day_start(tz, date + chrono::Days::new(1)),
+    )
+}
+
+fn day_start(tz: Tz, date: NaiveDate) -> DateTime<Utc> {
+    let midnight = date.and_time(NaiveTime::MIN);
+    // Zones that switch to daylight saving time at midnight skip 00:00,
+    // their day starts an hour later
+    tz.from_local_datetime(&midnight)
+        .earliest()
+        .or_else(|| {
+            tz.from_local_datetime(&(midnight + Duration::hours(1)))
+                .earliest()
+        })
+        .map(|start| start.with_timezone(&Utc))
+        .unwrap_or_else(|| Utc.from_utc_datetime(&midnight))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(date: &str) -> DateTime<Utc> {
+        date.parse().unwrap()
+    }
+
+    fn date(value: &str) -> NaiveDate {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn days_follow_the_zone() {
+        let shanghai = parse_timezone("Asia/Shanghai").unwrap();
+        assert_eq!(
+            day_bounds(shanghai, date("2024-05-01")),
+            (utc("2024-04-30T16:00:00Z"), utc("2024-05-01T16:00:00Z"))
+        );
+        assert_eq!(
+            local_date(shanghai, utc("2024-04-30T16:30:00Z")),
+            date("2024-05-01")
+        );
+        assert!(parse_timezone("Mars/Olympus").is_none());
+    }
+
+    #[test]
+    fn daylight_saving_days_are_shorter_or_longer() {
+        let new_york = parse_timezone("America/New_York").unwrap();
+        let (start, end) = day_bounds(new_york, date("2024-03-10"));
+        assert_eq!(start, utc("2024-03-10T05:00:00Z"));
+        assert_eq!(end - start, Duration::hours(23));
+        let (start, end) = day_bounds(new_york, date("2024-11-03"));
+        assert_eq!(start, utc("2024-11-03T04:00:00Z"));
+        assert_eq!(end - start, Duration::hours(25));
+    }
+
+    #[test]
+    fn day_starts_after_a_skipped_midnight() {
+        // Chile moves its clocks from midnight to 01:00
+        let santiago = parse_timezone("America/Santiago").unwrap();
+        let (start, end) = day_bounds(santiago, date("2024-09-08"));
+        assert_eq!(start, utc("2024-09-08T04:00:00Z"));
+        assert_eq!(end, utc("2024-09-09T03:00:00Z"));
+    }
+}
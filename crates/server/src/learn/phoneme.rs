@@ -0,0 +1,700 @@
+//! Phoneme level pronunciation diagnostics
+//!
+//! ASR only tells us which words were heard, so the diagnosis works on the
+//! words the learner got wrong: the reference and heard words are aligned,
+//! and each substituted pair is compared phoneme by phoneme. Phonemes come
+//! from the sentence's own transcription, then `dict_pronunciations`, then
+//! a rough spelling based guess. Mismatches between sounds Chinese speakers
+//! commonly confuse are flagged and come with a tip.
+//!
+//! The report is stored in `learn_read_practices.detected_errors`, and
+//! [`weak_sounds`] aggregates the stored reports into a weak-sound profile.
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use salvo::oapi::ToSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::db::schema::*;
+
+/// Attempts on a phoneme before it can show up in the weak-sound profile
+pub const MIN_ATTEMPTS: u32 = 3;
+
+/// Pairs of phonemes Chinese speakers commonly substitute for each other
+const CONFUSABLE: &[(&str, &str)] = &[
+    ("θ", "s"),
+    ("θ", "f"),
+    ("θ", "t"),
+    ("ð", "d"),
+    ("ð", "z"),
+    ("ð", "l"),
+    ("v", "w"),
+    ("v", "f"),
+    ("l", "n"),
+    ("l", "r"),
+    ("r", "w"),
+    ("n", "ŋ"),
+    ("ɪ", "iː"),
+    ("ʊ", "uː"),
+    ("æ", "e"),
+    ("æ", "ʌ"),
+    ("ʌ", "ɑː"),
+    ("ɒ", "əʊ"),
+    ("ɜː", "ə"),
+    ("ʃ", "s"),
+    ("ʒ", "ʃ"),
+    ("ʒ", "r"),
+    ("z", "s"),
+    ("dʒ", "ʒ"),
+    ("tʃ", "ʃ"),
+    ("eɪ", "e"),
+];
+
+/// Multi-character phonemes, longest first
+const CLUSTERS: &[&str] = &["tʃ", "dʒ", "eɪ", "aɪ", "ɔɪ", "aʊ", "əʊ", "ɪə", "eə", "ʊə"];
+
+/// Spelling patterns for the fallback, longest first within a letter
+const SPELLINGS: &[(&str, &[&str])] = &[
+    ("tch", &["tʃ"]),
+    ("igh", &["aɪ"]),
+    ("dge", &["dʒ"]),
+    ("th", &["θ"]),
+    ("sh", &["ʃ"]),
+    ("ch", &["tʃ"]),
+    ("ph", &["f"]),
+    ("ng", &["ŋ"]),
+    ("nk", &["ŋ", "k"]),
+    ("ck", &["k"]),
+    ("wh", &["w"]),
+    ("qu", &["k", "w"]),
+    ("ee", &["iː"]),
+    ("ea", &["iː"]),
+    ("oo", &["uː"]),
+    ("ai", &["eɪ"]),
+    ("ay", &["eɪ"]),
+    ("oa", &["əʊ"]),
+    ("ou", &["aʊ"]),
+    ("ow", &["aʊ"]),
+    ("oi", &["ɔɪ"]),
+    ("oy", &["ɔɪ"]),
+    ("ar", &["ɑː"]),
+    ("or", &["ɔː"]),
+    ("er", &["ə"]),
+    ("ir", &["ɜː"]),
+    ("ur", &["ɜː"]),
+    ("a", &["æ"]),
+    ("b", &["b"]),
+    ("d", &["d"]),
+    ("e", &["e"]),
+    ("f", &["f"]),
+    ("g", &["ɡ"]),
+    ("h", &["h"]),
+    ("i", &["ɪ"]),
+    ("j", &["dʒ"]),
+    ("k", &["k"]),
+    ("l", &["l"]),
+    ("m", &["m"]),
+    ("n", &["n"]),
+    ("o", &["ɒ"]),
+    ("p", &["p"]),
+    ("q", &["k"]),
+    ("r", &["r"]),
+    ("s", &["s"]),
+    ("t", &["t"]),
+    ("u", &["ʌ"]),
+    ("v", &["v"]),
+    ("w", &["w"]),
+    ("x", &["k", "s"]),
+    ("z", &["z"]),
+];
+
+/// Function words whose "th" is voiced
+const VOICED_TH: &[&str] = &[
+    "the", "this", "that", "these", "those", "they", "them", "their", "there", "then", "than",
+    "though", "with", "other", "mother", "father", "brother", "weather",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PhonemeStat {
+    pub phoneme: String,
+    pub attempts: u32,
+    pub errors: u32,
+    /// One of the sentence's focus sounds
+    #[serde(default)]
+    pub focus: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PhonemeIssue {
+    /// Reference word the phoneme belongs to
+    pub word: String,
+    /// Word heard in its place
+    pub heard: String,
+    pub expected: String,
+    /// Phoneme heard instead, absent when it was dropped
+    pub actual: Option<String>,
+    /// A substitution Chinese speakers commonly make
+    pub confusable: bool,
+    #[serde(default)]
+    pub focus: bool,
+    pub tip: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct PhonemeReport {
+    pub phonemes: Vec<PhonemeStat>,
+    pub issues: Vec<PhonemeIssue>,
+    /// Reference words nothing was heard for
+    pub omitted_words: Vec<String>,
+    /// The sentence's common mistakes that match an issue
+    #[serde(default)]
+    pub hints: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Substitution {
+    pub phoneme: String,
+    pub count: u32,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WeakSound {
+    pub phoneme: String,
+    pub attempts: u32,
+    pub errors: u32,
+    pub error_rate: f32,
+    /// What was heard instead, most frequent first
+    pub substitutions: Vec<Substitution>,
+    /// Times the phoneme was dropped
+    pub omissions: u32,
+    pub tip: Option<String>,
+}
+
+/// Normalized words of `text`, as the word level scoring splits them
+pub fn words(text: &str) -> Vec<String> {
+    text.to_lowercase()
+        .split_whitespace()
+        .map(|s| s.trim_matches(|c: char| !c.is_alphanumeric()).to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Split an IPA transcription into phonemes, dropping stress and syllable marks
+///
+/// Only the first variant is used when several are given.
+pub fn ipa_phonemes(ipa: &str) -> Vec<String> {
+    let variant = ipa.split([',', ';']).next().unwrap_or_default();
+    let normalized: Vec<char> = variant
+        .replace(['ʧ'], "tʃ")
+        .replace(['ʤ'], "dʒ")
+        .replace("oʊ", "əʊ")
+        .replace('ɝ', "ɜː")
+        .replace(':', "ː")
+        .chars()
+        .filter_map(|c| match c {
+            'ɹ' => Some('r'),
+            'ɛ' => Some('e'),
+            'ɚ' => Some('ə'),
+            'ɫ' => Some('l'),
+            'g' => Some('ɡ'),
+            'ɐ' => Some('ʌ'),
+            'ˈ' | 'ˌ' | '.' | '/' | '[' | ']' | '(' | ')' | '‿' | 'ʰ' | '-' => None,
+            c if c.is_whitespace() => None,
+            '\u{0300}'..='\u{036f}' => None,
+            c => Some(c),
+        })
+        .collect();
+
+    let mut phonemes: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < normalized.len() {
+        if normalized[i] == 'ː' {
+            if let Some(last) = phonemes.last_mut() {
+                last.push('ː');
+            }
+            i += 1;
+            continue;
+        }
+        let pair: String = normalized[i..(i + 2).min(normalized.len())]
+            .iter()
+            .collect();
+        if CLUSTERS.contains(&pair.as_str()) {
+            phonemes.push(pair);
+            i += 2;
+        } else {
+            phonemes.push(normalized[i].to_string());
+            i += 1;
+        }
+    }
+    phonemes
+}
+
+/// Rough phonemes of an English word from its spelling
+pub fn grapheme_phonemes(word: &str) -> Vec<String> {
+    let word = word.to_lowercase();
+    let letters: Vec<char> = word.chars().filter(|c| c.is_ascii_alphabetic()).collect();
+    let mut phonemes: Vec<String> = Vec::new();
+    let mut i = 0;
+    while i < letters.len() {
+        let c = letters[i];
+        let next = letters.get(i + 1).copied();
+        // Doubled consonants are one sound, final "e" after a consonant is silent
+        if i > 0 && letters[i - 1] == c && !"aeiou".contains(c) {
+            i += 1;
+            continue;
+        }
+        if c == 'e' && i + 1 == letters.len() && i >= 3 && !"aeiou".contains(letters[i - 1]) {
+            break;
+        }
+        match (c, next) {
+            ('c', Some('e' | 'i' | 'y')) => {
+                phonemes.push("s".to_string());
+                i += 1;
+                continue;
+            }
+            ('c', _) => {
+                phonemes.push("k".to_string());
+                i += 1;
+                continue;
+            }
+            ('y', _) => {
+                phonemes.push(if i == 0 { "j" } else { "ɪ" }.to_string());
+                i += 1;
+                continue;
+            }
+            _ => {}
+        }
+        let rest: String = letters[i..].iter().collect();
+        let Some((pattern, sounds)) = SPELLINGS.iter().find(|(p, _)| rest.starts_with(p)) else {
+            i += 1;
+            continue;
+        };
+        if *pattern == "th" && VOICED_TH.contains(&word.as_str()) {
+            phonemes.push("ð".to_string());
+        } else {
+            phonemes.extend(sounds.iter().map(|s| s.to_string()));
+        }
+        i += pattern.len();
+    }
+    phonemes
+}
+
+/// Whether learners commonly say `actual` for `expected`
+pub fn is_confusable(expected: &str, actual: &str) -> bool {
+    CONFUSABLE
+        .iter()
+        .any(|&(a, b)| (a == expected && b == actual) || (a == actual && b == expected))
+}
+
+/// How to produce `phoneme`, for the sounds learners struggle with most
+pub fn tip(phoneme: &str) -> Option<&'static str> {
+    let tip = match phoneme {
+        "θ" => "/θ/ 舌尖轻放在上下齿之间送气，不要发成 /s/ 或 /f/",
+        "ð" => "/ð/ 舌尖放在齿间并振动声带，不要发成 /d/ 或 /z/",
+        "v" => "/v/ 上齿轻触下唇并振动声带，不要发成 /w/",
+        "w" => "/w/ 双唇收圆向前突出，牙齿不要碰嘴唇",
+        "l" => "/l/ 舌尖抵住上齿龈，气流从舌头两侧出",
+        "n" => "/n/ 舌尖抵住上齿龈，气流从鼻腔出",
+        "r" => "/r/ 舌尖向后卷但不接触上颚，双唇略圆",
+        "ŋ" => "/ŋ/ 舌根抵住软腭，注意与 /n/ 区分",
+        "ɪ" => "/ɪ/ 短而放松，不要拉长成 /iː/",
+        "iː" => "/iː/ 嘴角向两边展开并拉长",
+        "ʊ" => "/ʊ/ 短而放松，不要拉长成 /uː/",
+        "uː" => "/uː/ 双唇收圆并拉长",
+        "æ" => "/æ/ 嘴张大，舌位放低，不要发成 /e/",
+        "ʌ" => "/ʌ/ 嘴半开，短促有力",
+        "ʒ" => "/ʒ/ 与 /ʃ/ 口型相同但要振动声带",
+        "ʃ" => "/ʃ/ 双唇略突出，舌面抬向硬腭",
+        "z" => "/z/ 与 /s/ 口型相同但要振动声带",
+        "ɜː" => "/ɜː/ 舌身居中，嘴唇放松并拉长",
+        _ => return None,
+    };
+    Some(tip)
+}
+
+/// Phonemes for each of `words` known to the dictionary, primary first
+pub fn lexicon(
+    conn: &mut PgConnection,
+    words: &[String],
+) -> QueryResult<HashMap<String, Vec<String>>> {
+    let rows = dict_pronunciations::table
+        .inner_join(dict_words::table.on(dict_words::id.eq(dict_pronunciations::word_id)))
+        .filter(dict_words::word_lower.eq_any(words))
+        .filter(
+            dict_words::language
+                .eq("en")
+                .or(dict_words::language.is_null()),
+        )
+        .order((
+            dict_words::word_lower.asc(),
+            dict_pronunciations::is_primary.desc().nulls_last(),
+            dict_pronunciations::id.asc(),
+        ))
+        .select((dict_words::word_lower, dict_pronunciations::ipa))
+        .load::<(String, String)>(conn)?;
+
+    let mut lexicon = HashMap::new();
+    for (word, ipa) in rows {
+        let phonemes = ipa_phonemes(&ipa);
+        if !phonemes.is_empty() {
+            lexicon.entry(word).or_insert(phonemes);
+        }
+    }
+    Ok(lexicon)
+}
+
+/// Per-word phonemes from a sentence transcription, when it has one entry
+/// per word of the reference
+pub fn transcription_lexicon(reference: &str, transcription: &str) -> HashMap<String, Vec<String>> {
+    let reference = words(reference);
+    let parts: Vec<&str> = transcription
+        .trim_matches(|c| c == '/' || c == '[' || c == ']')
+        .split_whitespace()
+        .collect();
+    if parts.len() != reference.len() {
+        return HashMap::new();
+    }
+    reference
+        .into_iter()
+        .zip(parts)
+        .map(|(word, ipa)| (word, ipa_phonemes(ipa)))
+        .filter(|(_, phonemes)| !phonemes.is_empty())
+        .collect()
+}
+
+/// Compare what was heard with the reference phoneme by phoneme
+///
+/// `focus` holds the sentence's focus sounds, either words of the sentence
+/// or spellings of a sound such as "sh".
+pub fn diagnose(
+    reference: &str,
+    heard: &str,
+    lexicon: &HashMap<String, Vec<String>>,
+    focus: &[String],
+    common_mistakes: &[String],
+) -> PhonemeReport {
+    let reference = words(reference);
+    let heard = words(heard);
+    let phonemes_of = |word: &str| {
+        lexicon
+            .get(word)
+            .cloned()
+            .unwrap_or_else(|| grapheme_phonemes(word))
+    };
+    let focus_words: HashSet<&str> = focus
+        .iter()
+        .map(|f| f.as_str())
+        .filter(|f| reference.iter().any(|w| w == f))
+        .collect();
+    let focus_sounds: HashSet<String> = focus
+        .iter()
+        .filter(|f| !focus_words.contains(f.as_str()))
+        .flat_map(|f| {
+            if f.is_ascii() {
+                grapheme_phonemes(f)
+            } else {
+                ipa_phonemes(f)
+            }
+        })
+        .collect();
+
+    let mut stats: Vec<PhonemeStat> = Vec::new();
+    let mut count =
+        |phoneme: &str, error: bool| match stats.iter_mut().find(|s| s.phoneme == phoneme) {
+            Some(stat) => {
+                stat.attempts += 1;
+                stat.errors += u32::from(error);
+            }
+            None => stats.push(PhonemeStat {
+                phoneme: phoneme.to_string(),
+                attempts: 1,
+                errors: u32::from(error),
+                focus: focus_sounds.contains(phoneme),
+            }),
+        };
+    let mut report = PhonemeReport::default();
+
+    for (r, h) in align(&reference, &heard) {
+        let Some(r) = r else {
+            continue;
+        };
+        let word = &reference[r];
+        let expected = phonemes_of(word);
+        let Some(h) = h else {
+            report.omitted_words.push(word.clone());
+            continue;
+        };
+        if heard[h] == *word {
+            for phoneme in &expected {
+                count(phoneme, false);
+            }
+            continue;
+        }
+        let actual = phonemes_of(&heard[h]);
+        for (e, a) in align(&expected, &actual) {
+            let Some(e) = e else {
+                continue;
+            };
+            let expected = &expected[e];
+            let actual = a.map(|a| actual[a].clone());
+            let error = actual.as_ref() != Some(expected);
+            count(expected, error);
+            if error {
+                report.issues.push(PhonemeIssue {
+                    word: word.clone(),
+                    heard: heard[h].clone(),
+                    expected: expected.clone(),
+                    confusable: actual
+                        .as_deref()
+                        .is_some_and(|a| is_confusable(expected, a)),
+                    actual,
+                    focus: focus_words.contains(word.as_str()) || focus_sounds.contains(expected),
+                    tip: tip(expected).map(str::to_string),
+                });
+            }
+        }
+    }
+
+    stats.sort_by(|a, b| {
+        b.errors
+            .cmp(&a.errors)
+            .then_with(|| a.phoneme.cmp(&b.phoneme))
+    });
+    report.phonemes = stats;
+    report.hints = common_mistakes
+        .iter()
+        .filter(|hint| {
+            let hint = hint.to_lowercase();
+            report.issues.iter().any(|issue| hint.contains(&issue.word))
+                || report.omitted_words.iter().any(|word| hint.contains(word))
+        })
+        .cloned()
+        .collect();
+    report
+}
+
+/// Strings of a `focus_sounds` or `common_mistakes` JSON array
+pub fn json_strings(value: Option<&Value>) -> Vec<String> {
+    value
+        .and_then(Value::as_array)
+        .map(|items| {
+            items
+                .iter()
+                .filter_map(Value::as_str)
+                .map(|s| s.trim().to_lowercase())
+                .filter(|s| !s.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Phonemes the learner most often gets wrong in read practices since `since`
+pub fn weak_sounds(
+    conn: &mut PgConnection,
+    user_id: i64,
+    since: DateTime<Utc>,
+    limit: usize,
+) -> QueryResult<Vec<WeakSound>> {
+    let reports: Vec<PhonemeReport> = learn_read_practices::table
+        .filter(learn_read_practices::user_id.eq(user_id))
+        .filter(learn_read_practices::created_at.ge(since))
+        .filter(learn_read_practices::detected_errors.is_not_null())
+        .select(learn_read_practices::detected_errors)
+        .load::<Option<Value>>(conn)?
+        .into_iter()
+        .flatten()
+        .filter_map(|value| serde_json::from_value(value).ok())
+        .collect();
+    Ok(aggregate(&reports, limit))
+}
+
+fn aggregate(reports: &[PhonemeReport], limit: usize) -> Vec<WeakSound> {
+    let mut sounds: HashMap<&str, WeakSound> = HashMap::new();
+    let mut substitutions: HashMap<(&str, &str), u32> = HashMap::new();
+    for report in reports {
+        for stat in &report.phonemes {
+            let sound = sounds
+                .entry(stat.phoneme.as_str())
+                .or_insert_with(|| WeakSound {
+                    phoneme: stat.phoneme.clone(),
+                    attempts: 0,
+                    errors: 0,
+                    error_rate: 0.0,
+                    substitutions: Vec::new(),
+                    omissions: 0,
+                    tip: tip(&stat.phoneme).map(str::to_string),
+                });
+            sound.attempts += stat.attempts;
+            sound.errors += stat.errors;
+        }
+        for issue in &report.issues {
+            match &issue.actual {
+                Some(actual) => {
+                    *substitutions
+                        .entry((issue.expected.as_str(), actual.as_str()))
+                        .or_default() += 1
+                }
+                None => {
+                    if let Some(sound) = sounds.get_mut(issue.expected.as_str()) {
+                        sound.omissions += 1;
+                    }
+                }
+            }
+        }
+    }
+    for ((expected, actual), count) in substitutions {
+        if let Some(sound) = sounds.get_mut(expected) {
+            sound.substitutions.push(Substitution {
+                phoneme: actual.to_string(),
+                count,
+            });
+        }
+    }
+
+    let mut weak: Vec<WeakSound> = sounds
+        .into_values()
+        .filter(|s| s.attempts >= MIN_ATTEMPTS && s.errors > 0)
+        .map(|mut s| {
+            s.error_rate = s.errors as f32 / s.attempts as f32;
+            s.substitutions.sort_by(|a, b| {
+                b.count
+                    .cmp(&a.count)
+                    .then_with(|| a.phoneme.cmp(&b.phoneme))
+            });
+            s
+        })
+        .collect();
+    weak.sort_by(|a, b| {
+        b.error_rate
+            .total_cmp(&a.error_rate)
+            .then_with(|| b.errors.cmp(&a.errors))
+            .then_with(|| a.phoneme.cmp(&b.phoneme))
+    });
+    weak.truncate(limit);
+    weak
+}
+
+/// Minimal edit alignment of two sequences, pairs of indexes into each
+///
+/// `None` on one side is an item missing there. Also aligns the words and
+/// characters of dictations and the words of shadowing recordings.
+pub(crate) fn align<T: PartialEq>(a: &[T], b: &[T]) -> Vec<(Option<usize>, Option<usize>)> {
+    let mut cost = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for (i, row) in cost.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for j in 0..=b.len() {
+        cost[0][j] = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let substitution = cost[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            cost[i][j] = substitution.min(cost[i - 1][j] + 1).min(cost[i][j - 1] + 1);
+        }
+    }
+
+    let mut pairs = Vec::with_capacity(a.len().max(b.len()));
+    let (mut i, mut j) = (a.len(), b.len());
+    while i > 0 || j > 0 {
+        // Prefer matches, then dropping from `a`, so "want it" against
+        // "wan" reads as "want" said wrong rather than "it" said wrong
+        let diagonal = i > 0 && j > 0;
+        if diagonal && a[i - 1] == b[j - 1] && cost[i][j] == cost[i - 1][j - 1] {
+            pairs.push((Some(i - 1), Some(j - 1)));
+            i -= 1;
+            j -= 1;
+        } else if i > 0 && cost[i][j] == cost[i - 1][j] + 1 {
+            pairs.push((Some(i - 1), None));
+            i -= 1;
+        } else if diagonal && cost[i][j] == cost[i - 1][j - 1] + 1 {
+            pairs.push((Some(i - 1), Some(j - 1)));
+            i -= 1;
+            j -= 1;
+        } else {
+            pairs.push((None, Some(j - 1)));
+            j -= 1;
+        }
+    }
+    pairs.reverse();
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_ipa_into_phonemes() {
+        assert_eq!(ipa_phonemes("/ˈθɪŋk/"), ["θ", "ɪ", "ŋ", "k"]);
+        assert_eq!(ipa_phonemes("tʃiːz"), ["tʃ", "iː", "z"]);
+        assert_eq!(ipa_phonemes("ˈhəʊm, hoʊm"), ["h", "əʊ", "m"]);
+        assert_eq!(ipa_phonemes("ɡoʊ"), ipa_phonemes("gəʊ"));
+    }
+
+    #[test]
+    fn guesses_phonemes_from_spelling() {
+        assert_eq!(grapheme_phonemes("think"), ["θ", "ɪ", "ŋ", "k"]);
+        assert_eq!(grapheme_phonemes("this"), ["ð", "ɪ", "s"]);
+        assert_eq!(grapheme_phonemes("ship"), ["ʃ", "ɪ", "p"]);
+        assert_eq!(grapheme_phonemes("city"), ["s", "ɪ", "t", "ɪ"]);
+    }
+
+    #[test]
+    fn flags_confusable_substitutions() {
+        let lexicon = HashMap::from([
+            ("think".to_string(), ipa_phonemes("θɪŋk")),
+            ("sink".to_string(), ipa_phonemes("sɪŋk")),
+        ]);
+        let report = diagnose("I think so", "I sink so", &lexicon, &[], &[]);
+        assert_eq!(report.issues.len(), 1);
+        let issue = &report.issues[0];
+        assert_eq!(issue.expected, "θ");
+        assert_eq!(issue.actual.as_deref(), Some("s"));
+        assert!(issue.confusable);
+        assert!(issue.tip.is_some());
+        assert!(report.omitted_words.is_empty());
+    }
+
+    #[test]
+    fn reports_dropped_phonemes_and_words() {
+        let lexicon = HashMap::from([
+            ("want".to_string(), ipa_phonemes("wɒnt")),
+            ("wan".to_string(), ipa_phonemes("wɒn")),
+        ]);
+        let report = diagnose(
+            "I want it now",
+            "I wan now",
+            &lexicon,
+            &["want".to_string()],
+            &["want final t".to_string()],
+        );
+        assert_eq!(report.omitted_words, ["it"]);
+        assert_eq!(report.issues.len(), 1);
+        assert_eq!(report.issues[0].expected, "t");
+        assert_eq!(report.issues[0].actual, None);
+        assert!(report.issues[0].focus);
+        assert_eq!(report.hints, ["want final t"]);
+    }
+
+    #[test]
+    fn aggregates_weak_sounds() {
+        let lexicon = HashMap::from([
+            ("think".to_string(), ipa_phonemes("θɪŋk")),
+            ("sink".to_string(), ipa_phonemes("sɪŋk")),
+            ("thing".to_string(), ipa_phonemes("θɪŋ")),
+        ]);
+        let reports = vec![
+            diagnose("think", "sink", &lexicon, &[], &[]),
+            diagnose("think", "think", &lexicon, &[], &[]),
+            diagnose("thing", "thing", &lexicon, &[], &[]),
+        ];
+        let weak = aggregate(&reports, 10);
+        assert_eq!(weak.len(), 1);
+        assert_eq!(weak[0].phoneme, "θ");
+        assert_eq!((weak[0].attempts, weak[0].errors), (3, 1));
+        assert_eq!(weak[0].substitutions[0].phoneme, "s");
+    }
+}
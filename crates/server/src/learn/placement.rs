@@ -0,0 +1,842 @@
+//! Adaptive placement test
+//!
+//! A test runs through a fixed plan of vocabulary, grammar, reading and
+//! speaking items, but each item is generated only when it is reached, at
+//! the level currently estimated for the learner. The estimate follows a
+//! staircase: a right answer moves it up by the step, a wrong one down, and
+//! the step halves whenever the direction changes, so it settles on the
+//! level the learner answers about half right. Levels use the 1-10 scale of
+//! `difficulty` in the asset tables.
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use diesel::dsl::{max, min, sql};
+use diesel::prelude::*;
+use diesel::sql_types::{Integer, Nullable, SmallInt};
+use rand::seq::{IndexedRandom, SliceRandom};
+use salvo::oapi::ToSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use super::phoneme;
+use super::quiz::{self, QuizQuestionType, QuizWord};
+use crate::db::schema::*;
+use crate::models::achievement::{NewUserProfile, UpdateUserProfile};
+use crate::models::asset::{Context, ReadSubject, Stage};
+use crate::models::learn::*;
+
+pub const MIN_LEVEL: i16 = 1;
+pub const MAX_LEVEL: i16 = 10;
+/// Estimate a test starts from when the learner gives none
+const DEFAULT_START_LEVEL: i16 = 3;
+const START_STEP: f32 = 2.0;
+const MIN_STEP: f32 = 0.5;
+/// Share of the sentence's words a speaking answer must contain
+const SPEAKING_PASS_RATIO: f32 = 0.8;
+/// Recommendations of each kind
+const RECOMMENDATIONS: i64 = 5;
+/// Dictionary words tried per vocabulary item
+const VOCABULARY_CANDIDATES: i64 = 5;
+
+/// Item types in the order a test asks them
+const PLAN: [PlacementItemType; 16] = {
+    use PlacementItemType::*;
+    [
+        Vocabulary, Grammar, Vocabulary, Grammar, Reading, Vocabulary, Grammar, Vocabulary,
+        Reading, Grammar, Vocabulary, Grammar, Vocabulary, Reading, Grammar, Speaking,
+    ]
+};
+
+/// Grammar items: level, sentence with a blank, options with the right one first
+const GRAMMAR: &[(i16, &str, [&str; 4])] = &[
+    (1, "She ___ a student.", ["is", "are", "am", "be"]),
+    (1, "I ___ two brothers.", ["have", "has", "having", "haves"]),
+    (
+        1,
+        "They ___ football every Sunday.",
+        ["play", "plays", "playing", "to play"],
+    ),
+    (
+        2,
+        "He ___ to school every day.",
+        ["goes", "go", "going", "gone"],
+    ),
+    (
+        2,
+        "There ___ some apples on the table.",
+        ["are", "is", "be", "am"],
+    ),
+    (2, "___ you like coffee?", ["Do", "Does", "Are", "Is"]),
+    (
+        3,
+        "Yesterday we ___ a great movie.",
+        ["watched", "watch", "watching", "have watched"],
+    ),
+    (
+        3,
+        "She is ___ than her sister.",
+        ["taller", "tall", "tallest", "more tall"],
+    ),
+    (
+        3,
+        "I ___ TV when you called.",
+        ["was watching", "watched", "am watching", "watch"],
+    ),
+    (
+        4,
+        "I have lived here ___ 2015.",
+        ["since", "for", "from", "at"],
+    ),
+    (
+        4,
+        "If it rains, we ___ at home.",
+        ["will stay", "stayed", "would stay", "stay will"],
+    ),
+    (
+        4,
+        "This is the book ___ I told you about.",
+        ["that", "who", "what", "where"],
+    ),
+    (
+        5,
+        "She has ___ finished her homework.",
+        ["already", "yet", "still", "ever"],
+    ),
+    (
+        5,
+        "The letter ___ by my grandmother.",
+        ["was written", "wrote", "was wrote", "has writing"],
+    ),
+    (
+        5,
+        "You ___ wear a seatbelt; it's the law.",
+        ["must", "might", "could", "would"],
+    ),
+    (
+        6,
+        "If I ___ more time, I would learn the piano.",
+        ["had", "have", "will have", "would have"],
+    ),
+    (
+        6,
+        "He asked me where I ___.",
+        ["lived", "live", "do live", "am living"],
+    ),
+    (
+        6,
+        "I'm not used to ___ up so early.",
+        ["getting", "get", "got", "have got"],
+    ),
+    (
+        7,
+        "By the time we arrived, the film ___.",
+        [
+            "had already started",
+            "already started",
+            "has already started",
+            "was already starting",
+        ],
+    ),
+    (
+        7,
+        "She denied ___ the money.",
+        ["taking", "to take", "take", "to have take"],
+    ),
+    (
+        7,
+        "___ the bad weather, the match went ahead.",
+        ["Despite", "Although", "Even though", "However"],
+    ),
+    (
+        8,
+        "If she had studied harder, she ___ the exam.",
+        [
+            "would have passed",
+            "would pass",
+            "had passed",
+            "will have passed",
+        ],
+    ),
+    (
+        8,
+        "Not only ___ late, but he also forgot the tickets.",
+        ["was he", "he was", "he is", "did he be"],
+    ),
+    (
+        8,
+        "I'd rather you ___ smoke in here.",
+        ["didn't", "don't", "won't", "not"],
+    ),
+    (
+        9,
+        "___ had I sat down than the phone rang.",
+        ["No sooner", "Hardly", "Scarcely", "As soon as"],
+    ),
+    (
+        9,
+        "It's high time we ___ a decision.",
+        ["made", "make", "will make", "have made"],
+    ),
+    (
+        9,
+        "The proposal, ___ merits are obvious, was rejected.",
+        ["whose", "which", "that", "whom"],
+    ),
+    (
+        10,
+        "Were he ___ the truth, he would be furious.",
+        ["to discover", "discovering", "discovered", "discovers"],
+    ),
+    (
+        10,
+        "So ___ was the storm that the ferry was cancelled.",
+        ["severe", "severely", "severity", "severer"],
+    ),
+    (
+        10,
+        "She insisted that he ___ present at the meeting.",
+        ["be", "is", "was", "will be"],
+    ),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum PlacementItemType {
+    /// Pick the meaning of a word from a frequency band
+    Vocabulary,
+    /// Pick the word that completes a sentence
+    Grammar,
+    /// Pick the translation of a sentence
+    Reading,
+    /// Read a sentence aloud
+    Speaking,
+}
+
+impl PlacementItemType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PlacementItemType::Vocabulary => "vocabulary",
+            PlacementItemType::Grammar => "grammar",
+            PlacementItemType::Reading => "reading",
+            PlacementItemType::Speaking => "speaking",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        [
+            PlacementItemType::Vocabulary,
+            PlacementItemType::Grammar,
+            PlacementItemType::Reading,
+            PlacementItemType::Speaking,
+        ]
+        .into_iter()
+        .find(|t| t.as_str() == value)
+    }
+}
+
+/// Item as shown to the learner, the answer is revealed once answered
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PlacementItemView {
+    pub id: i64,
+    pub position: i32,
+    /// vocabulary | grammar | reading | speaking
+    pub item_type: String,
+    pub level: i16,
+    /// Word (vocabulary), sentence with a blank (grammar) or sentence to
+    /// translate or read aloud
+    pub prompt: String,
+    pub choices: Option<Value>,
+    pub answered: bool,
+    pub user_answer: Option<Value>,
+    /// Absent for skipped items
+    pub is_correct: Option<bool>,
+    pub answer: Option<Value>,
+}
+
+impl From<PlacementItem> for PlacementItemView {
+    fn from(item: PlacementItem) -> Self {
+        let answered = item.answered_at.is_some();
+        Self {
+            id: item.id,
+            position: item.position,
+            item_type: item.item_type,
+            level: item.level,
+            prompt: item.prompt,
+            choices: item.choices,
+            answered,
+            user_answer: item.user_answer,
+            is_correct: item.is_correct,
+            answer: answered.then_some(item.answer),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Recommendations {
+    pub level: i16,
+    pub stages: Vec<Stage>,
+    pub contexts: Vec<Context>,
+    pub read_subjects: Vec<ReadSubject>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PlacementView {
+    pub test: PlacementTest,
+    pub items: Vec<PlacementItemView>,
+    /// Present once the test is completed
+    pub recommendations: Option<Recommendations>,
+}
+
+struct ItemDraft {
+    item_type: PlacementItemType,
+    level: i16,
+    source_id: Option<i64>,
+    prompt: String,
+    choices: Option<Value>,
+    answer: Value,
+}
+
+/// Start a placement test and generate its first item
+pub fn start(
+    conn: &mut PgConnection,
+    user_id: i64,
+    start_level: Option<i16>,
+) -> QueryResult<PlacementView> {
+    let estimate = start_level
+        .unwrap_or(DEFAULT_START_LEVEL)
+        .clamp(MIN_LEVEL, MAX_LEVEL);
+    conn.transaction(|conn| {
+        let test = diesel::insert_into(learn_placement_tests::table)
+            .values(&NewPlacementTest {
+                user_id,
+                item_count: PLAN.len() as i32,
+                estimate: f32::from(estimate),
+                step: START_STEP,
+            })
+            .get_result::<PlacementTest>(conn)?;
+        insert_next_item(conn, &test, &[])?;
+        load_view(conn, test)
+    })
+}
+
+/// Load a placement test of the user with its items so far
+pub fn get_test(conn: &mut PgConnection, user_id: i64, test_id: i64) -> QueryResult<PlacementView> {
+    let test = learn_placement_tests::table
+        .filter(learn_placement_tests::id.eq(test_id))
+        .filter(learn_placement_tests::user_id.eq(user_id))
+        .first::<PlacementTest>(conn)?;
+    load_view(conn, test)
+}
+
+/// Check the answer to an item, move the estimate and generate the next item
+///
+/// A `null` answer skips the item without moving the estimate. Speaking items
+/// take the transcript of the recording. The final item completes the test
+/// and saves the level on the profile. Answering twice returns the stored
+/// result.
+pub fn answer_item(
+    conn: &mut PgConnection,
+    user_id: i64,
+    test_id: i64,
+    item_id: i64,
+    answer: Value,
+    now: DateTime<Utc>,
+) -> QueryResult<PlacementView> {
+    conn.transaction(|conn| {
+        let test = learn_placement_tests::table
+            .filter(learn_placement_tests::id.eq(test_id))
+            .filter(learn_placement_tests::user_id.eq(user_id))
+            .for_update()
+            .first::<PlacementTest>(conn)?;
+        let item = learn_placement_items::table
+            .filter(learn_placement_items::id.eq(item_id))
+            .filter(learn_placement_items::test_id.eq(test_id))
+            .first::<PlacementItem>(conn)?;
+        if item.answered_at.is_some() || test.completed_at.is_some() {
+            return load_view(conn, test);
+        }
+
+        let correct = if answer.is_null() {
+            None
+        } else {
+            Some(check_answer(&item, &answer))
+        };
+        diesel::update(learn_placement_items::table.find(item.id))
+            .set((
+                learn_placement_items::user_answer.eq(Some(answer)),
+                learn_placement_items::is_correct.eq(correct),
+                learn_placement_items::answered_at.eq(Some(now)),
+            ))
+            .execute(conn)?;
+
+        let (estimate, step) = match correct {
+            Some(correct) => {
+                let previous = learn_placement_items::table
+                    .filter(learn_placement_items::test_id.eq(test_id))
+                    .filter(learn_placement_items::position.lt(item.position))
+                    .filter(learn_placement_items::is_correct.is_not_null())
+                    .order(learn_placement_items::position.desc())
+                    .select(learn_placement_items::is_correct)
+                    .first::<Option<bool>>(conn)
+                    .optional()?
+                    .flatten();
+                next_estimate(test.estimate, test.step, previous, correct)
+            }
+            None => (test.estimate, test.step),
+        };
+        let answered_count = test.answered_count + 1;
+        let completed = answered_count >= test.item_count;
+        let final_level = completed.then(|| level_of(estimate));
+        let test = diesel::update(learn_placement_tests::table.find(test.id))
+            .set((
+                learn_placement_tests::answered_count.eq(answered_count),
+                learn_placement_tests::correct_count
+                    .eq(test.correct_count + i32::from(correct == Some(true))),
+                learn_placement_tests::estimate.eq(estimate),
+                learn_placement_tests::step.eq(step),
+                learn_placement_tests::final_level.eq(final_level),
+                learn_placement_tests::completed_at.eq(completed.then_some(now)),
+            ))
+            .get_result::<PlacementTest>(conn)?;
+
+        if let Some(level) = final_level {
+            diesel::insert_into(archive_user_profiles::table)
+                .values(&NewUserProfile { user_id })
+                .on_conflict(archive_user_profiles::user_id)
+                .do_nothing()
+                .execute(conn)?;
+            diesel::update(archive_user_profiles::table)
+                .filter(archive_user_profiles::user_id.eq(user_id))
+                .set(&UpdateUserProfile {
+                    placement_level: Some(level),
+                    placed_at: Some(now),
+                    updated_at: Some(now),
+                    ..Default::default()
+                })
+                .execute(conn)?;
+        } else {
+            let used = learn_placement_items::table
+                .filter(learn_placement_items::test_id.eq(test.id))
+                .load::<PlacementItem>(conn)?;
+            insert_next_item(conn, &test, &used)?;
+        }
+        load_view(conn, test)
+    })
+}
+
+/// Stages, contexts and read subjects around `level`, closest first
+pub fn recommendations(
+    conn: &mut PgConnection,
+    user_id: i64,
+    level: i16,
+) -> QueryResult<Recommendations> {
+    let distance = || sql::<Integer>(&format!("abs(difficulty - {level})"));
+    let stages = asset_stages::table
+        .filter(
+            asset_stages::is_active
+                .eq(true)
+                .or(asset_stages::is_active.is_null()),
+        )
+        .filter(asset_stages::difficulty.is_not_null())
+        .order((distance(), asset_stages::display_order.asc().nulls_last()))
+        .limit(RECOMMENDATIONS)
+        .load::<Stage>(conn)?;
+    let contexts = asset_contexts::table
+        .filter(
+            asset_contexts::is_active
+                .eq(true)
+                .or(asset_contexts::is_active.is_null()),
+        )
+        .filter(
+            asset_contexts::user_id
+                .is_null()
+                .or(asset_contexts::user_id.eq(user_id)),
+        )
+        .filter(asset_contexts::difficulty.is_not_null())
+        .order((distance(), asset_contexts::display_order.asc().nulls_last()))
+        .limit(RECOMMENDATIONS)
+        .load::<Context>(conn)?;
+    let read_subjects = asset_read_subjects::table
+        .filter(asset_read_subjects::difficulty.is_not_null())
+        .order((distance(), asset_read_subjects::id.asc()))
+        .limit(RECOMMENDATIONS)
+        .load::<ReadSubject>(conn)?;
+    Ok(Recommendations {
+        level,
+        stages,
+        contexts,
+        read_subjects,
+    })
+}
+
+fn load_view(conn: &mut PgConnection, test: PlacementTest) -> QueryResult<PlacementView> {
+    let items = learn_placement_items::table
+        .filter(learn_placement_items::test_id.eq(test.id))
+        .order(learn_placement_items::position.asc())
+        .load::<PlacementItem>(conn)?;
+    let recommendations = match test.final_level {
+        Some(level) => Some(recommendations(conn, test.user_id, level)?),
+        None => None,
+    };
+    Ok(PlacementView {
+        test,
+        items: items.into_iter().map(Into::into).collect(),
+        recommendations,
+    })
+}
+
+/// Move the estimate after an answer, halving the step when the direction changes
+fn next_estimate(estimate: f32, step: f32, previous: Option<bool>, correct: bool) -> (f32, f32) {
+    let step = if previous.is_some_and(|previous| previous != correct) {
+        (step / 2.0).max(MIN_STEP)
+    } else {
+        step
+    };
+    let estimate = if correct {
+        estimate + step
+    } else {
+        estimate - step
+    };
+    (
+        estimate.clamp(f32::from(MIN_LEVEL), f32::from(MAX_LEVEL)),
+        step,
+    )
+}
+
+fn level_of(estimate: f32) -> i16 {
+    (estimate.round() as i16).clamp(MIN_LEVEL, MAX_LEVEL)
+}
+
+fn check_answer(item: &PlacementItem, answer: &Value) -> bool {
+    match PlacementItemType::parse(&item.item_type) {
+        Some(PlacementItemType::Speaking) => {
+            let reference = item
+                .answer
+                .as_array()
+                .and_then(|a| a.first())
+                .and_then(Value::as_str)
+                .unwrap_or_default();
+            answer
+                .as_str()
+                .is_some_and(|heard| speaking_ratio(reference, heard) >= SPEAKING_PASS_RATIO)
+        }
+        Some(_) => quiz::check_answer(QuizQuestionType::DefinitionChoice, &item.answer, answer).0,
+        None => false,
+    }
+}
+
+/// Share of the reference words found in what was heard
+fn speaking_ratio(reference: &str, heard: &str) -> f32 {
+    let reference = phoneme::words(reference);
+    if reference.is_empty() {
+        return 0.0;
+    }
+    let mut heard = phoneme::words(heard);
+    let found = reference
+        .iter()
+        .filter(|word| match heard.iter().position(|h| h == *word) {
+            Some(index) => {
+                heard.swap_remove(index);
+                true
+            }
+            None => false,
+        })
+        .count();
+    found as f32 / reference.len() as f32
+}
+
+fn insert_next_item(
+    conn: &mut PgConnection,
+    test: &PlacementTest,
+    used: &[PlacementItem],
+) -> QueryResult<()> {
+    let position = used.len() as i32 + 1;
+    let Some(&planned) = PLAN.get(used.len()) else {
+        return Ok(());
+    };
+    let level = level_of(test.estimate);
+    let draft = draft_item(conn, planned, level, used)?;
+    diesel::insert_into(learn_placement_items::table)
+        .values(&NewPlacementItem {
+            test_id: test.id,
+            user_id: test.user_id,
+            position,
+            item_type: draft.item_type.as_str().to_owned(),
+            level: draft.level,
+            source_id: draft.source_id,
+            prompt: draft.prompt,
+            choices: draft.choices,
+            answer: draft.answer,
+        })
+        .execute(conn)?;
+    Ok(())
+}
+
+/// An item of the planned type, or a grammar item when there is no material
+fn draft_item(
+    conn: &mut PgConnection,
+    planned: PlacementItemType,
+    level: i16,
+    used: &[PlacementItem],
+) -> QueryResult<ItemDraft> {
+    // Vocabulary items point at words, reading and speaking items at sentences
+    let used_sources = |types: &[PlacementItemType]| -> Vec<i64> {
+        used.iter()
+            .filter(|i| PlacementItemType::parse(&i.item_type).is_some_and(|t| types.contains(&t)))
+            .filter_map(|i| i.source_id)
+            .collect()
+    };
+    let draft = match planned {
+        PlacementItemType::Vocabulary => {
+            vocabulary_item(conn, level, &used_sources(&[PlacementItemType::Vocabulary]))?
+        }
+        PlacementItemType::Reading | PlacementItemType::Speaking => {
+            let used_sentences =
+                used_sources(&[PlacementItemType::Reading, PlacementItemType::Speaking]);
+            sentence_item(conn, planned, level, &used_sentences)?
+        }
+        PlacementItemType::Grammar => None,
+    };
+    Ok(draft.unwrap_or_else(|| {
+        let used_prompts: HashSet<&str> = used.iter().map(|i| i.prompt.as_str()).collect();
+        grammar_item(level, &used_prompts)
+    }))
+}
+
+/// Frequency scores (0-100) of the words used at `level`, common words first
+fn frequency_band(level: i16) -> (i16, i16) {
+    let high = 100 - (level - 1) * 10;
+    (high - 10, high)
+}
+
+fn vocabulary_item(
+    conn: &mut PgConnection,
+    level: i16,
+    used_sources: &[i64],
+) -> QueryResult<Option<ItemDraft>> {
+    let mut rng = rand::rng();
+    let (low, high) = frequency_band(level);
+    // Widen the band when the dictionary has nothing suitable in it
+    let ids = dict_words::table
+        .select((min(dict_words::id), max(dict_words::id)))
+        .first::<(Option<i64>, Option<i64>)>(conn)?;
+    for (low, high) in [(low, high), (low - 10, high + 10)] {
+        let pool = quiz::sample(ids, &mut rng, |start, after| {
+            let query = dict_words::table
+                .filter(dict_words::frequency.between(low, high))
+                .filter(dict_words::id.ne_all(used_sources))
+                .filter(
+                    dict_words::is_active
+                        .eq(true)
+                        .or(dict_words::is_active.is_null()),
+                )
+                .filter(
+                    dict_words::id
+                        .eq_any(dict_definitions::table.select(dict_definitions::word_id)),
+                )
+                .select((dict_words::id, dict_words::word, dict_words::frequency))
+                .order(dict_words::id.asc())
+                .limit(quiz::SAMPLE_POOL);
+            if after {
+                query
+                    .filter(dict_words::id.ge(start))
+                    .load::<(i64, String, Option<i16>)>(conn)
+            } else {
+                query
+                    .filter(dict_words::id.lt(start))
+                    .load::<(i64, String, Option<i16>)>(conn)
+            }
+        })?;
+        let candidates: Vec<(i64, String, Option<i16>)> = pool
+            .choose_multiple(&mut rng, VOCABULARY_CANDIDATES)
+            .cloned()
+            .collect();
+        for (id, word, frequency) in candidates {
+            let word = QuizWord {
+                id,
+                word,
+                frequency,
+            };
+            if let Some(draft) = quiz::definition_question(conn, &word, &mut rng)? {
+                return Ok(Some(ItemDraft {
+                    item_type: PlacementItemType::Vocabulary,
+                    level,
+                    source_id: Some(id),
+                    prompt: draft.prompt,
+                    choices: draft.choices,
+                    answer: draft.answer,
+                }));
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn sentence_item(
+    conn: &mut PgConnection,
+    item_type: PlacementItemType,
+    level: i16,
+    used_sources: &[i64],
+) -> QueryResult<Option<ItemDraft>> {
+    let mut rng = rand::rng();
+    let ids = asset_read_sentences::table
+        .select((min(asset_read_sentences::id), max(asset_read_sentences::id)))
+        .first::<(Option<i64>, Option<i64>)>(conn)?;
+    let pool = quiz::sample(ids, &mut rng, |start, after| {
+        let query = asset_read_sentences::table
+            .inner_join(
+                asset_read_subjects::table
+                    .on(asset_read_subjects::id.eq(asset_read_sentences::subject_id)),
+            )
+            .filter(asset_read_sentences::id.ne_all(used_sources))
+            .select((
+                asset_read_sentences::id,
+                asset_read_sentences::content_en,
+                asset_read_sentences::content_zh,
+                sql::<Nullable<SmallInt>>(
+                    "coalesce(asset_read_sentences.difficulty, asset_read_subjects.difficulty)",
+                ),
+            ))
+            .order(asset_read_sentences::id.asc())
+            .limit(quiz::SAMPLE_POOL);
+        if after {
+            query
+                .filter(asset_read_sentences::id.ge(start))
+                .load::<(i64, String, String, Option<i16>)>(conn)
+        } else {
+            query
+                .filter(asset_read_sentences::id.lt(start))
+                .load::<(i64, String, String, Option<i16>)>(conn)
+        }
+    })?;
+    // The sentences of the sample closest to the level, one of them at random
+    let distance = |difficulty: Option<i16>| (difficulty.unwrap_or(level) - level).abs();
+    let Some(closest) = pool
+        .iter()
+        .map(|(.., difficulty)| distance(*difficulty))
+        .min()
+    else {
+        return Ok(None);
+    };
+    let nearest: Vec<(i64, String, String, Option<i16>)> = pool
+        .into_iter()
+        .filter(|(.., difficulty)| distance(*difficulty) == closest)
+        .collect();
+    let Some((id, content_en, content_zh, difficulty)) = nearest.choose(&mut rng).cloned() else {
+        return Ok(None);
+    };
+    let level = difficulty.unwrap_or(level).clamp(MIN_LEVEL, MAX_LEVEL);
+
+    if item_type == PlacementItemType::Speaking {
+        return Ok(Some(ItemDraft {
+            item_type,
+            level,
+            source_id: Some(id),
+            prompt: content_en.clone(),
+            choices: None,
+            answer: json!([content_en]),
+        }));
+    }
+
+    let pool = quiz::sample(ids, &mut rng, |start, after| {
+        let query = asset_read_sentences::table
+            .filter(asset_read_sentences::id.ne(id))
+            .filter(asset_read_sentences::content_zh.ne(&content_zh))
+            .select(asset_read_sentences::content_zh)
+            .order(asset_read_sentences::id.asc())
+            .limit(quiz::SAMPLE_POOL);
+        if after {
+            query
+                .filter(asset_read_sentences::id.ge(start))
+                .load::<String>(conn)
+        } else {
+            query
+                .filter(asset_read_sentences::id.lt(start))
+                .load::<String>(conn)
+        }
+    })?;
+    let mut choices: Vec<String> = pool.choose_multiple(&mut rng, 3).cloned().collect();
+    if choices.len() < 3 {
+        return Ok(None);
+    }
+    choices.push(content_zh.clone());
+    choices.shuffle(&mut rng);
+    Ok(Some(ItemDraft {
+        item_type,
+        level,
+        source_id: Some(id),
+        prompt: content_en,
+        choices: Some(json!(choices)),
+        answer: json!([content_zh]),
+    }))
+}
+
+/// An unused grammar item as close to `level` as possible
+fn grammar_item(level: i16, used_prompts: &HashSet<&str>) -> ItemDraft {
+    let available: Vec<&(i16, &str, [&str; 4])> = GRAMMAR
+        .iter()
+        .filter(|(_, prompt, _)| !used_prompts.contains(prompt))
+        .collect();
+    let closest = available
+        .iter()
+        .map(|(l, _, _)| (l - level).abs())
+        .min()
+        .unwrap_or(0);
+    let candidates: Vec<&(i16, &str, [&str; 4])> = available
+        .into_iter()
+        .filter(|(l, _, _)| (l - level).abs() == closest)
+        .collect();
+    let mut rng = rand::rng();
+    // The bank is larger than a test, so there is always one left
+    let &(item_level, prompt, options) =
+        candidates.choose(&mut rng).copied().unwrap_or(&GRAMMAR[0]);
+    let mut choices = options.to_vec();
+    choices.shuffle(&mut rng);
+    ItemDraft {
+        item_type: PlacementItemType::Grammar,
+        level: item_level,
+        source_id: None,
+        prompt: prompt.to_owned(),
+        choices: Some(json!(choices)),
+        answer: json!([options[0]]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn staircase_settles_between_levels() {
+        let (estimate, step) = next_estimate(3.0, START_STEP, None, true);
+        assert_eq!((estimate, step), (5.0, 2.0));
+        let (estimate, step) = next_estimate(estimate, step, Some(true), false);
+        assert_eq!((estimate, step), (4.0, 1.0));
+        let (estimate, step) = next_estimate(estimate, step, Some(false), true);
+        assert_eq!((estimate, step), (4.5, 0.5));
+        let (estimate, step) = next_estimate(estimate, step, Some(true), false);
+        assert_eq!((estimate, step), (4.0, MIN_STEP));
+        assert_eq!(next_estimate(9.5, 2.0, None, true).0, 10.0);
+    }
+
+    #[test]
+    fn frequency_bands_cover_the_scale() {
+        assert_eq!(frequency_band(1), (90, 100));
+        assert_eq!(frequency_band(10), (0, 10));
+    }
+
+    #[test]
+    fn grammar_bank_covers_every_level() {
+        for level in MIN_LEVEL..=MAX_LEVEL {
+            assert!(GRAMMAR.iter().any(|(l, _, _)| *l == level));
+        }
+        assert!(GRAMMAR.len() > PLAN.len());
+    }
+
+    #[test]
+    fn speaking_counts_reference_words_heard() {
+        assert_eq!(speaking_ratio("Nice to meet you.", "nice to meet you"), 1.0);
+        assert_eq!(speaking_ratio("Nice to meet you.", "nice meat you"), 0.5);
+    }
+}
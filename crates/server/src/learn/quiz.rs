@@ -0,0 +1,800 @@
+//! Quizzes generated from dictionary data
+//!
+//! Questions are built from definitions (multiple choice), example sentences
+//! (cloze), synonym/antonym relations (matching) and word forms (form drills).
+//! Accepted answers are stored with each question so they can be checked
+//! later without regenerating anything.
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use diesel::dsl::{max, min};
+use diesel::prelude::*;
+use rand::seq::{IndexedRandom, SliceRandom};
+use salvo::oapi::ToSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+
+use super::achievement::{self, AchievementEvent};
+use super::session::{blank_out, normalize_answer};
+use super::stats::{self, Activity};
+use crate::db::schema::*;
+use crate::models::achievement::AchievementBadge;
+use crate::models::dict::{Definition, Form};
+use crate::models::learn::*;
+
+/// Options in a definition question, including the right one
+const CHOICE_COUNT: usize = 4;
+/// Distractors come from words whose frequency score (0-100) is this close
+const FREQUENCY_BAND_WIDTH: i16 = 10;
+const MATCHING_PAIRS: usize = 4;
+const MIN_MATCHING_PAIRS: usize = 3;
+/// Example sentences tried per word when looking for a cloze
+const CLOZE_SENTENCES: i64 = 10;
+/// Rows read from a random point of a table, random picks are made among them
+pub(super) const SAMPLE_POOL: i64 = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QuizQuestionType {
+    /// Pick the definition of a word
+    DefinitionChoice,
+    /// Fill a word into an example sentence
+    Cloze,
+    /// Match words to their synonyms or antonyms
+    Matching,
+    /// Type a form of a word, e.g. its past tense
+    FormDrill,
+}
+
+impl QuizQuestionType {
+    pub const ALL: [QuizQuestionType; 4] = [
+        QuizQuestionType::DefinitionChoice,
+        QuizQuestionType::Cloze,
+        QuizQuestionType::Matching,
+        QuizQuestionType::FormDrill,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            QuizQuestionType::DefinitionChoice => "definition_choice",
+            QuizQuestionType::Cloze => "cloze",
+            QuizQuestionType::Matching => "matching",
+            QuizQuestionType::FormDrill => "form_drill",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.as_str() == value)
+    }
+}
+
+/// What a quiz should contain
+#[derive(Debug, Clone)]
+pub struct QuizOptions {
+    pub size: usize,
+    /// Question types to use, all types when empty
+    pub types: Vec<QuizQuestionType>,
+    /// Words to quiz on; the user's vocabulary topped up with random
+    /// dictionary words when empty
+    pub words: Vec<String>,
+    /// Difficulty of the random words used to fill up the quiz
+    pub difficulty: Option<i16>,
+}
+
+/// Question as shown to the learner
+///
+/// The accepted answer is only revealed once the question is answered.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct QuizQuestionView {
+    pub id: i64,
+    pub position: i32,
+    /// definition_choice | cloze | matching | form_drill
+    pub question_type: String,
+    pub word_id: Option<i64>,
+    /// Word (definition_choice, form_drill), sentence with a blank (cloze) or
+    /// relation type (matching)
+    pub prompt: String,
+    /// Part of speech (definition_choice) or form type (form_drill)
+    pub hint: Option<String>,
+    /// List of definitions, or `{"left": [...], "right": [...]}` for matching
+    pub choices: Option<Value>,
+    pub answered: bool,
+    pub user_answer: Option<Value>,
+    pub is_correct: Option<bool>,
+    pub answer: Option<Value>,
+}
+
+impl From<QuizQuestion> for QuizQuestionView {
+    fn from(question: QuizQuestion) -> Self {
+        let answered = question.answered_at.is_some();
+        Self {
+            id: question.id,
+            position: question.position,
+            question_type: question.question_type,
+            word_id: question.word_id,
+            prompt: question.prompt,
+            hint: question.hint,
+            choices: question.choices,
+            answered,
+            user_answer: question.user_answer,
+            is_correct: question.is_correct,
+            answer: answered.then_some(question.answer),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct QuizView {
+    pub quiz: Quiz,
+    pub questions: Vec<QuizQuestionView>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct QuizAnswerResult {
+    pub question: QuizQuestionView,
+    /// Matching questions: number of pairs matched correctly
+    pub correct_pairs: Option<usize>,
+    pub quiz: Quiz,
+    /// Achievements unlocked by this answer
+    pub unlocked: Vec<AchievementBadge>,
+}
+
+pub(super) struct QuizWord {
+    pub(super) id: i64,
+    pub(super) word: String,
+    pub(super) frequency: Option<i16>,
+}
+
+pub(super) struct Draft {
+    pub(super) question_type: QuizQuestionType,
+    pub(super) word_id: Option<i64>,
+    pub(super) prompt: String,
+    pub(super) hint: Option<String>,
+    pub(super) choices: Option<Value>,
+    pub(super) answer: Value,
+}
+
+/// Generate a quiz for the user and store it
+pub fn generate_quiz(
+    conn: &mut PgConnection,
+    user_id: i64,
+    options: &QuizOptions,
+) -> QueryResult<QuizView> {
+    let types: Vec<QuizQuestionType> = if options.types.is_empty() {
+        QuizQuestionType::ALL.to_vec()
+    } else {
+        options.types.clone()
+    };
+    let mut rng = rand::rng();
+    let words = pick_words(conn, user_id, options, &mut rng)?;
+    let mut drafts = Vec::with_capacity(options.size);
+    let mut used = HashSet::new();
+
+    if types.contains(&QuizQuestionType::Matching) {
+        // Matching questions cover several words, keep them to a fair share
+        let share = (options.size / types.len()).max(1);
+        for relation_type in ["synonym", "antonym"] {
+            if drafts.len() >= share {
+                break;
+            }
+            let available: Vec<&QuizWord> =
+                words.iter().filter(|w| !used.contains(&w.id)).collect();
+            if let Some((draft, word_ids)) =
+                matching_question(conn, &available, relation_type, &mut rng)?
+            {
+                used.extend(word_ids);
+                drafts.push(draft);
+            }
+        }
+    }
+
+    let single_types: Vec<QuizQuestionType> = types
+        .iter()
+        .copied()
+        .filter(|t| *t != QuizQuestionType::Matching)
+        .collect();
+    let mut counts: HashMap<QuizQuestionType, usize> = HashMap::new();
+    for word in words.iter().filter(|w| !used.contains(&w.id)) {
+        if drafts.len() >= options.size {
+            break;
+        }
+        // Least used types first so the quiz stays mixed
+        let mut order = single_types.clone();
+        order.shuffle(&mut rng);
+        order.sort_by_key(|t| counts.get(t).copied().unwrap_or(0));
+        for question_type in order {
+            let draft = match question_type {
+                QuizQuestionType::DefinitionChoice => definition_question(conn, word, &mut rng)?,
+                QuizQuestionType::Cloze => cloze_question(conn, word)?,
+                QuizQuestionType::FormDrill => form_question(conn, word, &mut rng)?,
+                QuizQuestionType::Matching => None,
+            };
+            if let Some(draft) = draft {
+                *counts.entry(question_type).or_default() += 1;
+                drafts.push(draft);
+                break;
+            }
+        }
+    }
+    drafts.shuffle(&mut rng);
+
+    conn.transaction(|conn| {
+        let quiz = diesel::insert_into(learn_quizzes::table)
+            .values(&NewQuiz {
+                user_id,
+                question_count: drafts.len() as i32,
+            })
+            .get_result::<Quiz>(conn)?;
+        let questions: Vec<NewQuizQuestion> = drafts
+            .into_iter()
+            .enumerate()
+            .map(|(index, draft)| NewQuizQuestion {
+                quiz_id: quiz.id,
+                user_id,
+                position: index as i32 + 1,
+                question_type: draft.question_type.as_str().to_owned(),
+                word_id: draft.word_id,
+                prompt: draft.prompt,
+                hint: draft.hint,
+                choices: draft.choices,
+                answer: draft.answer,
+            })
+            .collect();
+        if !questions.is_empty() {
+            diesel::insert_into(learn_quiz_questions::table)
+                .values(&questions)
+                .execute(conn)?;
+        }
+        load_quiz_view(conn, quiz)
+    })
+}
+
+/// Load a quiz of the user with its questions
+pub fn get_quiz(conn: &mut PgConnection, user_id: i64, quiz_id: i64) -> QueryResult<QuizView> {
+    let quiz = learn_quizzes::table
+        .filter(learn_quizzes::id.eq(quiz_id))
+        .filter(learn_quizzes::user_id.eq(user_id))
+        .first::<Quiz>(conn)?;
+    load_quiz_view(conn, quiz)
+}
+
+fn load_quiz_view(conn: &mut PgConnection, quiz: Quiz) -> QueryResult<QuizView> {
+    let questions = learn_quiz_questions::table
+        .filter(learn_quiz_questions::quiz_id.eq(quiz.id))
+        .order(learn_quiz_questions::position.asc())
+        .load::<QuizQuestion>(conn)?;
+    Ok(QuizView {
+        quiz,
+        questions: questions.into_iter().map(Into::into).collect(),
+    })
+}
+
+/// Check and store the answer to a question
+///
+/// Text questions take a string, matching questions an object mapping each
+/// left item to the chosen right item. Answering twice returns the stored
+/// result.
+pub fn answer_question(
+    conn: &mut PgConnection,
+    user_id: i64,
+    quiz_id: i64,
+    question_id: i64,
+    answer: Value,
+    now: DateTime<Utc>,
+) -> QueryResult<QuizAnswerResult> {
+    conn.transaction(|conn| {
+        let question = learn_quiz_questions::table
+            .filter(learn_quiz_questions::id.eq(question_id))
+            .filter(learn_quiz_questions::quiz_id.eq(quiz_id))
+            .filter(learn_quiz_questions::user_id.eq(user_id))
+            .for_update()
+            .first::<QuizQuestion>(conn)?;
+        let question_type = QuizQuestionType::parse(&question.question_type);
+
+        if question.answered_at.is_some() {
+            let correct_pairs = question_type
+                .filter(|t| *t == QuizQuestionType::Matching)
+                .zip(question.user_answer.as_ref())
+                .map(|(t, given)| check_answer(t, &question.answer, given).1.unwrap_or(0));
+            let quiz = learn_quizzes::table.find(quiz_id).first::<Quiz>(conn)?;
+            return Ok(QuizAnswerResult {
+                question: question.into(),
+                correct_pairs,
+                quiz,
+                unlocked: Vec::new(),
+            });
+        }
+
+        let (correct, correct_pairs) = match question_type {
+            Some(t) => check_answer(t, &question.answer, &answer),
+            None => (false, None),
+        };
+        let question = diesel::update(learn_quiz_questions::table.find(question.id))
+            .set((
+                learn_quiz_questions::user_answer.eq(Some(answer)),
+                learn_quiz_questions::is_correct.eq(Some(correct)),
+                learn_quiz_questions::answered_at.eq(Some(now)),
+            ))
+            .get_result::<QuizQuestion>(conn)?;
+
+        let quiz = learn_quizzes::table
+            .find(quiz_id)
+            .for_update()
+            .first::<Quiz>(conn)?;
+        let answered_count = quiz.answered_count + 1;
+        let completed = answered_count >= quiz.question_count;
+        let quiz = diesel::update(learn_quizzes::table.find(quiz_id))
+            .set((
+                learn_quizzes::answered_count.eq(answered_count),
+                learn_quizzes::correct_count.eq(quiz.correct_count + i32::from(correct)),
+                learn_quizzes::completed_at.eq(completed.then_some(now)),
+            ))
+            .get_result::<Quiz>(conn)?;
+        stats::record_activity(conn, user_id, Activity::QuizAnswer, now)?;
+        if completed {
+            stats::record_activity(conn, user_id, Activity::SessionCompleted, now)?;
+        }
+        let unlocked =
+            achievement::evaluate_and_notify(conn, user_id, AchievementEvent::Studied, now)?;
+
+        Ok(QuizAnswerResult {
+            question: question.into(),
+            correct_pairs,
+            quiz,
+            unlocked,
+        })
+    })
+}
+
+/// Compare an answer with the stored accepted answer
+///
+/// Returns whether it is right and, for matching questions, how many pairs
+/// were matched correctly.
+pub fn check_answer(
+    question_type: QuizQuestionType,
+    expected: &Value,
+    answer: &Value,
+) -> (bool, Option<usize>) {
+    if question_type == QuizQuestionType::Matching {
+        let (Some(expected), Some(answer)) = (expected.as_object(), answer.as_object()) else {
+            return (false, Some(0));
+        };
+        let matched = expected
+            .iter()
+            .filter(|(left, right)| {
+                let given = answer.get(*left).and_then(Value::as_str);
+                let right = right.as_str();
+                given.zip(right).is_some_and(|(given, right)| {
+                    normalize_answer(given) == normalize_answer(right)
+                })
+            })
+            .count();
+        return (matched == expected.len(), Some(matched));
+    }
+
+    let Some(given) = answer.as_str().map(normalize_answer) else {
+        return (false, None);
+    };
+    let correct = expected
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .any(|accepted| normalize_answer(accepted) == given);
+    (correct, None)
+}
+
+/// Words the quiz is about, in random order
+fn pick_words(
+    conn: &mut PgConnection,
+    user_id: i64,
+    options: &QuizOptions,
+    rng: &mut impl rand::Rng,
+) -> QueryResult<Vec<QuizWord>> {
+    // Not every word has material for every question type, take spares
+    let limit = options.size * 2;
+    let wanted: Vec<String> = if options.words.is_empty() {
+        let ids = learn_vocabularies::table
+            .filter(learn_vocabularies::user_id.eq(user_id))
+            .select((min(learn_vocabularies::id), max(learn_vocabularies::id)))
+            .first::<(Option<i64>, Option<i64>)>(conn)?;
+        let pool = sample(ids, rng, |start, after| {
+            let query = learn_vocabularies::table
+                .filter(learn_vocabularies::user_id.eq(user_id))
+                .select(learn_vocabularies::word)
+                .order(learn_vocabularies::id.asc())
+                .limit(SAMPLE_POOL);
+            if after {
+                query
+                    .filter(learn_vocabularies::id.ge(start))
+                    .load::<String>(conn)
+            } else {
+                query
+                    .filter(learn_vocabularies::id.lt(start))
+                    .load::<String>(conn)
+            }
+        })?;
+        pool.choose_multiple(rng, limit).cloned().collect()
+    } else {
+        options.words.clone()
+    };
+    let wanted: Vec<String> = wanted
+        .iter()
+        .map(|w| w.trim().to_lowercase())
+        .filter(|w| !w.is_empty())
+        .collect();
+
+    let mut words: Vec<QuizWord> = dict_words::table
+        .filter(dict_words::word_lower.eq_any(&wanted))
+        .select((dict_words::id, dict_words::word, dict_words::frequency))
+        .load::<(i64, String, Option<i16>)>(conn)?
+        .into_iter()
+        .map(|(id, word, frequency)| QuizWord {
+            id,
+            word,
+            frequency,
+        })
+        .collect();
+
+    if options.words.is_empty() && words.len() < limit {
+        let known: Vec<i64> = words.iter().map(|w| w.id).collect();
+        let ids = dict_words::table
+            .select((min(dict_words::id), max(dict_words::id)))
+            .first::<(Option<i64>, Option<i64>)>(conn)?;
+        let pool = sample(ids, rng, |start, after| {
+            let mut query = dict_words::table
+                .filter(dict_words::id.ne_all(&known))
+                .filter(
+                    dict_words::is_active
+                        .eq(true)
+                        .or(dict_words::is_active.is_null()),
+                )
+                .filter(
+                    dict_words::id
+                        .eq_any(dict_definitions::table.select(dict_definitions::word_id)),
+                )
+                .select((dict_words::id, dict_words::word, dict_words::frequency))
+                .order(dict_words::id.asc())
+                .limit(SAMPLE_POOL)
+                .into_boxed();
+            if let Some(difficulty) = options.difficulty {
+                query = query.filter(dict_words::difficulty.eq(difficulty));
+            }
+            query = if after {
+                query.filter(dict_words::id.ge(start))
+            } else {
+                query.filter(dict_words::id.lt(start))
+            };
+            query.load::<(i64, String, Option<i16>)>(conn)
+        })?;
+        let extra = pool.choose_multiple(rng, limit - words.len());
+        words.extend(extra.map(|(id, word, frequency)| QuizWord {
+            id: *id,
+            word: word.clone(),
+            frequency: *frequency,
+        }));
+    }
+
+    words.shuffle(rng);
+    Ok(words)
+}
+
+/// Up to [`SAMPLE_POOL`] rows read in id order from a random id of
+/// `(min, max)`, wrapping around to the lowest ids when the end is reached
+///
+/// `load(start, true)` loads rows from `start` on, `load(start, false)` the
+/// rows before it; both ordered by id and limited to the pool size, so the
+/// database only walks an index instead of sorting the table.
+pub(super) fn sample<T>(
+    (min, max): (Option<i64>, Option<i64>),
+    rng: &mut impl rand::Rng,
+    mut load: impl FnMut(i64, bool) -> QueryResult<Vec<T>>,
+) -> QueryResult<Vec<T>> {
+    let (Some(min), Some(max)) = (min, max) else {
+        return Ok(Vec::new());
+    };
+    let start = rng.random_range(min..=max);
+    let mut rows = load(start, true)?;
+    if (rows.len() as i64) < SAMPLE_POOL && start > min {
+        let missing = SAMPLE_POOL as usize - rows.len();
+        rows.extend(load(start, false)?.into_iter().take(missing));
+    }
+    Ok(rows)
+}
+
+pub(super) fn definition_question(
+    conn: &mut PgConnection,
+    word: &QuizWord,
+    rng: &mut impl rand::Rng,
+) -> QueryResult<Option<Draft>> {
+    let Some(definition) = dict_definitions::table
+        .filter(dict_definitions::word_id.eq(word.id))
+        .order((
+            dict_definitions::is_primary.desc().nulls_last(),
+            dict_definitions::definition_order.asc().nulls_last(),
+        ))
+        .first::<Definition>(conn)
+        .optional()?
+    else {
+        return Ok(None);
+    };
+
+    let mut choices = distractor_definitions(conn, &definition, word.frequency, rng)?;
+    if choices.len() < CHOICE_COUNT - 1 {
+        return Ok(None);
+    }
+    choices.push(definition.definition.clone());
+    choices.shuffle(rng);
+
+    Ok(Some(Draft {
+        question_type: QuizQuestionType::DefinitionChoice,
+        word_id: Some(word.id),
+        prompt: word.word.clone(),
+        hint: definition.part_of_speech,
+        choices: Some(json!(choices)),
+        answer: json!([definition.definition]),
+    }))
+}
+
+/// Definitions of other words to offer as wrong options
+///
+/// Prefers definitions of the same part of speech from words of similar
+/// frequency, so the right option does not stand out; the constraints are
+/// relaxed when there are not enough of those.
+fn distractor_definitions(
+    conn: &mut PgConnection,
+    definition: &Definition,
+    frequency: Option<i16>,
+    rng: &mut impl rand::Rng,
+) -> QueryResult<Vec<String>> {
+    let needed = CHOICE_COUNT - 1;
+    let band = frequency.map(|f| (f - FREQUENCY_BAND_WIDTH, f + FREQUENCY_BAND_WIDTH));
+    let attempts = [
+        (definition.part_of_speech.as_deref(), band),
+        (definition.part_of_speech.as_deref(), None),
+        (None, None),
+    ];
+    let ids = dict_definitions::table
+        .select((min(dict_definitions::id), max(dict_definitions::id)))
+        .first::<(Option<i64>, Option<i64>)>(conn)?;
+
+    let mut found: Vec<String> = Vec::with_capacity(needed);
+    for (part_of_speech, band) in attempts {
+        let pool = sample(ids, rng, |start, after| {
+            let mut query = dict_definitions::table
+                .inner_join(dict_words::table.on(dict_words::id.eq(dict_definitions::word_id)))
+                .filter(dict_definitions::word_id.ne(definition.word_id))
+                .filter(dict_definitions::language.eq(&definition.language))
+                .filter(dict_definitions::definition.ne(&definition.definition))
+                .select(dict_definitions::definition)
+                .order(dict_definitions::id.asc())
+                .limit(SAMPLE_POOL)
+                .into_boxed();
+            if let Some(part_of_speech) = part_of_speech {
+                query = query.filter(dict_definitions::part_of_speech.eq(part_of_speech));
+            }
+            if let Some((low, high)) = band {
+                query = query.filter(dict_words::frequency.between(low, high));
+            }
+            query = if after {
+                query.filter(dict_definitions::id.ge(start))
+            } else {
+                query.filter(dict_definitions::id.lt(start))
+            };
+            query.load::<String>(conn)
+        })?;
+        for definition in pool.choose_multiple(rng, pool.len()) {
+            if found.len() < needed && !found.contains(definition) {
+                found.push(definition.clone());
+            }
+        }
+        if found.len() >= needed {
+            break;
+        }
+    }
+    Ok(found)
+}
+
+fn cloze_question(conn: &mut PgConnection, word: &QuizWord) -> QueryResult<Option<Draft>> {
+    let sentences = dict_word_sentences::table
+        .inner_join(
+            dict_sentences::table.on(dict_sentences::id.eq(dict_word_sentences::sentence_id)),
+        )
+        .filter(dict_word_sentences::word_id.eq(word.id))
+        .order(dict_word_sentences::priority_order.desc().nulls_last())
+        .limit(CLOZE_SENTENCES)
+        .select(dict_sentences::sentence)
+        .load::<String>(conn)?;
+    if sentences.is_empty() {
+        return Ok(None);
+    }
+
+    // Example sentences often use an inflected form of the word
+    let mut variants = vec![word.word.clone()];
+    variants.extend(
+        dict_forms::table
+            .filter(dict_forms::word_id.eq(word.id))
+            .select(dict_forms::form)
+            .load::<String>(conn)?,
+    );
+
+    let blanked = sentences.iter().find_map(|sentence| {
+        variants
+            .iter()
+            .find_map(|variant| blank_out(sentence, variant))
+    });
+    Ok(blanked.map(|(sentence, answer)| Draft {
+        question_type: QuizQuestionType::Cloze,
+        word_id: Some(word.id),
+        prompt: sentence,
+        hint: None,
+        choices: None,
+        answer: json!([answer]),
+    }))
+}
+
+fn form_question(
+    conn: &mut PgConnection,
+    word: &QuizWord,
+    rng: &mut impl rand::Rng,
+) -> QueryResult<Option<Draft>> {
+    let forms = dict_forms::table
+        .filter(dict_forms::word_id.eq(word.id))
+        .filter(dict_forms::form_type.is_not_null())
+        .load::<Form>(conn)?;
+
+    let mut by_type: HashMap<String, Vec<String>> = HashMap::new();
+    for form in forms {
+        let Some(form_type) = form.form_type else {
+            continue;
+        };
+        if !form.form.eq_ignore_ascii_case(&word.word) {
+            by_type.entry(form_type).or_default().push(form.form);
+        }
+    }
+    let form_types: Vec<&String> = by_type.keys().collect();
+    let Some(form_type) = form_types.choose(rng).map(|t| (*t).clone()) else {
+        return Ok(None);
+    };
+    let accepted = by_type.remove(&form_type).unwrap_or_default();
+
+    Ok(Some(Draft {
+        question_type: QuizQuestionType::FormDrill,
+        word_id: Some(word.id),
+        prompt: word.word.clone(),
+        hint: Some(form_type),
+        choices: None,
+        answer: json!(accepted),
+    }))
+}
+
+/// Match some of `words` to their synonyms or antonyms
+///
+/// Returns the question and the ids of the words it uses.
+fn matching_question(
+    conn: &mut PgConnection,
+    words: &[&QuizWord],
+    relation_type: &str,
+    rng: &mut impl rand::Rng,
+) -> QueryResult<Option<(Draft, Vec<i64>)>> {
+    let word_ids: Vec<i64> = words.iter().map(|w| w.id).collect();
+    let mut relations = dict_relations::table
+        .inner_join(dict_words::table.on(dict_words::id.eq(dict_relations::related_word_id)))
+        .filter(dict_relations::word_id.eq_any(&word_ids))
+        .filter(dict_relations::relation_type.eq(relation_type))
+        .select((dict_relations::word_id, dict_words::word))
+        .load::<(i64, String)>(conn)?;
+    relations.shuffle(rng);
+
+    let names: HashMap<i64, &str> = words.iter().map(|w| (w.id, w.word.as_str())).collect();
+    let mut pairs: Vec<(i64, String, String)> = Vec::with_capacity(MATCHING_PAIRS);
+    let mut taken = HashSet::new();
+    for (word_id, related) in relations {
+        if pairs.len() >= MATCHING_PAIRS {
+            break;
+        }
+        let Some(word) = names.get(&word_id) else {
+            continue;
+        };
+        // Each word and each related word may appear once, or the matching
+        // would be ambiguous
+        if pairs
+            .iter()
+            .any(|(_, left, _)| left.eq_ignore_ascii_case(word))
+            || !taken.insert(related.to_lowercase())
+            || related.eq_ignore_ascii_case(word)
+        {
+            continue;
+        }
+        pairs.push((word_id, (*word).to_owned(), related));
+    }
+    if pairs.len() < MIN_MATCHING_PAIRS {
+        return Ok(None);
+    }
+
+    let left: Vec<&String> = pairs.iter().map(|(_, word, _)| word).collect();
+    let mut right: Vec<&String> = pairs.iter().map(|(_, _, related)| related).collect();
+    right.shuffle(rng);
+    let answer: Map<String, Value> = pairs
+        .iter()
+        .map(|(_, word, related)| (word.clone(), Value::from(related.clone())))
+        .collect();
+
+    let draft = Draft {
+        question_type: QuizQuestionType::Matching,
+        word_id: None,
+        prompt: relation_type.to_owned(),
+        hint: None,
+        choices: Some(json!({ "left": left, "right": right })),
+        answer: Value::Object(answer),
+    };
+    Ok(Some((
+        draft,
+        pairs.into_iter().map(|(id, _, _)| id).collect(),
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+
+    #[test]
+    fn checks_typed_answers_loosely() {
+        let accepted = json!(["went", "gone"]);
+        let check = |answer: Value| check_answer(QuizQuestionType::FormDrill, &accepted, &answer);
+        assert_eq!(check(json!(" Went. ")), (true, None));
+        assert_eq!(check(json!("gone")), (true, None));
+        assert_eq!(check(json!("goed")), (false, None));
+        assert_eq!(check(json!(["went"])), (false, None));
+    }
+
+    #[test]
+    fn counts_matched_pairs() {
+        let expected = json!({"big": "large", "fast": "quick", "happy": "glad"});
+        let answer = json!({"big": "Large", "fast": "glad", "happy": "quick"});
+        assert_eq!(
+            check_answer(QuizQuestionType::Matching, &expected, &answer),
+            (false, Some(1))
+        );
+        assert_eq!(
+            check_answer(QuizQuestionType::Matching, &expected, &expected),
+            (true, Some(3))
+        );
+        assert_eq!(
+            check_answer(QuizQuestionType::Matching, &expected, &json!("large")),
+            (false, Some(0))
+        );
+    }
+
+    #[test]
+    fn samples_wrap_around_the_id_range() {
+        let ids: Vec<i64> = (1..=300).collect();
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..20 {
+            let rows = sample((Some(1), Some(300)), &mut rng, |start, after| {
+                let rows = ids.iter().copied().filter(|&id| (id >= start) == after);
+                Ok(rows.take(SAMPLE_POOL as usize).collect())
+            })
+            .unwrap();
+            assert_eq!(rows.len(), SAMPLE_POOL as usize);
+            let unique: HashSet<i64> = rows.iter().copied().collect();
+            assert_eq!(unique.len(), rows.len());
+        }
+
+        let few = sample((Some(5), Some(7)), &mut rng, |start, after| {
+            Ok([5, 6, 7]
+                .into_iter()
+                .filter(|&id| (id >= start) == after)
+                .collect())
+        })
+        .unwrap();
+        assert_eq!(few.len(), 3);
+
+        let empty: Vec<i64> = sample((None, None), &mut rng, |_, _| unreachable!()).unwrap();
+        assert!(empty.is_empty());
+    }
+}
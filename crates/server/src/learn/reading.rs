@@ -0,0 +1,356 @@
+//! Read-aloud progress
+//!
+//! A read subject is an ordered list of sentences. Every practice of one of
+//! its sentences refreshes the learner's `learn_read_progress` row for the
+//! subject (`exercise_id` is the subject id). A sentence counts as read once
+//! a practice of it scored at least [`PASS_SCORE`]; the subject is complete
+//! when all of its sentences are.
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use diesel::dsl::{count_distinct, count_star};
+use diesel::prelude::*;
+use salvo::oapi::ToSchema;
+use serde::Serialize;
+
+use crate::db::schema::*;
+use crate::learn::stats::{self, Activity, PracticeItem};
+use crate::models::asset::ReadSubject;
+use crate::models::learn::{
+    NewReadPractice, NewReadProgress, ReadPractice, ReadProgress, UpdateReadProgress,
+};
+
+/// Lowest overall score for a sentence to count as read
+pub const PASS_SCORE: i32 = 60;
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct SentenceProgress {
+    pub sentence_id: i64,
+    pub sentence_order: i32,
+    pub attempts: i64,
+    pub best_score: Option<i32>,
+    pub last_practiced_at: Option<DateTime<Utc>>,
+}
+
+impl SentenceProgress {
+    pub fn passed(&self) -> bool {
+        self.best_score.is_some_and(|score| score >= PASS_SCORE)
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SubjectProgress {
+    pub subject: ReadSubject,
+    pub total_sentences: i64,
+    pub passed_sentences: i64,
+    /// Absent until the learner practiced a sentence of the subject
+    pub progress: Option<ReadProgress>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SubjectProgressDetail {
+    #[serde(flatten)]
+    pub summary: SubjectProgress,
+    pub sentences: Vec<SentenceProgress>,
+}
+
+/// Store a read practice and refresh the progress on its subject
+///
+/// Only practices `graded` by the server earn XP, and only for a known
+/// sentence. Returns the progress only when the practice is of a known
+/// sentence.
+pub fn record_practice(
+    conn: &mut PgConnection,
+    new_practice: &NewReadPractice,
+    graded: bool,
+) -> QueryResult<(ReadPractice, Option<ReadProgress>)> {
+    conn.transaction(|conn| {
+        let practice = diesel::insert_into(learn_read_practices::table)
+            .values(new_practice)
+            .get_result::<ReadPractice>(conn)?;
+
+        let sentence = match practice.sentence_id {
+            Some(sentence_id) => asset_read_sentences::table
+                .find(sentence_id)
+                .select((
+                    asset_read_sentences::subject_id,
+                    asset_read_sentences::sentence_order,
+                ))
+                .first::<(i64, i32)>(conn)
+                .optional()?,
+            None => None,
+        };
+        let progress = match sentence {
+            Some((subject_id, sentence_order)) => Some(refresh_progress(
+                conn,
+                practice.user_id,
+                subject_id,
+                sentence_order,
+                practice.created_at,
+            )?),
+            None => None,
+        };
+
+        let item = match (graded, practice.sentence_id, sentence) {
+            (true, Some(sentence_id), Some(_)) => Some(PracticeItem::Reading { sentence_id }),
+            _ => None,
+        };
+        stats::record_activity(
+            conn,
+            practice.user_id,
+            Activity::Practice { item },
+            practice.created_at,
+        )?;
+        Ok((practice, progress))
+    })
+}
+
+/// Subjects the learner has started, most recently practiced first
+pub fn list_progress(conn: &mut PgConnection, user_id: i64) -> QueryResult<Vec<SubjectProgress>> {
+    let rows = learn_read_progress::table
+        .inner_join(
+            asset_read_subjects::table
+                .on(asset_read_subjects::id.eq(learn_read_progress::exercise_id)),
+        )
+        .filter(learn_read_progress::user_id.eq(user_id))
+        .order((
+            learn_read_progress::last_practiced_at.desc().nulls_last(),
+            learn_read_progress::id.desc(),
+        ))
+        .select((
+            learn_read_progress::all_columns,
+            asset_read_subjects::all_columns,
+        ))
+        .load::<(ReadProgress, ReadSubject)>(conn)?;
+    let subject_ids: Vec<i64> = rows.iter().map(|(_, subject)| subject.id).collect();
+
+    let totals: HashMap<i64, i64> = asset_read_sentences::table
+        .filter(asset_read_sentences::subject_id.eq_any(&subject_ids))
+        .group_by(asset_read_sentences::subject_id)
+        .select((asset_read_sentences::subject_id, count_star()))
+        .load::<(i64, i64)>(conn)?
+        .into_iter()
+        .collect();
+    let passed: HashMap<i64, i64> = learn_read_practices::table
+        .inner_join(
+            asset_read_sentences::table.on(asset_read_sentences::id
+                .nullable()
+                .eq(learn_read_practices::sentence_id)),
+        )
+        .filter(learn_read_practices::user_id.eq(user_id))
+        .filter(learn_read_practices::overall_score.ge(PASS_SCORE))
+        .filter(asset_read_sentences::subject_id.eq_any(&subject_ids))
+        .group_by(asset_read_sentences::subject_id)
+        .select((
+            asset_read_sentences::subject_id,
+            count_distinct(asset_read_sentences::id),
+        ))
+        .load::<(i64, i64)>(conn)?
+        .into_iter()
+        .collect();
+
+    Ok(rows
+        .into_iter()
+        .map(|(progress, subject)| SubjectProgress {
+            total_sentences: totals.get(&subject.id).copied().unwrap_or(0),
+            passed_sentences: passed.get(&subject.id).copied().unwrap_or(0),
+            progress: Some(progress),
+            subject,
+        })
+        .collect())
+}
+
+/// Progress on one subject with the state of each of its sentences
+pub fn subject_progress(
+    conn: &mut PgConnection,
+    user_id: i64,
+    subject_id: i64,
+) -> QueryResult<Option<SubjectProgressDetail>> {
+    let Some(subject) = asset_read_subjects::table
+        .find(subject_id)
+        .first::<ReadSubject>(conn)
+        .optional()?
+    else {
+        return Ok(None);
+    };
+    let progress = learn_read_progress::table
+        .filter(learn_read_progress::user_id.eq(user_id))
+        .filter(learn_read_progress::exercise_id.eq(subject_id))
+        .first::<ReadProgress>(conn)
+        .optional()?;
+    let (sentences, _) = sentence_progress(conn, user_id, subject_id)?;
+
+    Ok(Some(SubjectProgressDetail {
+        summary: SubjectProgress {
+            subject,
+            total_sentences: sentences.len() as i64,
+            passed_sentences: sentences.iter().filter(|s| s.passed()).count() as i64,
+            progress,
+        },
+        sentences,
+    }))
+}
+
+/// Sentences of a subject in order with the learner's attempts on each,
+/// and the scores of all those attempts
+fn sentence_progress(
+    conn: &mut PgConnection,
+    user_id: i64,
+    subject_id: i64,
+) -> QueryResult<(Vec<SentenceProgress>, Vec<i32>)> {
+    let mut sentences: Vec<SentenceProgress> = asset_read_sentences::table
+        .filter(asset_read_sentences::subject_id.eq(subject_id))
+        .order(asset_read_sentences::sentence_order.asc())
+        .select((
+            asset_read_sentences::id,
+            asset_read_sentences::sentence_order,
+        ))
+        .load::<(i64, i32)>(conn)?
+        .into_iter()
+        .map(|(sentence_id, sentence_order)| SentenceProgress {
+            sentence_id,
+            sentence_order,
+            attempts: 0,
+            best_score: None,
+            last_practiced_at: None,
+        })
+        .collect();
+    let index: HashMap<i64, usize> = sentences
+        .iter()
+        .enumerate()
+        .map(|(i, s)| (s.sentence_id, i))
+        .collect();
+    let sentence_ids: Vec<i64> = index.keys().copied().collect();
+
+    let attempts = learn_read_practices::table
+        .filter(learn_read_practices::user_id.eq(user_id))
+        .filter(learn_read_practices::sentence_id.eq_any(sentence_ids))
+        .select((
+            learn_read_practices::sentence_id,
+            learn_read_practices::overall_score,
+            learn_read_practices::created_at,
+        ))
+        .load::<(Option<i64>, Option<i32>, DateTime<Utc>)>(conn)?;
+
+    let mut scores = Vec::with_capacity(attempts.len());
+    for (sentence_id, score, created_at) in attempts {
+        let Some(sentence) = sentence_id
+            .and_then(|id| index.get(&id))
+            .map(|&i| &mut sentences[i])
+        else {
+            continue;
+        };
+        sentence.attempts += 1;
+        sentence.best_score = sentence.best_score.max(score);
+        sentence.last_practiced_at = sentence.last_practiced_at.max(Some(created_at));
+        scores.extend(score);
+    }
+    Ok((sentences, scores))
+}
+
+fn refresh_progress(
+    conn: &mut PgConnection,
+    user_id: i64,
+    subject_id: i64,
+    practiced_order: i32,
+    at: DateTime<Utc>,
+) -> QueryResult<ReadProgress> {
+    diesel::insert_into(learn_read_progress::table)
+        .values(&NewReadProgress {
+            user_id,
+            exercise_id: subject_id,
+            current_sentence_order: Some(practiced_order),
+            progress_percent: Some(0),
+        })
+        .on_conflict((
+            learn_read_progress::user_id,
+            learn_read_progress::exercise_id,
+        ))
+        .do_nothing()
+        .execute(conn)?;
+    let existing = learn_read_progress::table
+        .filter(learn_read_progress::user_id.eq(user_id))
+        .filter(learn_read_progress::exercise_id.eq(subject_id))
+        .for_update()
+        .first::<ReadProgress>(conn)?;
+
+    let (sentences, scores) = sentence_progress(conn, user_id, subject_id)?;
+    let passed = sentences.iter().filter(|s| s.passed()).count();
+    let completed = !sentences.is_empty() && passed == sentences.len();
+    let average_score = (!scores.is_empty()).then(|| {
+        (scores.iter().map(|&s| f64::from(s)).sum::<f64>() / scores.len() as f64).round() as i32
+    });
+
+    diesel::update(learn_read_progress::table.find(existing.id))
+        .set((
+            &UpdateReadProgress {
+                current_sentence_order: Some(next_sentence_order(&sentences, practiced_order)),
+                progress_percent: Some(percent(passed, sentences.len())),
+                completed_at: existing.completed_at.or(completed.then_some(at)),
+                last_practiced_at: Some(at),
+                practice_count: Some(sentences.iter().map(|s| s.attempts).sum::<i64>() as i32),
+                average_score,
+            },
+            learn_read_progress::updated_at.eq(at),
+        ))
+        .get_result::<ReadProgress>(conn)
+}
+
+fn percent(passed: usize, total: usize) -> i32 {
+    if total == 0 {
+        0
+    } else {
+        (passed * 100 / total) as i32
+    }
+}
+
+/// Sentence to continue with: the first one not yet read after the one just
+/// practiced, wrapping around to the start, or that one when all are read
+fn next_sentence_order(sentences: &[SentenceProgress], practiced_order: i32) -> i32 {
+    sentences
+        .iter()
+        .filter(|s| !s.passed() && s.sentence_order > practiced_order)
+        .chain(sentences.iter().filter(|s| !s.passed()))
+        .map(|s| s.sentence_order)
+        .next()
+        .unwrap_or(practiced_order)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sentence(sentence_order: i32, best_score: Option<i32>) -> SentenceProgress {
+        SentenceProgress {
+            sentence_id: i64::from(sentence_order),
+            sentence_order,
+            attempts: i64::from(best_score.is_some()),
+            best_score,
+            last_practiced_at: None,
+        }
+    }
+
+    #[test]
+    fn continues_with_the_next_unread_sentence() {
+        let sentences = vec![
+            sentence(1, None),
+            sentence(2, Some(80)),
+            sentence(3, Some(40)),
+            sentence(4, None),
+        ];
+        assert_eq!(next_sentence_order(&sentences, 2), 3);
+        assert_eq!(next_sentence_order(&sentences, 3), 4);
+        assert_eq!(next_sentence_order(&sentences, 4), 1);
+
+        let read = vec![sentence(1, Some(90)), sentence(2, Some(PASS_SCORE))];
+        assert_eq!(next_sentence_order(&read, 2), 2);
+    }
+
+    #[test]
+    fn percent_rounds_down() {
+        assert_eq!(percent(0, 0), 0);
+        assert_eq!(percent(2, 3), 66);
+        assert_eq!(percent(3, 3), 100);
+    }
+}
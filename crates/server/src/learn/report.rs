@@ -0,0 +1,729 @@
+//! Progress reports over a month or a chosen range of days
+//!
+//! A report covers local calendar days, both ends included. Activity inside
+//! the range (minutes, chats, new words, errors, pronunciation scores,
+//! achievements) is aggregated from the source tables; the mastery
+//! distribution is the current state of the words known by the end of the
+//! range, since mastery history is not kept. The same data is rendered as
+//! JSON, a self-contained HTML page and a plain text PDF set in the
+//! configured font, and can be shared as a link (see [`share`]).
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Datelike, Days, Months, NaiveDate, Utc};
+use diesel::prelude::*;
+use salvo::oapi::ToSchema;
+use serde::Serialize;
+
+pub use self::font::Font;
+use self::pdf::{TextPdf, TextStyle};
+use super::achievement::MASTERED_LEVEL;
+use super::local_time::{day_bounds, local_date, user_timezone};
+use crate::config::AppConfig;
+use crate::db::schema::*;
+use crate::models::learn::DailyStat;
+
+mod font;
+mod pdf;
+pub mod share;
+
+/// Longest range a report covers
+pub const MAX_REPORT_DAYS: i64 = 366;
+/// Recurring errors listed in a report
+const TOP_ERRORS: usize = 10;
+/// Highest vocabulary mastery level
+const MAX_MASTERY_LEVEL: i32 = 5;
+
+/// Days a report covers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportPeriod {
+    /// The learner's current calendar month
+    CurrentMonth,
+    Month {
+        year: i32,
+        month: u32,
+    },
+    Range {
+        from: NaiveDate,
+        to: NaiveDate,
+    },
+}
+
+impl ReportPeriod {
+    /// Period from query parameters: `month` as YYYY-MM, or `from` and `to`
+    pub fn parse(
+        month: Option<&str>,
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    ) -> Result<Self, &'static str> {
+        match (month, from, to) {
+            (None, None, None) => Ok(ReportPeriod::CurrentMonth),
+            (Some(month), None, None) => {
+                let first = NaiveDate::parse_from_str(&format!("{month}-01"), "%Y-%m-%d")
+                    .map_err(|_| "invalid month format, use YYYY-MM")?;
+                Ok(ReportPeriod::Month {
+                    year: first.year(),
+                    month: first.month(),
+                })
+            }
+            (None, Some(from), Some(to)) => {
+                if to < from || (to - from).num_days() >= MAX_REPORT_DAYS {
+                    return Err("invalid date range, at most 366 days");
+                }
+                Ok(ReportPeriod::Range { from, to })
+            }
+            _ => Err("give either month, or both from and to"),
+        }
+    }
+
+    /// First and last day of the period
+    pub fn resolve(self, today: NaiveDate) -> (NaiveDate, NaiveDate) {
+        let first_of_month =
+            |year: i32, month: u32| NaiveDate::from_ymd_opt(year, month, 1).unwrap_or(today);
+        let month_bounds = |first: NaiveDate| {
+            let last = first
+                .checked_add_months(Months::new(1))
+                .map_or(first, |next| next - Days::new(1));
+            (first, last)
+        };
+        match self {
+            ReportPeriod::CurrentMonth => month_bounds(first_of_month(today.year(), today.month())),
+            ReportPeriod::Month { year, month } => month_bounds(first_of_month(year, month)),
+            ReportPeriod::Range { from, to } => (from, to),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DailyMinutes {
+    pub date: NaiveDate,
+    pub minutes: i32,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MinutesReport {
+    pub total: i32,
+    /// Total of the same number of days right before the range
+    pub previous_total: i32,
+    /// Days with any study time
+    pub active_days: usize,
+    pub daily: Vec<DailyMinutes>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChatReport {
+    pub count: i64,
+    pub minutes: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct VocabularyReport {
+    /// Words added during the range
+    pub added: i64,
+    /// Words in the vocabulary at the end of the range
+    pub total: i64,
+    /// Of those, words mastered now
+    pub mastered: i64,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MasteryBucket {
+    pub level: i32,
+    pub words: i64,
+}
+
+/// A mistake made several times in chats
+#[derive(Debug, PartialEq, Serialize, ToSchema)]
+pub struct RecurringError {
+    pub issue_type: String,
+    pub text: String,
+    /// Most recent correction
+    pub suggestion: Option<String>,
+    pub count: usize,
+}
+
+/// Average reading scores of one local day
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PronunciationPoint {
+    pub date: NaiveDate,
+    pub attempts: usize,
+    pub pronunciation: Option<f32>,
+    pub fluency: Option<f32>,
+    pub overall: Option<f32>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct EarnedAchievement {
+    pub name: String,
+    pub description: Option<String>,
+    pub earned_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ProgressReport {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub generated_at: DateTime<Utc>,
+    pub minutes: MinutesReport,
+    pub chats: ChatReport,
+    pub vocabulary: VocabularyReport,
+    pub mastery: Vec<MasteryBucket>,
+    pub top_errors: Vec<RecurringError>,
+    pub pronunciation: Vec<PronunciationPoint>,
+    pub achievements: Vec<EarnedAchievement>,
+}
+
+/// Gather the report of a user for a period
+pub fn build(
+    conn: &mut PgConnection,
+    user_id: i64,
+    period: ReportPeriod,
+    now: DateTime<Utc>,
+) -> QueryResult<ProgressReport> {
+    let tz = user_timezone(conn, user_id)?;
+    let (from, to) = period.resolve(local_date(tz, now));
+    let (start, _) = day_bounds(tz, from);
+    let (_, end) = day_bounds(tz, to);
+    let days = (to - from).num_days() as u64 + 1;
+    let previous_from = from - Days::new(days);
+
+    let stats = learn_daily_stats::table
+        .filter(learn_daily_stats::user_id.eq(user_id))
+        .filter(learn_daily_stats::stat_date.ge(previous_from))
+        .filter(learn_daily_stats::stat_date.le(to))
+        .load::<DailyStat>(conn)?;
+    let by_date: HashMap<NaiveDate, i32> = stats
+        .iter()
+        .map(|s| (s.stat_date, s.minutes_studied.unwrap_or(0)))
+        .collect();
+    let daily: Vec<DailyMinutes> = from
+        .iter_days()
+        .take(days as usize)
+        .map(|date| DailyMinutes {
+            date,
+            minutes: by_date.get(&date).copied().unwrap_or(0),
+        })
+        .collect();
+    let minutes = MinutesReport {
+        total: daily.iter().map(|d| d.minutes).sum(),
+        previous_total: by_date
+            .iter()
+            .filter(|(date, _)| **date < from)
+            .map(|(_, minutes)| minutes)
+            .sum(),
+        active_days: daily.iter().filter(|d| d.minutes > 0).count(),
+        daily,
+    };
+
+    let chat_durations = learn_chats::table
+        .filter(learn_chats::user_id.eq(user_id))
+        .filter(learn_chats::created_at.ge(start))
+        .filter(learn_chats::created_at.lt(end))
+        .select(learn_chats::duration_ms)
+        .load::<Option<i32>>(conn)?;
+    let chats = ChatReport {
+        count: chat_durations.len() as i64,
+        minutes: chat_durations
+            .iter()
+            .map(|d| d.unwrap_or(0) as i64)
+            .sum::<i64>()
+            / 60_000,
+    };
+
+    let levels = learn_vocabularies::table
+        .filter(learn_vocabularies::user_id.eq(user_id))
+        .filter(learn_vocabularies::first_seen_at.lt(end))
+        .select((
+            learn_vocabularies::first_seen_at,
+            learn_vocabularies::mastery_level,
+        ))
+        .load::<(DateTime<Utc>, Option<i32>)>(conn)?;
+    let mut mastery: Vec<MasteryBucket> = (0..=MAX_MASTERY_LEVEL)
+        .map(|level| MasteryBucket { level, words: 0 })
+        .collect();
+    for (_, level) in &levels {
+        mastery[level.unwrap_or(0).clamp(0, MAX_MASTERY_LEVEL) as usize].words += 1;
+    }
+    let vocabulary = VocabularyReport {
+        added: levels.iter().filter(|(seen, _)| *seen >= start).count() as i64,
+        total: levels.len() as i64,
+        mastered: levels
+            .iter()
+            .filter(|(_, level)| level.unwrap_or(0) >= MASTERED_LEVEL)
+            .count() as i64,
+    };
+
+    let issues = learn_chat_issues::table
+        .filter(learn_chat_issues::user_id.eq(user_id))
+        .filter(learn_chat_issues::created_at.ge(start))
+        .filter(learn_chat_issues::created_at.lt(end))
+        .order(learn_chat_issues::created_at.asc())
+        .select((
+            learn_chat_issues::issue_type,
+            learn_chat_issues::original_text,
+            learn_chat_issues::suggested_text,
+        ))
+        .load::<(String, Option<String>, Option<String>)>(conn)?;
+    let top_errors = recurring_errors(issues);
+
+    let readings = learn_read_practices::table
+        .filter(learn_read_practices::user_id.eq(user_id))
+        .filter(learn_read_practices::created_at.ge(start))
+        .filter(learn_read_practices::created_at.lt(end))
+        .select((
+            learn_read_practices::created_at,
+            learn_read_practices::pronunciation_score,
+            learn_read_practices::fluency_score,
+            learn_read_practices::overall_score,
+        ))
+        .load::<(DateTime<Utc>, Option<i32>, Option<i32>, Option<i32>)>(conn)?;
+    let mut readings_by_day: BTreeMap<NaiveDate, Vec<[Option<i32>; 3]>> = BTreeMap::new();
+    for (created_at, pronunciation, fluency, overall) in readings {
+        readings_by_day
+            .entry(local_date(tz, created_at))
+            .or_default()
+            .push([pronunciation, fluency, overall]);
+    }
+    let pronunciation = readings_by_day
+        .into_iter()
+        .map(|(date, scores)| {
+            let average = |i: usize| {
+                let values: Vec<i32> = scores.iter().filter_map(|s| s[i]).collect();
+                (!values.is_empty())
+                    .then(|| values.iter().sum::<i32>() as f32 / values.len() as f32)
+            };
+            PronunciationPoint {
+                date,
+                attempts: scores.len(),
+                pronunciation: average(0),
+                fluency: average(1),
+                overall: average(2),
+            }
+        })
+        .collect();
+
+    let achievements = learn_achievements::table
+        .filter(learn_achievements::user_id.eq(user_id))
+        .filter(learn_achievements::earned_at.ge(start))
+        .filter(learn_achievements::earned_at.lt(end))
+        .order(learn_achievements::earned_at.asc())
+        .select((
+            learn_achievements::achievement_name,
+            learn_achievements::description_en,
+            learn_achievements::earned_at,
+        ))
+        .load::<(String, Option<String>, DateTime<Utc>)>(conn)?
+        .into_iter()
+        .map(|(name, description, earned_at)| EarnedAchievement {
+            name,
+            description,
+            earned_at,
+        })
+        .collect();
+
+    Ok(ProgressReport {
+        from,
+        to,
+        generated_at: now,
+        minutes,
+        chats,
+        vocabulary,
+        mastery,
+        top_errors,
+        pronunciation,
+        achievements,
+    })
+}
+
+/// Most frequent chat issues, grouped by type and case-insensitive text
+///
+/// `issues` is in chronological order so the last suggestion wins.
+fn recurring_errors(issues: Vec<(String, Option<String>, Option<String>)>) -> Vec<RecurringError> {
+    let mut grouped: HashMap<(String, String), RecurringError> = HashMap::new();
+    for (issue_type, original, suggestion) in issues {
+        let Some(text) = original
+            .map(|t| t.trim().to_owned())
+            .filter(|t| !t.is_empty())
+        else {
+            continue;
+        };
+        let error = grouped
+            .entry((issue_type.clone(), text.to_lowercase()))
+            .or_insert_with(|| RecurringError {
+                issue_type,
+                text,
+                suggestion: None,
+                count: 0,
+            });
+        error.count += 1;
+        if suggestion.as_deref().is_some_and(|s| !s.trim().is_empty()) {
+            error.suggestion = suggestion;
+        }
+    }
+    let mut errors: Vec<RecurringError> = grouped.into_values().collect();
+    errors.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then_with(|| a.text.to_lowercase().cmp(&b.text.to_lowercase()))
+    });
+    errors.truncate(TOP_ERRORS);
+    errors
+}
+
+fn minutes_change(report: &ProgressReport) -> String {
+    let change = report.minutes.total - report.minutes.previous_total;
+    match change {
+        0 => "same as the previous period".to_owned(),
+        c if c > 0 => format!("{c} more than the previous period"),
+        c => format!("{} fewer than the previous period", -c),
+    }
+}
+
+fn score(value: Option<f32>) -> String {
+    value.map_or_else(|| "-".to_owned(), |v| format!("{v:.0}"))
+}
+
+/// Standalone HTML page with inline styles, suitable for sharing
+pub fn to_html(report: &ProgressReport) -> String {
+    let mut html = String::new();
+    let title = format!("Progress report {} – {}", report.from, report.to);
+    let _ = writeln!(
+        html,
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+         <title>{title}</title>\n<style>\n\
+         body{{font-family:-apple-system,Helvetica,Arial,sans-serif;max-width:760px;margin:2rem auto;padding:0 1rem;color:#222}}\n\
+         h1{{font-size:1.6rem}}h2{{font-size:1.15rem;margin-top:2rem;border-bottom:1px solid #ddd;padding-bottom:.3rem}}\n\
+         table{{border-collapse:collapse;width:100%}}td,th{{text-align:left;padding:.25rem .5rem;border-bottom:1px solid #eee}}\n\
+         .bar{{background:#4a90d9;height:.8rem;border-radius:2px}}.muted{{color:#777;font-size:.85rem}}\n\
+         .stats{{display:flex;flex-wrap:wrap;gap:1rem}}.stat{{background:#f5f7fa;border-radius:6px;padding:.6rem 1rem}}\n\
+         .stat b{{display:block;font-size:1.3rem}}\n</style>\n</head>\n<body>\n<h1>{title}</h1>"
+    );
+
+    let _ = writeln!(
+        html,
+        "<div class=\"stats\">\
+         <div class=\"stat\"><b>{}</b>minutes studied</div>\
+         <div class=\"stat\"><b>{}</b>active days</div>\
+         <div class=\"stat\"><b>{}</b>chats</div>\
+         <div class=\"stat\"><b>{}</b>new words</div>\
+         <div class=\"stat\"><b>{}</b>words mastered</div></div>\n\
+         <p class=\"muted\">{} minutes, {}.</p>",
+        report.minutes.total,
+        report.minutes.active_days,
+        report.chats.count,
+        report.vocabulary.added,
+        report.vocabulary.mastered,
+        report.minutes.total,
+        minutes_change(report),
+    );
+
+    html.push_str("<h2>Study time</h2>\n<table>\n");
+    let busiest = report
+        .minutes
+        .daily
+        .iter()
+        .map(|d| d.minutes)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    for day in &report.minutes.daily {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td style=\"width:70%\"><div class=\"bar\" style=\"width:{}%\"></div></td><td>{} min</td></tr>",
+            day.date,
+            day.minutes * 100 / busiest,
+            day.minutes
+        );
+    }
+    html.push_str("</table>\n");
+
+    let _ = writeln!(
+        html,
+        "<h2>Vocabulary</h2>\n<p>{} words in total, {} added in this period, {} mastered.</p>\n<table>\n<tr><th>Mastery level</th><th>Words</th></tr>",
+        report.vocabulary.total, report.vocabulary.added, report.vocabulary.mastered
+    );
+    for bucket in &report.mastery {
+        let _ = writeln!(
+            html,
+            "<tr><td>{}</td><td>{}</td></tr>",
+            bucket.level, bucket.words
+        );
+    }
+    html.push_str("</table>\n");
+
+    html.push_str("<h2>Recurring errors</h2>\n");
+    if report.top_errors.is_empty() {
+        html.push_str("<p class=\"muted\">No errors recorded.</p>\n");
+    } else {
+        html.push_str(
+            "<table>\n<tr><th>Said</th><th>Better</th><th>Type</th><th>Times</th></tr>\n",
+        );
+        for error in &report.top_errors {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                escape_html(&error.text),
+                escape_html(error.suggestion.as_deref().unwrap_or("")),
+                escape_html(&error.issue_type),
+                error.count
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("<h2>Pronunciation</h2>\n");
+    if report.pronunciation.is_empty() {
+        html.push_str("<p class=\"muted\">No reading practice in this period.</p>\n");
+    } else {
+        html.push_str("<table>\n<tr><th>Day</th><th>Attempts</th><th>Pronunciation</th><th>Fluency</th><th>Overall</th></tr>\n");
+        for point in &report.pronunciation {
+            let _ = writeln!(
+                html,
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                point.date,
+                point.attempts,
+                score(point.pronunciation),
+                score(point.fluency),
+                score(point.overall)
+            );
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("<h2>Achievements</h2>\n");
+    if report.achievements.is_empty() {
+        html.push_str("<p class=\"muted\">No new achievements in this period.</p>\n");
+    } else {
+        html.push_str("<ul>\n");
+        for achievement in &report.achievements {
+            let _ = writeln!(
+                html,
+                "<li><b>{}</b> {} <span class=\"muted\">{}</span></li>",
+                escape_html(&achievement.name),
+                escape_html(achievement.description.as_deref().unwrap_or("")),
+                achievement.earned_at.date_naive()
+            );
+        }
+        html.push_str("</ul>\n");
+    }
+
+    let _ = writeln!(
+        html,
+        "<p class=\"muted\">Generated {}</p>\n</body>\n</html>",
+        report.generated_at.format("%Y-%m-%d %H:%M UTC")
+    );
+    html
+}
+
+/// The font PDF reports are set in, read from `REPORT_FONT_PATH` on first use
+pub fn pdf_font() -> Result<&'static Font, String> {
+    static FONT: OnceLock<Font> = OnceLock::new();
+    if let Some(font) = FONT.get() {
+        return Ok(font);
+    }
+    let path = &AppConfig::get().report.font_path;
+    let data = std::fs::read(path).map_err(|e| format!("cannot read {path}: {e}"))?;
+    let font = Font::parse(data).map_err(|e| format!("{path}: {e}"))?;
+    Ok(FONT.get_or_init(|| font))
+}
+
+/// Printable PDF version of the report
+pub fn to_pdf(report: &ProgressReport, font: &Font) -> Vec<u8> {
+    let mut pdf = TextPdf::new(font);
+    pdf.line(
+        TextStyle::Title,
+        &format!("Progress report {} - {}", report.from, report.to),
+    );
+    pdf.gap(6.0);
+    pdf.line(
+        TextStyle::Body,
+        &format!(
+            "{} minutes studied on {} days, {}.",
+            report.minutes.total,
+            report.minutes.active_days,
+            minutes_change(report)
+        ),
+    );
+    pdf.line(
+        TextStyle::Body,
+        &format!(
+            "{} chats, {} minutes of conversation.",
+            report.chats.count, report.chats.minutes
+        ),
+    );
+
+    pdf.gap(10.0);
+    pdf.line(TextStyle::Heading, "Study time");
+    for day in report.minutes.daily.iter().filter(|d| d.minutes > 0) {
+        pdf.line(
+            TextStyle::Body,
+            &format!("{}   {} min", day.date, day.minutes),
+        );
+    }
+
+    pdf.gap(10.0);
+    pdf.line(TextStyle::Heading, "Vocabulary");
+    pdf.line(
+        TextStyle::Body,
+        &format!(
+            "{} words in total, {} added in this period, {} mastered.",
+            report.vocabulary.total, report.vocabulary.added, report.vocabulary.mastered
+        ),
+    );
+    let distribution: Vec<String> = report
+        .mastery
+        .iter()
+        .map(|b| format!("level {}: {}", b.level, b.words))
+        .collect();
+    pdf.line(TextStyle::Body, &distribution.join(", "));
+
+    pdf.gap(10.0);
+    pdf.line(TextStyle::Heading, "Recurring errors");
+    if report.top_errors.is_empty() {
+        pdf.line(TextStyle::Small, "No errors recorded.");
+    }
+    for error in &report.top_errors {
+        let suggestion = error
+            .suggestion
+            .as_deref()
+            .map(|s| format!(" -> \"{s}\""))
+            .unwrap_or_default();
+        pdf.line(
+            TextStyle::Body,
+            &format!(
+                "{}x  \"{}\"{} ({})",
+                error.count, error.text, suggestion, error.issue_type
+            ),
+        );
+    }
+
+    pdf.gap(10.0);
+    pdf.line(TextStyle::Heading, "Pronunciation");
+    if report.pronunciation.is_empty() {
+        pdf.line(TextStyle::Small, "No reading practice in this period.");
+    }
+    for point in &report.pronunciation {
+        pdf.line(
+            TextStyle::Body,
+            &format!(
+                "{}   {} attempts, pronunciation {}, fluency {}, overall {}",
+                point.date,
+                point.attempts,
+                score(point.pronunciation),
+                score(point.fluency),
+                score(point.overall)
+            ),
+        );
+    }
+
+    pdf.gap(10.0);
+    pdf.line(TextStyle::Heading, "Achievements");
+    if report.achievements.is_empty() {
+        pdf.line(TextStyle::Small, "No new achievements in this period.");
+    }
+    for achievement in &report.achievements {
+        pdf.line(
+            TextStyle::Body,
+            &format!(
+                "{}   {}",
+                achievement.earned_at.date_naive(),
+                achievement.name
+            ),
+        );
+    }
+
+    pdf.gap(10.0);
+    pdf.line(
+        TextStyle::Small,
+        &format!(
+            "Generated {}",
+            report.generated_at.format("%Y-%m-%d %H:%M UTC")
+        ),
+    );
+    pdf.finish()
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn resolves_periods() {
+        let today = date(2024, 2, 10);
+        assert_eq!(
+            ReportPeriod::parse(None, None, None)
+                .unwrap()
+                .resolve(today),
+            (date(2024, 2, 1), date(2024, 2, 29))
+        );
+        assert_eq!(
+            ReportPeriod::parse(Some("2023-12"), None, None)
+                .unwrap()
+                .resolve(today),
+            (date(2023, 12, 1), date(2023, 12, 31))
+        );
+        assert!(ReportPeriod::parse(Some("2023-13"), None, None).is_err());
+        assert!(ReportPeriod::parse(None, Some(date(2024, 1, 1)), None).is_err());
+        assert!(ReportPeriod::parse(None, Some(date(2024, 1, 2)), Some(date(2024, 1, 1))).is_err());
+    }
+
+    #[test]
+    fn groups_recurring_errors() {
+        let issue = |t: &str, s: Option<&str>| {
+            (
+                "grammar".to_owned(),
+                Some(t.to_owned()),
+                s.map(str::to_owned),
+            )
+        };
+        let errors = recurring_errors(vec![
+            issue("He go", Some("He goes")),
+            issue("a apple", None),
+            issue("he go ", Some("He went")),
+            issue(" ", None),
+        ]);
+        assert_eq!(
+            errors,
+            vec![
+                RecurringError {
+                    issue_type: "grammar".to_owned(),
+                    text: "He go".to_owned(),
+                    suggestion: Some("He went".to_owned()),
+                    count: 2,
+                },
+                RecurringError {
+                    issue_type: "grammar".to_owned(),
+                    text: "a apple".to_owned(),
+                    suggestion: None,
+                    count: 1,
+                },
+            ]
+        );
+        assert_eq!(
+            escape_html("<b>\"x\" & y</b>"),
+            "&lt;b&gt;&quot;x&quot; &amp; y&lt;/b&gt;"
+        );
+    }
+}
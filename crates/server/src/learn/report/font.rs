@@ -0,0 +1,529 @@
+//! TrueType fonts embedded in PDF reports
+//!
+//! Reports mix English and Chinese, which the standard PDF fonts cannot
+//! show, so the configured font is embedded. Only the glyphs a document uses
+//! are kept: the others are emptied while glyph ids stay the same, which lets
+//! the PDF address glyphs by id (`/CIDToGIDMap /Identity`). Fonts need
+//! TrueType outlines (`glyf`); CFF based OpenType fonts are rejected.
+
+use std::collections::BTreeSet;
+
+/// Tables copied into a subset, in tag order as the directory requires
+const SUBSET_TABLES: &[&[u8; 4]] = &[
+    b"cvt ", b"fpgm", b"glyf", b"head", b"hhea", b"hmtx", b"loca", b"maxp", b"prep",
+];
+
+/// Composite glyph flags
+const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+const WE_HAVE_A_SCALE: u16 = 0x0008;
+const MORE_COMPONENTS: u16 = 0x0020;
+const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+
+/// Character to glyph mapping of the font
+#[derive(Debug, Clone, Copy)]
+enum Cmap {
+    /// Offset of a format 4 subtable, Basic Multilingual Plane only
+    Segments(usize),
+    /// Offset of a format 12 subtable
+    Groups(usize),
+}
+
+#[derive(Debug)]
+pub struct Font {
+    data: Vec<u8>,
+    /// Tag, offset and length of each table
+    tables: Vec<([u8; 4], usize, usize)>,
+    /// Absent in subsets, which address glyphs by id only
+    cmap: Option<Cmap>,
+    pub units_per_em: u16,
+    pub num_glyphs: u16,
+    long_metrics: u16,
+    long_loca: bool,
+    pub ascent: i16,
+    pub descent: i16,
+    /// xMin, yMin, xMax, yMax in font units
+    pub bbox: [i16; 4],
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn i16_at(data: &[u8], offset: usize) -> Option<i16> {
+    u16_at(data, offset).map(|v| v as i16)
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+/// Table checksum: the sum of big-endian words, zero padded
+fn checksum(data: &[u8]) -> u32 {
+    data.chunks(4).fold(0u32, |sum, chunk| {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        sum.wrapping_add(u32::from_be_bytes(word))
+    })
+}
+
+impl Font {
+    /// Parse a TrueType font, or the first font of a TrueType collection
+    pub fn parse(data: Vec<u8>) -> Result<Self, &'static str> {
+        let font = Self::read(data)?;
+        if font.cmap.is_none() {
+            return Err("font has no Unicode character map");
+        }
+        Ok(font)
+    }
+
+    /// Parse a font that may lack a character map
+    fn read(data: Vec<u8>) -> Result<Self, &'static str> {
+        let invalid = "invalid TrueType font";
+        let start = match data.get(..4) {
+            Some(b"ttcf") => u32_at(&data, 12).ok_or(invalid)? as usize,
+            Some(b"OTTO") => return Err("CFF fonts are not supported, use TrueType outlines"),
+            Some(_) => 0,
+            None => return Err(invalid),
+        };
+        let count = u16_at(&data, start + 4).ok_or(invalid)? as usize;
+        let mut tables = Vec::with_capacity(count);
+        for i in 0..count {
+            let record = start + 12 + 16 * i;
+            let tag: [u8; 4] = data
+                .get(record..record + 4)
+                .and_then(|tag| tag.try_into().ok())
+                .ok_or(invalid)?;
+            let offset = u32_at(&data, record + 8).ok_or(invalid)? as usize;
+            let length = u32_at(&data, record + 12).ok_or(invalid)? as usize;
+            if offset
+                .checked_add(length)
+                .is_none_or(|end| end > data.len())
+            {
+                return Err(invalid);
+            }
+            tables.push((tag, offset, length));
+        }
+        let table = |tag: &[u8; 4]| {
+            tables
+                .iter()
+                .find(|(t, _, _)| t == tag)
+                .map(|(_, offset, _)| *offset)
+        };
+        for tag in [b"head", b"hhea", b"maxp", b"hmtx", b"loca", b"glyf"] {
+            if table(tag).is_none() {
+                return Err("font lacks a required table, TrueType outlines are needed");
+            }
+        }
+        let head = table(b"head").ok_or(invalid)?;
+        let hhea = table(b"hhea").ok_or(invalid)?;
+        let maxp = table(b"maxp").ok_or(invalid)?;
+
+        // Prefer the full Unicode mapping, then the Basic Multilingual Plane
+        let mut cmap = None;
+        let cmap_table = table(b"cmap").unwrap_or_default();
+        let subtables = match table(b"cmap") {
+            Some(_) => u16_at(&data, cmap_table + 2).ok_or(invalid)? as usize,
+            None => 0,
+        };
+        for i in 0..subtables {
+            let record = cmap_table + 4 + 8 * i;
+            let platform = u16_at(&data, record).ok_or(invalid)?;
+            let encoding = u16_at(&data, record + 2).ok_or(invalid)?;
+            let offset = cmap_table + u32_at(&data, record + 4).ok_or(invalid)? as usize;
+            let unicode = platform == 0 || (platform == 3 && matches!(encoding, 1 | 10));
+            match u16_at(&data, offset) {
+                Some(12) if unicode => cmap = Some(Cmap::Groups(offset)),
+                Some(4) if unicode && cmap.is_none() => cmap = Some(Cmap::Segments(offset)),
+                _ => {}
+            }
+        }
+
+        let font = Self {
+            cmap,
+            units_per_em: u16_at(&data, head + 18).ok_or(invalid)?.max(1),
+            bbox: [
+                i16_at(&data, head + 36).ok_or(invalid)?,
+                i16_at(&data, head + 38).ok_or(invalid)?,
+                i16_at(&data, head + 40).ok_or(invalid)?,
+                i16_at(&data, head + 42).ok_or(invalid)?,
+            ],
+            long_loca: i16_at(&data, head + 50).ok_or(invalid)? == 1,
+            ascent: i16_at(&data, hhea + 4).ok_or(invalid)?,
+            descent: i16_at(&data, hhea + 6).ok_or(invalid)?,
+            long_metrics: u16_at(&data, hhea + 34).ok_or(invalid)?.max(1),
+            num_glyphs: u16_at(&data, maxp + 4).ok_or(invalid)?,
+            tables,
+            data,
+        };
+        if font
+            .glyph_range(font.num_glyphs.saturating_sub(1))
+            .is_none()
+        {
+            return Err(invalid);
+        }
+        Ok(font)
+    }
+
+    fn table(&self, tag: &[u8; 4]) -> Option<&[u8]> {
+        self.tables
+            .iter()
+            .find(|(t, _, _)| t == tag)
+            .map(|(_, offset, length)| &self.data[*offset..*offset + *length])
+    }
+
+    /// Glyph of `c`, 0 (the missing glyph) when the font has none
+    pub fn glyph(&self, c: char) -> u16 {
+        let found = match self.cmap {
+            Some(Cmap::Groups(offset)) => self.group_glyph(offset, c as u32),
+            Some(Cmap::Segments(offset)) => u16::try_from(c as u32)
+                .ok()
+                .and_then(|c| self.segment_glyph(offset, c)),
+            None => None,
+        };
+        found.filter(|g| *g < self.num_glyphs).unwrap_or(0)
+    }
+
+    /// Glyph of `c` in a format 12 subtable
+    fn group_glyph(&self, offset: usize, c: u32) -> Option<u16> {
+        let data = &self.data;
+        let count = u32_at(data, offset + 12)? as usize;
+        let group = |i: usize| {
+            let at = offset + 16 + 12 * i;
+            Some((
+                u32_at(data, at)?,
+                u32_at(data, at + 4)?,
+                u32_at(data, at + 8)?,
+            ))
+        };
+        let index = partition(count, |i| group(i).is_some_and(|(_, end, _)| end < c));
+        let (start, end, first) = group(index)?;
+        (start <= c && c <= end).then(|| (first + c - start) as u16)
+    }
+
+    /// Glyph of `c` in a format 4 subtable
+    fn segment_glyph(&self, offset: usize, c: u16) -> Option<u16> {
+        let data = &self.data;
+        let segments = u16_at(data, offset + 6)? as usize / 2;
+        let ends = offset + 14;
+        let starts = ends + 2 * segments + 2;
+        let deltas = starts + 2 * segments;
+        let range_offsets = deltas + 2 * segments;
+        let index = partition(segments, |i| {
+            u16_at(data, ends + 2 * i).is_some_and(|e| e < c)
+        });
+        let start = u16_at(data, starts + 2 * index)?;
+        if c < start {
+            return None;
+        }
+        let delta = u16_at(data, deltas + 2 * index)?;
+        let range_offset = u16_at(data, range_offsets + 2 * index)? as usize;
+        if range_offset == 0 {
+            return Some(c.wrapping_add(delta));
+        }
+        // The offset counts from where it is stored into the glyph array
+        let at = range_offsets + 2 * index + range_offset + 2 * (c - start) as usize;
+        let glyph = u16_at(data, at)?;
+        (glyph != 0).then(|| glyph.wrapping_add(delta))
+    }
+
+    /// Advance width of `glyph` in font units
+    pub fn advance(&self, glyph: u16) -> u16 {
+        let Some(hmtx) = self.table(b"hmtx") else {
+            return 0;
+        };
+        let index = glyph.min(self.long_metrics - 1) as usize;
+        u16_at(hmtx, 4 * index).unwrap_or(0)
+    }
+
+    /// Byte range of `glyph` in the `glyf` table
+    fn glyph_range(&self, glyph: u16) -> Option<(usize, usize)> {
+        let loca = self.table(b"loca")?;
+        let glyf = self.table(b"glyf")?;
+        let index = glyph as usize;
+        let (start, end) = if self.long_loca {
+            (
+                u32_at(loca, 4 * index)? as usize,
+                u32_at(loca, 4 * index + 4)? as usize,
+            )
+        } else {
+            (
+                2 * u16_at(loca, 2 * index)? as usize,
+                2 * u16_at(loca, 2 * index + 2)? as usize,
+            )
+        };
+        (start <= end && end <= glyf.len()).then_some((start, end))
+    }
+
+    /// Glyphs a composite glyph is built from, none for a simple glyph
+    fn components(&self, glyph: u16) -> Vec<u16> {
+        let (Some(glyf), Some((start, end))) = (self.table(b"glyf"), self.glyph_range(glyph))
+        else {
+            return Vec::new();
+        };
+        let outline = &glyf[start..end];
+        if i16_at(outline, 0).is_none_or(|contours| contours >= 0) {
+            return Vec::new();
+        }
+        let mut components = Vec::new();
+        let mut at = 10;
+        while let (Some(flags), Some(component)) = (u16_at(outline, at), u16_at(outline, at + 2)) {
+            components.push(component);
+            at += 4 + if flags & ARG_1_AND_2_ARE_WORDS != 0 {
+                4
+            } else {
+                2
+            };
+            at += if flags & WE_HAVE_A_SCALE != 0 {
+                2
+            } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+                4
+            } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+                8
+            } else {
+                0
+            };
+            if flags & MORE_COMPONENTS == 0 {
+                break;
+            }
+        }
+        components
+    }
+
+    /// A font program keeping only `glyphs`, the parts of composite glyphs
+    /// and the missing glyph, with every glyph id unchanged
+    pub fn subset(&self, glyphs: &BTreeSet<u16>) -> Vec<u8> {
+        let mut keep: BTreeSet<u16> = BTreeSet::new();
+        let mut pending: Vec<u16> = glyphs.iter().copied().chain([0]).collect();
+        while let Some(glyph) = pending.pop() {
+            if glyph < self.num_glyphs && keep.insert(glyph) {
+                pending.extend(self.components(glyph));
+            }
+        }
+
+        let source = self.table(b"glyf").unwrap_or_default();
+        let mut glyf = Vec::new();
+        let mut loca = Vec::with_capacity(4 * (self.num_glyphs as usize + 1));
+        for glyph in 0..self.num_glyphs {
+            loca.extend_from_slice(&(glyf.len() as u32).to_be_bytes());
+            if !keep.contains(&glyph) {
+                continue;
+            }
+            if let Some((start, end)) = self.glyph_range(glyph) {
+                glyf.extend_from_slice(&source[start..end]);
+                glyf.resize(glyf.len().next_multiple_of(4), 0);
+            }
+        }
+        loca.extend_from_slice(&(glyf.len() as u32).to_be_bytes());
+
+        let mut head = self.table(b"head").unwrap_or_default().to_vec();
+        if head.len() >= 52 {
+            head[8..12].fill(0);
+            // Offsets are written as 32 bit
+            head[50..52].copy_from_slice(&1u16.to_be_bytes());
+        }
+
+        let tables: Vec<(&[u8; 4], Vec<u8>)> = SUBSET_TABLES
+            .iter()
+            .filter_map(|tag| {
+                let data = match *tag {
+                    b"glyf" => std::mem::take(&mut glyf),
+                    b"loca" => std::mem::take(&mut loca),
+                    b"head" => std::mem::take(&mut head),
+                    tag => self.table(tag)?.to_vec(),
+                };
+                Some((*tag, data))
+            })
+            .collect();
+
+        let count = tables.len() as u16;
+        let power = 1u16 << (15 - count.leading_zeros());
+        let mut out = Vec::new();
+        out.extend_from_slice(&0x0001_0000u32.to_be_bytes());
+        out.extend_from_slice(&count.to_be_bytes());
+        out.extend_from_slice(&(power * 16).to_be_bytes());
+        out.extend_from_slice(&(power.trailing_zeros() as u16).to_be_bytes());
+        out.extend_from_slice(&(count * 16 - power * 16).to_be_bytes());
+        let mut offset = 12 + 16 * tables.len();
+        let mut head_offset = None;
+        for (tag, data) in &tables {
+            out.extend_from_slice(*tag);
+            out.extend_from_slice(&checksum(data).to_be_bytes());
+            out.extend_from_slice(&(offset as u32).to_be_bytes());
+            out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            if *tag == b"head" {
+                head_offset = Some(offset);
+            }
+            offset += data.len().next_multiple_of(4);
+        }
+        for (_, data) in &tables {
+            out.extend_from_slice(data);
+            out.resize(out.len().next_multiple_of(4), 0);
+        }
+        if let Some(head) = head_offset {
+            let adjustment = 0xB1B0_AFBAu32.wrapping_sub(checksum(&out));
+            out[head + 8..head + 12].copy_from_slice(&adjustment.to_be_bytes());
+        }
+        out
+    }
+}
+
+/// First index in `0..count` for which `before` is false
+fn partition(count: usize, before: impl Fn(usize) -> bool) -> usize {
+    let (mut low, mut high) = (0, count);
+    while low < high {
+        let middle = (low + high) / 2;
+        if before(middle) {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    low
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    /// A font of six glyphs: missing, "A", "中", a composite "Ä" made of "A"
+    /// and glyph 4, an accent without a character, and an unused glyph
+    pub fn sample_font() -> Font {
+        let simple = |marker: u8| {
+            let mut outline = vec![0, 1, 0, 0, 0, 0, 0, 10, 0, 10];
+            outline.extend_from_slice(&[0, 0, 0, 0, marker, 0]);
+            outline
+        };
+        let mut composite = vec![0xff, 0xff, 0, 0, 0, 0, 0, 10, 0, 10];
+        composite.extend_from_slice(&(MORE_COMPONENTS | ARG_1_AND_2_ARE_WORDS).to_be_bytes());
+        composite.extend_from_slice(&[0, 1, 0, 0, 0, 0]);
+        composite.extend_from_slice(&0u16.to_be_bytes());
+        composite.extend_from_slice(&[0, 4, 0, 0]);
+        let outlines = [
+            simple(1),
+            simple(2),
+            simple(3),
+            composite,
+            simple(5),
+            simple(6),
+        ];
+        let mut glyf = Vec::new();
+        let mut loca = Vec::new();
+        for outline in &outlines {
+            loca.extend_from_slice(&((glyf.len() / 2) as u16).to_be_bytes());
+            glyf.extend_from_slice(outline);
+        }
+        loca.extend_from_slice(&((glyf.len() / 2) as u16).to_be_bytes());
+
+        let mut head = vec![0u8; 54];
+        head[18..20].copy_from_slice(&1000u16.to_be_bytes());
+        head[40..42].copy_from_slice(&900i16.to_be_bytes());
+        let mut hhea = vec![0u8; 36];
+        hhea[4..6].copy_from_slice(&800i16.to_be_bytes());
+        hhea[6..8].copy_from_slice(&(-200i16).to_be_bytes());
+        hhea[34..36].copy_from_slice(&3u16.to_be_bytes());
+        let mut maxp = vec![0u8; 6];
+        maxp[4..6].copy_from_slice(&(outlines.len() as u16).to_be_bytes());
+        let mut hmtx = Vec::new();
+        for advance in [500u16, 600, 1000] {
+            hmtx.extend_from_slice(&advance.to_be_bytes());
+            hmtx.extend_from_slice(&[0, 0]);
+        }
+        // Format 4: "A" and "Ä" by delta, "中" through the glyph array
+        let segments: [(u16, u16, u16, u16); 4] = [
+            (0x41, 0x41, 0u16.wrapping_sub(0x40), 0),
+            (0xc4, 0xc4, 0u16.wrapping_sub(0xc1), 0),
+            (0x4e2d, 0x4e2d, 0, 4),
+            (0xffff, 0xffff, 1, 0),
+        ];
+        let mut cmap = vec![0, 0, 0, 1, 0, 3, 0, 1, 0, 0, 0, 12];
+        let mut subtable = vec![0, 4, 0, 0, 0, 0, 0, 8, 0, 0, 0, 0, 0, 0];
+        for (_, end, _, _) in segments {
+            subtable.extend_from_slice(&end.to_be_bytes());
+        }
+        subtable.extend_from_slice(&[0, 0]);
+        for (start, _, _, _) in segments {
+            subtable.extend_from_slice(&start.to_be_bytes());
+        }
+        for (_, _, delta, _) in segments {
+            subtable.extend_from_slice(&delta.to_be_bytes());
+        }
+        for (_, _, _, range_offset) in segments {
+            subtable.extend_from_slice(&range_offset.to_be_bytes());
+        }
+        subtable.extend_from_slice(&2u16.to_be_bytes());
+        let length = subtable.len() as u16;
+        subtable[2..4].copy_from_slice(&length.to_be_bytes());
+        cmap.extend_from_slice(&subtable);
+
+        let tables: [(&[u8; 4], Vec<u8>); 7] = [
+            (b"cmap", cmap),
+            (b"glyf", glyf),
+            (b"head", head),
+            (b"hhea", hhea),
+            (b"hmtx", hmtx),
+            (b"loca", loca),
+            (b"maxp", maxp),
+        ];
+        let mut data = vec![0, 1, 0, 0, 0, tables.len() as u8, 0, 0, 0, 0, 0, 0];
+        let mut offset = 12 + 16 * tables.len();
+        for (tag, table) in &tables {
+            data.extend_from_slice(*tag);
+            data.extend_from_slice(&[0; 4]);
+            data.extend_from_slice(&(offset as u32).to_be_bytes());
+            data.extend_from_slice(&(table.len() as u32).to_be_bytes());
+            offset += table.len();
+        }
+        for (_, table) in &tables {
+            data.extend_from_slice(table);
+        }
+        Font::parse(data).unwrap()
+    }
+
+    #[test]
+    fn maps_characters_to_glyphs() {
+        let font = sample_font();
+        assert_eq!(font.num_glyphs, 6);
+        assert_eq!(
+            (font.glyph('A'), font.glyph('中'), font.glyph('Ä')),
+            (1, 2, 3)
+        );
+        assert_eq!((font.glyph('B'), font.glyph('😀')), (0, 0));
+        assert_eq!(
+            (font.advance(1), font.advance(2), font.advance(5)),
+            (600, 1000, 1000)
+        );
+        assert_eq!((font.ascent, font.descent, font.bbox[2]), (800, -200, 900));
+        assert_eq!(font.components(3), [1, 4]);
+        assert!(font.components(1).is_empty());
+    }
+
+    #[test]
+    fn subsets_keep_glyph_ids() {
+        let font = sample_font();
+        let subset = Font::read(font.subset(&BTreeSet::from([3]))).unwrap();
+        assert!(subset.long_loca);
+        assert_eq!(subset.num_glyphs, font.num_glyphs);
+        let outline = |font: &Font, glyph: u16| {
+            let (start, end) = font.glyph_range(glyph).unwrap();
+            font.table(b"glyf").unwrap()[start..end].to_vec()
+        };
+        for glyph in [0, 1, 3, 4] {
+            assert!(outline(&subset, glyph).starts_with(&outline(&font, glyph)));
+        }
+        assert!(outline(&subset, 2).is_empty());
+        assert!(outline(&subset, 5).is_empty());
+        assert_eq!(checksum(&font.subset(&BTreeSet::new())), 0xB1B0_AFBA);
+    }
+
+    #[test]
+    fn rejects_other_fonts() {
+        assert!(Font::parse(b"OTTO\0\0\0\0".to_vec()).is_err());
+        assert!(Font::parse(Vec::new()).is_err());
+        assert!(Font::parse(vec![0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]).is_err());
+    }
+}
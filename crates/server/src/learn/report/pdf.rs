@@ -0,0 +1,358 @@
+//! Minimal PDF writer for plain text documents
+//!
+//! Covers what server-side reports need: A4 pages of left-aligned lines.
+//! Reports mix English and Chinese, so text is set in an embedded TrueType
+//! font rather than the standard Helvetica: a composite font (`Type0`,
+//! `Identity-H`) addresses glyphs by id, the font program is subset to the
+//! glyphs drawn and a `ToUnicode` map keeps the text searchable. Headings
+//! are emboldened by stroking the outlines.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use super::font::Font;
+
+const PAGE_WIDTH: f32 = 595.0;
+const PAGE_HEIGHT: f32 = 842.0;
+const MARGIN: f32 = 56.0;
+/// Name of the embedded font, tagged as a subset
+const FONT_NAME: &str = "COLANG+ReportSans";
+/// Entries a `ToUnicode` map block may hold
+const CMAP_BLOCK: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextStyle {
+    Title,
+    Heading,
+    Body,
+    Small,
+}
+
+impl TextStyle {
+    fn bold(self) -> bool {
+        matches!(self, TextStyle::Title | TextStyle::Heading)
+    }
+
+    fn size(self) -> f32 {
+        match self {
+            TextStyle::Title => 20.0,
+            TextStyle::Heading => 14.0,
+            TextStyle::Body => 11.0,
+            TextStyle::Small => 9.0,
+        }
+    }
+}
+
+/// A document built line by line, starting new pages as needed
+pub struct TextPdf<'a> {
+    font: &'a Font,
+    /// Glyphs drawn so far with the character each shows
+    glyphs: BTreeMap<u16, char>,
+    pages: Vec<String>,
+    current: String,
+    y: f32,
+}
+
+impl<'a> TextPdf<'a> {
+    pub fn new(font: &'a Font) -> Self {
+        Self {
+            font,
+            glyphs: BTreeMap::new(),
+            pages: Vec::new(),
+            current: String::new(),
+            y: PAGE_HEIGHT - MARGIN,
+        }
+    }
+
+    /// Width of `text` in points at `size`
+    fn width(&self, text: &str, size: f32) -> f32 {
+        let units: u32 = text
+            .chars()
+            .map(|c| self.font.advance(self.font.glyph(c)) as u32)
+            .sum();
+        units as f32 * size / self.font.units_per_em as f32
+    }
+
+    /// Add text, wrapped at word boundaries to the page width
+    pub fn line(&mut self, style: TextStyle, text: &str) {
+        let size = style.size();
+        // Bold text is stroked as well as filled
+        let (mode, stroke) = if style.bold() {
+            (2, size * 0.03)
+        } else {
+            (0, 0.0)
+        };
+        let max_width = PAGE_WIDTH - 2.0 * MARGIN;
+        for line in wrap(text, max_width, |line| self.width(line, size)) {
+            let height = size * 1.4;
+            if self.y - height < MARGIN {
+                self.new_page();
+            }
+            self.y -= height;
+            let glyphs = self.encode(&line);
+            self.current.push_str(&format!(
+                "{stroke:.2} w BT /F1 {size} Tf {mode} Tr {MARGIN} {:.1} Td <{glyphs}> Tj ET\n",
+                self.y
+            ));
+        }
+    }
+
+    /// Glyph ids of `text` as hex, recording the glyphs used
+    fn encode(&mut self, text: &str) -> String {
+        let mut hex = String::with_capacity(4 * text.len());
+        for c in text.chars() {
+            let glyph = self.font.glyph(c);
+            if glyph != 0 {
+                self.glyphs.entry(glyph).or_insert(c);
+            }
+            write!(hex, "{glyph:04X}").ok();
+        }
+        hex
+    }
+
+    /// Vertical space, dropped at the top of a page
+    pub fn gap(&mut self, points: f32) {
+        if self.y < PAGE_HEIGHT - MARGIN {
+            self.y -= points;
+        }
+    }
+
+    fn new_page(&mut self) {
+        self.pages.push(std::mem::take(&mut self.current));
+        self.y = PAGE_HEIGHT - MARGIN;
+    }
+
+    /// Serialize the document
+    pub fn finish(mut self) -> Vec<u8> {
+        if !self.current.is_empty() || self.pages.is_empty() {
+            self.new_page();
+        }
+
+        let font = self.font;
+        // PDF glyph space is 1000 units per em
+        let scale = |units: f32| (units * 1000.0 / font.units_per_em as f32).round() as i32;
+        let widths: Vec<String> = self
+            .glyphs
+            .keys()
+            .map(|glyph| format!("{glyph} [{}]", scale(font.advance(*glyph) as f32)))
+            .collect();
+        let [x_min, y_min, x_max, y_max] = font.bbox.map(|v| scale(v as f32));
+        let to_unicode = to_unicode(&self.glyphs);
+        let program = font.subset(&self.glyphs.keys().copied().collect::<BTreeSet<u16>>());
+
+        let mut objects: Vec<Vec<u8>> = vec![
+            b"<< /Type /Catalog /Pages 2 0 R >>".to_vec(),
+            // The page tree, filled in once the page ids are known
+            Vec::new(),
+            format!(
+                "<< /Type /Font /Subtype /Type0 /BaseFont /{FONT_NAME} /Encoding /Identity-H \
+                 /DescendantFonts [4 0 R] /ToUnicode 7 0 R >>"
+            )
+            .into_bytes(),
+            format!(
+                "<< /Type /Font /Subtype /CIDFontType2 /BaseFont /{FONT_NAME} \
+                 /CIDSystemInfo << /Registry (Adobe) /Ordering (Identity) /Supplement 0 >> \
+                 /FontDescriptor 5 0 R /CIDToGIDMap /Identity /DW 1000 /W [{}] >>",
+                widths.join(" ")
+            )
+            .into_bytes(),
+            format!(
+                "<< /Type /FontDescriptor /FontName /{FONT_NAME} /Flags 4 \
+                 /FontBBox [{x_min} {y_min} {x_max} {y_max}] /ItalicAngle 0 /Ascent {} \
+                 /Descent {} /CapHeight {} /StemV 80 /FontFile2 6 0 R >>",
+                scale(font.ascent as f32),
+                scale(font.descent as f32),
+                scale(font.ascent as f32)
+            )
+            .into_bytes(),
+            stream(
+                &format!("/Length {0} /Length1 {0}", program.len()),
+                &program,
+            ),
+            stream(
+                &format!("/Length {}", to_unicode.len()),
+                to_unicode.as_bytes(),
+            ),
+        ];
+        let mut kids = Vec::with_capacity(self.pages.len());
+        for content in &self.pages {
+            let page_id = objects.len() + 1;
+            kids.push(format!("{page_id} 0 R"));
+            objects.push(
+                format!(
+                    "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {PAGE_WIDTH} {PAGE_HEIGHT}] \
+                     /Resources << /Font << /F1 3 0 R >> >> /Contents {} 0 R >>",
+                    page_id + 1
+                )
+                .into_bytes(),
+            );
+            objects.push(stream(
+                &format!("/Length {}", content.len()),
+                content.as_bytes(),
+            ));
+        }
+        objects[1] = format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            kids.len()
+        )
+        .into_bytes();
+
+        // The comment of high bytes marks the file as binary
+        let mut out = b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec();
+        let mut offsets = Vec::with_capacity(objects.len());
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.extend_from_slice(format!("{} 0 obj\n", i + 1).as_bytes());
+            out.extend_from_slice(object);
+            out.extend_from_slice(b"\nendobj\n");
+        }
+        let xref = out.len();
+        out.extend_from_slice(
+            format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes(),
+        );
+        for offset in offsets {
+            out.extend_from_slice(format!("{offset:010} 00000 n \n").as_bytes());
+        }
+        out.extend_from_slice(
+            format!(
+                "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+                objects.len() + 1
+            )
+            .as_bytes(),
+        );
+        out
+    }
+}
+
+/// A stream object with `entries` in its dictionary
+fn stream(entries: &str, data: &[u8]) -> Vec<u8> {
+    let mut object = format!("<< {entries} >>\nstream\n").into_bytes();
+    object.extend_from_slice(data);
+    object.extend_from_slice(b"\nendstream");
+    object
+}
+
+/// CMap from glyph ids back to the characters they show
+fn to_unicode(glyphs: &BTreeMap<u16, char>) -> String {
+    let mut cmap = String::from(
+        "/CIDInit /ProcSet findresource begin\n12 dict begin\nbegincmap\n\
+         /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+         /CMapName /Adobe-Identity-UCS def\n/CMapType 2 def\n\
+         1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n",
+    );
+    let entries: Vec<(&u16, &char)> = glyphs.iter().collect();
+    for block in entries.chunks(CMAP_BLOCK) {
+        writeln!(cmap, "{} beginbfchar", block.len()).ok();
+        for (glyph, c) in block {
+            let utf16: String = c
+                .encode_utf16(&mut [0; 2])
+                .iter()
+                .map(|unit| format!("{unit:04X}"))
+                .collect();
+            writeln!(cmap, "<{glyph:04X}> <{utf16}>").ok();
+        }
+        cmap.push_str("endbfchar\n");
+    }
+    cmap.push_str("endcmap\nCMapName currentdict /CMapResource defineresource pop\nend\nend\n");
+    cmap
+}
+
+/// Split text into lines at most `max_width` wide, breaking words wider
+/// than a line such as Chinese sentences, which have no spaces
+fn wrap(text: &str, max_width: f32, width: impl Fn(&str) -> f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let joined = if line.is_empty() {
+            word.to_owned()
+        } else {
+            format!("{line} {word}")
+        };
+        if width(&joined) <= max_width {
+            line = joined;
+            continue;
+        }
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+        }
+        for c in word.chars() {
+            line.push(c);
+            if width(&line) > max_width && line.chars().count() > 1 {
+                line.pop();
+                lines.push(std::mem::replace(&mut line, c.to_string()));
+            }
+        }
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::font::tests::sample_font;
+    use super::*;
+
+    fn find(haystack: &[u8], needle: &str) -> Option<usize> {
+        haystack
+            .windows(needle.len())
+            .position(|window| window == needle.as_bytes())
+    }
+
+    #[test]
+    fn wraps_by_width() {
+        let chars = |line: &str| line.chars().count() as f32;
+        assert_eq!(wrap("one two three", 7.0, chars), vec!["one two", "three"]);
+        assert_eq!(wrap("abcdefghij", 4.0, chars), vec!["abcd", "efgh", "ij"]);
+        assert_eq!(wrap("a 学习中文", 2.0, chars), vec!["a", "学习", "中文"]);
+        assert_eq!(wrap("", 4.0, chars), vec![""]);
+    }
+
+    #[test]
+    fn embeds_used_glyphs() {
+        let font = sample_font();
+        let mut pdf = TextPdf::new(&font);
+        pdf.line(TextStyle::Heading, "A中");
+        pdf.line(TextStyle::Body, "(Ä)");
+        let bytes = pdf.finish();
+        assert!(find(&bytes, "<00010002> Tj").is_some());
+        assert!(find(&bytes, "<000000030000> Tj").is_some());
+        assert!(find(&bytes, "/Subtype /Type0").is_some());
+        assert!(find(&bytes, "/W [1 [600] 2 [1000] 3 [1000]]").is_some());
+        assert!(
+            find(
+                &bytes,
+                "3 beginbfchar\n<0001> <0041>\n<0002> <4E2D>\n<0003> <00C4>"
+            )
+            .is_some()
+        );
+        assert!(find(&bytes, "/FontFile2 6 0 R").is_some());
+        assert!(find(&bytes, "Helvetica").is_none());
+    }
+
+    #[test]
+    fn cross_reference_points_at_objects() {
+        let font = sample_font();
+        let mut pdf = TextPdf::new(&font);
+        for i in 0..60 {
+            pdf.line(TextStyle::Body, &format!("Line {i}"));
+        }
+        let bytes = pdf.finish();
+        assert!(bytes.starts_with(b"%PDF-1.4\n"));
+        assert!(find(&bytes, "/Count 2").is_some());
+        let xref = find(&bytes, "xref\n").unwrap();
+        let table = String::from_utf8(bytes[xref..].to_vec()).unwrap();
+        let offsets: Vec<usize> = table
+            .lines()
+            .skip(3)
+            .take_while(|line| line.ends_with(" n "))
+            .map(|line| line[..10].parse().unwrap())
+            .collect();
+        assert!(!offsets.is_empty());
+        for (i, offset) in offsets.iter().enumerate() {
+            assert!(bytes[*offset..].starts_with(format!("{} 0 obj", i + 1).as_bytes()));
+        }
+    }
+}
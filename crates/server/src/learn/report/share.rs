@@ -0,0 +1,114 @@
+//! Report links that work without signing in
+//!
+//! A share fixes the days of a report when it is created and is found by the
+//! hash of a random token, like login codes. It stops working once it
+//! expires or is revoked; the report itself is built afresh on every view.
+
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+
+use super::{ProgressReport, ReportPeriod, build};
+use crate::db::schema::*;
+use crate::learn::local_time::{local_date, user_timezone};
+use crate::models::learn::{NewReportShare, ReportShare};
+
+const SHARE_COLUMNS: (
+    learn_report_shares::id,
+    learn_report_shares::user_id,
+    learn_report_shares::from_date,
+    learn_report_shares::to_date,
+    learn_report_shares::expires_at,
+    learn_report_shares::revoked_at,
+    learn_report_shares::created_at,
+) = (
+    learn_report_shares::id,
+    learn_report_shares::user_id,
+    learn_report_shares::from_date,
+    learn_report_shares::to_date,
+    learn_report_shares::expires_at,
+    learn_report_shares::revoked_at,
+    learn_report_shares::created_at,
+);
+
+/// Share the report of `period` for `days`, returning the share and its
+/// token, which is not stored
+pub fn create(
+    conn: &mut PgConnection,
+    user_id: i64,
+    period: ReportPeriod,
+    days: i64,
+    now: DateTime<Utc>,
+) -> QueryResult<(ReportShare, String)> {
+    let tz = user_timezone(conn, user_id)?;
+    let (from_date, to_date) = period.resolve(local_date(tz, now));
+    let token = crate::auth::random_code();
+    let share = diesel::insert_into(learn_report_shares::table)
+        .values(&NewReportShare {
+            user_id,
+            token_hash: crate::auth::hash_code(&token),
+            from_date,
+            to_date,
+            expires_at: now + Duration::days(days),
+        })
+        .returning(SHARE_COLUMNS)
+        .get_result::<ReportShare>(conn)?;
+    Ok((share, token))
+}
+
+/// Shares of a user that still work, newest first
+pub fn list(
+    conn: &mut PgConnection,
+    user_id: i64,
+    now: DateTime<Utc>,
+) -> QueryResult<Vec<ReportShare>> {
+    learn_report_shares::table
+        .filter(learn_report_shares::user_id.eq(user_id))
+        .filter(learn_report_shares::revoked_at.is_null())
+        .filter(learn_report_shares::expires_at.gt(now))
+        .order(learn_report_shares::created_at.desc())
+        .select(SHARE_COLUMNS)
+        .load::<ReportShare>(conn)
+}
+
+/// Stop a share from working; false when the user has no such share
+pub fn revoke(
+    conn: &mut PgConnection,
+    user_id: i64,
+    share_id: i64,
+    now: DateTime<Utc>,
+) -> QueryResult<bool> {
+    let revoked = diesel::update(
+        learn_report_shares::table
+            .filter(learn_report_shares::id.eq(share_id))
+            .filter(learn_report_shares::user_id.eq(user_id))
+            .filter(learn_report_shares::revoked_at.is_null()),
+    )
+    .set(learn_report_shares::revoked_at.eq(now))
+    .execute(conn)?;
+    Ok(revoked > 0)
+}
+
+/// The report behind `token`, `None` when the link is unknown, expired or
+/// revoked
+pub fn open(
+    conn: &mut PgConnection,
+    token: &str,
+    now: DateTime<Utc>,
+) -> QueryResult<Option<ProgressReport>> {
+    let share = learn_report_shares::table
+        .filter(learn_report_shares::token_hash.eq(crate::auth::hash_code(token)))
+        .filter(learn_report_shares::revoked_at.is_null())
+        .filter(learn_report_shares::expires_at.gt(now))
+        .select(SHARE_COLUMNS)
+        .first::<ReportShare>(conn)
+        .optional()?;
+    share
+        .map(|share| {
+            let period = ReportPeriod::Range {
+                from: share.from_date,
+                to: share.to_date,
+            };
+            build(conn, share.user_id, period, now)
+        })
+        .transpose()
+}
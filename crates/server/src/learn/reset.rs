@@ -0,0 +1,314 @@
+//! Resets that can be undone
+//!
+//! A reset moves the user's rows of its tables into a snapshot instead of
+//! deleting them outright. Until the snapshot expires [`restore`] puts the
+//! rows back with their original ids; afterwards [`purge_expired`] drops the
+//! snapshot together with the audio files its rows pointed at, which are kept
+//! on disk in the meantime.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Jsonb};
+use salvo::oapi::ToSchema;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+use crate::db::schema::*;
+use crate::models::ResetSnapshot;
+
+/// What a reset clears
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResetKind {
+    IssueWords,
+    Chats,
+    WritePractices,
+    ReadPractices,
+    Vocabulary,
+    DailyStats,
+    Achievements,
+    Suggestions,
+}
+
+impl ResetKind {
+    pub const ALL: [ResetKind; 8] = [
+        ResetKind::IssueWords,
+        ResetKind::Chats,
+        ResetKind::WritePractices,
+        ResetKind::ReadPractices,
+        ResetKind::Vocabulary,
+        ResetKind::DailyStats,
+        ResetKind::Achievements,
+        ResetKind::Suggestions,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ResetKind::IssueWords => "issue_words",
+            ResetKind::Chats => "chats",
+            ResetKind::WritePractices => "write_practices",
+            ResetKind::ReadPractices => "read_practices",
+            ResetKind::Vocabulary => "vocabulary",
+            ResetKind::DailyStats => "daily_stats",
+            ResetKind::Achievements => "achievements",
+            ResetKind::Suggestions => "suggestions",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.as_str() == value)
+    }
+
+    /// Tables cleared, all keyed by `user_id`; the first is the one reported
+    pub fn tables(self) -> &'static [&'static str] {
+        match self {
+            ResetKind::IssueWords => &["learn_issue_words"],
+            ResetKind::Chats => &["learn_chats", "learn_chat_issues", "learn_chat_turns"],
+            ResetKind::WritePractices => &["learn_write_practices"],
+            ResetKind::ReadPractices => &["learn_read_practices", "learn_read_progress"],
+            ResetKind::Vocabulary => &["learn_vocabularies"],
+            ResetKind::DailyStats => &["learn_daily_stats"],
+            ResetKind::Achievements => &["learn_achievements"],
+            ResetKind::Suggestions => &["learn_suggestions"],
+        }
+    }
+}
+
+/// Columns holding audio paths relative to the space path
+const AUDIO_COLUMNS: &[(&str, &str)] = &[
+    ("learn_chat_turns", "audio_path"),
+    ("learn_read_practices", "user_audio_path"),
+];
+
+/// Everything but the archived rows
+const SNAPSHOT_COLUMNS: (
+    learn_reset_snapshots::id,
+    learn_reset_snapshots::user_id,
+    learn_reset_snapshots::kind,
+    learn_reset_snapshots::counts,
+    learn_reset_snapshots::created_at,
+    learn_reset_snapshots::expires_at,
+) = (
+    learn_reset_snapshots::id,
+    learn_reset_snapshots::user_id,
+    learn_reset_snapshots::kind,
+    learn_reset_snapshots::counts,
+    learn_reset_snapshots::created_at,
+    learn_reset_snapshots::expires_at,
+);
+
+/// Outcome of undoing a reset
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RestoredReset {
+    pub kind: String,
+    /// Rows put back per table
+    pub restored: BTreeMap<String, usize>,
+    /// Rows left out because they clash with rows created since the reset
+    pub skipped: usize,
+}
+
+#[derive(QueryableByName)]
+struct JsonRows {
+    #[diesel(sql_type = Jsonb)]
+    rows: Value,
+}
+
+/// Move the user's rows of a reset into a snapshot restorable for
+/// `restore_days`
+///
+/// Returns `None` when there was nothing to reset.
+pub fn archive(
+    conn: &mut PgConnection,
+    user_id: i64,
+    kind: ResetKind,
+    restore_days: i64,
+    now: DateTime<Utc>,
+) -> QueryResult<Option<ResetSnapshot>> {
+    conn.transaction(|conn| {
+        let mut rows = Map::new();
+        let mut counts = Map::new();
+        let mut total = 0;
+        for table in kind.tables() {
+            let removed = diesel::sql_query(format!(
+                "WITH removed AS (DELETE FROM {table} WHERE user_id = $1 RETURNING *) \
+                 SELECT COALESCE(jsonb_agg(removed), '[]'::jsonb) AS rows FROM removed"
+            ))
+            .bind::<BigInt, _>(user_id)
+            .get_result::<JsonRows>(conn)?
+            .rows;
+            let count = removed.as_array().map_or(0, Vec::len);
+            total += count;
+            counts.insert(table.to_string(), count.into());
+            rows.insert(table.to_string(), removed);
+        }
+        if total == 0 {
+            return Ok(None);
+        }
+        diesel::insert_into(learn_reset_snapshots::table)
+            .values((
+                learn_reset_snapshots::user_id.eq(user_id),
+                learn_reset_snapshots::kind.eq(kind.as_str()),
+                learn_reset_snapshots::rows.eq(Value::Object(rows)),
+                learn_reset_snapshots::counts.eq(Value::Object(counts)),
+                learn_reset_snapshots::created_at.eq(now),
+                learn_reset_snapshots::expires_at.eq(now + Duration::days(restore_days)),
+            ))
+            .returning(SNAPSHOT_COLUMNS)
+            .get_result::<ResetSnapshot>(conn)
+            .map(Some)
+    })
+}
+
+/// Snapshots of a user that can still be restored, newest first
+pub fn list(
+    conn: &mut PgConnection,
+    user_id: i64,
+    now: DateTime<Utc>,
+) -> QueryResult<Vec<ResetSnapshot>> {
+    learn_reset_snapshots::table
+        .filter(learn_reset_snapshots::user_id.eq(user_id))
+        .filter(learn_reset_snapshots::expires_at.gt(now))
+        .order(learn_reset_snapshots::created_at.desc())
+        .select(SNAPSHOT_COLUMNS)
+        .load::<ResetSnapshot>(conn)
+}
+
+/// Put the rows of a snapshot back and drop it
+///
+/// Returns `None` when the snapshot does not exist, belongs to someone else
+/// or has expired.
+pub fn restore(
+    conn: &mut PgConnection,
+    user_id: i64,
+    snapshot_id: i64,
+    now: DateTime<Utc>,
+) -> QueryResult<Option<RestoredReset>> {
+    conn.transaction(|conn| {
+        let snapshot = learn_reset_snapshots::table
+            .filter(learn_reset_snapshots::id.eq(snapshot_id))
+            .filter(learn_reset_snapshots::user_id.eq(user_id))
+            .filter(learn_reset_snapshots::expires_at.gt(now))
+            .select((learn_reset_snapshots::kind, learn_reset_snapshots::rows))
+            .for_update()
+            .first::<(String, Value)>(conn)
+            .optional()?;
+        let Some((kind, rows)) = snapshot else {
+            return Ok(None);
+        };
+        let kind = ResetKind::parse(&kind).ok_or(diesel::result::Error::NotFound)?;
+
+        let mut restored = BTreeMap::new();
+        let mut skipped = 0;
+        // Table names come from the kind, never from the stored JSON
+        for table in kind.tables() {
+            let Some(table_rows) = rows.get(*table) else {
+                continue;
+            };
+            let archived = table_rows.as_array().map_or(0, Vec::len);
+            let inserted = diesel::sql_query(format!(
+                "INSERT INTO {table} SELECT * FROM jsonb_populate_recordset(NULL::{table}, $1) \
+                 ON CONFLICT DO NOTHING"
+            ))
+            .bind::<Jsonb, _>(table_rows)
+            .execute(conn)?;
+            skipped += archived.saturating_sub(inserted);
+            restored.insert(table.to_string(), inserted);
+        }
+        diesel::delete(learn_reset_snapshots::table.find(snapshot_id)).execute(conn)?;
+        Ok(Some(RestoredReset {
+            kind: kind.as_str().to_owned(),
+            restored,
+            skipped,
+        }))
+    })
+}
+
+/// Drop the snapshots past their restore window and their audio files
+///
+/// Returns the number of snapshots dropped.
+pub fn purge_expired(
+    conn: &mut PgConnection,
+    space_path: &Path,
+    now: DateTime<Utc>,
+) -> QueryResult<usize> {
+    let expired = learn_reset_snapshots::table
+        .filter(learn_reset_snapshots::expires_at.le(now))
+        .select(learn_reset_snapshots::id)
+        .load::<i64>(conn)?;
+    let mut purged = 0;
+    for id in expired {
+        // One at a time, snapshots can be large
+        let rows = diesel::delete(learn_reset_snapshots::table.find(id))
+            .returning(learn_reset_snapshots::rows)
+            .get_result::<Value>(conn)
+            .optional()?;
+        let Some(rows) = rows else {
+            continue;
+        };
+        // The rows are gone for good, so nothing points at these files anymore
+        for path in audio_paths(&rows) {
+            if let Err(e) = fs::remove_file(space_path.join(path))
+                && e.kind() != std::io::ErrorKind::NotFound
+            {
+                tracing::warn!(
+                    "Failed to remove audio {} of reset snapshot {}: {}",
+                    path,
+                    id,
+                    e
+                );
+            }
+        }
+        purged += 1;
+    }
+    Ok(purged)
+}
+
+/// Audio files referenced by archived rows
+fn audio_paths(rows: &Value) -> Vec<&str> {
+    AUDIO_COLUMNS
+        .iter()
+        .filter_map(|(table, column)| Some((rows.get(*table)?.as_array()?, *column)))
+        .flat_map(|(table_rows, column)| {
+            table_rows
+                .iter()
+                .filter_map(move |row| row.get(column)?.as_str())
+        })
+        // Stored paths are generated by the server, but never leave the audio tree
+        .filter(|path| path.starts_with("learn/audios/") && !path.contains(".."))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn kinds_round_trip() {
+        for kind in ResetKind::ALL {
+            assert_eq!(ResetKind::parse(kind.as_str()), Some(kind));
+        }
+        assert_eq!(ResetKind::parse("users"), None);
+    }
+
+    #[test]
+    fn collects_audio_of_archived_rows() {
+        let rows = json!({
+            "learn_chats": [{"id": 1, "title": "learn/audios/1/not_audio.mp3"}],
+            "learn_chat_turns": [
+                {"id": 2, "audio_path": "learn/audios/1/user_1.webm"},
+                {"id": 3, "audio_path": null},
+                {"id": 4, "audio_path": "learn/audios/1/../../../etc/passwd"},
+            ],
+            "learn_read_practices": [{"id": 5, "user_audio_path": "learn/audios/1/read_2.wav"}],
+        });
+        assert_eq!(
+            audio_paths(&rows),
+            ["learn/audios/1/user_1.webm", "learn/audios/1/read_2.wav"]
+        );
+    }
+}
@@ -0,0 +1,325 @@
+//! English words for a Chinese phrase
+//!
+//! The Chinese side of the dictionary is the zh translations of words and
+//! definitions plus the definitions written in Chinese. Rows sharing at least
+//! two consecutive characters with the query are fetched through an index of
+//! their one and two character substrings (see the `dict_chinese_bigrams`
+//! migration; trigram indexes cannot serve patterns that short). Their text
+//! is cut into senses ("行程表；日程" holds two), and those senses double as
+//! the lexicon the query is segmented with: "旅行行程表" becomes "旅行" and
+//! "行程表" when both are senses somewhere. A sense scores by how much of the
+//! query it covers and how little else it says, so "行程表" ranks above
+//! "旅行行程表" for the query "行程表", and above "行程" which only covers part
+//! of it. Frequent words win ties.
+
+use std::collections::{HashMap, HashSet};
+
+use diesel::prelude::*;
+use diesel::sql_types::{Array, Int8, Nullable, Text};
+use salvo::oapi::ToSchema;
+use serde::Serialize;
+
+use crate::db::schema::*;
+use crate::services::language::Script;
+
+/// Longest word the segmenter tries, in characters
+const MAX_WORD_CHARS: usize = 8;
+
+/// Rows fetched per source before scoring, see the ranking in [`lookup`]
+const CANDIDATES: i64 = 500;
+
+/// An English word whose Chinese sense matches the query
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ReverseMatch {
+    pub word_id: i64,
+    pub word: String,
+    pub part_of_speech: Option<String>,
+    /// Chinese sense that matched
+    pub sense: String,
+    /// English definition the sense translates, when it came from one
+    pub definition: Option<String>,
+    /// 0-1, how well the sense matches the query
+    pub score: f32,
+    /// 0-100, higher is more common
+    pub frequency: Option<i16>,
+}
+
+#[derive(QueryableByName)]
+struct Candidate {
+    #[diesel(sql_type = Int8)]
+    word_id: i64,
+    #[diesel(sql_type = Text)]
+    text: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    definition: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    part_of_speech: Option<String>,
+}
+
+fn is_han(c: char) -> bool {
+    Script::Han.contains(c)
+}
+
+/// Runs of Chinese characters in `text`, anything else separates them
+fn han_runs(text: &str) -> Vec<String> {
+    text.split(|c: char| !is_han(c))
+        .filter(|run| !run.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Substrings a row must contain one of to be worth scoring, as indexed by
+/// `dict_bigrams`
+///
+/// Every bigram of the runs, so any text containing a word of the query is
+/// found; a run of one character is searched as is.
+fn grams(runs: &[String]) -> Vec<String> {
+    let mut grams: Vec<String> = Vec::new();
+    for run in runs {
+        let chars: Vec<char> = run.chars().collect();
+        let run_grams: Vec<String> = if chars.len() < 2 {
+            vec![run.clone()]
+        } else {
+            chars.windows(2).map(|pair| pair.iter().collect()).collect()
+        };
+        for gram in run_grams {
+            if !grams.contains(&gram) {
+                grams.push(gram);
+            }
+        }
+    }
+    grams
+}
+
+/// Senses of a translation or definition, without parenthesized notes
+fn senses(text: &str) -> Vec<String> {
+    let mut plain = String::with_capacity(text.len());
+    let mut depth = 0usize;
+    for c in text.chars() {
+        match c {
+            '(' | '（' | '[' | '【' => depth += 1,
+            ')' | '）' | ']' | '】' => depth = depth.saturating_sub(1),
+            _ if depth == 0 => plain.push(c),
+            _ => {}
+        }
+    }
+    plain
+        .split(['；', ';', '，', ',', '、', '/', '|', '。'])
+        .map(str::trim)
+        .filter(|sense| !sense.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Forward maximum matching of a run against known words
+///
+/// Characters starting no known word are kept one by one.
+fn segment(run: &str, known: impl Fn(&str) -> bool) -> Vec<String> {
+    let chars: Vec<char> = run.chars().collect();
+    let mut words = Vec::new();
+    let mut start = 0;
+    while start < chars.len() {
+        let end = (start + 2..=chars.len().min(start + MAX_WORD_CHARS))
+            .rev()
+            .find(|&end| known(&chars[start..end].iter().collect::<String>()))
+            .unwrap_or(start + 1);
+        words.push(chars[start..end].iter().collect());
+        start = end;
+    }
+    words
+}
+
+/// Terms a sense is compared with: the runs, then the known words they are
+/// made of, so a sense matching part of the query still counts
+fn terms(runs: &[String], known: &HashSet<String>) -> Vec<String> {
+    let mut terms: Vec<String> = runs.to_vec();
+    for run in runs {
+        for word in segment(run, |word| word != run && known.contains(word)) {
+            if known.contains(&word) && !terms.contains(&word) {
+                terms.push(word);
+            }
+        }
+    }
+    terms
+}
+
+/// How well `sense` matches a query of `query_chars` characters, 0-1
+///
+/// The best term the sense contains scores its share of the query, scaled
+/// between half and all of it by the share of the sense the term fills.
+fn score(sense: &str, terms: &[String], query_chars: usize) -> f32 {
+    let sense_chars = sense.chars().count();
+    terms
+        .iter()
+        .filter(|term| sense.contains(term.as_str()))
+        .map(|term| {
+            let term_chars = term.chars().count();
+            let coverage = term_chars as f32 / query_chars as f32;
+            let precision = term_chars as f32 / sense_chars as f32;
+            coverage * (0.5 + 0.5 * precision)
+        })
+        .fold(0.0, f32::max)
+}
+
+/// English words whose Chinese senses match `query`, best first
+pub fn lookup(
+    conn: &mut PgConnection,
+    query: &str,
+    limit: usize,
+) -> QueryResult<Vec<ReverseMatch>> {
+    let runs = han_runs(query);
+    if runs.is_empty() {
+        return Ok(Vec::new());
+    }
+    // Rows sharing more of the query's grams cover more of it, shorter ones
+    // say less besides; ranked here so the limit keeps the best
+    let rank = |text: &str| {
+        format!(
+            "(SELECT count(*) FROM unnest($1::text[]) AS g WHERE strpos({text}, g) > 0) DESC, \
+             length({text})"
+        )
+    };
+    let candidates = diesel::sql_query(format!(
+        "(SELECT t.origin_id AS word_id, t.translation AS text, \
+                 NULL::text AS definition, NULL::text AS part_of_speech \
+            FROM dict_translations t \
+           WHERE t.origin_entity = 'word' AND t.language LIKE 'zh%' \
+             AND dict_bigrams(t.translation) && $1 \
+           ORDER BY {} LIMIT $2) \
+         UNION ALL \
+         (SELECT d.word_id, t.translation, d.definition, d.part_of_speech \
+            FROM dict_translations t JOIN dict_definitions d ON d.id = t.origin_id \
+           WHERE t.origin_entity = 'definition' AND t.language LIKE 'zh%' \
+             AND dict_bigrams(t.translation) && $1 \
+           ORDER BY {} LIMIT $2) \
+         UNION ALL \
+         (SELECT d.word_id, d.definition, NULL, d.part_of_speech \
+            FROM dict_definitions d \
+           WHERE d.language LIKE 'zh%' AND dict_bigrams(d.definition) && $1 \
+           ORDER BY {} LIMIT $2)",
+        rank("t.translation"),
+        rank("t.translation"),
+        rank("d.definition"),
+    ))
+    .bind::<Array<Text>, _>(grams(&runs))
+    .bind::<Int8, _>(CANDIDATES)
+    .load::<Candidate>(conn)?;
+
+    let candidates: Vec<(Candidate, Vec<String>)> = candidates
+        .into_iter()
+        .map(|candidate| {
+            let senses = senses(&candidate.text);
+            (candidate, senses)
+        })
+        .collect();
+    let known: HashSet<String> = candidates
+        .iter()
+        .flat_map(|(_, senses)| senses.iter().cloned())
+        .collect();
+    let terms = terms(&runs, &known);
+    let query_chars: usize = runs.iter().map(|run| run.chars().count()).sum();
+
+    // Best sense of each word
+    let mut best: HashMap<i64, (f32, Candidate, String)> = HashMap::new();
+    for (candidate, senses) in candidates {
+        let Some((score, sense)) = senses
+            .into_iter()
+            .map(|sense| (score(&sense, &terms, query_chars), sense))
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+        else {
+            continue;
+        };
+        if score <= 0.0 {
+            continue;
+        }
+        let better = best
+            .get(&candidate.word_id)
+            .is_none_or(|(known, _, _)| score > *known);
+        if better {
+            best.insert(candidate.word_id, (score, candidate, sense));
+        }
+    }
+
+    let word_ids: Vec<i64> = best.keys().copied().collect();
+    let words: HashMap<i64, (String, Option<String>, Option<i16>)> = dict_words::table
+        .filter(dict_words::id.eq_any(&word_ids))
+        .filter(
+            dict_words::is_active
+                .eq(true)
+                .or(dict_words::is_active.is_null()),
+        )
+        .select((
+            dict_words::id,
+            dict_words::word,
+            dict_words::word_type,
+            dict_words::frequency,
+        ))
+        .load::<(i64, String, Option<String>, Option<i16>)>(conn)?
+        .into_iter()
+        .map(|(id, word, word_type, frequency)| (id, (word, word_type, frequency)))
+        .collect();
+
+    let mut matches: Vec<ReverseMatch> = best
+        .into_iter()
+        .filter_map(|(word_id, (score, candidate, sense))| {
+            let (word, word_type, frequency) = words.get(&word_id)?.clone();
+            Some(ReverseMatch {
+                word_id,
+                word,
+                part_of_speech: candidate.part_of_speech.or(word_type),
+                sense,
+                definition: candidate.definition,
+                score,
+                frequency,
+            })
+        })
+        .collect();
+    // Up to a tenth of a point for the most frequent words
+    let ranking =
+        |m: &ReverseMatch| m.score + f32::from(m.frequency.unwrap_or(0).clamp(0, 100)) / 1000.0;
+    matches.sort_by(|a, b| {
+        ranking(b)
+            .total_cmp(&ranking(a))
+            .then_with(|| a.word.cmp(&b.word))
+    });
+    matches.truncate(limit);
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn known(words: &[&str]) -> HashSet<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn splits_senses() {
+        assert_eq!(senses("行程表；日程"), ["行程表", "日程"]);
+        assert_eq!(senses("（美）假期, 休假"), ["假期", "休假"]);
+    }
+
+    #[test]
+    fn segments_longest_known_words() {
+        let known = known(&["旅行", "行程", "行程表"]);
+        let known = |word: &str| known.contains(word);
+        assert_eq!(segment("旅行程表", known), ["旅行", "程", "表"]);
+        assert_eq!(segment("我的行程表", known), ["我", "的", "行程表"]);
+        assert_eq!(segment("旅行行程表", known), ["旅行", "行程表"]);
+        assert_eq!(han_runs("行程 表abc!"), ["行程", "表"]);
+        assert_eq!(grams(&han_runs("行程表 书 程表")), ["行程", "程表", "书"]);
+    }
+
+    #[test]
+    fn exact_senses_rank_first() {
+        let runs = han_runs("行程表");
+        let terms = terms(&runs, &known(&["行程", "行程表", "旅行行程表"]));
+        let exact = score("行程表", &terms, 3);
+        let longer = score("旅行行程表", &terms, 3);
+        let partial = score("行程", &terms, 3);
+        assert_eq!(exact, 1.0);
+        assert!(exact > longer && longer > partial && partial > 0.0);
+        assert_eq!(score("日程", &terms, 3), 0.0);
+    }
+}
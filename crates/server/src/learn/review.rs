@@ -0,0 +1,155 @@
+//! Apply review grades to a user's vocabulary and issue words
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use salvo::oapi::ToSchema;
+use serde::{Deserialize, Serialize};
+
+use super::scheduler::{ReviewGrade, ReviewState};
+use super::stats::{self, Activity};
+use crate::config::SchedulerConfig;
+use crate::db::schema::*;
+use crate::models::learn::{IssueWord, NewReviewLog, UserVocabulary};
+
+/// Kind of item that can be scheduled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewItemType {
+    Vocabulary,
+    IssueWord,
+}
+
+impl ReviewItemType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReviewItemType::Vocabulary => "vocabulary",
+            ReviewItemType::IssueWord => "issue_word",
+        }
+    }
+}
+
+/// Schedule of an item after a review
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ReviewResult {
+    pub item_type: ReviewItemType,
+    pub item_id: i64,
+    pub grade: ReviewGrade,
+    /// 1 (new) to 5 (mastered)
+    pub mastery_level: i32,
+    pub ease_factor: f32,
+    pub review_interval_days: i32,
+    pub next_review_at: DateTime<Utc>,
+}
+
+/// Grade one item of `user_id`, update its schedule, log the review and count
+/// it in the daily statistics
+///
+/// Returns `NotFound` if the item does not exist or belongs to someone else.
+pub fn grade_item(
+    conn: &mut PgConnection,
+    user_id: i64,
+    item_type: ReviewItemType,
+    item_id: i64,
+    grade: ReviewGrade,
+    now: DateTime<Utc>,
+    config: &SchedulerConfig,
+) -> QueryResult<ReviewResult> {
+    conn.transaction(|conn| {
+        let correct = i32::from(grade.is_correct());
+        let (before, outcome, due) = match item_type {
+            ReviewItemType::Vocabulary => {
+                let item = learn_vocabularies::table
+                    .filter(learn_vocabularies::id.eq(item_id))
+                    .filter(learn_vocabularies::user_id.eq(user_id))
+                    .for_update()
+                    .first::<UserVocabulary>(conn)?;
+                let due = item.next_review_at.is_none_or(|at| at <= now);
+                let before = ReviewState {
+                    interval_days: item.review_interval_days,
+                    ease: item.ease_factor,
+                };
+                let outcome = before.review(grade, now, config);
+
+                diesel::update(learn_vocabularies::table.find(item.id))
+                    .set((
+                        learn_vocabularies::review_interval_days.eq(outcome.interval_days),
+                        learn_vocabularies::ease_factor.eq(outcome.ease),
+                        learn_vocabularies::mastery_level.eq(Some(outcome.mastery_level)),
+                        learn_vocabularies::next_review_at.eq(Some(outcome.next_review_at)),
+                        learn_vocabularies::last_practiced_at.eq(Some(now)),
+                        learn_vocabularies::practice_count
+                            .eq(Some(item.practice_count.unwrap_or(0) + 1)),
+                        learn_vocabularies::correct_count
+                            .eq(Some(item.correct_count.unwrap_or(0) + correct)),
+                    ))
+                    .execute(conn)?;
+                (before, outcome, due)
+            }
+            ReviewItemType::IssueWord => {
+                let item = learn_issue_words::table
+                    .filter(learn_issue_words::id.eq(item_id))
+                    .filter(learn_issue_words::user_id.eq(user_id))
+                    .for_update()
+                    .first::<IssueWord>(conn)?;
+                let due = item.next_review_at.is_none_or(|at| at <= now);
+                // Seed data sets an interval on words that were never reviewed
+                let interval_days = if item.pick_count > 0 {
+                    item.review_interval_days.unwrap_or(0)
+                } else {
+                    0
+                };
+                let before = ReviewState {
+                    interval_days,
+                    ease: item.ease_factor,
+                };
+                let outcome = before.review(grade, now, config);
+
+                diesel::update(learn_issue_words::table.find(item.id))
+                    .set((
+                        learn_issue_words::review_interval_days.eq(Some(outcome.interval_days)),
+                        learn_issue_words::ease_factor.eq(outcome.ease),
+                        learn_issue_words::mastery_level.eq(outcome.mastery_level),
+                        learn_issue_words::next_review_at.eq(Some(outcome.next_review_at)),
+                        learn_issue_words::last_picked_at.eq(Some(now)),
+                        learn_issue_words::pick_count.eq(item.pick_count + 1),
+                        learn_issue_words::correct_count.eq(item.correct_count + correct),
+                    ))
+                    .execute(conn)?;
+                (before, outcome, due)
+            }
+        };
+
+        diesel::insert_into(learn_review_logs::table)
+            .values(&NewReviewLog {
+                user_id,
+                item_type: item_type.as_str().to_owned(),
+                item_id,
+                grade: grade.as_str().to_owned(),
+                interval_before: before.interval_days,
+                interval_after: outcome.interval_days,
+                ease_after: outcome.ease,
+            })
+            .execute(conn)?;
+        stats::record_activity(
+            conn,
+            user_id,
+            Activity::Review {
+                item_type,
+                item_id,
+                correct: grade.is_correct(),
+                due,
+            },
+            now,
+        )?;
+
+        Ok(ReviewResult {
+            item_type,
+            item_id,
+            grade,
+            mastery_level: outcome.mastery_level,
+            ease_factor: outcome.ease,
+            review_interval_days: outcome.interval_days,
+            next_review_at: outcome.next_review_at,
+        })
+    })
+}
@@ -0,0 +1,252 @@
+//! Spaced-repetition scheduling (SM-2 variant as used by Anki)
+//!
+//! An item is described by its current interval in days (0 for new or
+//! forgotten items) and its ease factor. Each review grade produces the next
+//! interval, ease, mastery level and due date.
+
+use chrono::{DateTime, Duration, Utc};
+use salvo::oapi::ToSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::config::SchedulerConfig;
+
+/// How well the learner remembered an item
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewGrade {
+    /// Forgotten, show again soon
+    Again,
+    /// Remembered with serious difficulty
+    Hard,
+    /// Remembered after some hesitation
+    Good,
+    /// Remembered instantly
+    Easy,
+}
+
+impl ReviewGrade {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ReviewGrade::Again => "again",
+            ReviewGrade::Hard => "hard",
+            ReviewGrade::Good => "good",
+            ReviewGrade::Easy => "easy",
+        }
+    }
+
+    /// Whether the answer counts as correct
+    pub fn is_correct(self) -> bool {
+        self != ReviewGrade::Again
+    }
+}
+
+/// Scheduling state of a single item
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReviewState {
+    /// Current interval, 0 for new or forgotten items
+    pub interval_days: i32,
+    pub ease: f32,
+}
+
+/// Result of grading an item
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReviewOutcome {
+    pub interval_days: i32,
+    pub ease: f32,
+    /// 1 (new) to 5 (mastered), derived from the interval
+    pub mastery_level: i32,
+    pub next_review_at: DateTime<Utc>,
+}
+
+impl ReviewState {
+    /// State of an item that was never reviewed
+    pub fn new_item(config: &SchedulerConfig) -> Self {
+        Self {
+            interval_days: 0,
+            ease: config.initial_ease,
+        }
+    }
+
+    /// Apply a review grade at `now`
+    pub fn review(
+        self,
+        grade: ReviewGrade,
+        now: DateTime<Utc>,
+        config: &SchedulerConfig,
+    ) -> ReviewOutcome {
+        let ease = match grade {
+            ReviewGrade::Again => self.ease - config.again_ease_penalty,
+            ReviewGrade::Hard => self.ease - config.hard_ease_penalty,
+            ReviewGrade::Good => self.ease,
+            ReviewGrade::Easy => self.ease + config.easy_ease_bonus,
+        }
+        .max(config.min_ease);
+
+        let interval_days = if grade == ReviewGrade::Again {
+            0
+        } else if self.interval_days <= 0 {
+            // New or relearning item graduates to a fixed first interval
+            match grade {
+                ReviewGrade::Easy => config.easy_interval_days,
+                _ => config.graduating_interval_days,
+            }
+        } else {
+            let current = self.interval_days as f32;
+            let next = match grade {
+                ReviewGrade::Hard => current * config.hard_interval_factor,
+                ReviewGrade::Good => current * self.ease,
+                _ => current * self.ease * config.easy_bonus,
+            } * config.interval_modifier;
+            // Remembering an item always pushes it at least one day further out
+            (next.round() as i32).max(self.interval_days + 1)
+        }
+        .clamp(0, config.max_interval_days);
+
+        let next_review_at = if interval_days == 0 {
+            now + Duration::minutes(config.again_delay_minutes)
+        } else {
+            now + Duration::days(interval_days as i64)
+        };
+
+        ReviewOutcome {
+            interval_days,
+            ease,
+            mastery_level: mastery_level(interval_days),
+            next_review_at,
+        }
+    }
+}
+
+/// Map an interval to the 1-5 mastery scale stored on items
+pub fn mastery_level(interval_days: i32) -> i32 {
+    match interval_days {
+        i32::MIN..=0 => 1,
+        1..=2 => 2,
+        3..=9 => 3,
+        10..=29 => 4,
+        _ => 5,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-01-01T08:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn state(interval_days: i32, ease: f32) -> ReviewState {
+        ReviewState {
+            interval_days,
+            ease,
+        }
+    }
+
+    #[test]
+    fn new_item_graduates() {
+        let config = SchedulerConfig::default();
+        let item = ReviewState::new_item(&config);
+
+        let good = item.review(ReviewGrade::Good, now(), &config);
+        assert_eq!(good.interval_days, 1);
+        assert_eq!(good.ease, 2.5);
+        assert_eq!(good.next_review_at, now() + Duration::days(1));
+
+        let easy = item.review(ReviewGrade::Easy, now(), &config);
+        assert_eq!(easy.interval_days, 4);
+        assert!((easy.ease - 2.65).abs() < 1e-6);
+
+        let hard = item.review(ReviewGrade::Hard, now(), &config);
+        assert_eq!(hard.interval_days, 1);
+        assert!((hard.ease - 2.35).abs() < 1e-6);
+    }
+
+    #[test]
+    fn again_resets_interval_and_lowers_ease() {
+        let config = SchedulerConfig::default();
+        let outcome = state(30, 2.5).review(ReviewGrade::Again, now(), &config);
+        assert_eq!(outcome.interval_days, 0);
+        assert!((outcome.ease - 2.3).abs() < 1e-6);
+        assert_eq!(outcome.mastery_level, 1);
+        assert_eq!(outcome.next_review_at, now() + Duration::minutes(10));
+    }
+
+    #[test]
+    fn intervals_grow_by_ease() {
+        let config = SchedulerConfig::default();
+        let item = state(10, 2.5);
+
+        assert_eq!(
+            item.review(ReviewGrade::Good, now(), &config).interval_days,
+            25
+        );
+        assert_eq!(
+            item.review(ReviewGrade::Hard, now(), &config).interval_days,
+            12
+        );
+        // 10 * 2.5 * 1.3 = 32.5
+        assert_eq!(
+            item.review(ReviewGrade::Easy, now(), &config).interval_days,
+            33
+        );
+    }
+
+    #[test]
+    fn hard_always_makes_progress() {
+        let config = SchedulerConfig::default();
+        // 1 * 1.2 rounds back to 1
+        let outcome = state(1, 2.5).review(ReviewGrade::Hard, now(), &config);
+        assert_eq!(outcome.interval_days, 2);
+    }
+
+    #[test]
+    fn ease_is_floored() {
+        let config = SchedulerConfig::default();
+        let outcome = state(5, 1.35).review(ReviewGrade::Again, now(), &config);
+        assert_eq!(outcome.ease, config.min_ease);
+    }
+
+    #[test]
+    fn interval_is_capped() {
+        let config = SchedulerConfig::default();
+        let outcome = state(300, 2.5).review(ReviewGrade::Easy, now(), &config);
+        assert_eq!(outcome.interval_days, config.max_interval_days);
+        assert_eq!(outcome.mastery_level, 5);
+    }
+
+    #[test]
+    fn interval_modifier_scales_reviews() {
+        let config = SchedulerConfig {
+            interval_modifier: 0.8,
+            ..Default::default()
+        };
+        let outcome = state(10, 2.5).review(ReviewGrade::Good, now(), &config);
+        assert_eq!(outcome.interval_days, 20);
+    }
+
+    #[test]
+    fn mastery_follows_interval() {
+        assert_eq!(mastery_level(0), 1);
+        assert_eq!(mastery_level(1), 2);
+        assert_eq!(mastery_level(3), 3);
+        assert_eq!(mastery_level(10), 4);
+        assert_eq!(mastery_level(30), 5);
+    }
+
+    #[test]
+    fn repeated_good_answers() {
+        let config = SchedulerConfig::default();
+        let mut item = ReviewState::new_item(&config);
+        let mut intervals = vec![];
+        for _ in 0..4 {
+            let outcome = item.review(ReviewGrade::Good, now(), &config);
+            intervals.push(outcome.interval_days);
+            item = state(outcome.interval_days, outcome.ease);
+        }
+        // 1 -> 2.5 rounds to 3 -> 7.5 rounds to 8 -> 20
+        assert_eq!(intervals, vec![1, 3, 8, 20]);
+    }
+}
@@ -0,0 +1,1037 @@
+//! Daily review sessions
+//!
+//! A session mixes due vocabulary, due issue words and recent chat issues.
+//! Every item gets an exercise type based on what the dictionary has for the
+//! word and how well the learner knows it. Answers are checked here and the
+//! resulting grades go back to the scheduler.
+
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use diesel::dsl::{max, min};
+use diesel::prelude::*;
+use rand::seq::{IndexedRandom, SliceRandom};
+use regex::RegexBuilder;
+use salvo::oapi::ToSchema;
+use serde::{Deserialize, Serialize};
+
+use super::achievement::{self, AchievementEvent};
+use super::quiz::{SAMPLE_POOL, sample};
+use super::review::{ReviewItemType, ReviewResult, grade_item};
+use super::scheduler::ReviewGrade;
+use super::stats::{self, Activity};
+use crate::config::SchedulerConfig;
+use crate::db::schema::*;
+use crate::models::achievement::AchievementBadge;
+use crate::models::dict::Pronunciation;
+use crate::models::learn::*;
+
+/// Chat issues from this many days back are offered for review
+const CHAT_ISSUE_LOOKBACK_DAYS: i64 = 7;
+/// Options shown in recognition exercises, including the right one
+const CHOICE_COUNT: usize = 4;
+/// Example sentences kept per word when looking for a cloze
+const SENTENCES_PER_WORD: usize = 3;
+const BLANK: &str = "_____";
+
+/// Source of a session item
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SessionItemType {
+    Vocabulary,
+    IssueWord,
+    ChatIssue,
+}
+
+impl SessionItemType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SessionItemType::Vocabulary => "vocabulary",
+            SessionItemType::IssueWord => "issue_word",
+            SessionItemType::ChatIssue => "chat_issue",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "vocabulary" => Some(SessionItemType::Vocabulary),
+            "issue_word" => Some(SessionItemType::IssueWord),
+            "chat_issue" => Some(SessionItemType::ChatIssue),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExerciseType {
+    /// Pick the meaning of the shown word
+    Recognition,
+    /// Type the word for the shown meaning, or the correction of a chat mistake
+    Recall,
+    /// Fill the word into an example sentence
+    Cloze,
+    /// Type the word after hearing it
+    Listening,
+}
+
+impl ExerciseType {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ExerciseType::Recognition => "recognition",
+            ExerciseType::Recall => "recall",
+            ExerciseType::Cloze => "cloze",
+            ExerciseType::Listening => "listening",
+        }
+    }
+}
+
+/// Session item as shown to the learner
+///
+/// The expected answer is only revealed once the item is answered.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SessionItemView {
+    pub id: i64,
+    pub position: i32,
+    /// vocabulary | issue_word | chat_issue
+    pub item_type: String,
+    pub item_id: i64,
+    /// recognition | recall | cloze | listening
+    pub exercise_type: String,
+    /// Word (recognition), meaning (recall), sentence with a blank (cloze),
+    /// empty for listening
+    pub prompt: String,
+    pub hint: Option<String>,
+    /// Options for recognition exercises
+    pub choices: Option<Vec<String>>,
+    /// Audio to play for listening exercises
+    pub audio_url: Option<String>,
+    pub answered: bool,
+    pub user_answer: Option<String>,
+    pub is_correct: Option<bool>,
+    pub grade: Option<String>,
+    pub expected_answer: Option<String>,
+}
+
+impl From<ReviewSessionItem> for SessionItemView {
+    fn from(item: ReviewSessionItem) -> Self {
+        let answered = item.answered_at.is_some();
+        Self {
+            id: item.id,
+            position: item.position,
+            item_type: item.item_type,
+            item_id: item.item_id,
+            exercise_type: item.exercise_type,
+            prompt: item.prompt,
+            hint: item.hint,
+            choices: item
+                .choices
+                .and_then(|choices| serde_json::from_value(choices).ok()),
+            audio_url: item.audio_url,
+            answered,
+            user_answer: item.user_answer,
+            is_correct: item.is_correct,
+            grade: item.grade,
+            expected_answer: answered.then_some(item.expected_answer),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SessionView {
+    pub session: ReviewSession,
+    pub items: Vec<SessionItemView>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct AnswerResult {
+    pub item: SessionItemView,
+    /// New schedule of the reviewed word, absent if it no longer exists or
+    /// the item was already answered before
+    pub review: Option<ReviewResult>,
+    pub session: ReviewSession,
+    /// Achievements unlocked by this answer
+    pub unlocked: Vec<AchievementBadge>,
+}
+
+/// Something due for review, before an exercise is chosen for it
+struct Candidate {
+    item_type: SessionItemType,
+    item_id: i64,
+    word: String,
+    /// Chinese translation, vocabulary only
+    meaning: Option<String>,
+    mastery: i32,
+    /// Chat issues: the mistake to correct
+    correction: Option<Correction>,
+}
+
+struct Correction {
+    original: String,
+    suggested: String,
+    description: Option<String>,
+}
+
+/// What the dictionary knows about a word
+#[derive(Default)]
+struct WordMaterial {
+    /// Primary English definition
+    definition: Option<String>,
+    sentences: Vec<String>,
+    audio_url: Option<String>,
+}
+
+struct Exercise {
+    exercise_type: ExerciseType,
+    prompt: String,
+    hint: Option<String>,
+    choices: Option<Vec<String>>,
+    audio_url: Option<String>,
+    expected_answer: String,
+}
+
+/// Return today's active session of the user, or generate one with up to `size` items
+///
+/// `None` when nothing is due; no session is stored then.
+pub fn get_or_create_session(
+    conn: &mut PgConnection,
+    user_id: i64,
+    size: i64,
+    today: NaiveDate,
+    now: DateTime<Utc>,
+) -> QueryResult<Option<SessionView>> {
+    if let Some(session) = active_session(conn, user_id, today)? {
+        return load_session_view(conn, session).map(Some);
+    }
+
+    let candidates = load_candidates(conn, user_id, size, now)?;
+    let words: Vec<String> = candidates
+        .iter()
+        .filter(|c| c.correction.is_none())
+        .map(|c| c.word.clone())
+        .collect();
+    let materials = load_materials(conn, &words)?;
+    let mut rng = rand::rng();
+    let distractors = load_distractors(conn, user_id, &candidates, &materials, &mut rng)?;
+
+    let exercises: Vec<(Candidate, Exercise)> = candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            let material = materials.get(&candidate.word);
+            let exercise = choose_exercise(&candidate, material, &distractors, &mut rng)?;
+            Some((candidate, exercise))
+        })
+        .collect();
+    if exercises.is_empty() {
+        return Ok(None);
+    }
+
+    conn.transaction(|conn| {
+        // A concurrent request may have created the session meanwhile, the
+        // unique index on active sessions turns this insert into a no-op then
+        let inserted = diesel::insert_into(learn_review_sessions::table)
+            .values(&NewReviewSession {
+                user_id,
+                session_date: today,
+                item_count: exercises.len() as i32,
+            })
+            .on_conflict_do_nothing()
+            .get_result::<ReviewSession>(conn)
+            .optional()?;
+        let Some(session) = inserted else {
+            return active_session(conn, user_id, today)?
+                .map(|session| load_session_view(conn, session))
+                .transpose();
+        };
+
+        let items: Vec<NewReviewSessionItem> = exercises
+            .into_iter()
+            .enumerate()
+            .map(|(index, (candidate, exercise))| NewReviewSessionItem {
+                session_id: session.id,
+                user_id,
+                position: index as i32 + 1,
+                item_type: candidate.item_type.as_str().to_owned(),
+                item_id: candidate.item_id,
+                exercise_type: exercise.exercise_type.as_str().to_owned(),
+                word: candidate.word,
+                prompt: exercise.prompt,
+                hint: exercise.hint,
+                choices: exercise.choices.map(|choices| choices.into()),
+                audio_url: exercise.audio_url,
+                expected_answer: exercise.expected_answer,
+            })
+            .collect();
+        diesel::insert_into(learn_review_session_items::table)
+            .values(&items)
+            .execute(conn)?;
+
+        load_session_view(conn, session).map(Some)
+    })
+}
+
+/// Today's active session of the user, there is at most one
+fn active_session(
+    conn: &mut PgConnection,
+    user_id: i64,
+    today: NaiveDate,
+) -> QueryResult<Option<ReviewSession>> {
+    learn_review_sessions::table
+        .filter(learn_review_sessions::user_id.eq(user_id))
+        .filter(learn_review_sessions::session_date.eq(today))
+        .filter(learn_review_sessions::status.eq("active"))
+        .first::<ReviewSession>(conn)
+        .optional()
+}
+
+/// Load a session of the user with its items
+pub fn get_session(
+    conn: &mut PgConnection,
+    user_id: i64,
+    session_id: i64,
+) -> QueryResult<SessionView> {
+    let session = learn_review_sessions::table
+        .filter(learn_review_sessions::id.eq(session_id))
+        .filter(learn_review_sessions::user_id.eq(user_id))
+        .first::<ReviewSession>(conn)?;
+    load_session_view(conn, session)
+}
+
+fn load_session_view(conn: &mut PgConnection, session: ReviewSession) -> QueryResult<SessionView> {
+    let items = learn_review_session_items::table
+        .filter(learn_review_session_items::session_id.eq(session.id))
+        .order(learn_review_session_items::position.asc())
+        .load::<ReviewSessionItem>(conn)?;
+    Ok(SessionView {
+        session,
+        items: items.into_iter().map(Into::into).collect(),
+    })
+}
+
+/// Check an answer, store it and feed the grade back into scheduling
+///
+/// Wrong answers are graded "again". Right answers are graded "good" unless
+/// the learner rated them "hard" or "easy" themselves. Answering an item a
+/// second time returns the stored outcome without grading again.
+#[allow(clippy::too_many_arguments)]
+pub fn answer_item(
+    conn: &mut PgConnection,
+    user_id: i64,
+    session_id: i64,
+    item_id: i64,
+    answer: &str,
+    self_grade: Option<ReviewGrade>,
+    now: DateTime<Utc>,
+    config: &SchedulerConfig,
+) -> QueryResult<AnswerResult> {
+    conn.transaction(|conn| {
+        let item = learn_review_session_items::table
+            .filter(learn_review_session_items::id.eq(item_id))
+            .filter(learn_review_session_items::session_id.eq(session_id))
+            .filter(learn_review_session_items::user_id.eq(user_id))
+            .for_update()
+            .first::<ReviewSessionItem>(conn)?;
+
+        if item.answered_at.is_some() {
+            let session = learn_review_sessions::table
+                .find(session_id)
+                .first::<ReviewSession>(conn)?;
+            return Ok(AnswerResult {
+                item: item.into(),
+                review: None,
+                session,
+                unlocked: Vec::new(),
+            });
+        }
+
+        let correct = normalize_answer(answer) == normalize_answer(&item.expected_answer);
+        let grade = if correct {
+            self_grade
+                .filter(|g| g.is_correct())
+                .unwrap_or(ReviewGrade::Good)
+        } else {
+            ReviewGrade::Again
+        };
+
+        let item = diesel::update(learn_review_session_items::table.find(item.id))
+            .set((
+                learn_review_session_items::user_answer.eq(Some(answer.to_owned())),
+                learn_review_session_items::is_correct.eq(Some(correct)),
+                learn_review_session_items::grade.eq(Some(grade.as_str())),
+                learn_review_session_items::answered_at.eq(Some(now)),
+            ))
+            .get_result::<ReviewSessionItem>(conn)?;
+
+        let scheduled = match SessionItemType::parse(&item.item_type) {
+            Some(SessionItemType::Vocabulary) => Some((ReviewItemType::Vocabulary, item.item_id)),
+            Some(SessionItemType::IssueWord) => Some((ReviewItemType::IssueWord, item.item_id)),
+            Some(SessionItemType::ChatIssue) => {
+                issue_word_for_chat_issue(conn, user_id, item.item_id)?
+                    .map(|id| (ReviewItemType::IssueWord, id))
+            }
+            None => None,
+        };
+        let review = match scheduled {
+            Some((item_type, id)) => {
+                grade_item(conn, user_id, item_type, id, grade, now, config).optional()?
+            }
+            None => None,
+        };
+
+        let session = learn_review_sessions::table
+            .find(session_id)
+            .for_update()
+            .first::<ReviewSession>(conn)?;
+        let answered_count = session.answered_count + 1;
+        let completed = answered_count >= session.item_count;
+        let session = diesel::update(learn_review_sessions::table.find(session_id))
+            .set((
+                learn_review_sessions::answered_count.eq(answered_count),
+                learn_review_sessions::correct_count.eq(session.correct_count + i32::from(correct)),
+                learn_review_sessions::status.eq(if completed { "completed" } else { "active" }),
+                learn_review_sessions::completed_at.eq(completed.then_some(now)),
+            ))
+            .get_result::<ReviewSession>(conn)?;
+        if completed {
+            stats::record_activity(conn, user_id, Activity::SessionCompleted, now)?;
+        }
+        let unlocked =
+            achievement::evaluate_and_notify(conn, user_id, AchievementEvent::Studied, now)?;
+
+        Ok(AnswerResult {
+            item: item.into(),
+            review,
+            session,
+            unlocked,
+        })
+    })
+}
+
+/// Lowercase, unify apostrophes and drop surrounding punctuation so that
+/// "Went." matches "went"
+pub fn normalize_answer(text: &str) -> String {
+    text.replace(['\u{2019}', '\u{2018}'], "'")
+        .split_whitespace()
+        .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric() && c != '\''))
+        .filter(|w| !w.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Due vocabulary, due issue words and recent chat issues, interleaved
+fn load_candidates(
+    conn: &mut PgConnection,
+    user_id: i64,
+    size: i64,
+    now: DateTime<Utc>,
+) -> QueryResult<Vec<Candidate>> {
+    let vocabularies = learn_vocabularies::table
+        .filter(learn_vocabularies::user_id.eq(user_id))
+        .filter(
+            learn_vocabularies::next_review_at
+                .is_null()
+                .or(learn_vocabularies::next_review_at.le(now)),
+        )
+        .order(learn_vocabularies::next_review_at.asc().nulls_first())
+        .limit(size)
+        .load::<UserVocabulary>(conn)?
+        .into_iter()
+        .map(|v| Candidate {
+            item_type: SessionItemType::Vocabulary,
+            item_id: v.id,
+            word: v.word.to_lowercase(),
+            meaning: v.word_zh,
+            mastery: v.mastery_level.unwrap_or(1),
+            correction: None,
+        })
+        .collect();
+
+    let issue_words = learn_issue_words::table
+        .filter(learn_issue_words::user_id.eq(user_id))
+        .filter(
+            learn_issue_words::next_review_at
+                .is_null()
+                .or(learn_issue_words::next_review_at.le(now)),
+        )
+        .order(learn_issue_words::next_review_at.asc().nulls_first())
+        .limit(size)
+        .load::<IssueWord>(conn)?
+        .into_iter()
+        .map(|w| Candidate {
+            item_type: SessionItemType::IssueWord,
+            item_id: w.id,
+            word: w.word.to_lowercase(),
+            // The dictionary definition is preferred, see choose_exercise
+            meaning: None,
+            mastery: w.mastery_level,
+            correction: None,
+        })
+        .collect();
+
+    let chat_issues = learn_chat_issues::table
+        .filter(learn_chat_issues::user_id.eq(user_id))
+        .filter(learn_chat_issues::created_at.ge(now - Duration::days(CHAT_ISSUE_LOOKBACK_DAYS)))
+        .filter(learn_chat_issues::issue_type.ne("suggestion"))
+        .filter(learn_chat_issues::original_text.is_not_null())
+        .filter(learn_chat_issues::suggested_text.is_not_null())
+        .order(learn_chat_issues::created_at.desc())
+        .limit(size)
+        .load::<ChatIssue>(conn)?
+        .into_iter()
+        .filter_map(|issue| {
+            let original = issue.original_text?.trim().to_owned();
+            let suggested = issue.suggested_text?.trim().to_owned();
+            if original.is_empty() || suggested.is_empty() {
+                return None;
+            }
+            Some(Candidate {
+                item_type: SessionItemType::ChatIssue,
+                item_id: issue.id,
+                word: original.to_lowercase(),
+                meaning: None,
+                mastery: 1,
+                correction: Some(Correction {
+                    original,
+                    suggested,
+                    description: issue.description_zh.or(issue.description_en),
+                }),
+            })
+        })
+        .collect();
+
+    Ok(interleave(
+        vec![vocabularies, issue_words, chat_issues],
+        size as usize,
+    ))
+}
+
+/// Take items round-robin from each queue, skipping words already taken
+fn interleave(queues: Vec<Vec<Candidate>>, size: usize) -> Vec<Candidate> {
+    let mut queues: Vec<_> = queues.into_iter().map(|q| q.into_iter()).collect();
+    let mut seen = HashSet::new();
+    let mut out = Vec::with_capacity(size);
+    while out.len() < size {
+        let mut progressed = false;
+        for queue in queues.iter_mut() {
+            if out.len() >= size {
+                break;
+            }
+            for candidate in queue.by_ref() {
+                if seen.insert(candidate.word.clone()) {
+                    out.push(candidate);
+                    progressed = true;
+                    break;
+                }
+            }
+        }
+        if !progressed {
+            break;
+        }
+    }
+    out
+}
+
+fn load_materials(
+    conn: &mut PgConnection,
+    words: &[String],
+) -> QueryResult<HashMap<String, WordMaterial>> {
+    let mut materials: HashMap<String, WordMaterial> = HashMap::new();
+    if words.is_empty() {
+        return Ok(materials);
+    }
+
+    let dict_words: HashMap<i64, String> = dict_words::table
+        .filter(dict_words::word_lower.eq_any(words))
+        .select((dict_words::id, dict_words::word_lower))
+        .load::<(i64, String)>(conn)?
+        .into_iter()
+        .collect();
+    let word_ids: Vec<i64> = dict_words.keys().copied().collect();
+
+    let definitions = dict_definitions::table
+        .filter(dict_definitions::word_id.eq_any(&word_ids))
+        .filter(dict_definitions::language.eq("en"))
+        .order((
+            dict_definitions::word_id,
+            dict_definitions::is_primary.desc().nulls_last(),
+            dict_definitions::definition_order.asc(),
+        ))
+        .select((dict_definitions::word_id, dict_definitions::definition))
+        .load::<(i64, String)>(conn)?;
+    for (word_id, definition) in definitions {
+        if let Some(word) = dict_words.get(&word_id) {
+            materials
+                .entry(word.clone())
+                .or_default()
+                .definition
+                .get_or_insert(definition);
+        }
+    }
+
+    let sentences = dict_word_sentences::table
+        .inner_join(
+            dict_sentences::table.on(dict_sentences::id.eq(dict_word_sentences::sentence_id)),
+        )
+        .filter(dict_word_sentences::word_id.eq_any(&word_ids))
+        .order((
+            dict_word_sentences::word_id,
+            dict_word_sentences::priority_order.desc().nulls_last(),
+        ))
+        .select((dict_word_sentences::word_id, dict_sentences::sentence))
+        .load::<(i64, String)>(conn)?;
+    for (word_id, sentence) in sentences {
+        if let Some(word) = dict_words.get(&word_id) {
+            let material = materials.entry(word.clone()).or_default();
+            if material.sentences.len() < SENTENCES_PER_WORD {
+                material.sentences.push(sentence);
+            }
+        }
+    }
+
+    let pronunciations = dict_pronunciations::table
+        .filter(dict_pronunciations::word_id.eq_any(&word_ids))
+        .filter(
+            dict_pronunciations::audio_url
+                .is_not_null()
+                .or(dict_pronunciations::audio_path.is_not_null()),
+        )
+        .order((
+            dict_pronunciations::word_id,
+            dict_pronunciations::is_primary.desc().nulls_last(),
+        ))
+        .load::<Pronunciation>(conn)?;
+    for pronunciation in pronunciations {
+        if let Some(word) = dict_words.get(&pronunciation.word_id) {
+            let material = materials.entry(word.clone()).or_default();
+            if material.audio_url.is_none() {
+                material.audio_url = pronunciation.playable_url();
+            }
+        }
+    }
+
+    Ok(materials)
+}
+
+/// Meanings of other words, used as wrong options in recognition exercises
+///
+/// Kept apart by language so that all options of an exercise are written in
+/// the language of the answer.
+#[derive(Default)]
+struct Distractors {
+    /// Chinese translations from the vocabulary
+    translations: Vec<String>,
+    /// English dictionary definitions
+    definitions: Vec<String>,
+}
+
+fn load_distractors(
+    conn: &mut PgConnection,
+    user_id: i64,
+    candidates: &[Candidate],
+    materials: &HashMap<String, WordMaterial>,
+    rng: &mut impl rand::Rng,
+) -> QueryResult<Distractors> {
+    let mut distractors = Distractors::default();
+    for candidate in candidates {
+        match &candidate.meaning {
+            Some(meaning) => distractors.translations.push(meaning.clone()),
+            None => distractors.definitions.extend(
+                materials
+                    .get(&candidate.word)
+                    .and_then(|m| m.definition.clone()),
+            ),
+        }
+    }
+
+    // Sessions with few items borrow meanings from the rest of the vocabulary
+    // and the dictionary
+    if distractors.translations.len() < CHOICE_COUNT * 2 {
+        let ids = learn_vocabularies::table
+            .filter(learn_vocabularies::user_id.eq(user_id))
+            .select((min(learn_vocabularies::id), max(learn_vocabularies::id)))
+            .first::<(Option<i64>, Option<i64>)>(conn)?;
+        let pool = sample(ids, rng, |start, after| {
+            let query = learn_vocabularies::table
+                .filter(learn_vocabularies::user_id.eq(user_id))
+                .filter(learn_vocabularies::word_zh.is_not_null())
+                .select(learn_vocabularies::word_zh.assume_not_null())
+                .order(learn_vocabularies::id.asc())
+                .limit(SAMPLE_POOL);
+            if after {
+                query
+                    .filter(learn_vocabularies::id.ge(start))
+                    .load::<String>(conn)
+            } else {
+                query
+                    .filter(learn_vocabularies::id.lt(start))
+                    .load::<String>(conn)
+            }
+        })?;
+        distractors
+            .translations
+            .extend(pool.choose_multiple(rng, CHOICE_COUNT * 3).cloned());
+    }
+    if distractors.definitions.len() < CHOICE_COUNT * 2 {
+        let ids = dict_definitions::table
+            .select((min(dict_definitions::id), max(dict_definitions::id)))
+            .first::<(Option<i64>, Option<i64>)>(conn)?;
+        let pool = sample(ids, rng, |start, after| {
+            let query = dict_definitions::table
+                .filter(dict_definitions::language.eq("en"))
+                .select(dict_definitions::definition)
+                .order(dict_definitions::id.asc())
+                .limit(SAMPLE_POOL);
+            if after {
+                query
+                    .filter(dict_definitions::id.ge(start))
+                    .load::<String>(conn)
+            } else {
+                query
+                    .filter(dict_definitions::id.lt(start))
+                    .load::<String>(conn)
+            }
+        })?;
+        distractors
+            .definitions
+            .extend(pool.choose_multiple(rng, CHOICE_COUNT * 3).cloned());
+    }
+
+    for pool in [&mut distractors.translations, &mut distractors.definitions] {
+        let mut seen = HashSet::new();
+        pool.retain(|m| !m.trim().is_empty() && seen.insert(m.clone()));
+    }
+    Ok(distractors)
+}
+
+fn choose_exercise(
+    candidate: &Candidate,
+    material: Option<&WordMaterial>,
+    distractors: &Distractors,
+    rng: &mut impl rand::Rng,
+) -> Option<Exercise> {
+    if let Some(correction) = &candidate.correction {
+        return Some(Exercise {
+            exercise_type: ExerciseType::Recall,
+            prompt: correction.original.clone(),
+            hint: correction.description.clone(),
+            choices: None,
+            audio_url: None,
+            expected_answer: correction.suggested.clone(),
+        });
+    }
+
+    let (meaning, distractors) = match &candidate.meaning {
+        Some(meaning) => (Some(meaning.clone()), &distractors.translations),
+        None => (
+            material.and_then(|m| m.definition.clone()),
+            &distractors.definitions,
+        ),
+    };
+    let cloze = material.and_then(|m| {
+        m.sentences
+            .iter()
+            .find_map(|sentence| blank_out(sentence, &candidate.word))
+    });
+    let audio_url = material.and_then(|m| m.audio_url.clone());
+    let choices = meaning.as_ref().and_then(|meaning| {
+        let mut wrong: Vec<&String> = distractors.iter().filter(|d| *d != meaning).collect();
+        if wrong.len() < CHOICE_COUNT - 1 {
+            return None;
+        }
+        wrong.shuffle(rng);
+        let mut choices: Vec<String> = wrong.into_iter().take(CHOICE_COUNT - 1).cloned().collect();
+        choices.push(meaning.clone());
+        choices.shuffle(rng);
+        Some(choices)
+    });
+
+    // New words are recognized first, known ones have to be produced
+    let preference = match candidate.mastery {
+        i32::MIN..=2 => [
+            ExerciseType::Recognition,
+            ExerciseType::Listening,
+            ExerciseType::Cloze,
+            ExerciseType::Recall,
+        ],
+        3 => [
+            ExerciseType::Cloze,
+            ExerciseType::Listening,
+            ExerciseType::Recognition,
+            ExerciseType::Recall,
+        ],
+        _ => [
+            ExerciseType::Recall,
+            ExerciseType::Cloze,
+            ExerciseType::Listening,
+            ExerciseType::Recognition,
+        ],
+    };
+    let available: Vec<ExerciseType> = preference
+        .into_iter()
+        .filter(|t| match t {
+            ExerciseType::Recognition => choices.is_some(),
+            ExerciseType::Recall => meaning.is_some(),
+            ExerciseType::Cloze => cloze.is_some(),
+            ExerciseType::Listening => audio_url.is_some(),
+        })
+        .collect();
+    // Pick between the two preferred types for some variety
+    let exercise_type = *available[..available.len().min(2)].choose(rng)?;
+
+    let word = candidate.word.clone();
+    Some(match exercise_type {
+        ExerciseType::Recognition => Exercise {
+            exercise_type,
+            prompt: word,
+            hint: None,
+            choices,
+            audio_url,
+            expected_answer: meaning?,
+        },
+        ExerciseType::Recall => Exercise {
+            exercise_type,
+            prompt: meaning?,
+            hint: None,
+            choices: None,
+            audio_url: None,
+            expected_answer: word,
+        },
+        ExerciseType::Cloze => {
+            let (sentence, answer) = cloze?;
+            Exercise {
+                exercise_type,
+                prompt: sentence,
+                hint: meaning,
+                choices: None,
+                audio_url: None,
+                expected_answer: answer,
+            }
+        }
+        ExerciseType::Listening => Exercise {
+            exercise_type,
+            prompt: String::new(),
+            hint: None,
+            choices: None,
+            audio_url,
+            expected_answer: word,
+        },
+    })
+}
+
+/// Replace the first whole-word occurrence of `word` in `sentence` with a blank,
+/// returning the sentence and the text that was removed
+pub fn blank_out(sentence: &str, word: &str) -> Option<(String, String)> {
+    let pattern = format!(r"\b{}\b", regex::escape(word));
+    let re = RegexBuilder::new(&pattern)
+        .case_insensitive(true)
+        .build()
+        .ok()?;
+    let found = re.find(sentence)?;
+    let blanked = format!(
+        "{}{}{}",
+        &sentence[..found.start()],
+        BLANK,
+        &sentence[found.end()..]
+    );
+    Some((blanked, found.as_str().to_owned()))
+}
+
+/// Issue word matching a chat issue, created on first review
+fn issue_word_for_chat_issue(
+    conn: &mut PgConnection,
+    user_id: i64,
+    chat_issue_id: i64,
+) -> QueryResult<Option<i64>> {
+    let Some(issue) = learn_chat_issues::table
+        .filter(learn_chat_issues::id.eq(chat_issue_id))
+        .filter(learn_chat_issues::user_id.eq(user_id))
+        .first::<ChatIssue>(conn)
+        .optional()?
+    else {
+        return Ok(None);
+    };
+    let word = issue
+        .original_text
+        .as_deref()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+    if word.is_empty() {
+        return Ok(None);
+    }
+    let issue_type = if issue.issue_type == "grammar" {
+        "grammar"
+    } else {
+        "usage"
+    };
+
+    diesel::insert_into(learn_issue_words::table)
+        .values(&NewIssueWord {
+            user_id,
+            word: word.clone(),
+            issue_type: issue_type.to_owned(),
+            description_en: issue.description_en,
+            description_zh: issue.description_zh,
+            context: issue.suggested_text,
+        })
+        .on_conflict((
+            learn_issue_words::user_id,
+            learn_issue_words::word,
+            learn_issue_words::issue_type,
+        ))
+        .do_nothing()
+        .execute(conn)?;
+
+    learn_issue_words::table
+        .filter(learn_issue_words::user_id.eq(user_id))
+        .filter(learn_issue_words::word.eq(word))
+        .filter(learn_issue_words::issue_type.eq(issue_type))
+        .select(learn_issue_words::id)
+        .first::<i64>(conn)
+        .optional()
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    use super::*;
+
+    fn candidate(item_type: SessionItemType, word: &str, mastery: i32) -> Candidate {
+        Candidate {
+            item_type,
+            item_id: 0,
+            word: word.to_owned(),
+            meaning: Some(format!("meaning of {word}")),
+            mastery,
+            correction: None,
+        }
+    }
+
+    fn distractors() -> Distractors {
+        Distractors {
+            translations: ["猫", "狗", "鸟", "鱼"].map(str::to_owned).to_vec(),
+            definitions: ["a small animal", "a large animal", "a bird", "a fish"]
+                .map(str::to_owned)
+                .to_vec(),
+        }
+    }
+
+    #[test]
+    fn normalizes_answers() {
+        assert_eq!(normalize_answer("  Went. "), "went");
+        assert_eq!(normalize_answer("I\u{2019}m  \"fine\"!"), "i'm fine");
+        assert_eq!(normalize_answer("...?"), "");
+    }
+
+    #[test]
+    fn blanks_out_whole_words() {
+        assert_eq!(
+            blank_out("Cats chase the cat.", "cat"),
+            Some(("Cats chase the _____.".to_owned(), "cat".to_owned()))
+        );
+        assert_eq!(
+            blank_out("The Cat sleeps", "cat").map(|(_, answer)| answer),
+            Some("Cat".to_owned())
+        );
+        assert_eq!(blank_out("Concatenate", "cat"), None);
+    }
+
+    #[test]
+    fn interleaves_queues_without_repeating_words() {
+        let vocabulary = vec![
+            candidate(SessionItemType::Vocabulary, "a", 1),
+            candidate(SessionItemType::Vocabulary, "b", 1),
+            candidate(SessionItemType::Vocabulary, "c", 1),
+        ];
+        let issue_words = vec![
+            candidate(SessionItemType::IssueWord, "a", 1),
+            candidate(SessionItemType::IssueWord, "d", 1),
+        ];
+        let words: Vec<String> = interleave(vec![vocabulary, issue_words, Vec::new()], 4)
+            .into_iter()
+            .map(|c| c.word)
+            .collect();
+        assert_eq!(words, ["a", "d", "b", "c"]);
+    }
+
+    #[test]
+    fn new_words_are_recognized_and_known_ones_recalled() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let material = WordMaterial::default();
+        for _ in 0..20 {
+            let new = candidate(SessionItemType::Vocabulary, "cat", 1);
+            let exercise =
+                choose_exercise(&new, Some(&material), &distractors(), &mut rng).unwrap();
+            // Without sentences or audio the second choice is recall
+            assert!(matches!(
+                exercise.exercise_type,
+                ExerciseType::Recognition | ExerciseType::Recall
+            ));
+            if exercise.exercise_type == ExerciseType::Recognition {
+                let choices = exercise.choices.unwrap();
+                assert_eq!(choices.len(), CHOICE_COUNT);
+                assert!(choices.contains(&exercise.expected_answer));
+            }
+
+            let known = candidate(SessionItemType::Vocabulary, "cat", 5);
+            let exercise =
+                choose_exercise(&known, None, &Distractors::default(), &mut rng).unwrap();
+            assert_eq!(exercise.exercise_type, ExerciseType::Recall);
+            assert_eq!(exercise.prompt, "meaning of cat");
+            assert_eq!(exercise.expected_answer, "cat");
+        }
+    }
+
+    #[test]
+    fn recognition_options_match_the_answer_language() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let distractors = distractors();
+        let material = WordMaterial {
+            definition: Some("a domestic animal".to_owned()),
+            ..Default::default()
+        };
+        let mut issue_word = candidate(SessionItemType::IssueWord, "cat", 1);
+        issue_word.meaning = None;
+        let vocabulary = candidate(SessionItemType::Vocabulary, "cat", 1);
+        for (candidate, pool) in [
+            (&issue_word, &distractors.definitions),
+            (&vocabulary, &distractors.translations),
+        ] {
+            for _ in 0..20 {
+                let exercise =
+                    choose_exercise(candidate, Some(&material), &distractors, &mut rng).unwrap();
+                if let Some(choices) = exercise.choices {
+                    assert!(choices.iter().all(|choice| {
+                        *choice == exercise.expected_answer || pool.contains(choice)
+                    }));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn chat_issues_ask_for_the_correction() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut issue = candidate(SessionItemType::ChatIssue, "i goed", 1);
+        issue.correction = Some(Correction {
+            original: "I goed".to_owned(),
+            suggested: "I went".to_owned(),
+            description: None,
+        });
+        let exercise = choose_exercise(&issue, None, &distractors(), &mut rng).unwrap();
+        assert_eq!(exercise.exercise_type, ExerciseType::Recall);
+        assert_eq!(exercise.prompt, "I goed");
+        assert_eq!(exercise.expected_answer, "I went");
+    }
+
+    #[test]
+    fn words_without_material_get_no_exercise() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut bare = candidate(SessionItemType::IssueWord, "cat", 1);
+        bare.meaning = None;
+        assert!(choose_exercise(&bare, None, &distractors(), &mut rng).is_none());
+    }
+}
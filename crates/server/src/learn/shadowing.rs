@@ -0,0 +1,439 @@
+//! Shadowing: repeating model audio right after hearing it
+//!
+//! The learner's recording and the reference audio are both transcribed with
+//! word timings (the reference once, then cached in `asset_audio_timings`).
+//! Words said in both are paired up and compared: accuracy counts the
+//! reference words that were said, rhythm compares each word's share of the
+//! total duration, pauses are gaps of at least [`PAUSE_MS`] after the same
+//! words, and the speed ratio compares the time from the first to the last
+//! paired word.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use diesel::prelude::*;
+use salvo::oapi::ToSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use super::achievement::{self, AchievementEvent};
+use super::local_time::{day_bounds, local_date, user_timezone};
+use super::phoneme::align;
+use super::session::normalize_answer;
+use super::stats::{self, Activity, PracticeItem};
+use crate::db::schema::*;
+use crate::models::achievement::AchievementBadge;
+use crate::models::learn::{NewShadowingPractice, ShadowingPractice};
+use crate::services::ai_provider::WordTiming;
+
+/// Shortest silence between two words that counts as a pause
+pub const PAUSE_MS: i64 = 250;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ShadowingSource {
+    ScriptTurn,
+    ReadSentence,
+}
+
+impl ShadowingSource {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ShadowingSource::ScriptTurn => "script_turn",
+            ShadowingSource::ReadSentence => "read_sentence",
+        }
+    }
+}
+
+/// A transcribed word with its position in the audio
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct TimedWord {
+    pub word: String,
+    pub start_ms: i64,
+    pub end_ms: i64,
+}
+
+impl TimedWord {
+    fn duration_ms(&self) -> i64 {
+        (self.end_ms - self.start_ms).max(0)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct WordComparison {
+    pub word: String,
+    /// Whether the learner said this word of the reference audio
+    pub said: bool,
+    pub reference_ms: i64,
+    pub learner_ms: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct PauseComparison {
+    /// Words followed by a pause in the reference audio
+    pub reference: Vec<String>,
+    /// Words followed by a pause in the recording
+    pub learner: Vec<String>,
+    /// Pauses made after the same words
+    pub matched: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct ShadowingDetails {
+    pub words: Vec<WordComparison>,
+    pub pauses: PauseComparison,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShadowingScores {
+    pub accuracy: i32,
+    pub rhythm: i32,
+    pub pause: i32,
+    pub speed_ratio: Option<f32>,
+    pub overall: i32,
+    pub details: ShadowingDetails,
+}
+
+/// Averages of the practices of one local day
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ShadowingTrendPoint {
+    pub date: NaiveDate,
+    pub attempts: usize,
+    pub accuracy: f32,
+    pub rhythm: f32,
+    pub pause: f32,
+    pub speed_ratio: Option<f32>,
+    pub overall: f32,
+}
+
+/// Timed words of an ASR result, normalized like typed answers
+pub fn timed_words(words: &[WordTiming]) -> Vec<TimedWord> {
+    words
+        .iter()
+        .filter_map(|w| {
+            let word = normalize_answer(&w.word);
+            (!word.is_empty()).then(|| TimedWord {
+                word,
+                start_ms: (w.start_time * 1000.0).round() as i64,
+                end_ms: (w.end_time * 1000.0).round() as i64,
+            })
+        })
+        .collect()
+}
+
+/// Text and reference audio of what is to be shadowed
+pub fn source_reference(
+    conn: &mut PgConnection,
+    source: ShadowingSource,
+    source_id: i64,
+) -> QueryResult<Option<(String, Option<String>)>> {
+    match source {
+        ShadowingSource::ScriptTurn => asset_script_turns::table
+            .find(source_id)
+            .select((
+                asset_script_turns::content_en,
+                asset_script_turns::audio_path,
+            ))
+            .first::<(String, Option<String>)>(conn)
+            .optional(),
+        ShadowingSource::ReadSentence => asset_read_sentences::table
+            .find(source_id)
+            .select((
+                asset_read_sentences::content_en,
+                asset_read_sentences::audio_path,
+            ))
+            .first::<(String, Option<String>)>(conn)
+            .optional(),
+    }
+}
+
+/// Cached word timings of a reference audio file
+pub fn reference_timings(
+    conn: &mut PgConnection,
+    audio_path: &str,
+) -> QueryResult<Option<Vec<TimedWord>>> {
+    Ok(asset_audio_timings::table
+        .filter(asset_audio_timings::audio_path.eq(audio_path))
+        .select(asset_audio_timings::words)
+        .first::<serde_json::Value>(conn)
+        .optional()?
+        .and_then(|words| serde_json::from_value(words).ok()))
+}
+
+pub fn store_reference_timings(
+    conn: &mut PgConnection,
+    audio_path: &str,
+    transcript: &str,
+    words: &[TimedWord],
+) -> QueryResult<()> {
+    diesel::insert_into(asset_audio_timings::table)
+        .values((
+            asset_audio_timings::audio_path.eq(audio_path),
+            asset_audio_timings::transcript.eq(transcript),
+            asset_audio_timings::words.eq(json!(words)),
+        ))
+        .on_conflict(asset_audio_timings::audio_path)
+        .do_nothing()
+        .execute(conn)?;
+    Ok(())
+}
+
+/// Store a practice scored against `source` and count it in the daily
+/// statistics
+///
+/// Returns the practice and the achievements it unlocked.
+pub fn record(
+    conn: &mut PgConnection,
+    source: ShadowingSource,
+    new_practice: &NewShadowingPractice,
+) -> QueryResult<(ShadowingPractice, Vec<AchievementBadge>)> {
+    conn.transaction(|conn| {
+        let practice = diesel::insert_into(learn_shadowing_practices::table)
+            .values(new_practice)
+            .get_result::<ShadowingPractice>(conn)?;
+        let item = PracticeItem::Shadowing {
+            source,
+            source_id: practice.source_id,
+        };
+        stats::record_activity(
+            conn,
+            practice.user_id,
+            Activity::Practice { item: Some(item) },
+            practice.created_at,
+        )?;
+        let unlocked = achievement::evaluate_and_notify(
+            conn,
+            practice.user_id,
+            AchievementEvent::Studied,
+            practice.created_at,
+        )?;
+        Ok((practice, unlocked))
+    })
+}
+
+/// Daily averages over the last `days` local days, oldest first
+pub fn trend(
+    conn: &mut PgConnection,
+    user_id: i64,
+    days: u64,
+    now: DateTime<Utc>,
+) -> QueryResult<Vec<ShadowingTrendPoint>> {
+    let tz = user_timezone(conn, user_id)?;
+    let first_day = local_date(tz, now) - Days::new(days.saturating_sub(1));
+    let (since, _) = day_bounds(tz, first_day);
+    let practices = learn_shadowing_practices::table
+        .filter(learn_shadowing_practices::user_id.eq(user_id))
+        .filter(learn_shadowing_practices::created_at.ge(since))
+        .load::<ShadowingPractice>(conn)?;
+
+    let mut by_day: BTreeMap<NaiveDate, Vec<ShadowingPractice>> = BTreeMap::new();
+    for practice in practices {
+        by_day
+            .entry(local_date(tz, practice.created_at))
+            .or_default()
+            .push(practice);
+    }
+    Ok(by_day
+        .into_iter()
+        .map(|(date, practices)| {
+            let average = |score: fn(&ShadowingPractice) -> i32| {
+                practices.iter().map(|p| score(p) as f32).sum::<f32>() / practices.len() as f32
+            };
+            let ratios: Vec<f32> = practices.iter().filter_map(|p| p.speed_ratio).collect();
+            ShadowingTrendPoint {
+                date,
+                attempts: practices.len(),
+                accuracy: average(|p| p.accuracy_score),
+                rhythm: average(|p| p.rhythm_score),
+                pause: average(|p| p.pause_score),
+                speed_ratio: (!ratios.is_empty())
+                    .then(|| ratios.iter().sum::<f32>() / ratios.len() as f32),
+                overall: average(|p| p.overall_score),
+            }
+        })
+        .collect())
+}
+
+/// Compare a recording with the reference text and audio
+///
+/// `transcript` is what the learner was heard saying; `learner` holds its
+/// word timings, which may be empty when the ASR gave none.
+pub fn score(
+    reference_text: &str,
+    reference: &[TimedWord],
+    transcript: &str,
+    learner: &[TimedWord],
+) -> ShadowingScores {
+    let expected = normalize_answer(reference_text);
+    let expected: Vec<&str> = expected.split(' ').filter(|w| !w.is_empty()).collect();
+    let heard = normalize_answer(transcript);
+    let heard: Vec<&str> = heard.split(' ').filter(|w| !w.is_empty()).collect();
+    let said = align(&expected, &heard)
+        .into_iter()
+        .filter(|pair| matches!(pair, (Some(e), Some(h)) if expected[*e] == heard[*h]))
+        .count();
+    let accuracy = percent(said, expected.len());
+
+    let reference_words: Vec<&str> = reference.iter().map(|w| w.word.as_str()).collect();
+    let learner_words: Vec<&str> = learner.iter().map(|w| w.word.as_str()).collect();
+    // Indexes into `reference` and `learner` of the words said in both
+    let pairs: Vec<(usize, usize)> = align(&reference_words, &learner_words)
+        .into_iter()
+        .filter_map(|pair| match pair {
+            (Some(r), Some(l)) if reference_words[r] == learner_words[l] => Some((r, l)),
+            _ => None,
+        })
+        .collect();
+
+    let words = reference
+        .iter()
+        .enumerate()
+        .map(|(i, word)| {
+            let paired = pairs.iter().find(|(r, _)| *r == i);
+            WordComparison {
+                word: word.word.clone(),
+                said: paired.is_some(),
+                reference_ms: word.duration_ms(),
+                learner_ms: paired.map(|(_, l)| learner[*l].duration_ms()),
+            }
+        })
+        .collect();
+
+    let rhythm = rhythm_score(&pairs, reference, learner);
+    let mut pauses = PauseComparison {
+        reference: Vec::new(),
+        learner: Vec::new(),
+        matched: 0,
+    };
+    for window in pairs.windows(2) {
+        let ((r1, l1), (r2, l2)) = (window[0], window[1]);
+        let reference_pause = reference[r2].start_ms - reference[r1].end_ms >= PAUSE_MS;
+        let learner_pause = learner[l2].start_ms - learner[l1].end_ms >= PAUSE_MS;
+        if reference_pause {
+            pauses.reference.push(reference[r1].word.clone());
+        }
+        if learner_pause {
+            pauses.learner.push(learner[l1].word.clone());
+        }
+        pauses.matched += usize::from(reference_pause && learner_pause);
+    }
+    let pause = if pairs.len() < 2 {
+        0
+    } else if pauses.reference.is_empty() && pauses.learner.is_empty() {
+        100
+    } else {
+        percent(
+            2 * pauses.matched,
+            pauses.reference.len() + pauses.learner.len(),
+        )
+    };
+
+    let speed_ratio = match (pairs.first(), pairs.last()) {
+        (Some(&(r1, l1)), Some(&(r2, l2))) if r2 > r1 => {
+            let reference_span = reference[r2].end_ms - reference[r1].start_ms;
+            let learner_span = learner[l2].end_ms - learner[l1].start_ms;
+            (reference_span > 0 && learner_span > 0)
+                .then(|| learner_span as f32 / reference_span as f32)
+        }
+        _ => None,
+    };
+    let speed = speed_ratio.map_or(0.0, |ratio| ratio.min(1.0 / ratio) * 100.0);
+    let overall = (0.5 * accuracy as f32 + 0.2 * rhythm as f32 + 0.15 * pause as f32 + 0.15 * speed)
+        .round() as i32;
+
+    ShadowingScores {
+        accuracy,
+        rhythm,
+        pause,
+        speed_ratio,
+        overall,
+        details: ShadowingDetails { words, pauses },
+    }
+}
+
+/// 100 minus the share of time spent differently, comparing each paired
+/// word's part of the total duration
+fn rhythm_score(pairs: &[(usize, usize)], reference: &[TimedWord], learner: &[TimedWord]) -> i32 {
+    if pairs.len() < 2 {
+        return 0;
+    }
+    let reference_total: i64 = pairs.iter().map(|(r, _)| reference[*r].duration_ms()).sum();
+    let learner_total: i64 = pairs.iter().map(|(_, l)| learner[*l].duration_ms()).sum();
+    if reference_total == 0 || learner_total == 0 {
+        return 0;
+    }
+    let difference: f64 = pairs
+        .iter()
+        .map(|(r, l)| {
+            let reference_share = reference[*r].duration_ms() as f64 / reference_total as f64;
+            let learner_share = learner[*l].duration_ms() as f64 / learner_total as f64;
+            (reference_share - learner_share).abs()
+        })
+        .sum();
+    ((1.0 - difference / 2.0) * 100.0).round().clamp(0.0, 100.0) as i32
+}
+
+fn percent(part: usize, total: usize) -> i32 {
+    if total == 0 {
+        0
+    } else {
+        (part * 100 / total) as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timed(words: &[(&str, i64, i64)]) -> Vec<TimedWord> {
+        words
+            .iter()
+            .map(|(word, start_ms, end_ms)| TimedWord {
+                word: word.to_string(),
+                start_ms: *start_ms,
+                end_ms: *end_ms,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_timing_scores_full_marks() {
+        let reference = timed(&[("i", 0, 200), ("like", 200, 500), ("tea", 900, 1300)]);
+        let scores = score("I like tea.", &reference, "i like tea", &reference);
+        assert_eq!(
+            (scores.accuracy, scores.rhythm, scores.pause, scores.overall),
+            (100, 100, 100, 100)
+        );
+        assert_eq!(scores.speed_ratio, Some(1.0));
+        assert_eq!(scores.details.pauses.reference, vec!["like".to_owned()]);
+    }
+
+    #[test]
+    fn slower_speech_with_a_missed_pause() {
+        let reference = timed(&[("i", 0, 200), ("like", 200, 500), ("tea", 900, 1300)]);
+        // Twice as slow, evenly, without the pause before "tea"
+        let learner = timed(&[("i", 0, 400), ("like", 400, 1000), ("tea", 1000, 1800)]);
+        let scores = score("I like tea.", &reference, "i like tea", &learner);
+        assert_eq!(scores.accuracy, 100);
+        assert_eq!(scores.rhythm, 100);
+        assert_eq!(scores.pause, 0);
+        let ratio = scores.speed_ratio.unwrap();
+        assert!((ratio - 1800.0 / 1300.0).abs() < 1e-6);
+        assert_eq!(scores.details.pauses.learner, Vec::<String>::new());
+    }
+
+    #[test]
+    fn missing_words_lower_accuracy() {
+        let reference = timed(&[
+            ("i", 0, 200),
+            ("like", 200, 500),
+            ("green", 500, 800),
+            ("tea", 800, 1100),
+        ]);
+        let learner = timed(&[("i", 0, 200), ("like", 200, 500), ("tea", 500, 800)]);
+        let scores = score("I like green tea", &reference, "i like tea", &learner);
+        assert_eq!(scores.accuracy, 75);
+        let said: Vec<bool> = scores.details.words.iter().map(|w| w.said).collect();
+        assert_eq!(said, vec![true, true, false, true]);
+    }
+}
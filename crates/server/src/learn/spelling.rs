@@ -0,0 +1,260 @@
+//! "Did you mean" suggestions for misspelled dictionary lookups
+//!
+//! Candidates come from the database: words sharing trigrams with the
+//! misspelling, plus words of about the same length starting with the same
+//! letter, since short words share few trigrams ("teh" and "the" share none).
+//! Each candidate is scored with an edit distance where the slips people
+//! actually make are cheap: hitting a neighbouring key, swapping two letters
+//! or doubling a letter costs half an edit. Frequent words win ties, a
+//! learner who types "teh" almost certainly meant "the" and not "tea".
+
+use diesel::prelude::*;
+use diesel::sql_types::{Float, Int8, Integer, Nullable, SmallInt, Text};
+use salvo::oapi::ToSchema;
+use serde::Serialize;
+
+/// Candidates fetched per source before scoring
+const CANDIDATES: i64 = 100;
+
+/// Longest lookup that gets suggestions, the edit distance is quadratic in
+/// the length and nothing longer is a word
+pub const MAX_WORD_LENGTH: usize = 64;
+
+/// Cost of the cheap slips: neighbouring key, swapped or doubled letter
+const SLIP_COST: f32 = 0.5;
+
+/// A dictionary word close to what was typed
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Suggestion {
+    pub word_id: i64,
+    pub word: String,
+    /// Keyboard-aware edit distance to the lookup, in edits
+    pub distance: f32,
+    /// 0-100, higher is more common
+    pub frequency: Option<i16>,
+}
+
+#[derive(QueryableByName)]
+struct Candidate {
+    #[diesel(sql_type = Int8)]
+    id: i64,
+    #[diesel(sql_type = Text)]
+    word: String,
+    #[diesel(sql_type = Text)]
+    word_lower: String,
+    #[diesel(sql_type = Nullable<SmallInt>)]
+    frequency: Option<i16>,
+    #[diesel(sql_type = Float)]
+    similarity: f32,
+}
+
+/// Words close to `word`, best first; none for words over [`MAX_WORD_LENGTH`]
+pub fn suggest(conn: &mut PgConnection, word: &str, limit: usize) -> QueryResult<Vec<Suggestion>> {
+    let typed = word.trim().to_lowercase();
+    let length = typed.chars().count();
+    let Some(first) = typed.chars().next() else {
+        return Ok(Vec::new());
+    };
+    if length > MAX_WORD_LENGTH {
+        return Ok(Vec::new());
+    }
+    let prefix = match first {
+        '%' | '_' | '\\' => format!("\\{first}%"),
+        _ => format!("{first}%"),
+    };
+
+    let candidates = diesel::sql_query(
+        "(SELECT id, word, word_lower, frequency, similarity(word_lower, $1) AS similarity \
+           FROM dict_words \
+          WHERE word_lower % $1 AND COALESCE(is_active, TRUE) \
+          ORDER BY similarity DESC LIMIT $4) \
+         UNION \
+         (SELECT id, word, word_lower, frequency, similarity(word_lower, $1) AS similarity \
+           FROM dict_words \
+          WHERE word_lower LIKE $2 AND length(word_lower) BETWEEN $3 - 1 AND $3 + 1 \
+            AND COALESCE(is_active, TRUE) \
+          ORDER BY frequency DESC NULLS LAST LIMIT $4)",
+    )
+    .bind::<Text, _>(&typed)
+    .bind::<Text, _>(prefix)
+    .bind::<Integer, _>(length as i32)
+    .bind::<Int8, _>(CANDIDATES)
+    .load::<Candidate>(conn)?;
+
+    Ok(rank(&typed, candidates, limit))
+}
+
+/// Keep the candidates within reach of `typed` and order them
+fn rank(typed: &str, candidates: Vec<Candidate>, limit: usize) -> Vec<Suggestion> {
+    let max_distance = max_distance(typed.chars().count());
+    let mut scored: Vec<(f32, f32, Suggestion)> = candidates
+        .into_iter()
+        .filter(|candidate| candidate.word_lower != typed)
+        .filter_map(|candidate| {
+            let distance = keyboard_distance(typed, &candidate.word_lower);
+            (distance <= max_distance).then(|| {
+                // Up to half an edit for the most frequent words
+                let bonus = f32::from(candidate.frequency.unwrap_or(0).clamp(0, 100)) / 200.0;
+                (
+                    distance - bonus,
+                    candidate.similarity,
+                    Suggestion {
+                        word_id: candidate.id,
+                        word: candidate.word,
+                        distance,
+                        frequency: candidate.frequency,
+                    },
+                )
+            })
+        })
+        .collect();
+    scored.sort_by(|a, b| a.0.total_cmp(&b.0).then(b.1.total_cmp(&a.1)));
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, _, suggestion)| suggestion)
+        .collect()
+}
+
+/// Edits tolerated for a word of `length` characters
+fn max_distance(length: usize) -> f32 {
+    match length {
+        0..=4 => 1.0,
+        5..=8 => 2.0,
+        _ => 3.0,
+    }
+}
+
+/// Row and column of a letter on a QWERTY keyboard
+fn key_position(c: char) -> Option<(i32, i32)> {
+    const ROWS: [&str; 3] = ["qwertyuiop", "asdfghjkl", "zxcvbnm"];
+    ROWS.iter()
+        .enumerate()
+        .find_map(|(row, keys)| keys.find(c).map(|col| (row as i32, col as i32)))
+}
+
+/// Whether two letters are next to each other on the keyboard
+///
+/// Each row is shifted right of the one above, so a key touches the key
+/// above it and the one above-right, and the key below it and below-left.
+fn adjacent_keys(a: char, b: char) -> bool {
+    let (Some((row_a, col_a)), Some((row_b, col_b))) = (key_position(a), key_position(b)) else {
+        return false;
+    };
+    match row_b - row_a {
+        0 => (col_a - col_b).abs() == 1,
+        -1 => col_b == col_a || col_b == col_a + 1,
+        1 => col_b == col_a || col_b == col_a - 1,
+        _ => false,
+    }
+}
+
+fn substitution_cost(a: char, b: char) -> f32 {
+    if a == b {
+        0.0
+    } else if adjacent_keys(a, b) {
+        SLIP_COST
+    } else {
+        1.0
+    }
+}
+
+/// Edit distance from `typed` to `word` with cheap keyboard slips
+///
+/// Optimal string alignment distance where substituting a neighbouring key,
+/// swapping two adjacent letters and adding or dropping a doubled letter cost
+/// [`SLIP_COST`], any other edit costs 1.
+pub fn keyboard_distance(typed: &str, word: &str) -> f32 {
+    let a: Vec<char> = typed.chars().collect();
+    let b: Vec<char> = word.chars().collect();
+    let mut d = vec![vec![0.0f32; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i as f32;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j as f32;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            // An extra letter typed twice, or a double letter typed once
+            let deletion = if i > 1 && a[i - 1] == a[i - 2] {
+                SLIP_COST
+            } else {
+                1.0
+            };
+            let insertion = if j > 1 && b[j - 1] == b[j - 2] {
+                SLIP_COST
+            } else {
+                1.0
+            };
+            let mut best = (d[i - 1][j] + deletion)
+                .min(d[i][j - 1] + insertion)
+                .min(d[i - 1][j - 1] + substitution_cost(a[i - 1], b[j - 1]));
+            if i > 1
+                && j > 1
+                && a[i - 1] == b[j - 2]
+                && a[i - 2] == b[j - 1]
+                && a[i - 1] != a[i - 2]
+            {
+                best = best.min(d[i - 2][j - 2] + SLIP_COST);
+            }
+            d[i][j] = best;
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slips_cost_half_an_edit() {
+        assert_eq!(keyboard_distance("hello", "hello"), 0.0);
+        // Swapped letters
+        assert_eq!(keyboard_distance("teh", "the"), 0.5);
+        // Neighbouring key, r is next to e
+        assert_eq!(keyboard_distance("hrllo", "hello"), 0.5);
+        // x is far from e
+        assert_eq!(keyboard_distance("hxllo", "hello"), 1.0);
+        // Both doubles typed once
+        assert_eq!(keyboard_distance("ocured", "occurred"), 1.0);
+        // A letter typed twice
+        assert_eq!(keyboard_distance("helllo", "hello"), 0.5);
+        assert_eq!(keyboard_distance("cat", "pin"), 3.0);
+    }
+
+    #[test]
+    fn neighbours_follow_the_row_shift() {
+        assert!(adjacent_keys('s', 'w'));
+        assert!(adjacent_keys('s', 'e'));
+        assert!(adjacent_keys('s', 'z'));
+        assert!(adjacent_keys('s', 'x'));
+        assert!(!adjacent_keys('s', 'q'));
+        assert!(!adjacent_keys('s', 'c'));
+        assert!(!adjacent_keys('é', 'e'));
+    }
+
+    #[test]
+    fn frequent_words_win_ties() {
+        let candidate = |id, word: &str, frequency| Candidate {
+            id,
+            word: word.to_owned(),
+            word_lower: word.to_owned(),
+            frequency: Some(frequency),
+            similarity: 0.0,
+        };
+        let suggestions = rank(
+            "teh",
+            vec![
+                candidate(1, "tea", 40),
+                candidate(2, "the", 100),
+                candidate(3, "teh", 0),
+                candidate(4, "zebra", 100),
+            ],
+            5,
+        );
+        let words: Vec<&str> = suggestions.iter().map(|s| s.word.as_str()).collect();
+        assert_eq!(words, ["the", "tea"]);
+    }
+}
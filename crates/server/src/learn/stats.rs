@@ -0,0 +1,621 @@
+//! Daily statistics derived from learning activity
+//!
+//! Every recorded activity updates the row of its local day right away, and a
+//! nightly job recomputes each day from the source tables so that missed or
+//! failed updates do not linger. Study time is measured from the gaps between
+//! activities: a gap up to [`IDLE_GAP_SECONDS`] counts as studying, anything
+//! longer starts a new burst worth [`EVENT_SECONDS`].
+
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, Duration, NaiveDate, Timelike, Utc};
+use chrono_tz::Tz;
+use diesel::prelude::*;
+
+use super::local_time::{day_bounds, local_date, user_timezone};
+use super::review::ReviewItemType;
+use super::shadowing::ShadowingSource;
+use super::{achievement, streak};
+use crate::config::AppConfig;
+use crate::db::schema::*;
+use crate::diesel_exists;
+use crate::models::achievement::NewUserXpHistory;
+use crate::models::learn::*;
+
+/// Longest pause that still counts as studying
+pub const IDLE_GAP_SECONDS: i64 = 5 * 60;
+/// Time credited for an activity that starts a new burst
+pub const EVENT_SECONDS: i32 = 60;
+/// Local hour at which the previous day is reconciled
+const RECONCILE_HOUR: u32 = 3;
+/// Users active within this window are considered for reconciliation
+const RECONCILE_LOOKBACK_HOURS: i64 = 48;
+
+/// Something the learner did that counts towards the daily statistics
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Activity {
+    /// The learner sent a chat message
+    ChatTurn { chat_id: i64 },
+    /// A practice was recorded; only practices graded by the server name an
+    /// item and earn XP
+    Practice { item: Option<PracticeItem> },
+    /// A word was added to the vocabulary
+    NewWord,
+    /// A word was graded by the scheduler; only words that were due earn XP
+    Review {
+        item_type: ReviewItemType,
+        item_id: i64,
+        correct: bool,
+        due: bool,
+    },
+    /// A quiz question was answered
+    QuizAnswer,
+    /// A review session or quiz was finished
+    SessionCompleted,
+}
+
+/// What a practice graded by the server was about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PracticeItem {
+    /// Dictation of a dictionary word
+    Dictation { word_id: i64 },
+    /// Reading aloud of a stored sentence
+    Reading { sentence_id: i64 },
+    /// Shadowing of a script turn or read sentence
+    Shadowing {
+        source: ShadowingSource,
+        source_id: i64,
+    },
+}
+
+impl Activity {
+    /// XP earned for the activity, added to the history the leaderboards sum
+    pub fn xp(self) -> i32 {
+        match self {
+            Activity::ChatTurn { .. } | Activity::QuizAnswer => 2,
+            Activity::Review { due: false, .. } => 0,
+            Activity::Review { correct, .. } => {
+                if correct {
+                    2
+                } else {
+                    1
+                }
+            }
+            Activity::Practice { item: Some(_) } => 5,
+            Activity::Practice { item: None } => 0,
+            Activity::SessionCompleted => 10,
+            Activity::NewWord => 0,
+        }
+    }
+
+    /// `source_type` and `source_id` of the XP history row
+    fn xp_source(self) -> (&'static str, Option<i64>) {
+        match self {
+            Activity::ChatTurn { chat_id } => ("chat", Some(chat_id)),
+            Activity::Practice { item } => match item {
+                Some(PracticeItem::Dictation { word_id }) => ("dictation", Some(word_id)),
+                Some(PracticeItem::Reading { sentence_id }) => ("reading", Some(sentence_id)),
+                Some(PracticeItem::Shadowing { source, source_id }) => match source {
+                    ShadowingSource::ScriptTurn => ("shadowing_script_turn", Some(source_id)),
+                    ShadowingSource::ReadSentence => ("shadowing_read_sentence", Some(source_id)),
+                },
+                None => ("practice", None),
+            },
+            Activity::Review {
+                item_type, item_id, ..
+            } => (item_type.as_str(), Some(item_id)),
+            Activity::NewWord => ("review", None),
+            Activity::QuizAnswer => ("quiz", None),
+            Activity::SessionCompleted => ("session", None),
+        }
+    }
+
+    /// Whether the XP is paid once a day per item, however often the item is
+    /// practiced or graded again
+    fn once_a_day(self) -> bool {
+        matches!(self, Activity::Practice { .. } | Activity::Review { .. })
+    }
+}
+
+/// Counter changes caused by one activity
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Counts {
+    words_practiced: i32,
+    sessions_completed: i32,
+    errors_corrected: i32,
+    new_words_learned: i32,
+    review_words_count: i32,
+}
+
+/// Study time credited for an activity at `at` following one at `previous`
+pub fn active_increment(previous: Option<DateTime<Utc>>, at: DateTime<Utc>) -> i32 {
+    match previous {
+        // Reported out of order, already covered by the later activity
+        Some(previous) if at < previous => 0,
+        Some(previous) if at - previous <= Duration::seconds(IDLE_GAP_SECONDS) => {
+            (at - previous).num_seconds() as i32
+        }
+        _ => EVENT_SECONDS,
+    }
+}
+
+/// Update today's statistics of the user for one activity and count the day
+/// towards their streak
+///
+/// Practices and reviews of the same item earn XP once per local day.
+pub fn record_activity(
+    conn: &mut PgConnection,
+    user_id: i64,
+    activity: Activity,
+    at: DateTime<Utc>,
+) -> QueryResult<()> {
+    let tz = user_timezone(conn, user_id)?;
+    let date = local_date(tz, at);
+
+    let mut counts = Counts::default();
+    match activity {
+        Activity::ChatTurn { chat_id } => {
+            // A chat counts as one session per day, on its first message
+            let (start, end) = day_bounds(tz, date);
+            let turns: i64 = learn_chat_turns::table
+                .filter(learn_chat_turns::user_id.eq(user_id))
+                .filter(learn_chat_turns::chat_id.eq(chat_id))
+                .filter(learn_chat_turns::speaker.eq("user"))
+                .filter(learn_chat_turns::created_at.ge(start))
+                .filter(learn_chat_turns::created_at.lt(end))
+                .count()
+                .get_result(conn)?;
+            if turns <= 1 {
+                counts.sessions_completed = 1;
+            }
+        }
+        Activity::Practice { .. } | Activity::QuizAnswer => counts.words_practiced = 1,
+        Activity::NewWord => counts.new_words_learned = 1,
+        Activity::Review {
+            item_type, correct, ..
+        } => {
+            counts.words_practiced = 1;
+            counts.review_words_count = 1;
+            if item_type == ReviewItemType::IssueWord && correct {
+                counts.errors_corrected = 1;
+            }
+        }
+        Activity::SessionCompleted => counts.sessions_completed = 1,
+    }
+    // Finishing a session is not an activity of its own, its last answer was
+    let timed = activity != Activity::SessionCompleted;
+
+    conn.transaction(|conn| {
+        diesel::insert_into(learn_daily_stats::table)
+            .values(&NewDailyStat {
+                user_id,
+                stat_date: date,
+                minutes_studied: Some(0),
+                words_practiced: Some(0),
+                sessions_completed: Some(0),
+                errors_corrected: Some(0),
+                new_words_learned: Some(0),
+                review_words_count: Some(0),
+                corrected_by: None,
+                corrected_at: None,
+            })
+            .on_conflict((learn_daily_stats::user_id, learn_daily_stats::stat_date))
+            .do_nothing()
+            .execute(conn)?;
+        let stat = learn_daily_stats::table
+            .filter(learn_daily_stats::user_id.eq(user_id))
+            .filter(learn_daily_stats::stat_date.eq(date))
+            .for_update()
+            .first::<DailyStat>(conn)?;
+
+        let (active_seconds, last_activity_at) = if timed {
+            (
+                stat.active_seconds + active_increment(stat.last_activity_at, at),
+                stat.last_activity_at.max(Some(at)),
+            )
+        } else {
+            (stat.active_seconds, stat.last_activity_at)
+        };
+        let add = |current: Option<i32>, delta: i32| Some(current.unwrap_or(0) + delta);
+
+        diesel::update(learn_daily_stats::table.find(stat.id))
+            .set(&UpdateDailyStat {
+                minutes_studied: Some(active_seconds / 60),
+                words_practiced: add(stat.words_practiced, counts.words_practiced),
+                sessions_completed: add(stat.sessions_completed, counts.sessions_completed),
+                errors_corrected: add(stat.errors_corrected, counts.errors_corrected),
+                new_words_learned: add(stat.new_words_learned, counts.new_words_learned),
+                review_words_count: add(stat.review_words_count, counts.review_words_count),
+                active_seconds: Some(active_seconds),
+                last_activity_at,
+                ..Default::default()
+            })
+            .execute(conn)?;
+        let (source_type, source_id) = activity.xp_source();
+        let mut xp = activity.xp();
+        if xp > 0 && activity.once_a_day() {
+            let (start, end) = day_bounds(tz, date);
+            let paid = diesel_exists!(
+                archive_user_xp_history::table
+                    .filter(archive_user_xp_history::user_id.eq(user_id))
+                    .filter(archive_user_xp_history::source_type.eq(source_type))
+                    .filter(archive_user_xp_history::source_id.eq(source_id))
+                    .filter(archive_user_xp_history::created_at.ge(start))
+                    .filter(archive_user_xp_history::created_at.lt(end)),
+                conn
+            )?;
+            if paid {
+                xp = 0;
+            }
+        }
+        if xp > 0 {
+            achievement::award_xp(
+                conn,
+                &NewUserXpHistory {
+                    user_id,
+                    xp_amount: xp,
+                    source_type: source_type.to_owned(),
+                    source_id,
+                    description: None,
+                },
+                at,
+            )?;
+        }
+        streak::record_study(
+            conn,
+            user_id,
+            date,
+            active_seconds / 60,
+            at,
+            &AppConfig::get().streak,
+        )
+    })
+}
+
+/// Source rows of one local day, as loaded by [`reconcile_day`]
+#[derive(Debug, Default)]
+struct DayActivity {
+    /// Chat and time of the learner's chat turns
+    chat_turns: Vec<(i64, DateTime<Utc>)>,
+    /// Item type, grade and time of reviews
+    reviews: Vec<(String, String, DateTime<Utc>)>,
+    quiz_answers: Vec<DateTime<Utc>>,
+    write_practices: Vec<DateTime<Utc>>,
+    read_practices: Vec<DateTime<Utc>>,
+    new_words: Vec<DateTime<Utc>>,
+    completed_sessions: i64,
+    completed_quizzes: i64,
+}
+
+impl DayActivity {
+    fn is_empty(&self) -> bool {
+        self.events().is_empty()
+    }
+
+    /// Times of everything that counts as studying, in order
+    fn events(&self) -> Vec<DateTime<Utc>> {
+        let mut events: Vec<DateTime<Utc>> = self.chat_turns.iter().map(|(_, at)| *at).collect();
+        events.extend(self.reviews.iter().map(|(_, _, at)| *at));
+        events.extend(&self.quiz_answers);
+        events.extend(&self.write_practices);
+        events.extend(&self.read_practices);
+        events.extend(&self.new_words);
+        events.sort();
+        events
+    }
+
+    /// Counters of the day, replacing whatever the row held
+    fn totals(&self) -> UpdateDailyStat {
+        let events = self.events();
+        let mut active_seconds = 0;
+        let mut previous = None;
+        for at in &events {
+            active_seconds += active_increment(previous, *at);
+            previous = Some(*at);
+        }
+
+        let chats: BTreeSet<i64> = self
+            .chat_turns
+            .iter()
+            .map(|(chat_id, _)| *chat_id)
+            .collect();
+        let errors_corrected = self
+            .reviews
+            .iter()
+            .filter(|(item_type, grade, _)| {
+                item_type == ReviewItemType::IssueWord.as_str() && grade != "again"
+            })
+            .count();
+        let words_practiced = self.reviews.len()
+            + self.quiz_answers.len()
+            + self.write_practices.len()
+            + self.read_practices.len();
+        let sessions_completed =
+            chats.len() as i64 + self.completed_sessions + self.completed_quizzes;
+
+        UpdateDailyStat {
+            minutes_studied: Some(active_seconds / 60),
+            words_practiced: Some(words_practiced as i32),
+            sessions_completed: Some(sessions_completed as i32),
+            errors_corrected: Some(errors_corrected as i32),
+            new_words_learned: Some(self.new_words.len() as i32),
+            review_words_count: Some(self.reviews.len() as i32),
+            active_seconds: Some(active_seconds),
+            last_activity_at: previous,
+            ..Default::default()
+        }
+    }
+}
+
+/// Recompute one local day of a user from the source tables
+///
+/// Rows corrected by an admin are left alone. Returns `None` when the row was
+/// skipped or there was no activity on that day.
+pub fn reconcile_day(
+    conn: &mut PgConnection,
+    user_id: i64,
+    date: NaiveDate,
+    tz: Tz,
+) -> QueryResult<Option<DailyStat>> {
+    let (start, end) = day_bounds(tz, date);
+
+    let existing = learn_daily_stats::table
+        .filter(learn_daily_stats::user_id.eq(user_id))
+        .filter(learn_daily_stats::stat_date.eq(date))
+        .first::<DailyStat>(conn)
+        .optional()?;
+    if existing.as_ref().is_some_and(|s| s.corrected_at.is_some()) {
+        return Ok(None);
+    }
+
+    let chat_turns = learn_chat_turns::table
+        .filter(learn_chat_turns::user_id.eq(user_id))
+        .filter(learn_chat_turns::speaker.eq("user"))
+        .filter(learn_chat_turns::created_at.ge(start))
+        .filter(learn_chat_turns::created_at.lt(end))
+        .select((learn_chat_turns::chat_id, learn_chat_turns::created_at))
+        .load::<(i64, DateTime<Utc>)>(conn)?;
+    let reviews = learn_review_logs::table
+        .filter(learn_review_logs::user_id.eq(user_id))
+        .filter(learn_review_logs::reviewed_at.ge(start))
+        .filter(learn_review_logs::reviewed_at.lt(end))
+        .select((
+            learn_review_logs::item_type,
+            learn_review_logs::grade,
+            learn_review_logs::reviewed_at,
+        ))
+        .load::<(String, String, DateTime<Utc>)>(conn)?;
+    let quiz_answers = learn_quiz_questions::table
+        .filter(learn_quiz_questions::user_id.eq(user_id))
+        .filter(learn_quiz_questions::answered_at.ge(start))
+        .filter(learn_quiz_questions::answered_at.lt(end))
+        .select(learn_quiz_questions::answered_at.assume_not_null())
+        .load::<DateTime<Utc>>(conn)?;
+    let write_practices = learn_write_practices::table
+        .filter(learn_write_practices::user_id.eq(user_id))
+        .filter(learn_write_practices::created_at.ge(start))
+        .filter(learn_write_practices::created_at.lt(end))
+        .select(learn_write_practices::created_at)
+        .load::<DateTime<Utc>>(conn)?;
+    let read_practices = learn_read_practices::table
+        .filter(learn_read_practices::user_id.eq(user_id))
+        .filter(learn_read_practices::created_at.ge(start))
+        .filter(learn_read_practices::created_at.lt(end))
+        .select(learn_read_practices::created_at)
+        .load::<DateTime<Utc>>(conn)?;
+    let new_words = learn_vocabularies::table
+        .filter(learn_vocabularies::user_id.eq(user_id))
+        .filter(learn_vocabularies::first_seen_at.ge(start))
+        .filter(learn_vocabularies::first_seen_at.lt(end))
+        .select(learn_vocabularies::first_seen_at)
+        .load::<DateTime<Utc>>(conn)?;
+    let completed_sessions: i64 = learn_review_sessions::table
+        .filter(learn_review_sessions::user_id.eq(user_id))
+        .filter(learn_review_sessions::completed_at.ge(start))
+        .filter(learn_review_sessions::completed_at.lt(end))
+        .filter(learn_review_sessions::item_count.gt(0))
+        .count()
+        .get_result(conn)?;
+    let completed_quizzes: i64 = learn_quizzes::table
+        .filter(learn_quizzes::user_id.eq(user_id))
+        .filter(learn_quizzes::completed_at.ge(start))
+        .filter(learn_quizzes::completed_at.lt(end))
+        .count()
+        .get_result(conn)?;
+
+    let activity = DayActivity {
+        chat_turns,
+        reviews,
+        quiz_answers,
+        write_practices,
+        read_practices,
+        new_words,
+        completed_sessions,
+        completed_quizzes,
+    };
+    if activity.is_empty() && existing.is_none() {
+        return Ok(None);
+    }
+    let values = activity.totals();
+    diesel::insert_into(learn_daily_stats::table)
+        .values(&NewDailyStat {
+            user_id,
+            stat_date: date,
+            minutes_studied: None,
+            words_practiced: None,
+            sessions_completed: None,
+            errors_corrected: None,
+            new_words_learned: None,
+            review_words_count: None,
+            corrected_by: None,
+            corrected_at: None,
+        })
+        .on_conflict((learn_daily_stats::user_id, learn_daily_stats::stat_date))
+        .do_nothing()
+        .execute(conn)?;
+    diesel::update(learn_daily_stats::table)
+        .filter(learn_daily_stats::user_id.eq(user_id))
+        .filter(learn_daily_stats::stat_date.eq(date))
+        .set(&values)
+        .get_result::<DailyStat>(conn)
+        .map(Some)
+}
+
+/// Reconcile yesterday for every recently active user whose local time is
+/// now at the reconciliation hour
+///
+/// Meant to run once an hour; returns the number of days reconciled.
+pub fn reconcile_due_users(conn: &mut PgConnection, now: DateTime<Utc>) -> QueryResult<usize> {
+    let mut reconciled = 0;
+    for user_id in recently_active_users(conn, now)? {
+        let tz = user_timezone(conn, user_id)?;
+        let local = now.with_timezone(&tz);
+        if local.hour() != RECONCILE_HOUR {
+            continue;
+        }
+        let Some(yesterday) = local.date_naive().pred_opt() else {
+            continue;
+        };
+        if reconcile_day(conn, user_id, yesterday, tz)?.is_some() {
+            reconciled += 1;
+        }
+    }
+    Ok(reconciled)
+}
+
+fn recently_active_users(
+    conn: &mut PgConnection,
+    now: DateTime<Utc>,
+) -> QueryResult<BTreeSet<i64>> {
+    let since = now - Duration::hours(RECONCILE_LOOKBACK_HOURS);
+    let mut users = BTreeSet::new();
+    users.extend(
+        learn_chat_turns::table
+            .filter(learn_chat_turns::created_at.ge(since))
+            .select(learn_chat_turns::user_id)
+            .distinct()
+            .load::<i64>(conn)?,
+    );
+    users.extend(
+        learn_review_logs::table
+            .filter(learn_review_logs::reviewed_at.ge(since))
+            .select(learn_review_logs::user_id)
+            .distinct()
+            .load::<i64>(conn)?,
+    );
+    users.extend(
+        learn_quiz_questions::table
+            .filter(learn_quiz_questions::answered_at.ge(since))
+            .select(learn_quiz_questions::user_id)
+            .distinct()
+            .load::<i64>(conn)?,
+    );
+    users.extend(
+        learn_write_practices::table
+            .filter(learn_write_practices::created_at.ge(since))
+            .select(learn_write_practices::user_id)
+            .distinct()
+            .load::<i64>(conn)?,
+    );
+    users.extend(
+        learn_read_practices::table
+            .filter(learn_read_practices::created_at.ge(since))
+            .select(learn_read_practices::user_id)
+            .distinct()
+            .load::<i64>(conn)?,
+    );
+    users.extend(
+        learn_vocabularies::table
+            .filter(learn_vocabularies::first_seen_at.ge(since))
+            .select(learn_vocabularies::user_id)
+            .distinct()
+            .load::<i64>(conn)?,
+    );
+    Ok(users)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(seconds: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + seconds, 0).unwrap()
+    }
+
+    #[test]
+    fn active_time_between_activities() {
+        // First activity of the day starts a burst
+        assert_eq!(active_increment(None, at(0)), EVENT_SECONDS);
+        // Short pauses count in full
+        assert_eq!(active_increment(Some(at(0)), at(90)), 90);
+        assert_eq!(active_increment(Some(at(0)), at(IDLE_GAP_SECONDS)), 300);
+        // Longer pauses start a new burst
+        assert_eq!(
+            active_increment(Some(at(0)), at(IDLE_GAP_SECONDS + 1)),
+            EVENT_SECONDS
+        );
+        assert_eq!(active_increment(Some(at(100)), at(50)), 0);
+    }
+
+    #[test]
+    fn xp_for_graded_and_due_items() {
+        let reported = Activity::Practice { item: None };
+        assert_eq!(reported.xp(), 0);
+        let dictation = Activity::Practice {
+            item: Some(PracticeItem::Dictation { word_id: 7 }),
+        };
+        assert_eq!(dictation.xp(), 5);
+        assert_eq!(dictation.xp_source(), ("dictation", Some(7)));
+        assert!(dictation.once_a_day());
+
+        let review = |due| Activity::Review {
+            item_type: ReviewItemType::IssueWord,
+            item_id: 3,
+            correct: true,
+            due,
+        };
+        assert_eq!(review(true).xp(), 2);
+        assert_eq!(review(false).xp(), 0);
+        assert_eq!(review(true).xp_source(), ("issue_word", Some(3)));
+        assert!(!Activity::ChatTurn { chat_id: 1 }.once_a_day());
+    }
+
+    #[test]
+    fn empty_day_resets_the_counters() {
+        let activity = DayActivity::default();
+        assert!(activity.is_empty());
+        let totals = activity.totals();
+        assert_eq!(totals.minutes_studied, Some(0));
+        assert_eq!(totals.words_practiced, Some(0));
+        assert_eq!(totals.sessions_completed, Some(0));
+        assert_eq!(totals.active_seconds, Some(0));
+        assert_eq!(totals.last_activity_at, None);
+    }
+
+    #[test]
+    fn totals_of_a_day() {
+        let activity = DayActivity {
+            chat_turns: vec![(1, at(0)), (1, at(60)), (2, at(4000))],
+            reviews: vec![
+                ("issue_word".to_owned(), "good".to_owned(), at(120)),
+                ("issue_word".to_owned(), "again".to_owned(), at(180)),
+                ("vocabulary".to_owned(), "easy".to_owned(), at(240)),
+            ],
+            quiz_answers: vec![at(300)],
+            new_words: vec![at(3990)],
+            completed_quizzes: 1,
+            ..Default::default()
+        };
+        assert!(!activity.is_empty());
+        let totals = activity.totals();
+        // 0..300 studied, then a new burst at 3990 followed by 10 seconds
+        assert_eq!(totals.active_seconds, Some(60 + 300 + EVENT_SECONDS + 10));
+        assert_eq!(totals.minutes_studied, Some(7));
+        assert_eq!(totals.words_practiced, Some(4));
+        assert_eq!(totals.sessions_completed, Some(3));
+        assert_eq!(totals.errors_corrected, Some(1));
+        assert_eq!(totals.new_words_learned, Some(1));
+        assert_eq!(totals.review_words_count, Some(3));
+        assert_eq!(totals.last_activity_at, Some(at(4000)));
+    }
+}
@@ -0,0 +1,391 @@
+//! Daily learning streaks
+//!
+//! A local day counts once the learner studied the configured number of
+//! minutes on it. Missed days are covered by streak freezes while there are
+//! any, otherwise the streak breaks. Activity settles missed days as it comes
+//! in, an hourly job does it for everyone else right after their midnight.
+
+use std::collections::{BTreeMap, HashSet};
+
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use chrono_tz::Tz;
+use diesel::prelude::*;
+use salvo::oapi::ToSchema;
+use serde::Serialize;
+
+use super::local_time::{local_date, parse_timezone};
+use crate::config::StreakConfig;
+use crate::db::schema::*;
+use crate::models::achievement::{NewUserProfile, UserProfile};
+use crate::models::learn::NewStreakFreeze;
+
+/// Streak fields of a profile
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreakState {
+    pub current: i32,
+    pub longest: i32,
+    /// Last day that counted or was covered by a freeze
+    pub last_date: Option<NaiveDate>,
+    pub freezes: i32,
+}
+
+impl StreakState {
+    fn of(profile: &UserProfile) -> Self {
+        Self {
+            current: profile.current_streak_days,
+            longest: profile.longest_streak_days,
+            last_date: profile.last_activity_date,
+            freezes: profile.streak_freezes,
+        }
+    }
+
+    /// Cover the days missed before `today` with freezes, or break the streak
+    /// when there are not enough freezes for all of them
+    ///
+    /// A streak that breaks anyway keeps its freezes. Returns the days a
+    /// freeze was spent on.
+    pub fn settle(&mut self, today: NaiveDate) -> Vec<NaiveDate> {
+        let Some(last) = self.last_date else {
+            return Vec::new();
+        };
+        if self.current == 0 {
+            return Vec::new();
+        }
+        let missed: Vec<NaiveDate> = last
+            .iter_days()
+            .skip(1)
+            .take_while(|day| *day < today)
+            .collect();
+        if missed.is_empty() {
+            return missed;
+        }
+        if missed.len() > self.freezes.max(0) as usize {
+            self.current = 0;
+            return Vec::new();
+        }
+        self.freezes -= missed.len() as i32;
+        self.last_date = missed.last().copied();
+        missed
+    }
+
+    /// Count `today` towards the streak, settling missed days first
+    ///
+    /// Returns the days a freeze was spent on.
+    pub fn study(&mut self, today: NaiveDate, config: &StreakConfig) -> Vec<NaiveDate> {
+        let frozen = self.settle(today);
+        // Already counted, or a later day was after a time zone change
+        if self.last_date.is_some_and(|last| last >= today) && self.current > 0 {
+            return frozen;
+        }
+        let continues =
+            self.current > 0 && self.last_date.and_then(|d| d.succ_opt()) == Some(today);
+        self.current = if continues { self.current + 1 } else { 1 };
+        self.longest = self.longest.max(self.current);
+        self.last_date = Some(today);
+        if config.freeze_every_days > 0
+            && self.current % config.freeze_every_days == 0
+            && self.freezes < config.max_freezes
+        {
+            self.freezes += 1;
+        }
+        frozen
+    }
+}
+
+/// One day of the streak calendar
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StreakDay {
+    pub date: NaiveDate,
+    pub minutes_studied: i32,
+    /// Enough study for the day to count
+    pub active: bool,
+    /// Missed day covered by a streak freeze
+    pub frozen: bool,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct StreakView {
+    pub current_streak_days: i32,
+    pub longest_streak_days: i32,
+    pub last_activity_date: Option<NaiveDate>,
+    pub freezes_available: i32,
+    /// Minutes of study needed for a day to count
+    pub min_minutes: i32,
+    /// Today in the learner's time zone
+    pub today: NaiveDate,
+    /// Days between `from` and `to` with any study or a freeze, oldest first
+    pub days: Vec<StreakDay>,
+}
+
+/// Count `date` towards the streak of `user_id` once `minutes_studied`
+/// reaches the minimum
+pub fn record_study(
+    conn: &mut PgConnection,
+    user_id: i64,
+    date: NaiveDate,
+    minutes_studied: i32,
+    at: DateTime<Utc>,
+    config: &StreakConfig,
+) -> QueryResult<()> {
+    if minutes_studied < config.min_minutes {
+        return Ok(());
+    }
+    conn.transaction(|conn| {
+        let profile = lock_profile(conn, user_id)?;
+        if profile.last_activity_date == Some(date) && profile.current_streak_days > 0 {
+            return Ok(());
+        }
+        let mut state = StreakState::of(&profile);
+        let frozen = state.study(date, config);
+        save(conn, user_id, &state, &frozen, at)
+    })
+}
+
+/// Settle missed days of every running streak whose local day has ended
+///
+/// Meant to run every hour; returns the number of streaks changed.
+pub fn settle_due_streaks(conn: &mut PgConnection, now: DateTime<Utc>) -> QueryResult<usize> {
+    // Local dates are at most one day ahead of UTC, so a streak that counted
+    // the UTC date of today cannot have missed a local day yet
+    let candidates = archive_user_profiles::table
+        .filter(archive_user_profiles::current_streak_days.gt(0))
+        .filter(archive_user_profiles::last_activity_date.lt(now.date_naive()))
+        .select(archive_user_profiles::user_id)
+        .load::<i64>(conn)?;
+
+    let mut settled = 0;
+    for user_id in candidates {
+        let changed = conn.transaction(|conn| {
+            let profile = lock_profile(conn, user_id)?;
+            let tz = parse_timezone(&profile.timezone).unwrap_or(Tz::UTC);
+            let mut state = StreakState::of(&profile);
+            let frozen = state.settle(local_date(tz, now));
+            if state == StreakState::of(&profile) {
+                return Ok(false);
+            }
+            save(conn, user_id, &state, &frozen, now)?;
+            Ok::<_, diesel::result::Error>(true)
+        })?;
+        if changed {
+            settled += 1;
+        }
+    }
+    Ok(settled)
+}
+
+/// Streak of `user_id` with the calendar of `from` to `to`
+///
+/// Missed days that are not settled yet are already taken into account.
+pub fn streak_view(
+    conn: &mut PgConnection,
+    user_id: i64,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    now: DateTime<Utc>,
+    config: &StreakConfig,
+) -> QueryResult<StreakView> {
+    let profile = archive_user_profiles::table
+        .filter(archive_user_profiles::user_id.eq(user_id))
+        .first::<UserProfile>(conn)
+        .optional()?;
+    let tz = profile
+        .as_ref()
+        .and_then(|p| parse_timezone(&p.timezone))
+        .unwrap_or(Tz::UTC);
+    let today = local_date(tz, now);
+    let to = to.unwrap_or(today);
+    let from = from.unwrap_or(to - Days::new(29));
+
+    let mut state = profile
+        .as_ref()
+        .map(StreakState::of)
+        .unwrap_or(StreakState {
+            current: 0,
+            longest: 0,
+            last_date: None,
+            freezes: 0,
+        });
+    let pending: HashSet<NaiveDate> = state.settle(today).into_iter().collect();
+
+    let minutes: BTreeMap<NaiveDate, i32> = learn_daily_stats::table
+        .filter(learn_daily_stats::user_id.eq(user_id))
+        .filter(learn_daily_stats::stat_date.ge(from))
+        .filter(learn_daily_stats::stat_date.le(to))
+        .select((
+            learn_daily_stats::stat_date,
+            learn_daily_stats::minutes_studied,
+        ))
+        .load::<(NaiveDate, Option<i32>)>(conn)?
+        .into_iter()
+        .map(|(date, minutes)| (date, minutes.unwrap_or(0)))
+        .collect();
+    let mut frozen: HashSet<NaiveDate> = learn_streak_freezes::table
+        .filter(learn_streak_freezes::user_id.eq(user_id))
+        .filter(learn_streak_freezes::frozen_date.ge(from))
+        .filter(learn_streak_freezes::frozen_date.le(to))
+        .select(learn_streak_freezes::frozen_date)
+        .load::<NaiveDate>(conn)?
+        .into_iter()
+        .collect();
+    frozen.extend(pending.into_iter().filter(|d| (from..=to).contains(d)));
+
+    let mut dates: Vec<NaiveDate> = minutes.keys().chain(&frozen).copied().collect();
+    dates.sort();
+    dates.dedup();
+    let days = dates
+        .into_iter()
+        .map(|date| {
+            let minutes_studied = minutes.get(&date).copied().unwrap_or(0);
+            StreakDay {
+                date,
+                minutes_studied,
+                active: minutes_studied > 0 && minutes_studied >= config.min_minutes,
+                frozen: frozen.contains(&date),
+            }
+        })
+        .collect();
+
+    Ok(StreakView {
+        current_streak_days: state.current,
+        longest_streak_days: state.longest,
+        last_activity_date: state.last_date,
+        freezes_available: state.freezes,
+        min_minutes: config.min_minutes,
+        today,
+        days,
+    })
+}
+
+fn lock_profile(conn: &mut PgConnection, user_id: i64) -> QueryResult<UserProfile> {
+    diesel::insert_into(archive_user_profiles::table)
+        .values(&NewUserProfile { user_id })
+        .on_conflict(archive_user_profiles::user_id)
+        .do_nothing()
+        .execute(conn)?;
+    archive_user_profiles::table
+        .filter(archive_user_profiles::user_id.eq(user_id))
+        .for_update()
+        .first::<UserProfile>(conn)
+}
+
+fn save(
+    conn: &mut PgConnection,
+    user_id: i64,
+    state: &StreakState,
+    frozen: &[NaiveDate],
+    at: DateTime<Utc>,
+) -> QueryResult<()> {
+    diesel::update(archive_user_profiles::table)
+        .filter(archive_user_profiles::user_id.eq(user_id))
+        .set((
+            archive_user_profiles::current_streak_days.eq(state.current),
+            archive_user_profiles::longest_streak_days.eq(state.longest),
+            archive_user_profiles::last_activity_date.eq(state.last_date),
+            archive_user_profiles::streak_freezes.eq(state.freezes),
+            archive_user_profiles::updated_at.eq(at),
+        ))
+        .execute(conn)?;
+    if !frozen.is_empty() {
+        let rows: Vec<NewStreakFreeze> = frozen
+            .iter()
+            .map(|&frozen_date| NewStreakFreeze {
+                user_id,
+                frozen_date,
+            })
+            .collect();
+        diesel::insert_into(learn_streak_freezes::table)
+            .values(&rows)
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 3, d).unwrap()
+    }
+
+    fn state(current: i32, last: u32, freezes: i32) -> StreakState {
+        StreakState {
+            current,
+            longest: current,
+            last_date: Some(day(last)),
+            freezes,
+        }
+    }
+
+    #[test]
+    fn consecutive_days_extend_the_streak() {
+        let config = StreakConfig::default();
+        let mut s = state(3, 10, 0);
+        assert!(s.study(day(11), &config).is_empty());
+        assert_eq!(s.current, 4);
+        assert_eq!(s.longest, 4);
+        assert_eq!(s.last_date, Some(day(11)));
+
+        // Counting the same day twice changes nothing
+        s.study(day(11), &config);
+        assert_eq!(s.current, 4);
+    }
+
+    #[test]
+    fn missed_day_breaks_without_freezes() {
+        let config = StreakConfig::default();
+        let mut s = state(5, 10, 0);
+        s.settle(day(12));
+        assert_eq!(s.current, 0);
+        assert_eq!(s.longest, 5);
+
+        s.study(day(13), &config);
+        assert_eq!(s.current, 1);
+        assert_eq!(s.longest, 5);
+    }
+
+    #[test]
+    fn freezes_cover_missed_days() {
+        let config = StreakConfig::default();
+        let mut s = state(5, 10, 2);
+        let frozen = s.study(day(13), &config);
+        assert_eq!(frozen, vec![day(11), day(12)]);
+        assert_eq!(s.freezes, 0);
+        assert_eq!(s.current, 6);
+    }
+
+    #[test]
+    fn too_few_freezes_are_kept() {
+        let mut s = state(5, 10, 2);
+        // Three missed days, two freezes cannot save the streak
+        let frozen = s.settle(day(14));
+        assert!(frozen.is_empty());
+        assert_eq!(s.current, 0);
+        assert_eq!(s.freezes, 2);
+        assert_eq!(s.last_date, Some(day(10)));
+    }
+
+    #[test]
+    fn today_is_not_missed_yet() {
+        let mut s = state(5, 10, 0);
+        assert!(s.settle(day(11)).is_empty());
+        assert_eq!(s.current, 5);
+    }
+
+    #[test]
+    fn freezes_are_earned_up_to_the_limit() {
+        let config = StreakConfig {
+            min_minutes: 5,
+            freeze_every_days: 7,
+            max_freezes: 1,
+        };
+        let mut s = state(6, 10, 0);
+        s.study(day(11), &config);
+        assert_eq!(s.freezes, 1);
+
+        let mut s = state(13, 10, 1);
+        s.study(day(11), &config);
+        assert_eq!(s.freezes, 1);
+    }
+}
@@ -7,6 +7,8 @@ pub mod db;
 mod error;
 mod global;
 mod hoops;
+pub mod jobs;
+pub mod learn;
 pub mod models;
 mod routing;
 pub mod services;
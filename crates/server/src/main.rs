@@ -7,6 +7,8 @@ mod db;
 mod error;
 mod global;
 mod hoops;
+mod jobs;
+mod learn;
 mod models;
 mod routing;
 mod services;
@@ -43,6 +45,7 @@ async fn main() {
     let bind_addr = app_config.bind_addr.clone();
 
     db::init(&app_config.database);
+    jobs::start();
 
     salvo::http::request::set_global_secure_max_size(100_000_000);
     let router = routing::router();
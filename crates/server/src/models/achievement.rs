@@ -71,6 +71,21 @@ pub struct UserProfile {
     pub total_sessions: i32,
     pub joined_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Language the user already speaks (ISO 639-1)
+    pub native_lang: String,
+    /// Language the user is learning (ISO 639-1)
+    pub target_lang: String,
+    /// IANA time zone used for day boundaries, e.g. "Asia/Shanghai"
+    pub timezone: String,
+    /// Streak freezes earned and not spent yet
+    pub streak_freezes: i32,
+    /// Hidden from leaderboards and leagues
+    pub leaderboard_opt_out: bool,
+    /// League tier joined next week, 1 is the lowest
+    pub league_tier: i32,
+    /// Level from the placement test, 1-10 like asset difficulties
+    pub placement_level: Option<i16>,
+    pub placed_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Insertable)]
@@ -92,6 +107,14 @@ pub struct UpdateUserProfile {
     pub total_stages: Option<i32>,
     pub total_sessions: Option<i32>,
     pub updated_at: Option<DateTime<Utc>>,
+    pub native_lang: Option<String>,
+    pub target_lang: Option<String>,
+    pub timezone: Option<String>,
+    pub streak_freezes: Option<i32>,
+    pub leaderboard_opt_out: Option<bool>,
+    pub league_tier: Option<i32>,
+    pub placement_level: Option<i16>,
+    pub placed_at: Option<DateTime<Utc>>,
 }
 
 // ============================================================================
@@ -156,6 +179,50 @@ pub struct NewUserXpHistory {
     pub description: Option<String>,
 }
 
+// ============================================================================
+// Leagues (weekly cohorts)
+// ============================================================================
+
+#[derive(Queryable, Identifiable, Serialize, ToSchema, Debug, Clone)]
+#[diesel(table_name = archive_league_cohorts)]
+pub struct LeagueCohort {
+    pub id: i64,
+    pub week_start: NaiveDate,
+    pub tier: i32,
+    pub member_count: i32,
+    pub finalized_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = archive_league_cohorts)]
+pub struct NewLeagueCohort {
+    pub week_start: NaiveDate,
+    pub tier: i32,
+}
+
+#[derive(Queryable, Identifiable, Serialize, ToSchema, Debug, Clone)]
+#[diesel(table_name = archive_league_members)]
+pub struct LeagueMember {
+    pub id: i64,
+    pub cohort_id: i64,
+    pub user_id: i64,
+    pub week_start: NaiveDate,
+    pub weekly_xp: i32,
+    pub final_position: Option<i32>,
+    /// promoted | demoted | stayed, once the week is over
+    pub outcome: Option<String>,
+    pub joined_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = archive_league_members)]
+pub struct NewLeagueMember {
+    pub cohort_id: i64,
+    pub user_id: i64,
+    pub week_start: NaiveDate,
+}
+
 // ============================================================================
 // API Response Types
 // ============================================================================
@@ -219,7 +286,7 @@ impl From<RankDefinition> for RankInfo {
 }
 
 /// Simplified achievement badge for display
-#[derive(Serialize, Debug)]
+#[derive(Serialize, ToSchema, Debug, Clone)]
 pub struct AchievementBadge {
     pub code: String,
     pub name_en: String,
@@ -107,7 +107,8 @@ pub struct Role {
     pub code: Option<String>,
     pub name: String,
     pub kind: String,
-    pub owner_id: i64,
+    /// None once the owner's account is deleted
+    pub owner_id: Option<i64>,
     pub description: Option<String>,
     pub updated_by: Option<i64>,
     pub updated_at: DateTime<Utc>,
@@ -134,6 +135,35 @@ pub struct RoleUser {
     pub user_id: i64,
 }
 
+#[derive(Identifiable, Queryable, Serialize, ToSchema, Clone, Debug)]
+#[diesel(table_name = base_data_exports)]
+pub struct DataExport {
+    pub id: i64,
+    pub user_id: i64,
+    /// pending | ready | failed | expired
+    pub status: String,
+    #[serde(skip)]
+    pub file_path: Option<String>,
+    pub file_size: Option<i64>,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Identifiable, Queryable, Serialize, ToSchema, Clone, Debug)]
+#[diesel(table_name = base_account_deletions)]
+pub struct AccountDeletion {
+    pub id: i64,
+    pub user_id: i64,
+    pub requested_at: DateTime<Utc>,
+    /// The account is deleted at this time unless the request is cancelled
+    pub scheduled_for: DateTime<Utc>,
+    pub cancelled_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub removed: Option<Value>,
+}
+
 // #[derive(Identifiable, Insertable, Queryable, Serialize, ToSchema, Clone, Debug)]
 // pub struct Operation {
 //     pub id: i64,
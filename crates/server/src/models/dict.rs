@@ -149,6 +149,20 @@ pub struct Pronunciation {
     pub created_at: DateTime<Utc>,
 }
 
+impl Pronunciation {
+    /// URL clients can play this pronunciation from
+    ///
+    /// Imported pronunciations only have a file under the space path, those
+    /// are served by `/api/dict/pronunciations/{id}/audio`.
+    pub fn playable_url(&self) -> Option<String> {
+        self.audio_url.clone().or_else(|| {
+            self.audio_path
+                .as_ref()
+                .map(|_| format!("/api/dict/pronunciations/{}/audio", self.id))
+        })
+    }
+}
+
 #[derive(Insertable, Deserialize)]
 #[diesel(table_name = dict_pronunciations)]
 pub struct NewPronunciation {
@@ -265,9 +279,25 @@ pub struct RelationWithWord {
     pub relation_strength: Option<i16>,
 }
 
+/// How an inflected lookup was brought back to its dictionary entry
+#[derive(Serialize, ToSchema, Debug, Clone)]
+pub struct MatchedForm {
+    /// What was looked up, e.g. "went"
+    pub form: String,
+    /// plural | past | present_participle | comparative | ... (see `dict_forms`)
+    pub form_type: Option<String>,
+    pub lemma: String,
+    /// e.g. "past tense of go"
+    pub description: String,
+    /// Found by stripping a suffix rather than listed in `dict_forms`
+    pub guessed: bool,
+}
+
 #[derive(Serialize, ToSchema, Debug)]
 pub struct WordQueryResponse {
     pub word: Word,
+    /// Set when the word looked up is an inflected form of `word`
+    pub matched_form: Option<MatchedForm>,
     pub definitions: Vec<Definition>,
     pub sentences: Vec<Sentence>,
     pub pronunciations: Vec<Pronunciation>,
@@ -27,6 +27,9 @@ pub struct IssueWord {
     pub context: Option<String>,
     pub audio_timestamp: Option<i32>,
     pub created_at: DateTime<Utc>,
+    pub ease_factor: f32,
+    pub mastery_level: i32,
+    pub correct_count: i32,
 }
 
 #[derive(Insertable, Deserialize)]
@@ -98,6 +101,29 @@ pub struct ChatTurn {
     pub status: String,
     pub error: Option<String>,
     pub created_at: DateTime<Utc>,
+    pub target_lang: String,
+    pub native_lang: String,
+    /// Text keyed by language code, e.g. `{"en": "...", "ja": "..."}`
+    pub contents: Value,
+}
+
+impl ChatTurn {
+    /// Text of this turn in the given language, empty if there is none
+    pub fn content_in(&self, lang: &str) -> &str {
+        match self.contents.get(lang).and_then(|v| v.as_str()) {
+            Some(text) => text,
+            None => match lang {
+                "en" => &self.content_en,
+                "zh" => &self.content_zh,
+                _ => "",
+            },
+        }
+    }
+
+    /// Text in the target language the turn was recorded with
+    pub fn target_content(&self) -> &str {
+        self.content_in(&self.target_lang)
+    }
 }
 
 #[derive(Insertable, Deserialize)]
@@ -115,6 +141,9 @@ pub struct NewChatTurn {
     pub issues_count: Option<i32>,
     pub hesitation_count: Option<i32>,
     pub status: String,
+    pub target_lang: String,
+    pub native_lang: String,
+    pub contents: Value,
 }
 
 // ============================================================================
@@ -137,6 +166,8 @@ pub struct ChatIssue {
     pub description_zh: Option<String>,
     pub severity: Option<String>,
     pub created_at: DateTime<Utc>,
+    /// Explanations keyed by language code
+    pub descriptions: Value,
 }
 
 #[derive(Insertable, Deserialize)]
@@ -153,6 +184,7 @@ pub struct NewChatIssue {
     pub description_en: Option<String>,
     pub description_zh: Option<String>,
     pub severity: Option<String>,
+    pub descriptions: Value,
 }
 
 // ============================================================================
@@ -193,6 +225,17 @@ pub struct WritePractice {
     pub notes: Option<String>,
     pub updated_at: DateTime<Utc>,
     pub created_at: DateTime<Utc>,
+    /// word | sentence for dictations, absent for client-graded practices
+    pub mode: Option<String>,
+    /// Dictated text, only served once the dictation is graded
+    pub expected_text: Option<String>,
+    pub audio_path: Option<String>,
+    pub user_answer: Option<String>,
+    /// correct | typo | wrong_form | wrong_word
+    pub error_kind: Option<String>,
+    pub accuracy: Option<i32>,
+    pub diff: Option<Value>,
+    pub answered_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Insertable, Deserialize)]
@@ -205,6 +248,17 @@ pub struct NewWritePractice {
     pub notes: Option<String>,
 }
 
+#[derive(Insertable)]
+#[diesel(table_name = learn_write_practices)]
+pub struct NewDictation {
+    pub user_id: i64,
+    pub word_id: i64,
+    pub practice_id: String,
+    pub mode: String,
+    pub expected_text: String,
+    pub audio_path: Option<String>,
+}
+
 // ============================================================================
 // Reading Practice Attempts
 // ============================================================================
@@ -368,6 +422,12 @@ pub struct DailyStat {
     pub errors_corrected: Option<i32>,
     pub new_words_learned: Option<i32>,
     pub review_words_count: Option<i32>,
+    /// Study time measured from activity, minutes_studied is derived from it
+    pub active_seconds: i32,
+    pub last_activity_at: Option<DateTime<Utc>>,
+    /// Admin who corrected the row by hand, reconciliation skips such rows
+    pub corrected_by: Option<i64>,
+    pub corrected_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Insertable, Deserialize)]
@@ -381,9 +441,11 @@ pub struct NewDailyStat {
     pub errors_corrected: Option<i32>,
     pub new_words_learned: Option<i32>,
     pub review_words_count: Option<i32>,
+    pub corrected_by: Option<i64>,
+    pub corrected_at: Option<DateTime<Utc>>,
 }
 
-#[derive(AsChangeset, Deserialize)]
+#[derive(AsChangeset, Deserialize, Default)]
 #[diesel(table_name = learn_daily_stats)]
 pub struct UpdateDailyStat {
     pub minutes_studied: Option<i32>,
@@ -392,6 +454,10 @@ pub struct UpdateDailyStat {
     pub errors_corrected: Option<i32>,
     pub new_words_learned: Option<i32>,
     pub review_words_count: Option<i32>,
+    pub active_seconds: Option<i32>,
+    pub last_activity_at: Option<DateTime<Utc>>,
+    pub corrected_by: Option<i64>,
+    pub corrected_at: Option<DateTime<Utc>>,
 }
 
 // ============================================================================
@@ -411,6 +477,13 @@ pub struct UserVocabulary {
     pub practice_count: Option<i32>,
     pub correct_count: Option<i32>,
     pub next_review_at: Option<DateTime<Utc>>,
+    pub review_interval_days: i32,
+    pub ease_factor: f32,
+    /// Sentence the word was met in
+    pub context: Option<String>,
+    /// Dictionary entry of the word's lemma
+    pub word_id: Option<i64>,
+    pub enriched_at: Option<DateTime<Utc>>,
 }
 
 #[derive(Insertable, Deserialize)]
@@ -419,6 +492,7 @@ pub struct NewUserVocabulary {
     pub user_id: i64,
     pub word: String,
     pub word_zh: Option<String>,
+    pub context: Option<String>,
 }
 
 #[derive(AsChangeset, Deserialize)]
@@ -455,3 +529,385 @@ pub struct NewSuggestion {
     pub suggested_text: String,
     pub was_accepted: Option<bool>,
 }
+
+// ============================================================================
+// Review Logs (spaced-repetition history)
+// ============================================================================
+
+#[derive(Queryable, Identifiable, Serialize, ToSchema, Debug, Clone)]
+#[diesel(table_name = learn_review_logs)]
+pub struct ReviewLog {
+    pub id: i64,
+    pub user_id: i64,
+    /// vocabulary | issue_word
+    pub item_type: String,
+    pub item_id: i64,
+    /// again | hard | good | easy
+    pub grade: String,
+    pub interval_before: i32,
+    pub interval_after: i32,
+    pub ease_after: f32,
+    pub reviewed_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = learn_review_logs)]
+pub struct NewReviewLog {
+    pub user_id: i64,
+    pub item_type: String,
+    pub item_id: i64,
+    pub grade: String,
+    pub interval_before: i32,
+    pub interval_after: i32,
+    pub ease_after: f32,
+}
+
+// ============================================================================
+// Review Sessions (daily mixed exercises)
+// ============================================================================
+
+#[derive(Queryable, Identifiable, Serialize, ToSchema, Debug, Clone)]
+#[diesel(table_name = learn_review_sessions)]
+pub struct ReviewSession {
+    pub id: i64,
+    pub user_id: i64,
+    pub session_date: NaiveDate,
+    pub item_count: i32,
+    pub answered_count: i32,
+    pub correct_count: i32,
+    /// active | completed
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = learn_review_sessions)]
+pub struct NewReviewSession {
+    pub user_id: i64,
+    pub session_date: NaiveDate,
+    pub item_count: i32,
+}
+
+#[derive(Queryable, Identifiable, Debug, Clone)]
+#[diesel(table_name = learn_review_session_items)]
+pub struct ReviewSessionItem {
+    pub id: i64,
+    pub session_id: i64,
+    pub user_id: i64,
+    pub position: i32,
+    /// vocabulary | issue_word | chat_issue
+    pub item_type: String,
+    pub item_id: i64,
+    /// recognition | recall | cloze | listening
+    pub exercise_type: String,
+    pub word: String,
+    pub prompt: String,
+    pub hint: Option<String>,
+    pub choices: Option<Value>,
+    pub audio_url: Option<String>,
+    pub expected_answer: String,
+    pub user_answer: Option<String>,
+    pub is_correct: Option<bool>,
+    pub grade: Option<String>,
+    pub answered_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = learn_review_session_items)]
+pub struct NewReviewSessionItem {
+    pub session_id: i64,
+    pub user_id: i64,
+    pub position: i32,
+    pub item_type: String,
+    pub item_id: i64,
+    pub exercise_type: String,
+    pub word: String,
+    pub prompt: String,
+    pub hint: Option<String>,
+    pub choices: Option<Value>,
+    pub audio_url: Option<String>,
+    pub expected_answer: String,
+}
+
+// ============================================================================
+// Dictionary Quizzes
+// ============================================================================
+
+#[derive(Queryable, Identifiable, Serialize, ToSchema, Debug, Clone)]
+#[diesel(table_name = learn_quizzes)]
+pub struct Quiz {
+    pub id: i64,
+    pub user_id: i64,
+    pub question_count: i32,
+    pub answered_count: i32,
+    pub correct_count: i32,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = learn_quizzes)]
+pub struct NewQuiz {
+    pub user_id: i64,
+    pub question_count: i32,
+}
+
+#[derive(Queryable, Identifiable, Debug, Clone)]
+#[diesel(table_name = learn_quiz_questions)]
+pub struct QuizQuestion {
+    pub id: i64,
+    pub quiz_id: i64,
+    pub user_id: i64,
+    pub position: i32,
+    /// definition_choice | cloze | matching | form_drill
+    pub question_type: String,
+    pub word_id: Option<i64>,
+    pub prompt: String,
+    pub hint: Option<String>,
+    pub choices: Option<Value>,
+    pub answer: Value,
+    pub user_answer: Option<Value>,
+    pub is_correct: Option<bool>,
+    pub answered_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = learn_quiz_questions)]
+pub struct NewQuizQuestion {
+    pub quiz_id: i64,
+    pub user_id: i64,
+    pub position: i32,
+    pub question_type: String,
+    pub word_id: Option<i64>,
+    pub prompt: String,
+    pub hint: Option<String>,
+    pub choices: Option<Value>,
+    pub answer: Value,
+}
+
+// ============================================================================
+// Streak Freezes
+// ============================================================================
+
+#[derive(Queryable, Identifiable, Serialize, ToSchema, Debug, Clone)]
+#[diesel(table_name = learn_streak_freezes)]
+pub struct StreakFreeze {
+    pub id: i64,
+    pub user_id: i64,
+    /// Missed local day the freeze was spent on
+    pub frozen_date: NaiveDate,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = learn_streak_freezes)]
+pub struct NewStreakFreeze {
+    pub user_id: i64,
+    pub frozen_date: NaiveDate,
+}
+
+// ============================================================================
+// Placement Tests
+// ============================================================================
+
+#[derive(Queryable, Identifiable, Serialize, ToSchema, Debug, Clone)]
+#[diesel(table_name = learn_placement_tests)]
+pub struct PlacementTest {
+    pub id: i64,
+    pub user_id: i64,
+    pub item_count: i32,
+    pub answered_count: i32,
+    pub correct_count: i32,
+    /// Current level estimate on the 1-10 scale
+    pub estimate: f32,
+    #[serde(skip_serializing)]
+    pub step: f32,
+    pub final_level: Option<i16>,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = learn_placement_tests)]
+pub struct NewPlacementTest {
+    pub user_id: i64,
+    pub item_count: i32,
+    pub estimate: f32,
+    pub step: f32,
+}
+
+#[derive(Queryable, Identifiable, Debug, Clone)]
+#[diesel(table_name = learn_placement_items)]
+pub struct PlacementItem {
+    pub id: i64,
+    pub test_id: i64,
+    pub user_id: i64,
+    pub position: i32,
+    /// vocabulary | grammar | reading | speaking
+    pub item_type: String,
+    pub level: i16,
+    pub source_id: Option<i64>,
+    pub prompt: String,
+    pub choices: Option<Value>,
+    pub answer: Value,
+    pub user_answer: Option<Value>,
+    pub is_correct: Option<bool>,
+    pub answered_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = learn_placement_items)]
+pub struct NewPlacementItem {
+    pub test_id: i64,
+    pub user_id: i64,
+    pub position: i32,
+    pub item_type: String,
+    pub level: i16,
+    pub source_id: Option<i64>,
+    pub prompt: String,
+    pub choices: Option<Value>,
+    pub answer: Value,
+}
+
+// ============================================================================
+// Learning Goals
+// ============================================================================
+
+#[derive(Queryable, Identifiable, Serialize, ToSchema, Debug, Clone)]
+#[diesel(table_name = learn_goals)]
+pub struct Goal {
+    pub id: i64,
+    pub user_id: i64,
+    /// daily_minutes | weekly_words | word_list | exam
+    pub goal_type: String,
+    pub title: String,
+    /// Minutes per day (daily_minutes, exam) or new words per week (weekly_words)
+    pub target_value: Option<i32>,
+    /// Category whose words are to be mastered (word_list, exam)
+    pub category_id: Option<i64>,
+    pub baseline_mastered: i32,
+    pub starts_on: NaiveDate,
+    /// Deadline, or the exam date
+    pub target_date: Option<NaiveDate>,
+    pub is_active: bool,
+    pub achieved_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = learn_goals)]
+pub struct NewGoal {
+    pub user_id: i64,
+    pub goal_type: String,
+    pub title: String,
+    pub target_value: Option<i32>,
+    pub category_id: Option<i64>,
+    pub baseline_mastered: i32,
+    pub starts_on: NaiveDate,
+    pub target_date: Option<NaiveDate>,
+}
+
+#[derive(AsChangeset, Default)]
+#[diesel(table_name = learn_goals)]
+pub struct UpdateGoal {
+    pub title: Option<String>,
+    pub target_value: Option<i32>,
+    pub target_date: Option<NaiveDate>,
+    pub is_active: Option<bool>,
+    pub achieved_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+// ============================================================================
+// Shadowing Practices
+// ============================================================================
+
+#[derive(Queryable, Identifiable, Serialize, ToSchema, Debug, Clone)]
+#[diesel(table_name = learn_shadowing_practices)]
+pub struct ShadowingPractice {
+    pub id: i64,
+    pub user_id: i64,
+    /// script_turn | read_sentence
+    pub source_type: String,
+    pub source_id: i64,
+    pub reference_text: String,
+    pub user_audio_path: Option<String>,
+    pub transcript: String,
+    pub accuracy_score: i32,
+    pub rhythm_score: i32,
+    pub pause_score: i32,
+    /// Learner duration / reference duration, above 1 when slower
+    pub speed_ratio: Option<f32>,
+    pub overall_score: i32,
+    pub details: Value,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = learn_shadowing_practices)]
+pub struct NewShadowingPractice {
+    pub user_id: i64,
+    pub source_type: String,
+    pub source_id: i64,
+    pub reference_text: String,
+    pub user_audio_path: Option<String>,
+    pub transcript: String,
+    pub accuracy_score: i32,
+    pub rhythm_score: i32,
+    pub pause_score: i32,
+    pub speed_ratio: Option<f32>,
+    pub overall_score: i32,
+    pub details: Value,
+}
+
+// ============================================================================
+// Reset Snapshots
+// ============================================================================
+
+/// A reset that can still be undone; the archived rows are not loaded
+#[derive(Queryable, Serialize, ToSchema, Debug, Clone)]
+pub struct ResetSnapshot {
+    pub id: i64,
+    pub user_id: i64,
+    /// issue_words | chats | write_practices | read_practices | vocabulary |
+    /// daily_stats | achievements | suggestions
+    pub kind: String,
+    /// Archived row count per table
+    pub counts: Value,
+    pub created_at: DateTime<Utc>,
+    /// Last moment the reset can be restored
+    pub expires_at: DateTime<Utc>,
+}
+
+// ============================================================================
+// Report Shares
+// ============================================================================
+
+/// A link showing a progress report without signing in; the token itself is
+/// only returned when the link is created
+#[derive(Queryable, Serialize, ToSchema, Debug, Clone)]
+pub struct ReportShare {
+    pub id: i64,
+    pub user_id: i64,
+    /// First and last local day of the shared report
+    pub from_date: NaiveDate,
+    pub to_date: NaiveDate,
+    pub expires_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = learn_report_shares)]
+pub struct NewReportShare {
+    pub user_id: i64,
+    pub token_hash: String,
+    pub from_date: NaiveDate,
+    pub to_date: NaiveDate,
+    pub expires_at: DateTime<Utc>,
+}
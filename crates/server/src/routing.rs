@@ -16,7 +16,9 @@ mod achievement;
 mod asset;
 mod auth;
 mod dict;
+mod leaderboard;
 mod learn;
+mod recording;
 
 pub fn router() -> Router {
     Router::new()
@@ -47,7 +49,9 @@ pub fn router() -> Router {
                 .push(auth::router())
                 .push(asset::router())
                 .push(dict::router())
-                .push(learn::router()),
+                .push(leaderboard::router())
+                .push(learn::router())
+                .push(learn::shared_report_router()),
         )
         .push(
             Router::with_path("{**}").get(
@@ -12,6 +12,7 @@ use crate::models::*;
 use crate::{AppConfig, DepotExt, JsonResult, json_ok};
 
 mod avatar;
+mod personal_data;
 pub fn router() -> Router {
     Router::new()
         .push(Router::with_path("register").post(register))
@@ -26,6 +27,20 @@ pub fn router() -> Router {
                         .get(avatar::show)
                         .post(avatar::upload_avatar)
                         .delete(avatar::delete_avatar),
+                )
+                .push(
+                    Router::with_path("exports")
+                        .get(personal_data::list_exports)
+                        .post(personal_data::request_export)
+                        .push(
+                            Router::with_path("{id}/download").get(personal_data::download_export),
+                        ),
+                )
+                .push(
+                    Router::with_path("deletion")
+                        .get(personal_data::get_deletion)
+                        .post(personal_data::request_deletion)
+                        .delete(personal_data::cancel_deletion),
                 ),
         )
 }
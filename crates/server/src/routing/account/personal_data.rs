@@ -0,0 +1,149 @@
+use std::path::PathBuf;
+
+use chrono::Utc;
+use diesel::prelude::*;
+use salvo::oapi::ToSchema;
+use salvo::oapi::extract::*;
+use salvo::prelude::*;
+use serde::Serialize;
+
+use crate::db::schema::*;
+use crate::db::with_conn;
+use crate::models::{AccountDeletion, DataExport};
+use crate::user::personal_data::{self, DataInventory};
+use crate::{AppConfig, AppResult, DepotExt, JsonResult, json_ok};
+
+#[derive(Serialize, ToSchema)]
+pub struct DeletionView {
+    /// The scheduled deletion, if any
+    pub deletion: Option<AccountDeletion>,
+    /// What is removed with the account
+    pub data: DataInventory,
+}
+
+/// Queue an export of everything stored about the current user
+#[endpoint(tags("Account"))]
+pub async fn request_export(depot: &mut Depot) -> JsonResult<DataExport> {
+    let user_id = depot.user_id()?;
+    let export = with_conn(move |conn| personal_data::request_export(conn, user_id))
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to request data export: {:?}", e);
+            StatusError::internal_server_error().brief("failed to request data export")
+        })?;
+    json_ok(export)
+}
+
+/// Exports of the current user, newest first
+#[endpoint(tags("Account"))]
+pub async fn list_exports(depot: &mut Depot) -> JsonResult<Vec<DataExport>> {
+    let user_id = depot.user_id()?;
+    let exports = with_conn(move |conn| {
+        base_data_exports::table
+            .filter(base_data_exports::user_id.eq(user_id))
+            .order(base_data_exports::created_at.desc())
+            .load::<DataExport>(conn)
+    })
+    .await
+    .map_err(|_| StatusError::internal_server_error().brief("failed to list data exports"))?;
+    json_ok(exports)
+}
+
+/// Download a finished export archive
+#[handler]
+pub async fn download_export(
+    req: &mut Request,
+    depot: &mut Depot,
+    res: &mut Response,
+) -> AppResult<()> {
+    let user_id = depot.user_id()?;
+    let export_id = req
+        .param::<i64>("id")
+        .ok_or_else(|| StatusError::bad_request().brief("invalid export id"))?;
+
+    let file_path = with_conn(move |conn| {
+        base_data_exports::table
+            .filter(base_data_exports::id.eq(export_id))
+            .filter(base_data_exports::user_id.eq(user_id))
+            .filter(base_data_exports::status.eq("ready"))
+            .select(base_data_exports::file_path)
+            .first::<Option<String>>(conn)
+            .optional()
+    })
+    .await
+    .map_err(|_| StatusError::internal_server_error().brief("failed to get data export"))?
+    .flatten()
+    .ok_or_else(|| StatusError::not_found().brief("export not found or not ready"))?;
+
+    let path = PathBuf::from(&AppConfig::get().space_path).join(&file_path);
+    if !path.exists() {
+        tracing::warn!("Export archive not found: {:?}", path);
+        return Err(StatusError::not_found()
+            .brief("export file not found")
+            .into());
+    }
+    res.send_file(path, req.headers()).await;
+    Ok(())
+}
+
+/// Scheduled deletion of the current user and what it removes
+#[endpoint(tags("Account"))]
+pub async fn get_deletion(depot: &mut Depot) -> JsonResult<DeletionView> {
+    let user_id = depot.user_id()?;
+    let view = with_conn(move |conn| {
+        let space_path = PathBuf::from(&AppConfig::get().space_path);
+        Ok(DeletionView {
+            deletion: personal_data::pending_deletion(conn, user_id)?,
+            data: personal_data::inventory(conn, user_id, &space_path)?,
+        })
+    })
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to get account deletion: {:?}", e);
+        StatusError::internal_server_error().brief("failed to get account deletion")
+    })?;
+    json_ok(view)
+}
+
+/// Schedule the deletion of the current user's account
+///
+/// Everything is removed once the grace period is over, unless the request
+/// is cancelled before.
+#[endpoint(tags("Account"))]
+pub async fn request_deletion(depot: &mut Depot) -> JsonResult<DeletionView> {
+    let user_id = depot.user_id()?;
+    let view = with_conn(move |conn| {
+        let config = AppConfig::get();
+        let space_path = PathBuf::from(&config.space_path);
+        let deletion = personal_data::request_deletion(
+            conn,
+            user_id,
+            config.account.deletion_grace_days,
+            Utc::now(),
+        )?;
+        Ok(DeletionView {
+            deletion: Some(deletion),
+            data: personal_data::inventory(conn, user_id, &space_path)?,
+        })
+    })
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to request account deletion: {:?}", e);
+        StatusError::internal_server_error().brief("failed to request account deletion")
+    })?;
+    json_ok(view)
+}
+
+/// Cancel the scheduled deletion of the current user's account
+#[endpoint(tags("Account"))]
+pub async fn cancel_deletion(depot: &mut Depot) -> JsonResult<AccountDeletion> {
+    let user_id = depot.user_id()?;
+    let deletion = with_conn(move |conn| personal_data::cancel_deletion(conn, user_id, Utc::now()))
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to cancel account deletion: {:?}", e);
+            StatusError::internal_server_error().brief("failed to cancel account deletion")
+        })?
+        .ok_or_else(|| StatusError::not_found().brief("no account deletion scheduled"))?;
+    json_ok(deletion)
+}
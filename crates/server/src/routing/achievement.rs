@@ -2,10 +2,11 @@ use chrono::Utc;
 use diesel::prelude::*;
 use salvo::oapi::ToSchema;
 use salvo::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::db::schema::*;
 use crate::db::with_conn;
+use crate::learn::achievement;
 use crate::models::achievement::*;
 use crate::{AppResult, DepotExt, JsonResult, hoops, json_ok};
 
@@ -18,7 +19,12 @@ pub fn router() -> Router {
                 .hoop(hoops::require_auth)
                 .push(Router::with_path("profile").get(get_user_profile_summary))
                 .push(Router::with_path("my").get(list_user_achievements))
-                .push(Router::with_path("xp-history").get(list_xp_history)),
+                .push(Router::with_path("xp-history").get(list_xp_history))
+                .push(
+                    Router::with_path("unlocked")
+                        .get(list_unlocked_achievements)
+                        .push(Router::with_path("ack").post(acknowledge_achievements)),
+                ),
         )
 }
 
@@ -237,6 +243,52 @@ pub async fn list_xp_history(
     Ok(())
 }
 
+/// List achievements unlocked since the client last acknowledged them
+#[handler]
+pub async fn list_unlocked_achievements(depot: &mut Depot, res: &mut Response) -> AppResult<()> {
+    let user_id = depot.user_id()?;
+
+    let badges: Vec<AchievementBadge> =
+        with_conn(move |conn| achievement::pending_badges(conn, user_id))
+            .await
+            .map_err(|_| {
+                StatusError::internal_server_error().brief("failed to list unlocked achievements")
+            })?;
+
+    res.render(Json(badges));
+    Ok(())
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AcknowledgeAchievementsRequest {
+    /// Codes of the badges that were shown to the user
+    codes: Vec<String>,
+}
+
+/// Mark unlocked achievements as shown so they are not listed again
+#[handler]
+pub async fn acknowledge_achievements(
+    req: &mut Request,
+    depot: &mut Depot,
+    res: &mut Response,
+) -> AppResult<()> {
+    let user_id = depot.user_id()?;
+    let input: AcknowledgeAchievementsRequest = req
+        .parse_json()
+        .await
+        .map_err(|_| StatusError::bad_request().brief("invalid json"))?;
+
+    let acknowledged =
+        with_conn(move |conn| achievement::acknowledge(conn, user_id, &input.codes, Utc::now()))
+            .await
+            .map_err(|_| {
+                StatusError::internal_server_error().brief("failed to acknowledge achievements")
+            })?;
+
+    res.render(Json(serde_json::json!({ "acknowledged": acknowledged })));
+    Ok(())
+}
+
 // ============================================================================
 // Helper Functions
 // ============================================================================
@@ -1,5 +1,7 @@
 use salvo::prelude::*;
 
+use crate::hoops;
+
 mod context;
 mod reading;
 mod script;
@@ -47,6 +49,10 @@ pub fn router() -> Router {
                         .get(reading::list_read_sentences)
                         .push(Router::with_path("{id}/audio").get(reading::serve_sentence_audio)),
                 )
-                .push(Router::with_path("evaluate").post(reading::evaluate_pronunciation)),
+                .push(
+                    Router::with_path("evaluate")
+                        .hoop(hoops::require_auth)
+                        .post(reading::evaluate_pronunciation),
+                ),
         )
 }
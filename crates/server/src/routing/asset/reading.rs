@@ -1,17 +1,21 @@
 use std::path::PathBuf;
 
-use base64::prelude::{BASE64_STANDARD as BASE64, Engine};
 use diesel::prelude::*;
 use salvo::prelude::*;
 use serde::{Deserialize, Serialize};
 
-use crate::AppResult;
 use crate::config::AppConfig;
 use crate::db::schema::*;
 use crate::db::with_conn;
+use crate::learn::achievement::{self, AchievementEvent};
+use crate::learn::audio::save_audio_file;
+use crate::learn::phoneme::{self, PhonemeReport};
+use crate::learn::reading;
+use crate::models::achievement::AchievementBadge;
 use crate::models::asset::*;
-use crate::services::ai_provider::{AiProviderError, AsrResponse};
-use crate::services::create_provider_from_env;
+use crate::models::learn::{NewReadPractice, ReadPractice, ReadProgress};
+use crate::routing::recording;
+use crate::{AppResult, DepotExt};
 
 #[derive(Serialize, ToSchema)]
 pub struct PaginatedSubjects {
@@ -202,8 +206,16 @@ pub async fn serve_sentence_audio(req: &mut Request, res: &mut Response) -> AppR
 pub struct EvaluateRequest {
     /// Base64 encoded audio data
     pub audio_base64: String,
-    /// Reference text to compare against
+    /// Audio file extension, "wav" when absent
+    pub audio_format: Option<String>,
+    /// Reference text to compare against; with a sentence it may be left
+    /// empty and must otherwise have the sentence's words
+    #[serde(default)]
     pub reference_text: String,
+    /// Sentence being read, progress on its subject is updated
+    pub sentence_id: Option<i64>,
+    /// Groups attempts of one reading session, generated when absent
+    pub practice_id: Option<String>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -220,6 +232,14 @@ pub struct EvaluateResponse {
     pub intonation_score: i32,
     /// Feedback messages
     pub feedback: Vec<FeedbackItem>,
+    /// Phoneme level diagnosis
+    pub phonemes: PhonemeReport,
+    /// The recorded practice
+    pub practice: ReadPractice,
+    /// Progress on the sentence's subject
+    pub progress: Option<ReadProgress>,
+    /// Achievements unlocked by this practice
+    pub unlocked: Vec<AchievementBadge>,
 }
 
 #[derive(Debug, Serialize, ToSchema)]
@@ -232,7 +252,13 @@ pub struct FeedbackItem {
 }
 
 #[handler]
-pub async fn evaluate_pronunciation(req: &mut Request, res: &mut Response) -> AppResult<()> {
+pub async fn evaluate_pronunciation(
+    req: &mut Request,
+    depot: &mut Depot,
+    res: &mut Response,
+) -> AppResult<()> {
+    let user_id = depot.user_id()?;
+
     // Parse request body
     let body_bytes = req.payload().await.map_err(|e| {
         tracing::error!("evaluate_pronunciation: read_body error: {:?}", e);
@@ -244,35 +270,45 @@ pub async fn evaluate_pronunciation(req: &mut Request, res: &mut Response) -> Ap
         StatusError::bad_request().brief("invalid json")
     })?;
 
-    // Decode audio
-    let audio_data = BASE64
-        .decode(&input.audio_base64)
-        .map_err(|_| StatusError::bad_request().brief("invalid base64 audio"))?;
-
-    if audio_data.is_empty() {
+    let audio_data = recording::decode_audio(&input.audio_base64)?;
+    let audio_format = recording::audio_format(input.audio_format)?;
+
+    let sentence: Option<ReadSentence> = match input.sentence_id {
+        Some(sentence_id) => Some(
+            with_conn(move |conn| {
+                asset_read_sentences::table
+                    .find(sentence_id)
+                    .first::<ReadSentence>(conn)
+                    .optional()
+            })
+            .await
+            .map_err(|_| StatusError::internal_server_error().brief("failed to get sentence"))?
+            .ok_or_else(|| StatusError::not_found().brief("sentence not found"))?,
+        ),
+        None => None,
+    };
+    // A practice of a sentence is scored against the stored sentence, the
+    // client cannot swap in an easier text
+    let reference_text = match &sentence {
+        Some(sentence) => {
+            if !input.reference_text.trim().is_empty()
+                && phoneme::words(&input.reference_text) != phoneme::words(&sentence.content_en)
+            {
+                return Err(StatusError::bad_request()
+                    .brief("reference text does not match the sentence")
+                    .into());
+            }
+            sentence.content_en.clone()
+        }
+        None => input.reference_text,
+    };
+    if reference_text.trim().is_empty() {
         return Err(StatusError::bad_request()
-            .brief("audio data is empty")
+            .brief("reference text is empty")
             .into());
     }
 
-    // Get AI provider
-    let provider = create_provider_from_env()
-        .ok_or_else(|| StatusError::internal_server_error().brief("AI provider not configured"))?;
-
-    // Get ASR service
-    let asr = provider
-        .asr()
-        .ok_or_else(|| StatusError::internal_server_error().brief("ASR service not available"))?;
-
-    // Transcribe audio
-    tracing::info!("Evaluating pronunciation using {} ASR...", provider.name());
-    let asr_result: AsrResponse =
-        asr.transcribe(audio_data, Some("en"))
-            .await
-            .map_err(|e: AiProviderError| {
-                tracing::error!("{} ASR error: {:?}", provider.name(), e);
-                StatusError::internal_server_error().brief(e.to_string())
-            })?;
+    let asr_result = recording::transcribe(audio_data.clone(), "en").await?;
 
     let transcribed_text = asr_result.text.trim().to_string();
     tracing::info!("ASR result: {}", transcribed_text);
@@ -284,8 +320,96 @@ pub async fn evaluate_pronunciation(req: &mut Request, res: &mut Response) -> Ap
     }
 
     // Calculate scores by comparing transcribed text with reference
-    let (overall_score, pronunciation_score, fluency_score, intonation_score, feedback) =
-        calculate_pronunciation_score(&transcribed_text, &input.reference_text);
+    let (overall_score, pronunciation_score, fluency_score, intonation_score, mut feedback) =
+        calculate_pronunciation_score(&transcribed_text, &reference_text);
+
+    // Phoneme level diagnosis of the words that were not heard as written
+    let phonemes = {
+        let reference_text = reference_text.clone();
+        let transcribed_text = transcribed_text.clone();
+        let sentence = sentence.clone();
+        with_conn(move |conn| {
+            let mut words = phoneme::words(&reference_text);
+            words.extend(phoneme::words(&transcribed_text));
+            let mut lexicon = phoneme::lexicon(conn, &words)?;
+            let (focus, common_mistakes) = match &sentence {
+                Some(sentence) => {
+                    if let Some(transcription) = &sentence.phonetic_transcription {
+                        lexicon.extend(phoneme::transcription_lexicon(
+                            &reference_text,
+                            transcription,
+                        ));
+                    }
+                    (
+                        phoneme::json_strings(sentence.focus_sounds.as_ref()),
+                        phoneme::json_strings(sentence.common_mistakes.as_ref()),
+                    )
+                }
+                None => (Vec::new(), Vec::new()),
+            };
+            Ok(phoneme::diagnose(
+                &reference_text,
+                &transcribed_text,
+                &lexicon,
+                &focus,
+                &common_mistakes,
+            ))
+        })
+        .await
+        .map_err(|_| {
+            StatusError::internal_server_error().brief("failed to look up pronunciations")
+        })?
+    };
+    for issue in phonemes.issues.iter().filter(|i| i.confusable).take(2) {
+        if let Some(tip) = &issue.tip {
+            feedback.push(FeedbackItem {
+                item_type: "warning".to_string(),
+                message: format!("{}：{}", issue.word, tip),
+            });
+        }
+    }
+
+    let new_practice = NewReadPractice {
+        user_id,
+        sentence_id: sentence.map(|s| s.id),
+        practice_id: input
+            .practice_id
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+        user_audio_path: save_audio_file(user_id, &audio_data, "read", &audio_format).await,
+        pronunciation_score: Some(pronunciation_score),
+        fluency_score: Some(fluency_score),
+        intonation_score: Some(intonation_score),
+        overall_score: Some(overall_score),
+        detected_errors: serde_json::to_value(&phonemes).ok(),
+        ai_feedback_en: None,
+        ai_feedback_zh: (!feedback.is_empty()).then(|| {
+            feedback
+                .iter()
+                .map(|item| item.message.as_str())
+                .collect::<Vec<_>>()
+                .join("\n")
+        }),
+        waveform_data: None,
+    };
+    let (practice, progress, unlocked) = with_conn(move |conn| {
+        conn.transaction(|conn| {
+            let (practice, progress) = reading::record_practice(conn, &new_practice, true)?;
+            let unlocked = achievement::evaluate_and_notify(
+                conn,
+                user_id,
+                AchievementEvent::ReadingScored {
+                    score: overall_score,
+                },
+                practice.created_at,
+            )?;
+            Ok((practice, progress, unlocked))
+        })
+    })
+    .await
+    .map_err(|e| {
+        tracing::error!("evaluate_pronunciation: failed to record practice: {:?}", e);
+        StatusError::internal_server_error().brief("failed to record read practice")
+    })?;
 
     res.render(Json(EvaluateResponse {
         transcribed_text,
@@ -294,6 +418,10 @@ pub async fn evaluate_pronunciation(req: &mut Request, res: &mut Response) -> Ap
         fluency_score,
         intonation_score,
         feedback,
+        phonemes,
+        practice,
+        progress,
+        unlocked,
     }));
     Ok(())
 }
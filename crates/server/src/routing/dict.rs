@@ -2,12 +2,17 @@ use diesel::prelude::*;
 use itertools::Itertools;
 use salvo::oapi::ToSchema;
 use salvo::prelude::*;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::db::schema::*;
 use crate::db::with_conn;
+use crate::learn::autocomplete::{self, Completion};
+use crate::learn::inflection;
+use crate::learn::reverse_lookup::{self, ReverseMatch};
+use crate::learn::spelling::{self, Suggestion};
 use crate::models::dict::*;
-use crate::{JsonResult, json_ok};
+use crate::services::language::Script;
+use crate::{AppResult, JsonResult, json_ok};
 
 mod category;
 mod definition;
@@ -17,13 +22,18 @@ mod form;
 mod image;
 mod pronunciation;
 mod relation;
+mod search;
 mod searched_words;
 mod sentence;
 mod words;
 
 pub fn router() -> Router {
     Router::with_path("dict")
+        .push(Router::with_path("autocomplete").get(autocomplete))
         .push(Router::with_path("lookup").get(lookup))
+        .push(Router::with_path("reverse").get(reverse_lookup))
+        .push(Router::with_path("search").get(search::search))
+        .push(Router::with_path("suggestions").get(suggestions))
         .push(
             Router::with_path("dictionaries")
                 .get(dictionary::list_dictionaries)
@@ -53,6 +63,10 @@ pub fn router() -> Router {
             Router::with_path("words/{id}/pronunciations/{pronunciation_id}")
                 .delete(pronunciation::delete_pronunciation),
         )
+        .push(
+            Router::with_path("pronunciations/{id}/audio")
+                .get(pronunciation::serve_pronunciation_audio),
+        )
         .push(
             Router::with_path("words/{id}/examples")
                 .get(sentence::list_sentences)
@@ -94,9 +108,29 @@ pub(super) fn get_path_id(req: &Request, key: &str) -> Result<i64, StatusError>
         .map_err(|_| StatusError::bad_request().brief("invalid id"))
 }
 
+/// Suggestions sent along with a failed lookup
+const LOOKUP_SUGGESTIONS: usize = 5;
+
+/// Body of a lookup that found no word
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LookupMiss {
+    /// "Did you mean" words, best first; empty when nothing is close
+    pub suggestions: Vec<Suggestion>,
+}
+
 /// Lookup a word in the dictionary
-#[endpoint(tags("Dictionary"))]
-pub async fn lookup(req: &mut Request) -> JsonResult<WordQueryResponse> {
+///
+/// An inflected form ("went", "studies") returns the entry of its lemma with
+/// `matched_form` saying how it matched. A miss answers 404 with a
+/// [`LookupMiss`] body listing "did you mean" suggestions.
+#[endpoint(
+    tags("Dictionary"),
+    responses(
+        (status_code = 200, description = "The word or its lemma", body = WordQueryResponse),
+        (status_code = 404, description = "No such word", body = LookupMiss),
+    )
+)]
+pub async fn lookup(req: &mut Request, res: &mut Response) -> AppResult<()> {
     let word = req
         .query::<String>("word")
         .ok_or_else(|| StatusError::bad_request().brief("missing word query"))?;
@@ -108,7 +142,8 @@ pub async fn lookup(req: &mut Request) -> JsonResult<WordQueryResponse> {
             .into());
     }
 
-    let result: WordQueryResponse = with_conn(move |conn| {
+    let result = with_conn(move |conn| {
+        let mut matched_form = None;
         let word_record = dict_words::table
             .filter(dict_words::word_lower.eq(&word_lower_value))
             .first::<Word>(conn)
@@ -131,9 +166,22 @@ pub async fn lookup(req: &mut Request) -> JsonResult<WordQueryResponse> {
                     }
                 })
                 .collect::<String>();
-            dict_words::table
+            let word_record = dict_words::table
                 .filter(dict_words::word_lower.eq(&word_normalized))
-                .first::<Word>(conn)?
+                .first::<Word>(conn)
+                .optional()?;
+            if let Some(word_record) = word_record {
+                word_record
+            } else if let Some((lemma, form)) = inflection::resolve(conn, &word_lower_value)? {
+                matched_form = Some(form);
+                lemma
+            } else {
+                return Ok(Err(spelling::suggest(
+                    conn,
+                    &word_lower_value,
+                    LOOKUP_SUGGESTIONS,
+                )?));
+            }
         };
 
         println!("word_record: {:?}", word_record);
@@ -221,8 +269,9 @@ pub async fn lookup(req: &mut Request) -> JsonResult<WordQueryResponse> {
             )
             .load::<Dictionary>(conn)?;
 
-        Ok(WordQueryResponse {
+        Ok(Ok(WordQueryResponse {
             word: word_record,
+            matched_form,
             definitions,
             sentences,
             pronunciations,
@@ -231,10 +280,106 @@ pub async fn lookup(req: &mut Request) -> JsonResult<WordQueryResponse> {
             etymologies,
             forms,
             images,
-        })
+        }))
     })
     .await
-    .map_err(|_| StatusError::not_found().brief("word not found"))?;
+    .map_err(|e| {
+        tracing::error!("Failed to look up word: {:?}", e);
+        StatusError::internal_server_error().brief("failed to look up word")
+    })?;
+
+    match result {
+        Ok(result) => res.render(Json(result)),
+        Err(suggestions) => {
+            res.status_code(StatusCode::NOT_FOUND);
+            res.render(Json(LookupMiss { suggestions }));
+        }
+    }
+    Ok(())
+}
+
+/// Dictionary words close to a misspelled one, best first
+///
+/// Query parameters:
+/// - `word`: What was typed, at most 64 characters
+/// - `limit`: Max suggestions (default 5, max 20)
+#[endpoint(tags("Dictionary"))]
+pub async fn suggestions(req: &mut Request) -> JsonResult<Vec<Suggestion>> {
+    let word = req
+        .query::<String>("word")
+        .map(|w| w.trim().to_lowercase())
+        .filter(|w| !w.is_empty())
+        .ok_or_else(|| StatusError::bad_request().brief("missing word query"))?;
+    if word.chars().count() > spelling::MAX_WORD_LENGTH {
+        return Err(StatusError::bad_request().brief("word is too long").into());
+    }
+    let limit = req.query::<usize>("limit").unwrap_or(5).clamp(1, 20);
+
+    let suggestions = with_conn(move |conn| spelling::suggest(conn, &word, limit))
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to suggest words: {:?}", e);
+            StatusError::internal_server_error().brief("failed to suggest words")
+        })?;
+    json_ok(suggestions)
+}
+
+/// Completions of a partly typed word, most frequent first
+///
+/// Query parameters:
+/// - `q`: Start of the word
+/// - `limit`: Max completions (default 10, max 20)
+#[endpoint(tags("Dictionary"))]
+pub async fn autocomplete(req: &mut Request) -> JsonResult<Vec<Completion>> {
+    let prefix = req
+        .query::<String>("q")
+        .map(|q| q.trim().to_lowercase())
+        .filter(|q| !q.is_empty())
+        .ok_or_else(|| StatusError::bad_request().brief("missing q query"))?;
+    let limit = req.query::<i64>("limit").unwrap_or(10).clamp(1, 20);
+
+    let completions = with_conn(move |conn| autocomplete::complete(conn, &prefix, limit))
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to complete word: {:?}", e);
+            StatusError::internal_server_error().brief("failed to complete word")
+        })?;
+    json_ok(completions)
+}
+
+/// Longest Chinese text a reverse lookup accepts, in characters
+const REVERSE_QUERY_CHARS: usize = 32;
+
+/// English words for a Chinese word or phrase, best match first
+///
+/// The text is segmented into words and matched against the Chinese
+/// translations and definitions; each word comes with the sense that matched.
+///
+/// Query parameters:
+/// - `q`: Chinese text, e.g. "行程表"
+/// - `limit`: Max words (default 10, max 50)
+#[endpoint(tags("Dictionary"))]
+pub async fn reverse_lookup(req: &mut Request) -> JsonResult<Vec<ReverseMatch>> {
+    let query = req
+        .query::<String>("q")
+        .map(|q| q.trim().to_string())
+        .filter(|q| !q.is_empty())
+        .ok_or_else(|| StatusError::bad_request().brief("missing q query"))?;
+    if query.chars().count() > REVERSE_QUERY_CHARS {
+        return Err(StatusError::bad_request().brief("q is too long").into());
+    }
+    if !query.chars().any(|c| Script::Han.contains(c)) {
+        return Err(StatusError::bad_request()
+            .brief("q must contain Chinese characters")
+            .into());
+    }
+    let limit = req.query::<usize>("limit").unwrap_or(10).clamp(1, 50);
 
-    json_ok(result)
+    let matches = with_conn(move |conn| reverse_lookup::lookup(conn, &query, limit))
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to look up Chinese text: {:?}", e);
+            StatusError::internal_server_error().brief("failed to look up Chinese text")
+        })?;
+    json_ok(matches)
 }
@@ -1,11 +1,14 @@
+use std::path::PathBuf;
+
 use diesel::prelude::*;
 use salvo::prelude::*;
 use serde::Deserialize;
 
+use crate::config::AppConfig;
 use crate::db::schema::*;
 use crate::db::with_conn;
 use crate::models::dict::*;
-use crate::{JsonResult, json_ok};
+use crate::{AppResult, JsonResult, json_ok};
 
 #[handler]
 pub async fn list_pronunciations(req: &mut Request) -> JsonResult<Vec<Pronunciation>> {
@@ -74,3 +77,30 @@ pub async fn delete_pronunciation(req: &mut Request) -> JsonResult<()> {
     .map_err(|_| StatusError::internal_server_error().brief("failed to delete pronunciation"))?;
     json_ok(())
 }
+
+/// Serve the audio file of a pronunciation stored under the space path
+#[handler]
+pub async fn serve_pronunciation_audio(req: &mut Request, res: &mut Response) -> AppResult<()> {
+    let pronunciation_id = super::get_path_id(req, "id")?;
+    let audio_path: Option<String> = with_conn(move |conn| {
+        dict_pronunciations::table
+            .find(pronunciation_id)
+            .select(dict_pronunciations::audio_path)
+            .first::<Option<String>>(conn)
+    })
+    .await
+    .map_err(|_| StatusError::not_found().brief("pronunciation not found"))?;
+    let audio_path = audio_path
+        .ok_or_else(|| StatusError::not_found().brief("no audio for this pronunciation"))?;
+
+    let file_path = PathBuf::from(&AppConfig::get().space_path).join(&audio_path);
+    if !file_path.exists() {
+        tracing::warn!("Pronunciation audio file not found: {:?}", file_path);
+        return Err(StatusError::not_found()
+            .brief("audio file not found")
+            .into());
+    }
+
+    res.send_file(file_path, req.headers()).await;
+    Ok(())
+}
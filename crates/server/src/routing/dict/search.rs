@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Float, Text};
+use salvo::oapi::ToSchema;
+use salvo::prelude::*;
+use serde::Serialize;
+
+use crate::db::full_text_search::{
+    HEADLINE_OPTIONS, escape_like, headline_html, highlight_substring,
+};
+use crate::db::schema::{dict_definitions, dict_translations, dict_word_sentences, dict_words};
+use crate::db::with_conn;
+use crate::services::find_language;
+use crate::services::language::Script;
+use crate::{JsonResult, json_ok};
+
+/// ts_rank_cd normalization: divide by 1 + log(document length), so a short
+/// definition that matches ranks above a long one that mentions the term
+const RANK_NORMALIZATION: i32 = 1;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DefinitionHit {
+    pub definition_id: i64,
+    pub word_id: i64,
+    pub word: String,
+    pub part_of_speech: Option<String>,
+    /// Excerpt of the definition, matches are wrapped in `<b>` and `</b>`
+    pub snippet: String,
+    /// Relevance, higher is better (0 for plain substring matches)
+    pub rank: f32,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SentenceHit {
+    pub sentence_id: i64,
+    /// Words the sentence is an example of
+    pub word_ids: Vec<i64>,
+    /// Excerpt of the sentence, matches are wrapped in `<b>` and `</b>`
+    pub snippet: String,
+    pub rank: f32,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TranslationHit {
+    pub translation_id: i64,
+    /// What is translated, e.g. "word" or "definition"
+    pub origin_entity: String,
+    pub origin_id: i64,
+    /// Excerpt of the translation, matches are wrapped in `<b>` and `</b>`
+    pub snippet: String,
+    pub rank: f32,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DictSearchResponse {
+    pub query: String,
+    pub language: String,
+    /// Best match first in every list
+    pub definitions: Vec<DefinitionHit>,
+    pub sentences: Vec<SentenceHit>,
+    pub translations: Vec<TranslationHit>,
+}
+
+/// Search definitions, example sentences and translations
+///
+/// Query parameters:
+/// - `q`: Search text, web search syntax (`"exact phrase"`, `-excluded`, `or`)
+/// - `lang`: Language of the text searched (default "en"), selects the
+///   stemmer; Chinese, Japanese and Korean are also matched as substrings
+/// - `scope`: Only search `definitions`, `sentences` or `translations`
+/// - `limit`: Max hits per list (default 20, max 100)
+#[endpoint(tags("Dictionary"))]
+pub async fn search(req: &mut Request) -> JsonResult<DictSearchResponse> {
+    let query = req
+        .query::<String>("q")
+        .map(|q| q.trim().to_string())
+        .unwrap_or_default();
+    if query.is_empty() {
+        return Err(StatusError::bad_request().brief("q is required").into());
+    }
+    let language = req
+        .query::<String>("lang")
+        .unwrap_or_else(|| "en".to_owned());
+    let substring = substring_language(&language)?;
+    let scope = parse_scope(req.query::<String>("scope"))?;
+    let limit = req.query::<i64>("limit").unwrap_or(20).clamp(1, 100);
+    let wants = move |name: &str| scope.is_none_or(|s| s == name);
+
+    let search = Search {
+        query: query.clone(),
+        language: language.clone(),
+        substring,
+        limit,
+    };
+    let (definitions, sentences, translations) = with_conn(move |conn| {
+        let definitions = if wants("definitions") {
+            search_definitions(conn, &search)?
+        } else {
+            Vec::new()
+        };
+        let sentences = if wants("sentences") {
+            search_sentences(conn, &search)?
+        } else {
+            Vec::new()
+        };
+        let translations = if wants("translations") {
+            search_translations(conn, &search)?
+        } else {
+            Vec::new()
+        };
+        Ok((definitions, sentences, translations))
+    })
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to search dictionary: {:?}", e);
+        StatusError::internal_server_error().brief("failed to search dictionary")
+    })?;
+
+    json_ok(DictSearchResponse {
+        query,
+        language,
+        definitions,
+        sentences,
+        translations,
+    })
+}
+
+/// Whether `language` is searched as substrings too, as Chinese, Japanese
+/// and Korean are not split into words
+fn substring_language(language: &str) -> Result<bool, StatusError> {
+    let known = find_language(language)
+        .ok_or_else(|| StatusError::bad_request().brief("unsupported language"))?;
+    Ok(matches!(
+        known.script,
+        Script::Han | Script::Japanese | Script::Hangul
+    ))
+}
+
+/// The one list to search, all of them when `None`
+fn parse_scope(scope: Option<String>) -> Result<Option<&'static str>, StatusError> {
+    scope
+        .map(|scope| {
+            ["definitions", "sentences", "translations"]
+                .into_iter()
+                .find(|name| *name == scope)
+                .ok_or_else(|| {
+                    StatusError::bad_request()
+                        .brief("scope must be definitions, sentences or translations")
+                })
+        })
+        .transpose()
+}
+
+struct Search {
+    query: String,
+    language: String,
+    /// Also match the query as a substring
+    substring: bool,
+    limit: i64,
+}
+
+/// A row matching a search, see [`Search::matches`]
+#[derive(QueryableByName)]
+struct Match {
+    #[diesel(sql_type = BigInt)]
+    id: i64,
+    #[diesel(sql_type = Text)]
+    text: String,
+    #[diesel(sql_type = Text)]
+    headline: String,
+    #[diesel(sql_type = Float)]
+    rank: f32,
+}
+
+impl Search {
+    fn pattern(&self) -> String {
+        format!("%{}%", escape_like(&self.query))
+    }
+
+    /// ts_headline only marks full-text matches, substring hits are marked here
+    fn snippet(&self, headline: &str, text: &str) -> String {
+        headline_html(headline).unwrap_or_else(|| highlight_substring(text, &self.query))
+    }
+
+    /// Rows of `table` in the search language whose `search_vector` matches
+    /// the query, or whose `column` contains it for substring languages,
+    /// best first
+    ///
+    /// The text search configuration comes from `dict_search_config`, see
+    /// the `dict_full_text_search` migration. `table` and `column` are never
+    /// user input.
+    fn matches(
+        &self,
+        conn: &mut PgConnection,
+        table: &str,
+        column: &str,
+    ) -> QueryResult<Vec<Match>> {
+        let tsquery = "websearch_to_tsquery(dict_search_config($1), $2)";
+        let substring = if self.substring {
+            format!(" OR {column} ILIKE $5")
+        } else {
+            String::new()
+        };
+        let query = diesel::sql_query(format!(
+            "SELECT id, {column} AS text, \
+             ts_headline(dict_search_config($1), {column}, {tsquery}, $4) AS headline, \
+             ts_rank_cd(search_vector, {tsquery}, {RANK_NORMALIZATION}) AS rank \
+             FROM {table} \
+             WHERE language = $1 AND (search_vector @@ {tsquery}{substring}) \
+             ORDER BY rank DESC, id \
+             LIMIT $3"
+        ))
+        .into_boxed::<Pg>()
+        .bind::<Text, _>(self.language.clone())
+        .bind::<Text, _>(self.query.clone())
+        .bind::<BigInt, _>(self.limit)
+        .bind::<Text, _>(HEADLINE_OPTIONS);
+        let query = if self.substring {
+            query.bind::<Text, _>(self.pattern())
+        } else {
+            query
+        };
+        query.load::<Match>(conn)
+    }
+}
+
+fn search_definitions(conn: &mut PgConnection, search: &Search) -> QueryResult<Vec<DefinitionHit>> {
+    let matches = search.matches(conn, "dict_definitions", "definition")?;
+
+    let ids: Vec<i64> = matches.iter().map(|m| m.id).collect();
+    let details: HashMap<i64, (i64, Option<String>)> = dict_definitions::table
+        .filter(dict_definitions::id.eq_any(&ids))
+        .select((
+            dict_definitions::id,
+            dict_definitions::word_id,
+            dict_definitions::part_of_speech,
+        ))
+        .load::<(i64, i64, Option<String>)>(conn)?
+        .into_iter()
+        .map(|(id, word_id, part_of_speech)| (id, (word_id, part_of_speech)))
+        .collect();
+    let word_ids: Vec<i64> = details.values().map(|(word_id, _)| *word_id).collect();
+    let words: HashMap<i64, String> = dict_words::table
+        .filter(dict_words::id.eq_any(&word_ids))
+        .select((dict_words::id, dict_words::word))
+        .load::<(i64, String)>(conn)?
+        .into_iter()
+        .collect();
+
+    Ok(matches
+        .into_iter()
+        .filter_map(|m| {
+            let (word_id, part_of_speech) = details.get(&m.id)?.clone();
+            Some(DefinitionHit {
+                definition_id: m.id,
+                word_id,
+                word: words.get(&word_id).cloned().unwrap_or_default(),
+                part_of_speech,
+                snippet: search.snippet(&m.headline, &m.text),
+                rank: m.rank,
+            })
+        })
+        .collect())
+}
+
+fn search_sentences(conn: &mut PgConnection, search: &Search) -> QueryResult<Vec<SentenceHit>> {
+    let matches = search.matches(conn, "dict_sentences", "sentence")?;
+
+    let sentence_ids: Vec<i64> = matches.iter().map(|m| m.id).collect();
+    let mut word_ids: HashMap<i64, Vec<i64>> = HashMap::new();
+    for (sentence_id, word_id) in dict_word_sentences::table
+        .filter(dict_word_sentences::sentence_id.eq_any(&sentence_ids))
+        .select((
+            dict_word_sentences::sentence_id,
+            dict_word_sentences::word_id,
+        ))
+        .load::<(i64, i64)>(conn)?
+    {
+        word_ids.entry(sentence_id).or_default().push(word_id);
+    }
+
+    Ok(matches
+        .into_iter()
+        .map(|m| SentenceHit {
+            sentence_id: m.id,
+            word_ids: word_ids.remove(&m.id).unwrap_or_default(),
+            snippet: search.snippet(&m.headline, &m.text),
+            rank: m.rank,
+        })
+        .collect())
+}
+
+fn search_translations(
+    conn: &mut PgConnection,
+    search: &Search,
+) -> QueryResult<Vec<TranslationHit>> {
+    let matches = search.matches(conn, "dict_translations", "translation")?;
+
+    let ids: Vec<i64> = matches.iter().map(|m| m.id).collect();
+    let mut origins: HashMap<i64, (String, i64)> = dict_translations::table
+        .filter(dict_translations::id.eq_any(&ids))
+        .select((
+            dict_translations::id,
+            dict_translations::origin_entity,
+            dict_translations::origin_id,
+        ))
+        .load::<(i64, String, i64)>(conn)?
+        .into_iter()
+        .map(|(id, origin_entity, origin_id)| (id, (origin_entity, origin_id)))
+        .collect();
+
+    Ok(matches
+        .into_iter()
+        .filter_map(|m| {
+            let (origin_entity, origin_id) = origins.remove(&m.id)?;
+            Some(TranslationHit {
+                translation_id: m.id,
+                origin_entity,
+                origin_id,
+                snippet: search.snippet(&m.headline, &m.text),
+                rank: m.rank,
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zh_search(query: &str) -> Search {
+        Search {
+            query: query.to_owned(),
+            language: "zh".to_owned(),
+            substring: true,
+            limit: 20,
+        }
+    }
+
+    #[test]
+    fn snippets_mark_matches() {
+        let search = zh_search("苹果");
+        assert_eq!(
+            search.snippet("an \u{2}apple\u{3} a day", "an apple a day"),
+            "an <b>apple</b> a day"
+        );
+        assert_eq!(
+            search.snippet("我喜欢吃苹果", "我喜欢吃苹果"),
+            "我喜欢吃<b>苹果</b>"
+        );
+        assert_eq!(search.snippet("香蕉", "香蕉"), "香蕉");
+        assert_eq!(
+            search.snippet("<a>苹果</a>", "<a>苹果</a>"),
+            "&lt;a&gt;<b>苹果</b>&lt;/a&gt;"
+        );
+        assert_eq!(zh_search("50%_off").pattern(), "%50\\%\\_off%");
+    }
+
+    #[test]
+    fn validates_scope_and_language() {
+        assert_eq!(parse_scope(None).unwrap(), None);
+        assert_eq!(
+            parse_scope(Some("sentences".to_owned())).unwrap(),
+            Some("sentences")
+        );
+        assert!(parse_scope(Some("words".to_owned())).is_err());
+        assert!(parse_scope(Some(String::new())).is_err());
+        assert!(!substring_language("en").unwrap());
+        assert!(substring_language("zh").unwrap());
+        assert!(substring_language("ko").unwrap());
+        assert!(substring_language("xx").is_err());
+    }
+}
@@ -2,6 +2,7 @@ use diesel::prelude::*;
 use salvo::prelude::*;
 use serde::Deserialize;
 
+use crate::db::full_text_search::escape_like;
 use crate::db::schema::*;
 use crate::db::with_conn;
 use crate::models::dict::*;
@@ -26,7 +27,7 @@ pub async fn list_words(req: &mut Request) -> JsonResult<Vec<Word>> {
             .into_boxed();
 
         if let Some(term) = search_term {
-            query = query.filter(dict_words::word.ilike(format!("%{}%", term)));
+            query = query.filter(dict_words::word.ilike(format!("%{}%", escape_like(&term))));
         }
 
         if let Some(min_d) = min_diff {
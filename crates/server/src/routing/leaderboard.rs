@@ -0,0 +1,57 @@
+use chrono::Utc;
+use salvo::oapi::extract::*;
+use salvo::prelude::*;
+
+use crate::db::with_conn;
+use crate::learn::leaderboard::{self, BOARD_SIZE, Leaderboard, LeaderboardPeriod};
+use crate::learn::league::{self, LeagueView};
+use crate::{DepotExt, JsonResult, hoops, json_ok};
+
+pub fn router() -> Router {
+    Router::with_path("leaderboards")
+        .hoop(hoops::require_auth)
+        .get(get_leaderboard)
+        .push(Router::with_path("league").get(get_league))
+}
+
+/// XP leaderboard of this week (since Monday 00:00 UTC) or of all time
+///
+/// `period` is `weekly` (default) or `all_time`, `limit` is at most 100.
+#[endpoint(tags("Leaderboard"))]
+pub async fn get_leaderboard(
+    period: QueryParam<String, false>,
+    limit: QueryParam<i64, false>,
+    depot: &mut Depot,
+) -> JsonResult<Leaderboard> {
+    let user_id = depot.user_id()?;
+    let period = match period.into_inner() {
+        Some(value) => LeaderboardPeriod::parse(&value)
+            .ok_or_else(|| StatusError::bad_request().brief("period must be weekly or all_time"))?,
+        None => LeaderboardPeriod::Weekly,
+    };
+    let limit = limit.into_inner().unwrap_or(50).clamp(1, BOARD_SIZE);
+
+    let board =
+        with_conn(move |conn| leaderboard::leaderboard(conn, user_id, period, limit, Utc::now()))
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to load leaderboard: {:?}", e);
+                StatusError::internal_server_error().brief("failed to load leaderboard")
+            })?;
+    json_ok(board)
+}
+
+/// Standings of the current user's league this week, joining one if needed
+#[endpoint(tags("Leaderboard"))]
+pub async fn get_league(depot: &mut Depot) -> JsonResult<LeagueView> {
+    let user_id = depot.user_id()?;
+
+    let view = with_conn(move |conn| league::current_league(conn, user_id, Utc::now()))
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to load league: {:?}", e);
+            StatusError::internal_server_error().brief("failed to load league")
+        })?
+        .ok_or_else(|| StatusError::not_found().brief("leaderboards are turned off in settings"))?;
+    json_ok(view)
+}
@@ -4,30 +4,70 @@ use crate::hoops;
 
 mod achievement;
 mod chat;
+mod chat_search;
 mod daily_stat;
+mod dictation;
+mod goal;
 mod issue_word;
+mod placement;
 mod practice;
+mod quiz;
+mod report;
 mod reset;
+mod review;
+mod review_session;
+mod setting;
+mod shadowing;
+mod streak;
 mod suggestion;
 mod summary;
 mod vocabulary;
 
+/// Shared progress reports, readable without signing in
+pub fn shared_report_router() -> Router {
+    Router::with_path("reports/shared/{token}").get(report::get_shared_report)
+}
+
 pub fn router() -> Router {
     Router::with_path("learn")
         .hoop(hoops::require_auth)
         .push(Router::with_path("summary").get(summary::get_learn_summary))
+        .push(
+            Router::with_path("reports")
+                .get(report::get_report)
+                .push(Router::with_path("html").get(report::get_report_html))
+                .push(Router::with_path("pdf").get(report::get_report_pdf))
+                .push(
+                    Router::with_path("shares")
+                        .get(report::list_shares)
+                        .post(report::create_share)
+                        .push(Router::with_path("{id}").delete(report::revoke_share)),
+                ),
+        )
+        .push(
+            Router::with_path("resets")
+                .get(reset::list_resets)
+                .push(Router::with_path("{id}/restore").post(reset::restore_reset)),
+        )
+        .push(
+            Router::with_path("settings")
+                .get(setting::get_settings)
+                .put(setting::update_settings),
+        )
         .push(Router::with_path("audios/{user_id}/{filename}").get(chat::serve_audio))
         .push(
             Router::with_path("issue-words")
                 .get(issue_word::list_issue_words)
                 .post(issue_word::create_issue_word)
-                .delete(reset::reset_issue_words),
+                .delete(reset::reset_issue_words)
+                .push(Router::with_path("{id}/review").post(review::review_issue_word)),
         )
         .push(
             Router::with_path("chats")
                 .get(chat::list_chats)
                 .post(chat::create_chat)
                 .delete(reset::reset_chats)
+                .push(Router::with_path("search").get(chat_search::search_chats))
                 .push(
                     Router::with_path("{id}")
                         .put(chat::update_chat)
@@ -56,18 +96,87 @@ pub fn router() -> Router {
                 .post(practice::create_read_practice)
                 .delete(reset::reset_read_practices),
         )
+        .push(
+            Router::with_path("read-progress")
+                .get(practice::list_read_progress)
+                .push(Router::with_path("{subject_id}").get(practice::get_read_progress)),
+        )
+        .push(Router::with_path("weak-sounds").get(practice::list_weak_sounds))
+        .push(
+            Router::with_path("shadowing")
+                .get(shadowing::list_shadowing)
+                .post(shadowing::create_shadowing)
+                .push(Router::with_path("trend").get(shadowing::shadowing_trend)),
+        )
+        .push(
+            Router::with_path("dictations")
+                .post(dictation::create_dictation)
+                .push(Router::with_path("{id}/answer").post(dictation::answer_dictation)),
+        )
         .push(
             Router::with_path("vocabulary")
                 .get(vocabulary::list_vocabulary)
                 .post(vocabulary::create_vocabulary)
                 .delete(reset::reset_vocabulary)
-                .push(Router::with_path("toggle").post(vocabulary::toggle_vocabulary)),
+                .push(Router::with_path("toggle").post(vocabulary::toggle_vocabulary))
+                .push(Router::with_path("extract").post(vocabulary::extract_vocabulary))
+                .push(Router::with_path("bulk").post(vocabulary::bulk_add_vocabulary))
+                .push(Router::with_path("{id}/review").post(review::review_vocabulary)),
+        )
+        .push(
+            Router::with_path("review-sessions")
+                .post(review_session::create_review_session)
+                .push(
+                    Router::with_path("{id}")
+                        .get(review_session::get_review_session)
+                        .push(
+                            Router::with_path("items/{item_id}/answer")
+                                .post(review_session::answer_review_item),
+                        ),
+                ),
+        )
+        .push(
+            Router::with_path("quizzes").post(quiz::create_quiz).push(
+                Router::with_path("{id}").get(quiz::get_quiz).push(
+                    Router::with_path("questions/{question_id}/answer")
+                        .post(quiz::answer_quiz_question),
+                ),
+            ),
+        )
+        .push(
+            Router::with_path("placement")
+                .post(placement::start_placement)
+                .push(Router::with_path("recommendations").get(placement::get_recommendations))
+                .push(
+                    Router::with_path("{id}")
+                        .get(placement::get_placement)
+                        .push(
+                            Router::with_path("items/{item_id}/answer")
+                                .post(placement::answer_placement_item),
+                        ),
+                ),
         )
         .push(
             Router::with_path("daily-stats")
                 .get(daily_stat::list_daily_stats)
-                .post(daily_stat::upsert_daily_stat)
-                .delete(reset::reset_daily_stats),
+                .delete(reset::reset_daily_stats)
+                .push(
+                    Router::new()
+                        .hoop(hoops::require_admin)
+                        .post(daily_stat::upsert_daily_stat),
+                ),
+        )
+        .push(Router::with_path("streak").get(streak::get_streak))
+        .push(
+            Router::with_path("goals")
+                .get(goal::list_goals)
+                .post(goal::create_goal)
+                .push(Router::with_path("calendar").get(goal::export_calendar))
+                .push(
+                    Router::with_path("{id}")
+                        .put(goal::update_goal)
+                        .delete(goal::delete_goal),
+                ),
         )
         .push(
             Router::with_path("achievements")
@@ -1,23 +1,30 @@
-use std::path::PathBuf;
 use std::time::Duration;
 
 use base64::Engine;
 use base64::engine::general_purpose::STANDARD as BASE64;
-use chrono::{Utc, format};
+use chrono::format;
 use diesel::prelude::*;
 use salvo::oapi::ToSchema;
 use salvo::oapi::extract::*;
 use salvo::prelude::*;
 use serde::{Deserialize, Serialize};
 
-use crate::config::AppConfig;
 use crate::db::schema::*;
 use crate::db::with_conn;
+use crate::learn::achievement::{self, AchievementEvent};
+use crate::learn::audio::{self, save_audio_file};
+use crate::learn::reset::{self, ResetKind};
+use crate::learn::stats::{self, Activity};
+use crate::models::achievement::AchievementBadge;
 use crate::models::learn::{Chat, ChatIssue, ChatTurn, NewChat, NewChatIssue, NewChatTurn};
+use crate::routing::recording;
 use crate::services::{
-    AiProviderError, ChatMessage, StructuredChatResponse, TextIssue, create_provider_from_env,
+    AiProviderError, ChatMessage, LanguagePair, StructuredChatResponse, TextIssue,
+    create_provider_from_env,
 };
-use crate::{AppResult, DepotExt, JsonResult, OkResponse, json_ok};
+use crate::{AppConfig, AppResult, DepotExt, JsonResult, OkResponse, json_ok};
+
+use super::setting;
 
 // Type aliases for backward compatibility
 type ChatSession = Chat;
@@ -34,8 +41,8 @@ pub struct HistoryMessage {
 impl From<ChatTurn> for HistoryMessage {
     fn from(msg: ChatTurn) -> Self {
         HistoryMessage {
+            content: msg.target_content().to_owned(),
             role: msg.speaker,
-            content: msg.content_en,
         }
     }
 }
@@ -308,6 +315,12 @@ pub struct ChatTurnWithIssues {
     pub use_lang: String,
     pub content_en: String,
     pub content_zh: String,
+    /// Language being practiced in this turn
+    pub target_lang: String,
+    /// Language used for translations in this turn
+    pub native_lang: String,
+    /// Text keyed by language code, e.g. `{"en": "...", "ja": "..."}`
+    pub contents: serde_json::Value,
     pub audio_path: Option<String>,
     pub duration_ms: Option<i32>,
     pub words_per_minute: Option<f32>,
@@ -331,6 +344,9 @@ impl ChatTurnWithIssues {
             use_lang: turn.use_lang,
             content_en: turn.content_en,
             content_zh: turn.content_zh,
+            target_lang: turn.target_lang,
+            native_lang: turn.native_lang,
+            contents: turn.contents,
             audio_path: turn.audio_path,
             duration_ms: turn.duration_ms,
             words_per_minute: turn.words_per_minute,
@@ -353,6 +369,9 @@ impl ChatTurnWithIssues {
             use_lang: turn.use_lang,
             content_en: turn.content_en,
             content_zh: turn.content_zh,
+            target_lang: turn.target_lang,
+            native_lang: turn.native_lang,
+            contents: turn.contents,
             audio_path: turn.audio_path,
             duration_ms: turn.duration_ms,
             words_per_minute: turn.words_per_minute,
@@ -649,8 +668,10 @@ pub enum ChatSendRequest {
 pub struct TtsRequest {
     /// Text to synthesize
     pub text: String,
-    /// Voice option
+    /// Voice option, defaults to a voice for `language`
     pub voice: Option<String>,
+    /// Language of the text, defaults to the user's target language
+    pub language: Option<String>,
     /// Speed (0.5 - 2.0)
     pub speed: Option<f32>,
 }
@@ -662,6 +683,8 @@ pub struct ChatSendResponse {
     pub user_turn: ChatTurnWithIssues,
     /// AI's chat turn (status: processing, will be updated async)
     pub ai_turn: ChatTurnWithIssues,
+    /// Achievements unlocked by this message
+    pub unlocked: Vec<AchievementBadge>,
 }
 
 /// Response for TTS only
@@ -678,38 +701,83 @@ pub struct HistoryResponse {
     pub messages: Vec<HistoryMessage>,
 }
 
-const DEFAULT_SYSTEM_PROMPT: &str = r#"You are a friendly English conversation partner helping users practice their English speaking skills.
+/// Build the tutoring system prompt for a language pair
+fn system_prompt(langs: &LanguagePair) -> String {
+    let target = langs.target_name();
+    let native = langs.native_name();
+    let target_code = &langs.target;
+    let native_code = &langs.native;
+
+    let mut prompt = format!(
+        r#"You are a friendly {target} conversation partner helping a native {native} speaker practice their {target} speaking skills.
 
 CRITICAL: You MUST respond with a valid JSON object. NO OTHER TEXT ALLOWED.
 
 STEPS:
-1. Detect language: If Chinese set use_lang=\"zh\", if English set use_lang=\"en\", if mixed set use_lang=\"mix\"
+1. Detect language: If {native} set use_lang=\"{native_code}\", if {target} set use_lang=\"{target_code}\", if mixed set use_lang=\"mix\"
 
-2. *** MUST ANALYZE ENGLISH FOR ERRORS ***
-   When user writes in English, you MUST check for grammar/vocabulary errors.
+2. *** MUST ANALYZE {target} FOR ERRORS ***
+   When user writes in {target}, you MUST check for grammar/vocabulary errors.
    For EACH error found, add to 'issues' array:
-   {\"type\":\"grammar\",\"original\":\"wrong text\",\"suggested\":\"correct text\",\"description_en\":\"explanation\",\"description_zh\":\"中文解释\",\"severity\":\"medium\",\"start_position\":0,\"end_position\":0}
+   {{\"type\":\"grammar\",\"original\":\"wrong text\",\"suggested\":\"correct text\",\"description_target\":\"explanation in simple {target}\",\"description_native\":\"explanation in {native}\",\"severity\":\"medium\",\"start_position\":0,\"end_position\":0}}
 
    Then add a suggestion with the full corrected sentence:
-   {\"type\":\"suggestion\",\"original\":\"full original\",\"suggested\":\"full corrected\",\"description_en\":\"Better way to say this\",\"description_zh\":\"更好的表达\",\"severity\":\"low\",\"start_position\":0,\"end_position\":0}
+   {{\"type\":\"suggestion\",\"original\":\"full original\",\"suggested\":\"full corrected\",\"description_target\":\"Better way to say this, in {target}\",\"description_native\":\"the same in {native}\",\"severity\":\"low\",\"start_position\":0,\"end_position\":0}}
 
-3. Generate reply_en and reply_zh as natural conversation. Do NOT mention errors here - errors go in 'issues' only.
+3. Put the user's message in {target} into original_target and in {native} into original_native, translating whichever side they did not write.
+
+4. Generate reply_target in {target} and reply_native (its {native} translation) as natural conversation. Do NOT mention errors here - errors go in 'issues' only.
+"#
+    );
 
+    // Concrete example for the original English/Chinese setup
+    if target_code == "en" && native_code == "zh" {
+        prompt.push_str(
+            r#"
 EXAMPLE for \"I go to school yesterday\":
 {
   \"use_lang\":\"en\",
-  \"original_en\":\"I go to school yesterday\",
-  \"original_zh\":\"我昨天去学校了\",
-  \"reply_en\":\"That sounds nice! What did you do there?\",
-  \"reply_zh\":\"听起来不错！你在那里做了什么？\",
+  \"original_target\":\"I go to school yesterday\",
+  \"original_native\":\"我昨天去学校了\",
+  \"reply_target\":\"That sounds nice! What did you do there?\",
+  \"reply_native\":\"听起来不错！你在那里做了什么？\",
   \"issues\":[
-    {\"type\":\"grammar\",\"original\":\"go\",\"suggested\":\"went\",\"description_en\":\"Use past tense with yesterday\",\"description_zh\":\"yesterday要用过去式\",\"severity\":\"medium\",\"start_position\":2,\"end_position\":4},
-    {\"type\":\"suggestion\",\"original\":\"I go to school yesterday\",\"suggested\":\"I went to school yesterday\",\"description_en\":\"Corrected sentence\",\"description_zh\":\"修正后的句子\",\"severity\":\"low\",\"start_position\":0,\"end_position\":0}
+    {\"type\":\"grammar\",\"original\":\"go\",\"suggested\":\"went\",\"description_target\":\"Use past tense with yesterday\",\"description_native\":\"yesterday要用过去式\",\"severity\":\"medium\",\"start_position\":2,\"end_position\":4},
+    {\"type\":\"suggestion\",\"original\":\"I go to school yesterday\",\"suggested\":\"I went to school yesterday\",\"description_target\":\"Corrected sentence\",\"description_native\":\"修正后的句子\",\"severity\":\"low\",\"start_position\":0,\"end_position\":0}
   ]
 }
+"#,
+        );
+    }
 
+    prompt.push_str(
+        r#"
 JSON format:
-{\"use_lang\":\"\",\"original_en\":\"\",\"original_zh\":\"\",\"reply_en\":\"\",\"reply_zh\":\"\",\"issues\":[]}"#;
+{\"use_lang\":\"\",\"original_target\":\"\",\"original_native\":\"\",\"reply_target\":\"\",\"reply_native\":\"\",\"issues\":[]}"#,
+    );
+    prompt
+}
+
+/// Store text of both sides of a language pair keyed by language code
+fn contents_json(langs: &LanguagePair, target_text: &str, native_text: &str) -> serde_json::Value {
+    let mut contents = serde_json::Map::new();
+    if !target_text.is_empty() {
+        contents.insert(langs.target.clone(), target_text.into());
+    }
+    if !native_text.is_empty() {
+        contents.insert(langs.native.clone(), native_text.into());
+    }
+    serde_json::Value::Object(contents)
+}
+
+/// Text for the legacy `*_en` / `*_zh` columns
+fn legacy_text(contents: &serde_json::Value, lang: &str) -> String {
+    contents
+        .get(lang)
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_owned()
+}
 
 // ============================================================================
 // Database helper functions
@@ -742,8 +810,9 @@ struct SaveMessageParams {
     chat_id: i64,
     speaker: String,
     use_lang: String,
-    content_en: String,
-    content_zh: String,
+    langs: LanguagePair,
+    content_target: String,
+    content_native: String,
     audio_path: Option<String>,
     issues_count: Option<i32>,
 }
@@ -751,6 +820,11 @@ struct SaveMessageParams {
 /// Save a single message to database and return the created turn
 async fn save_message(params: SaveMessageParams, status: &str) -> Result<ChatTurn, StatusError> {
     let status = status.to_string();
+    let contents = contents_json(
+        &params.langs,
+        &params.content_target,
+        &params.content_native,
+    );
     with_conn(move |conn| {
         diesel::insert_into(learn_chat_turns::table)
             .values(&NewChatTurn {
@@ -758,14 +832,17 @@ async fn save_message(params: SaveMessageParams, status: &str) -> Result<ChatTur
                 chat_id: params.chat_id,
                 speaker: params.speaker,
                 use_lang: params.use_lang,
-                content_en: params.content_en,
-                content_zh: params.content_zh,
+                content_en: legacy_text(&contents, "en"),
+                content_zh: legacy_text(&contents, "zh"),
                 audio_path: params.audio_path,
                 duration_ms: None,
                 words_per_minute: None,
                 issues_count: params.issues_count,
                 hesitation_count: None,
                 status,
+                target_lang: params.langs.target,
+                native_lang: params.langs.native,
+                contents,
             })
             .get_result::<ChatTurn>(conn)
     })
@@ -776,59 +853,20 @@ async fn save_message(params: SaveMessageParams, status: &str) -> Result<ChatTur
     })
 }
 
-/// Get the audio storage directory for a user
-fn get_audio_dir(user_id: i64) -> PathBuf {
-    PathBuf::from(&AppConfig::get().space_path)
-        .join("learn/audios")
-        .join(user_id.to_string())
-}
-
-/// Save audio data to file and return the relative path
-async fn save_audio_file(
-    user_id: i64,
-    audio_data: &[u8],
-    prefix: &str,
-    format: &str,
-) -> Option<String> {
-    let audio_dir: PathBuf = get_audio_dir(user_id);
-
-    // Create directory if it doesn't exist
-    if let Err(e) = tokio::fs::create_dir_all(&audio_dir).await {
-        tracing::error!("Failed to create audio directory: {:?}", e);
-        return None;
-    }
-
-    // Generate unique filename with timestamp
-    let timestamp = Utc::now().format("%Y%m%d_%H%M%S_%3f");
-    let filename = format!("{}_{}.{}", prefix, timestamp, format);
-    let file_path = audio_dir.join(&filename);
-
-    // Write audio data to file
-    if let Err(e) = tokio::fs::write(&file_path, audio_data).await {
-        tracing::error!("Failed to save audio file: {:?}", e);
-        return None;
-    }
-
-    // Return relative path for database storage
-    let relative_path = format!("learn/audios/{}/{}", user_id, filename);
-    tracing::info!("Saved audio file: {}", relative_path);
-    Some(relative_path)
-}
-
-/// Clear session (delete all messages for this user's active chat)
+/// Clear session (archive all chats of this user, restorable for a while)
 async fn clear_user_session(user_id: i64) -> Result<(), StatusError> {
+    let restore_days = AppConfig::get().account.reset_restore_days;
     with_conn(move |conn| {
-        // Delete all chat turns for this user
-        diesel::delete(learn_chat_turns::table.filter(learn_chat_turns::user_id.eq(user_id)))
-            .execute(conn)?;
-
-        // Delete all chat sessions for this user
-        diesel::delete(learn_chats::table.filter(learn_chats::user_id.eq(user_id)))
-            .execute(conn)?;
-
-        Ok(())
+        reset::archive(
+            conn,
+            user_id,
+            ResetKind::Chats,
+            restore_days,
+            chrono::Utc::now(),
+        )
     })
     .await
+    .map(|_| ())
     .map_err(|e| {
         tracing::error!("Failed to clear session: {:?}", e);
         StatusError::internal_server_error().brief("database error")
@@ -896,6 +934,9 @@ pub async fn send_chat(
         StatusError::bad_request().brief("invalid json")
     })?;
 
+    // Native/target languages drive the ASR hint, the prompt and the TTS voice
+    let langs = setting::get_language_pair(user_id).await;
+
     // Get AI provider early - needed for both ASR and user input analysis
     let provider = create_provider_from_env()
         .ok_or_else(|| StatusError::internal_server_error().brief("AI provider not configured"))?;
@@ -903,30 +944,12 @@ pub async fn send_chat(
     // Process based on input type - transcribe audio if needed
     let (user_text, user_audio_data) = match &input {
         ChatSendRequest::Audio { audio_base64 } => {
-            // Decode audio
-            let audio_data = BASE64
-                .decode(audio_base64)
-                .map_err(|_| StatusError::bad_request().brief("invalid base64 audio"))?;
-
-            if audio_data.is_empty() {
-                return Err(StatusError::bad_request()
-                    .brief("audio data is empty")
-                    .into());
-            }
+            let audio_data = recording::decode_audio(audio_base64)?;
 
             // Transcribe audio to text using ASR
-            let asr = provider.asr().ok_or_else(|| {
-                StatusError::internal_server_error().brief("ASR service not available")
-            })?;
-
             tracing::info!("Calling {} ASR API...", provider.name());
-            let asr_result = asr
-                .transcribe(audio_data.clone(), Some("auto"))
-                .await
-                .map_err(|e: AiProviderError| {
-                    tracing::error!("{} ASR error: {:?}", provider.name(), e);
-                    StatusError::internal_server_error().brief(e.to_string())
-                })?;
+            let asr_result =
+                recording::transcribe(audio_data.clone(), &langs.conversation_asr_hint()).await?;
             tracing::info!("{} ASR API completed: {}", provider.name(), asr_result.text);
 
             if asr_result.text.trim().is_empty() {
@@ -981,17 +1004,29 @@ pub async fn send_chat(
         history.len()
     );
     let structured_response = chat_service
-        .chat_structured(history, &user_text, DEFAULT_SYSTEM_PROMPT)
+        .chat_structured(history, &user_text, &system_prompt(&langs), &langs)
         .await
         .unwrap_or_else(|e| {
             tracing::warn!("Failed to get structured response: {}, using defaults", e);
-            // Fallback: assume English, use original text
+            // Fallback: keep the original text on the side of the pair it was written in
+            let user_lang = langs
+                .guess_lang(&user_text)
+                .unwrap_or(&langs.target)
+                .to_owned();
             StructuredChatResponse {
-                use_lang: "en".to_string(),
-                original_en: user_text.clone(),
-                original_zh: String::new(),
-                reply_en: "I'm sorry, I couldn't process your input.".to_string(),
-                reply_zh: "抱歉，我无法处理您的输入。".to_string(),
+                original_target: if user_lang == langs.target {
+                    user_text.clone()
+                } else {
+                    String::new()
+                },
+                original_native: if user_lang == langs.native {
+                    user_text.clone()
+                } else {
+                    String::new()
+                },
+                use_lang: user_lang,
+                reply_target: langs.target_apology().to_owned(),
+                reply_native: langs.native_apology().to_owned(),
                 issues: vec![],
             }
         });
@@ -999,7 +1034,7 @@ pub async fn send_chat(
         "{} chat_structured completed: use_lang={}, reply_len={}",
         provider.name(),
         structured_response.use_lang,
-        structured_response.reply_en.len()
+        structured_response.reply_target.len()
     );
 
     println!("======structured_response: {:#?}", structured_response);
@@ -1010,8 +1045,9 @@ pub async fn send_chat(
             chat_id,
             speaker: "user".to_string(),
             use_lang: structured_response.use_lang.clone(),
-            content_en: structured_response.original_en.clone(),
-            content_zh: structured_response.original_zh.clone(),
+            langs: langs.clone(),
+            content_target: structured_response.original_target.clone(),
+            content_native: structured_response.original_native.clone(),
             audio_path: user_audio_path,
             issues_count: Some(structured_response.issues.len() as i32),
         },
@@ -1019,6 +1055,17 @@ pub async fn send_chat(
     )
     .await?;
 
+    let turn_at = user_turn.created_at;
+    let unlocked = with_conn(move |conn| {
+        stats::record_activity(conn, user_id, Activity::ChatTurn { chat_id }, turn_at)?;
+        achievement::evaluate_and_notify(conn, user_id, AchievementEvent::Studied, turn_at)
+    })
+    .await
+    .unwrap_or_else(|e| {
+        tracing::error!("Failed to record chat activity: {:?}", e);
+        Vec::new()
+    });
+
     // Save chat issues if any issues were found in user input
     if !structured_response.issues.is_empty() {
         tracing::info!(
@@ -1031,18 +1078,23 @@ pub async fn send_chat(
         let issues: Vec<NewChatIssue> = structured_response
             .issues
             .iter()
-            .map(|issue| NewChatIssue {
-                user_id,
-                chat_id,
-                chat_turn_id: user_turn_id,
-                issue_type: issue.issue_type.clone(),
-                start_position: issue.start_position,
-                end_position: issue.end_position,
-                original_text: Some(issue.original.clone()),
-                suggested_text: Some(issue.suggested.clone()),
-                description_en: Some(issue.description_en.clone()),
-                description_zh: Some(issue.description_zh.clone()),
-                severity: Some(issue.severity.clone()),
+            .map(|issue| {
+                let descriptions =
+                    contents_json(&langs, &issue.description_target, &issue.description_native);
+                NewChatIssue {
+                    user_id,
+                    chat_id,
+                    chat_turn_id: user_turn_id,
+                    issue_type: issue.issue_type.clone(),
+                    start_position: issue.start_position,
+                    end_position: issue.end_position,
+                    original_text: Some(issue.original.clone()),
+                    suggested_text: Some(issue.suggested.clone()),
+                    description_en: Some(legacy_text(&descriptions, "en")),
+                    description_zh: Some(legacy_text(&descriptions, "zh")),
+                    severity: Some(issue.severity.clone()),
+                    descriptions,
+                }
             })
             .collect();
 
@@ -1064,26 +1116,26 @@ pub async fn send_chat(
 
     // Spawn background task for AI response generation
     tracing::info!(
-        "Background: {} chat_structured API completed: reply_en_len={}",
+        "Background: {} chat_structured API completed: reply_target_len={}",
         provider.name(),
-        structured_response.reply_en.len()
+        structured_response.reply_target.len()
     );
 
     // Generate TTS for AI response if needed
     let ai_audio_path = if let Some(tts) = provider.tts() {
         tracing::info!(
             "Generating TTS for AI response ({} chars)...",
-            structured_response.reply_en.len()
+            structured_response.reply_target.len()
         );
-        // Use English voice for English response text
-        match tts
-            .synthesize(
-                &structured_response.reply_en,
-                Some("zh_female_vv_uranus_bigtts"),
-                None,
-            )
-            .await
-        {
+        // Use a voice that speaks the target language of the reply
+        let audio = match tts.voice_for_language(&langs.target) {
+            Ok(voice) => {
+                tts.synthesize(&structured_response.reply_target, voice, None)
+                    .await
+            }
+            Err(e) => Err(e),
+        };
+        match audio {
             Ok(tts_response) => {
                 tracing::info!(
                     "Background: TTS succeeded, saving {} bytes audio...",
@@ -1106,9 +1158,10 @@ pub async fn send_chat(
             user_id,
             chat_id,
             speaker: "assistant".to_string(),
-            use_lang: "en".to_owned(),
-            content_en: structured_response.reply_en.clone(),
-            content_zh: structured_response.reply_zh.clone(),
+            use_lang: langs.target.clone(),
+            langs: langs.clone(),
+            content_target: structured_response.reply_target.clone(),
+            content_native: structured_response.reply_native.clone(),
             audio_path: ai_audio_path,
             issues_count: None,
         },
@@ -1134,6 +1187,7 @@ pub async fn send_chat(
     json_ok(ChatSendResponse {
         user_turn: ChatTurnWithIssues::from_turn_with_issues(user_turn, user_issues),
         ai_turn: ChatTurnWithIssues::from_turn(ai_turn),
+        unlocked,
     })
 }
 
@@ -1143,7 +1197,7 @@ pub async fn text_to_speech(
     input: JsonBody<TtsRequest>,
     depot: &mut Depot,
 ) -> JsonResult<TtsResponse> {
-    let _user_id = depot.user_id()?;
+    let user_id = depot.user_id()?;
 
     if input.text.trim().is_empty() {
         return Err(StatusError::bad_request().brief("text is required").into());
@@ -1158,10 +1212,21 @@ pub async fn text_to_speech(
         .tts()
         .ok_or_else(|| StatusError::internal_server_error().brief("TTS service not available"))?;
 
+    let language = match &input.language {
+        Some(language) => language.clone(),
+        None => setting::get_language_pair(user_id).await.target,
+    };
+    let voice = match input.voice.as_deref() {
+        Some(voice) => Some(voice),
+        None => tts
+            .voice_for_language(&language)
+            .map_err(|e| StatusError::bad_request().brief(e.to_string()))?,
+    };
+
     // Generate audio
     tracing::info!("Calling {} TTS API...", provider.name());
     let tts_response = tts
-        .synthesize(&input.text, input.voice.as_deref(), input.speed)
+        .synthesize(&input.text, voice, input.speed)
         .await
         .map_err(|e: AiProviderError| {
             tracing::error!("{} TTS error: {:?}", provider.name(), e);
@@ -1307,10 +1372,7 @@ pub async fn serve_audio(
     }
 
     // Build the file path
-    let file_path = PathBuf::from(&AppConfig::get().space_path)
-        .join("learn/audios")
-        .join(path_user_id.to_string())
-        .join(&filename);
+    let file_path = audio::audio_dir(path_user_id).join(&filename);
 
     // Check if file exists
     if !file_path.exists() {
@@ -0,0 +1,211 @@
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Float, Jsonb, Text, Timestamptz};
+use salvo::oapi::ToSchema;
+use salvo::prelude::*;
+use serde::Serialize;
+
+use crate::db::full_text_search::configuration::TsConfigurationByName;
+use crate::db::full_text_search::*;
+use crate::db::schema::*;
+use crate::db::with_conn;
+use crate::{DepotExt, JsonResult, json_ok};
+
+/// Chat turn matching a history search
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChatTurnHit {
+    pub turn_id: i64,
+    pub chat_id: i64,
+    pub chat_title: String,
+    pub speaker: String,
+    /// Excerpt of the matching content in any language, HTML-escaped with
+    /// matches wrapped in `<b>` and `</b>`
+    pub snippet: String,
+    /// Relevance, higher is better (0 for plain substring matches)
+    pub rank: f32,
+    /// Frontend route of the chat containing this turn
+    pub chat_url: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Chat whose title matches a history search
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChatTitleHit {
+    pub chat_id: i64,
+    /// Title, HTML-escaped with matches wrapped in `<b>` and `</b>`
+    pub title: String,
+    /// Frontend route of the chat
+    pub chat_url: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChatSearchResponse {
+    pub query: String,
+    /// Matching turns, best match first
+    pub turns: Vec<ChatTurnHit>,
+    /// Chats whose title matches, newest first
+    pub chats: Vec<ChatTitleHit>,
+}
+
+/// Search the current user's chat history
+///
+/// Query parameters:
+/// - `q`: Search text, English is stemmed ("trips" finds "trip"), other languages match whole
+///   words and Chinese is matched as substring
+/// - `limit`: Max turns to return (default 20, max 100)
+#[endpoint(tags("Chat"))]
+pub async fn search_chats(req: &mut Request, depot: &mut Depot) -> JsonResult<ChatSearchResponse> {
+    let user_id = depot.user_id()?;
+    let query = req
+        .query::<String>("q")
+        .map(|q| q.trim().to_string())
+        .unwrap_or_default();
+    if query.is_empty() {
+        return Err(StatusError::bad_request().brief("q is required").into());
+    }
+    let limit = req.query::<i64>("limit").unwrap_or(20).clamp(1, 100);
+
+    let q = query.clone();
+    let (turn_rows, chat_rows) = with_conn(move |conn| {
+        let simple = TsConfigurationByName("simple");
+        let pattern = format!("%{}%", escape_like(&q));
+
+        // English is stemmed; the simple configuration finds words of other
+        // languages and the pattern Chinese, whose runs are one token
+        let turn_rows = diesel::sql_query(
+            "SELECT t.id AS turn_id, t.chat_id, c.title AS chat_title, t.speaker, t.contents, \
+                    t.created_at, \
+                    ts_headline('english', t.contents, plainto_tsquery('english', $2), $4) \
+                        AS headline_en, \
+                    ts_headline('simple', t.contents, plainto_tsquery('simple', $2), $4) \
+                        AS headline_simple, \
+                    ts_rank_cd(to_tsvector('english', t.contents), plainto_tsquery('english', $2)) \
+                        + ts_rank_cd(to_tsvector('simple', t.contents), plainto_tsquery('simple', $2)) \
+                        AS rank \
+             FROM learn_chat_turns t \
+             JOIN learn_chats c ON c.id = t.chat_id \
+             WHERE t.user_id = $1 \
+               AND (to_tsvector('english', t.contents) @@ plainto_tsquery('english', $2) \
+                    OR to_tsvector('simple', t.contents) @@ plainto_tsquery('simple', $2) \
+                    OR chat_contents_text(t.contents) ILIKE $3) \
+             ORDER BY rank DESC, t.created_at DESC \
+             LIMIT $5",
+        )
+        .bind::<BigInt, _>(user_id)
+        .bind::<Text, _>(q.clone())
+        .bind::<Text, _>(pattern.clone())
+        .bind::<Text, _>(HEADLINE_OPTIONS)
+        .bind::<BigInt, _>(limit)
+        .load::<TurnRow>(conn)?;
+
+        let query_title = plainto_tsquery_with_search_config(simple, q);
+        let chat_rows = learn_chats::table
+            .filter(learn_chats::user_id.eq(user_id))
+            .filter(
+                to_tsvector_with_search_config(simple, learn_chats::title)
+                    .matches(query_title.clone())
+                    .or(learn_chats::title.ilike(pattern)),
+            )
+            .order(learn_chats::created_at.desc())
+            .limit(10)
+            .select((
+                learn_chats::id,
+                learn_chats::title,
+                ts_headline_with_search_config_and_options(
+                    simple,
+                    learn_chats::title,
+                    query_title,
+                    HEADLINE_OPTIONS,
+                ),
+                learn_chats::created_at,
+            ))
+            .load::<(i64, String, String, DateTime<Utc>)>(conn)?;
+
+        Ok((turn_rows, chat_rows))
+    })
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to search chats: {:?}", e);
+        StatusError::internal_server_error().brief("failed to search chats")
+    })?;
+
+    let turns = turn_rows
+        .into_iter()
+        .map(|row| ChatTurnHit {
+            snippet: turn_snippet(&row, &query),
+            turn_id: row.turn_id,
+            chat_id: row.chat_id,
+            chat_title: row.chat_title,
+            speaker: row.speaker,
+            rank: row.rank,
+            chat_url: format!("/chat/{}", row.chat_id),
+            created_at: row.created_at,
+        })
+        .collect();
+
+    let chats = chat_rows
+        .into_iter()
+        .map(|(chat_id, title, headline, created_at)| ChatTitleHit {
+            chat_id,
+            title: headline_html(&headline).unwrap_or_else(|| highlight_substring(&title, &query)),
+            chat_url: format!("/chat/{chat_id}"),
+            created_at,
+        })
+        .collect();
+
+    json_ok(ChatSearchResponse {
+        query,
+        turns,
+        chats,
+    })
+}
+
+/// A turn matching a history search, see [`search_chats`]
+#[derive(QueryableByName)]
+struct TurnRow {
+    #[diesel(sql_type = BigInt)]
+    turn_id: i64,
+    #[diesel(sql_type = BigInt)]
+    chat_id: i64,
+    #[diesel(sql_type = Text)]
+    chat_title: String,
+    #[diesel(sql_type = Text)]
+    speaker: String,
+    /// Text of the turn keyed by language code
+    #[diesel(sql_type = Jsonb)]
+    contents: serde_json::Value,
+    #[diesel(sql_type = Timestamptz)]
+    created_at: DateTime<Utc>,
+    /// `contents` with English full-text matches marked
+    #[diesel(sql_type = Jsonb)]
+    headline_en: serde_json::Value,
+    /// `contents` with matches of the simple configuration marked
+    #[diesel(sql_type = Jsonb)]
+    headline_simple: serde_json::Value,
+    #[diesel(sql_type = Float)]
+    rank: f32,
+}
+
+/// Texts of a `contents` object, in key order
+fn texts(contents: &serde_json::Value) -> impl Iterator<Item = &str> {
+    contents
+        .as_object()
+        .into_iter()
+        .flat_map(|texts| texts.values())
+        .filter_map(|text| text.as_str())
+}
+
+/// Excerpt of the language that matched; substring matches, such as Chinese
+/// ones, are marked here since ts_headline can't see them
+fn turn_snippet(row: &TurnRow, query: &str) -> String {
+    texts(&row.headline_en)
+        .chain(texts(&row.headline_simple))
+        .find_map(headline_html)
+        .or_else(|| {
+            texts(&row.contents)
+                .map(|text| highlight_substring(text, query))
+                .find(|snippet| snippet.contains("<b>"))
+        })
+        .unwrap_or_else(|| highlight_substring(texts(&row.contents).next().unwrap_or(""), query))
+}
@@ -1,4 +1,4 @@
-use chrono::NaiveDate;
+use chrono::{NaiveDate, Utc};
 use diesel::prelude::*;
 use salvo::oapi::ToSchema;
 use salvo::prelude::*;
@@ -9,8 +9,11 @@ use crate::db::with_conn;
 use crate::models::learn::*;
 use crate::{AppResult, DepotExt};
 
+/// Manual correction of a day, statistics are otherwise derived from activity
 #[derive(Deserialize, ToSchema)]
 pub struct UpsertDailyStatRequest {
+    /// User whose statistics are corrected, defaults to the admin themselves
+    user_id: Option<i64>,
     stat_date: String,
     minutes_studied: Option<i32>,
     words_practiced: Option<i32>,
@@ -43,21 +46,26 @@ pub async fn list_daily_stats(
     Ok(())
 }
 
+/// Correct the statistics of a day by hand (admin only)
+///
+/// Corrected days are skipped by the nightly reconciliation.
 #[handler]
 pub async fn upsert_daily_stat(
     req: &mut Request,
     depot: &mut Depot,
     res: &mut Response,
 ) -> AppResult<()> {
-    let user_id = depot.user_id()?;
+    let admin_id = depot.user_id()?;
     let input: UpsertDailyStatRequest = req
         .parse_json()
         .await
         .map_err(|_| StatusError::bad_request().brief("invalid json"))?;
+    let user_id = input.user_id.unwrap_or(admin_id);
 
     let date = NaiveDate::parse_from_str(&input.stat_date, "%Y-%m-%d")
         .map_err(|_| StatusError::bad_request().brief("invalid date format, use YYYY-MM-DD"))?;
 
+    let corrected_at = Utc::now();
     let new_stat = NewDailyStat {
         user_id,
         stat_date: date,
@@ -67,6 +75,8 @@ pub async fn upsert_daily_stat(
         errors_corrected: input.errors_corrected,
         new_words_learned: input.new_words_learned,
         review_words_count: input.review_words_count,
+        corrected_by: Some(admin_id),
+        corrected_at: Some(corrected_at),
     };
 
     let stat: DailyStat = with_conn(move |conn| {
@@ -81,6 +91,9 @@ pub async fn upsert_daily_stat(
                 errors_corrected: input.errors_corrected,
                 new_words_learned: input.new_words_learned,
                 review_words_count: input.review_words_count,
+                corrected_by: Some(admin_id),
+                corrected_at: Some(corrected_at),
+                ..Default::default()
             })
             .get_result::<DailyStat>(conn)
     })
@@ -0,0 +1,116 @@
+use chrono::Utc;
+use diesel::prelude::*;
+use salvo::oapi::ToSchema;
+use salvo::oapi::extract::*;
+use salvo::prelude::*;
+use serde::Deserialize;
+
+use crate::config::AppConfig;
+use crate::db::with_conn;
+use crate::learn::audio::save_audio_file;
+use crate::learn::dictation::{self, DictationMode, DictationResult, DictationView};
+use crate::services::create_provider_from_env;
+use crate::{DepotExt, JsonResult, json_ok};
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateDictationRequest {
+    /// Dictionary word to dictate, the most overdue vocabulary word by default
+    word_id: Option<i64>,
+    /// word by default
+    mode: Option<DictationMode>,
+    /// Groups dictations of one sitting, generated when absent
+    practice_id: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AnswerDictationRequest {
+    answer: String,
+}
+
+/// Start a dictation: returns the audio to play, not the text
+#[endpoint(tags("Learn"))]
+pub async fn create_dictation(
+    input: JsonBody<CreateDictationRequest>,
+    depot: &mut Depot,
+) -> JsonResult<DictationView> {
+    let user_id = depot.user_id()?;
+    let input = input.into_inner();
+    let mode = input.mode.unwrap_or(DictationMode::Word);
+    let practice_id = input
+        .practice_id
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or_else(|| format!("dictation-{}", uuid::Uuid::new_v4()));
+
+    let target = with_conn(move |conn| dictation::prepare(conn, user_id, input.word_id, mode))
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to prepare dictation: {:?}", e);
+            StatusError::internal_server_error().brief("failed to prepare dictation")
+        })?
+        .ok_or_else(|| StatusError::not_found().brief("no word to dictate"))?;
+
+    // Stored pronunciations only exist for words, sentences are synthesized
+    let audio_path = match target.pronunciation_url.clone() {
+        Some(url) => url,
+        None => synthesize(user_id, &target.text).await?,
+    };
+
+    let view =
+        with_conn(move |conn| dictation::create(conn, user_id, practice_id, target, audio_path))
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to create dictation: {:?}", e);
+                StatusError::internal_server_error().brief("failed to create dictation")
+            })?;
+    json_ok(view)
+}
+
+/// Grade the typed answer to a dictation
+#[endpoint(tags("Learn"))]
+pub async fn answer_dictation(
+    id: PathParam<i64>,
+    input: JsonBody<AnswerDictationRequest>,
+    depot: &mut Depot,
+) -> JsonResult<DictationResult> {
+    let user_id = depot.user_id()?;
+    let practice_id = id.into_inner();
+    let answer = input.into_inner().answer;
+
+    let result = with_conn(move |conn| {
+        dictation::answer(
+            conn,
+            user_id,
+            practice_id,
+            &answer,
+            Utc::now(),
+            &AppConfig::get().scheduler,
+        )
+        .optional()
+    })
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to answer dictation: {:?}", e);
+        StatusError::internal_server_error().brief("failed to answer dictation")
+    })?
+    .ok_or_else(|| StatusError::not_found().brief("dictation not found"))?;
+    json_ok(result)
+}
+
+/// Speak the text and store the audio with the learner's files
+async fn synthesize(user_id: i64, text: &str) -> Result<String, StatusError> {
+    let provider = create_provider_from_env()
+        .ok_or_else(|| StatusError::internal_server_error().brief("AI provider not configured"))?;
+    let tts = provider
+        .tts()
+        .ok_or_else(|| StatusError::internal_server_error().brief("TTS service not available"))?;
+    let response = tts
+        .synthesize(text, tts.voice_for_language("en")?, None)
+        .await
+        .map_err(|e| {
+            tracing::error!("{} TTS error: {:?}", provider.name(), e);
+            StatusError::internal_server_error().brief(e.to_string())
+        })?;
+    save_audio_file(user_id, &response.audio_data, "dictation", &response.format)
+        .await
+        .ok_or_else(|| StatusError::internal_server_error().brief("failed to save audio"))
+}
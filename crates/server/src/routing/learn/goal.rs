@@ -0,0 +1,219 @@
+use chrono::{Days, NaiveDate, Utc};
+use diesel::prelude::*;
+use salvo::oapi::ToSchema;
+use salvo::oapi::extract::*;
+use salvo::prelude::*;
+use serde::Deserialize;
+
+use crate::db::schema::*;
+use crate::db::with_conn;
+use crate::learn::goal::{self, GoalProgress, GoalSpec, GoalType};
+use crate::models::learn::UpdateGoal;
+use crate::{AppResult, DepotExt, JsonResult, json_ok};
+
+/// Largest daily minutes or weekly words target
+const MAX_TARGET: i32 = 1000;
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateGoalRequest {
+    goal_type: GoalType,
+    /// Generated from the goal when absent
+    title: Option<String>,
+    /// Minutes per day (daily_minutes, exam, optional for word_list) or new
+    /// words per week (weekly_words)
+    target_value: Option<i32>,
+    /// Category whose words are to be mastered, required for word_list
+    category_id: Option<i64>,
+    /// Deadline (YYYY-MM-DD), required for exam
+    target_date: Option<NaiveDate>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateGoalRequest {
+    title: Option<String>,
+    target_value: Option<i32>,
+    target_date: Option<NaiveDate>,
+    is_active: Option<bool>,
+}
+
+/// Goals with their progress and plan, active ones unless `all=true`
+#[endpoint(tags("Learn"))]
+pub async fn list_goals(
+    all: QueryParam<bool, false>,
+    depot: &mut Depot,
+) -> JsonResult<Vec<GoalProgress>> {
+    let user_id = depot.user_id()?;
+    let all = all.into_inner().unwrap_or(false);
+
+    let goals = with_conn(move |conn| goal::list_goals(conn, user_id, all, Utc::now()))
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to list goals: {:?}", e);
+            StatusError::internal_server_error().brief("failed to list goals")
+        })?;
+    json_ok(goals)
+}
+
+#[endpoint(tags("Learn"))]
+pub async fn create_goal(
+    input: JsonBody<CreateGoalRequest>,
+    depot: &mut Depot,
+) -> JsonResult<GoalProgress> {
+    let user_id = depot.user_id()?;
+    let input = input.into_inner();
+
+    match input.goal_type {
+        GoalType::DailyMinutes | GoalType::WeeklyWords if input.target_value.is_none() => {
+            return Err(StatusError::bad_request()
+                .brief("target_value is required")
+                .into());
+        }
+        GoalType::WordList if input.category_id.is_none() => {
+            return Err(StatusError::bad_request()
+                .brief("category_id is required for a word list goal")
+                .into());
+        }
+        GoalType::Exam if input.target_date.is_none() => {
+            return Err(StatusError::bad_request()
+                .brief("target_date is required for an exam goal")
+                .into());
+        }
+        _ => {}
+    }
+    validate_target(input.target_value)?;
+    validate_target_date(input.target_date)?;
+
+    let progress = with_conn(move |conn| {
+        if let Some(category_id) = input.category_id {
+            let exists = taxon_categories::table
+                .find(category_id)
+                .select(taxon_categories::id)
+                .first::<i64>(conn)
+                .optional()?
+                .is_some();
+            if !exists {
+                return Ok(None);
+            }
+        }
+        let spec = GoalSpec {
+            goal_type: input.goal_type,
+            title: input.title,
+            target_value: input.target_value,
+            category_id: input.category_id,
+            target_date: input.target_date,
+        };
+        goal::create_goal(conn, user_id, spec, Utc::now()).map(Some)
+    })
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to create goal: {:?}", e);
+        StatusError::internal_server_error().brief("failed to create goal")
+    })?
+    .ok_or_else(|| StatusError::not_found().brief("category not found"))?;
+    json_ok(progress)
+}
+
+#[endpoint(tags("Learn"))]
+pub async fn update_goal(
+    id: PathParam<i64>,
+    input: JsonBody<UpdateGoalRequest>,
+    depot: &mut Depot,
+) -> JsonResult<GoalProgress> {
+    let user_id = depot.user_id()?;
+    let goal_id = id.into_inner();
+    let input = input.into_inner();
+    validate_target(input.target_value)?;
+    validate_target_date(input.target_date)?;
+
+    let changes = UpdateGoal {
+        title: input
+            .title
+            .map(|t| t.trim().to_owned())
+            .filter(|t| !t.is_empty()),
+        target_value: input.target_value,
+        target_date: input.target_date,
+        is_active: input.is_active,
+        ..Default::default()
+    };
+    let progress =
+        with_conn(move |conn| goal::update_goal(conn, user_id, goal_id, changes, Utc::now()))
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to update goal: {:?}", e);
+                StatusError::internal_server_error().brief("failed to update goal")
+            })?
+            .ok_or_else(|| StatusError::not_found().brief("goal not found"))?;
+    json_ok(progress)
+}
+
+#[handler]
+pub async fn delete_goal(
+    req: &mut Request,
+    depot: &mut Depot,
+    res: &mut Response,
+) -> AppResult<()> {
+    let user_id = depot.user_id()?;
+    let goal_id = req
+        .param::<i64>("id")
+        .ok_or_else(|| StatusError::bad_request().brief("missing goal id"))?;
+
+    let deleted = with_conn(move |conn| {
+        diesel::delete(
+            learn_goals::table
+                .filter(learn_goals::id.eq(goal_id))
+                .filter(learn_goals::user_id.eq(user_id)),
+        )
+        .execute(conn)
+    })
+    .await
+    .map_err(|_| StatusError::internal_server_error().brief("failed to delete goal"))?;
+    if deleted == 0 {
+        return Err(StatusError::not_found().brief("goal not found").into());
+    }
+
+    res.render(Json(serde_json::json!({ "ok": true })));
+    Ok(())
+}
+
+/// The study plan of the active goals as an iCalendar file
+#[handler]
+pub async fn export_calendar(depot: &mut Depot, res: &mut Response) -> AppResult<()> {
+    let user_id = depot.user_id()?;
+
+    let ics = with_conn(move |conn| {
+        let now = Utc::now();
+        goal::plan_events(conn, user_id, now).map(|events| goal::to_ics(&events, now))
+    })
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to build study plan calendar: {:?}", e);
+        StatusError::internal_server_error().brief("failed to build study plan calendar")
+    })?;
+
+    res.headers_mut().insert(
+        salvo::http::header::CONTENT_TYPE,
+        "text/calendar; charset=utf-8".parse().unwrap(),
+    );
+    res.headers_mut().insert(
+        salvo::http::header::CONTENT_DISPOSITION,
+        "attachment; filename=\"study-plan.ics\"".parse().unwrap(),
+    );
+    res.write_body(ics).ok();
+    Ok(())
+}
+
+fn validate_target(target_value: Option<i32>) -> Result<(), StatusError> {
+    if target_value.is_some_and(|v| !(1..=MAX_TARGET).contains(&v)) {
+        return Err(StatusError::bad_request().brief("target_value must be between 1 and 1000"));
+    }
+    Ok(())
+}
+
+fn validate_target_date(target_date: Option<NaiveDate>) -> Result<(), StatusError> {
+    // A day of slack for learners whose local date is behind UTC
+    let earliest = Utc::now().date_naive() - Days::new(1);
+    if target_date.is_some_and(|date| date < earliest) {
+        return Err(StatusError::bad_request().brief("target_date must not be in the past"));
+    }
+    Ok(())
+}
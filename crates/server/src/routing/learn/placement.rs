@@ -0,0 +1,118 @@
+use chrono::Utc;
+use diesel::prelude::*;
+use salvo::oapi::ToSchema;
+use salvo::oapi::extract::*;
+use salvo::prelude::*;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::db::schema::*;
+use crate::db::with_conn;
+use crate::learn::placement::{self, MAX_LEVEL, MIN_LEVEL, PlacementView, Recommendations};
+use crate::routing::recording;
+use crate::{DepotExt, JsonResult, json_ok};
+
+#[derive(Deserialize, ToSchema)]
+pub struct StartPlacementRequest {
+    /// Self-assessed level (1-10) the test starts from, 3 by default
+    start_level: Option<i16>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AnswerPlacementRequest {
+    /// The chosen option or, for speaking items, the transcript; `null` skips the item
+    #[serde(default)]
+    answer: Value,
+    /// Recording of a speaking item, transcribed in place of `answer`
+    audio_base64: Option<String>,
+}
+
+/// Start an adaptive placement test
+#[endpoint(tags("Learn"))]
+pub async fn start_placement(
+    input: JsonBody<StartPlacementRequest>,
+    depot: &mut Depot,
+) -> JsonResult<PlacementView> {
+    let user_id = depot.user_id()?;
+    let start_level = input.into_inner().start_level;
+    if start_level.is_some_and(|l| !(MIN_LEVEL..=MAX_LEVEL).contains(&l)) {
+        return Err(StatusError::bad_request()
+            .brief("start_level must be between 1 and 10")
+            .into());
+    }
+
+    let view = with_conn(move |conn| placement::start(conn, user_id, start_level))
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to start placement test: {:?}", e);
+            StatusError::internal_server_error().brief("failed to start placement test")
+        })?;
+    json_ok(view)
+}
+
+#[endpoint(tags("Learn"))]
+pub async fn get_placement(id: PathParam<i64>, depot: &mut Depot) -> JsonResult<PlacementView> {
+    let user_id = depot.user_id()?;
+    let test_id = id.into_inner();
+
+    let view = with_conn(move |conn| placement::get_test(conn, user_id, test_id).optional())
+        .await
+        .map_err(|_| StatusError::internal_server_error().brief("failed to fetch placement test"))?
+        .ok_or_else(|| StatusError::not_found().brief("placement test not found"))?;
+    json_ok(view)
+}
+
+/// Answer the current item of a placement test
+#[endpoint(tags("Learn"))]
+pub async fn answer_placement_item(
+    id: PathParam<i64>,
+    item_id: PathParam<i64>,
+    input: JsonBody<AnswerPlacementRequest>,
+    depot: &mut Depot,
+) -> JsonResult<PlacementView> {
+    let user_id = depot.user_id()?;
+    let test_id = id.into_inner();
+    let item_id = item_id.into_inner();
+    let input = input.into_inner();
+
+    let answer = match input.audio_base64 {
+        Some(audio_base64) => {
+            let audio_data = recording::decode_audio(&audio_base64)?;
+            Value::from(recording::transcribe(audio_data, "en").await?.text.trim())
+        }
+        None => input.answer,
+    };
+
+    let view = with_conn(move |conn| {
+        placement::answer_item(conn, user_id, test_id, item_id, answer, Utc::now()).optional()
+    })
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to answer placement item: {:?}", e);
+        StatusError::internal_server_error().brief("failed to answer placement item")
+    })?
+    .ok_or_else(|| StatusError::not_found().brief("placement item not found"))?;
+    json_ok(view)
+}
+
+/// Stages, contexts and read subjects for the level of the last placement test
+#[endpoint(tags("Learn"))]
+pub async fn get_recommendations(depot: &mut Depot) -> JsonResult<Recommendations> {
+    let user_id = depot.user_id()?;
+
+    let recommendations = with_conn(move |conn| {
+        let level = archive_user_profiles::table
+            .filter(archive_user_profiles::user_id.eq(user_id))
+            .select(archive_user_profiles::placement_level)
+            .first::<Option<i16>>(conn)
+            .optional()?
+            .flatten();
+        level
+            .map(|level| placement::recommendations(conn, user_id, level))
+            .transpose()
+    })
+    .await
+    .map_err(|_| StatusError::internal_server_error().brief("failed to fetch recommendations"))?
+    .ok_or_else(|| StatusError::not_found().brief("no placement level yet"))?;
+    json_ok(recommendations)
+}
@@ -1,13 +1,27 @@
+use chrono::{Duration, Utc};
 use diesel::prelude::*;
 use salvo::oapi::ToSchema;
 use salvo::prelude::*;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 use crate::db::schema::*;
 use crate::db::with_conn;
+use crate::learn::achievement::{self, AchievementEvent};
+use crate::learn::phoneme::{self, WeakSound};
+use crate::learn::reading::{self, SubjectProgress, SubjectProgressDetail};
+use crate::learn::stats::{self, Activity};
+use crate::models::achievement::AchievementBadge;
 use crate::models::learn::*;
 use crate::{AppResult, DepotExt};
 
+/// A recorded practice and the achievements it unlocked
+#[derive(Serialize)]
+pub struct CreatedPractice<T> {
+    #[serde(flatten)]
+    pub practice: T,
+    pub unlocked: Vec<AchievementBadge>,
+}
+
 // ============================================================================
 // Write Practices API
 // ============================================================================
@@ -32,8 +46,14 @@ pub async fn list_write_practices(
     let limit = req.query::<i64>("limit").unwrap_or(100).clamp(1, 500);
 
     let practices: Vec<WritePractice> = with_conn(move |conn| {
+        // Pending dictations are left out, their expected text is the answer
         let mut query = learn_write_practices::table
             .filter(learn_write_practices::user_id.eq(user_id))
+            .filter(
+                learn_write_practices::expected_text
+                    .is_null()
+                    .or(learn_write_practices::answered_at.is_not_null()),
+            )
             .order(learn_write_practices::updated_at.desc())
             .limit(limit)
             .into_boxed();
@@ -74,16 +94,26 @@ pub async fn create_write_practice(
         notes: input.notes,
     };
 
-    let practice: WritePractice = with_conn(move |conn| {
-        diesel::insert_into(learn_write_practices::table)
+    let created = with_conn(move |conn| {
+        let practice = diesel::insert_into(learn_write_practices::table)
             .values(&new_practice)
-            .get_result::<WritePractice>(conn)
+            .get_result::<WritePractice>(conn)?;
+        // The client reports its own success level, nothing to reward
+        let activity = Activity::Practice { item: None };
+        stats::record_activity(conn, user_id, activity, practice.created_at)?;
+        let unlocked = achievement::evaluate_and_notify(
+            conn,
+            user_id,
+            AchievementEvent::Studied,
+            practice.created_at,
+        )?;
+        Ok(CreatedPractice { practice, unlocked })
     })
     .await
     .map_err(|_| StatusError::internal_server_error().brief("failed to create write practice"))?;
 
     res.status_code(StatusCode::CREATED);
-    res.render(Json(practice));
+    res.render(Json(created));
     Ok(())
 }
 
@@ -167,15 +197,84 @@ pub async fn create_read_practice(
         waveform_data: input.waveform_data,
     };
 
-    let practice: ReadPractice = with_conn(move |conn| {
-        diesel::insert_into(learn_read_practices::table)
-            .values(&new_practice)
-            .get_result::<ReadPractice>(conn)
+    let created = with_conn(move |conn| {
+        // Scores from the client neither earn XP nor unlock score achievements,
+        // only those of evaluate_pronunciation do
+        let (practice, _) = reading::record_practice(conn, &new_practice, false)?;
+        let unlocked = achievement::evaluate_and_notify(
+            conn,
+            user_id,
+            AchievementEvent::Studied,
+            practice.created_at,
+        )?;
+        Ok(CreatedPractice { practice, unlocked })
     })
     .await
     .map_err(|_| StatusError::internal_server_error().brief("failed to create read practice"))?;
 
     res.status_code(StatusCode::CREATED);
-    res.render(Json(practice));
+    res.render(Json(created));
+    Ok(())
+}
+
+// ============================================================================
+// Reading Progress API
+// ============================================================================
+
+#[handler]
+pub async fn list_read_progress(depot: &mut Depot, res: &mut Response) -> AppResult<()> {
+    let user_id = depot.user_id()?;
+
+    let progress: Vec<SubjectProgress> =
+        with_conn(move |conn| reading::list_progress(conn, user_id))
+            .await
+            .map_err(|_| {
+                StatusError::internal_server_error().brief("failed to list read progress")
+            })?;
+
+    res.render(Json(progress));
+    Ok(())
+}
+
+#[handler]
+pub async fn get_read_progress(
+    req: &mut Request,
+    depot: &mut Depot,
+    res: &mut Response,
+) -> AppResult<()> {
+    let user_id = depot.user_id()?;
+    let subject_id: i64 = req
+        .param::<i64>("subject_id")
+        .ok_or_else(|| StatusError::bad_request().brief("missing subject_id"))?;
+
+    let progress: SubjectProgressDetail =
+        with_conn(move |conn| reading::subject_progress(conn, user_id, subject_id))
+            .await
+            .map_err(|_| StatusError::internal_server_error().brief("failed to get read progress"))?
+            .ok_or_else(|| StatusError::not_found().brief("read subject not found"))?;
+
+    res.render(Json(progress));
+    Ok(())
+}
+
+#[handler]
+pub async fn list_weak_sounds(
+    req: &mut Request,
+    depot: &mut Depot,
+    res: &mut Response,
+) -> AppResult<()> {
+    let user_id = depot.user_id()?;
+    let days = req.query::<i64>("days").unwrap_or(30).clamp(1, 365);
+    let limit = req.query::<usize>("limit").unwrap_or(10).clamp(1, 50);
+    let since = Utc::now() - Duration::days(days);
+
+    let sounds: Vec<WeakSound> =
+        with_conn(move |conn| phoneme::weak_sounds(conn, user_id, since, limit))
+            .await
+            .map_err(|_| {
+                StatusError::internal_server_error().brief("failed to list weak sounds")
+            })?;
+
+    res.render(Json(sounds));
     Ok(())
 }
@@ -0,0 +1,102 @@
+use chrono::Utc;
+use diesel::prelude::*;
+use salvo::oapi::ToSchema;
+use salvo::oapi::extract::*;
+use salvo::prelude::*;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::db::with_conn;
+use crate::learn::quiz::{self, QuizAnswerResult, QuizOptions, QuizQuestionType, QuizView};
+use crate::{DepotExt, JsonResult, json_ok};
+
+const DEFAULT_QUIZ_SIZE: usize = 10;
+const MAX_QUIZ_SIZE: usize = 30;
+const MAX_QUIZ_WORDS: usize = 100;
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateQuizRequest {
+    /// Number of questions, 10 by default and at most 30
+    size: Option<usize>,
+    /// definition_choice | cloze | matching | form_drill, all when empty
+    #[serde(default)]
+    types: Vec<QuizQuestionType>,
+    /// Words to quiz on, defaults to the user's vocabulary
+    #[serde(default)]
+    words: Vec<String>,
+    /// Difficulty of dictionary words added when the vocabulary is too small
+    difficulty: Option<i16>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AnswerQuestionRequest {
+    /// A string, or an object mapping left to right items for matching questions
+    answer: Value,
+}
+
+/// Generate a quiz from dictionary data
+#[endpoint(tags("Learn"))]
+pub async fn create_quiz(
+    input: JsonBody<CreateQuizRequest>,
+    depot: &mut Depot,
+) -> JsonResult<QuizView> {
+    let user_id = depot.user_id()?;
+    let input = input.into_inner();
+    if input.words.len() > MAX_QUIZ_WORDS {
+        return Err(StatusError::bad_request().brief("too many words").into());
+    }
+    let options = QuizOptions {
+        size: input
+            .size
+            .unwrap_or(DEFAULT_QUIZ_SIZE)
+            .clamp(1, MAX_QUIZ_SIZE),
+        types: input.types,
+        words: input.words,
+        difficulty: input.difficulty,
+    };
+
+    let view = with_conn(move |conn| quiz::generate_quiz(conn, user_id, &options))
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to generate quiz: {:?}", e);
+            StatusError::internal_server_error().brief("failed to generate quiz")
+        })?;
+    json_ok(view)
+}
+
+#[endpoint(tags("Learn"))]
+pub async fn get_quiz(id: PathParam<i64>, depot: &mut Depot) -> JsonResult<QuizView> {
+    let user_id = depot.user_id()?;
+    let quiz_id = id.into_inner();
+
+    let view = with_conn(move |conn| quiz::get_quiz(conn, user_id, quiz_id).optional())
+        .await
+        .map_err(|_| StatusError::internal_server_error().brief("failed to fetch quiz"))?
+        .ok_or_else(|| StatusError::not_found().brief("quiz not found"))?;
+    json_ok(view)
+}
+
+/// Check the answer to a quiz question
+#[endpoint(tags("Learn"))]
+pub async fn answer_quiz_question(
+    id: PathParam<i64>,
+    question_id: PathParam<i64>,
+    input: JsonBody<AnswerQuestionRequest>,
+    depot: &mut Depot,
+) -> JsonResult<QuizAnswerResult> {
+    let user_id = depot.user_id()?;
+    let quiz_id = id.into_inner();
+    let question_id = question_id.into_inner();
+    let answer = input.into_inner().answer;
+
+    let result = with_conn(move |conn| {
+        quiz::answer_question(conn, user_id, quiz_id, question_id, answer, Utc::now()).optional()
+    })
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to answer quiz question: {:?}", e);
+        StatusError::internal_server_error().brief("failed to answer quiz question")
+    })?
+    .ok_or_else(|| StatusError::not_found().brief("quiz question not found"))?;
+    json_ok(result)
+}
@@ -0,0 +1,197 @@
+use chrono::{NaiveDate, Utc};
+use salvo::oapi::ToSchema;
+use salvo::oapi::extract::*;
+use salvo::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::db::with_conn;
+use crate::learn::report::{self, ProgressReport, ReportPeriod, share};
+use crate::models::learn::ReportShare;
+use crate::{AppConfig, AppResult, DepotExt, JsonResult, OkResponse, json_ok, ok_response};
+
+/// Progress report as JSON
+///
+/// `month` (YYYY-MM) selects a calendar month, `from` and `to` (YYYY-MM-DD)
+/// a range of local days; the current month by default.
+#[endpoint(tags("Learn"))]
+pub async fn get_report(
+    month: QueryParam<String, false>,
+    from: QueryParam<String, false>,
+    to: QueryParam<String, false>,
+    depot: &mut Depot,
+) -> JsonResult<ProgressReport> {
+    let user_id = depot.user_id()?;
+    let period = parse_period(month.into_inner(), from.into_inner(), to.into_inner())?;
+    json_ok(build_report(user_id, period).await?)
+}
+
+/// Progress report as a standalone HTML page
+#[handler]
+pub async fn get_report_html(
+    req: &mut Request,
+    depot: &mut Depot,
+    res: &mut Response,
+) -> AppResult<()> {
+    let user_id = depot.user_id()?;
+    let period = parse_period(req.query("month"), req.query("from"), req.query("to"))?;
+    let report = build_report(user_id, period).await?;
+    render_html(&report, res);
+    Ok(())
+}
+
+fn render_html(report: &ProgressReport, res: &mut Response) {
+    res.headers_mut().insert(
+        salvo::http::header::CONTENT_TYPE,
+        "text/html; charset=utf-8".parse().unwrap(),
+    );
+    res.write_body(report::to_html(report)).ok();
+}
+
+/// Progress report as a PDF download
+#[handler]
+pub async fn get_report_pdf(
+    req: &mut Request,
+    depot: &mut Depot,
+    res: &mut Response,
+) -> AppResult<()> {
+    let user_id = depot.user_id()?;
+    let period = parse_period(req.query("month"), req.query("from"), req.query("to"))?;
+    let font = report::pdf_font().map_err(|e| {
+        tracing::error!("Failed to load report font: {}", e);
+        StatusError::internal_server_error().brief("report font not available")
+    })?;
+    let report = build_report(user_id, period).await?;
+
+    res.headers_mut().insert(
+        salvo::http::header::CONTENT_TYPE,
+        "application/pdf".parse().unwrap(),
+    );
+    res.headers_mut().insert(
+        salvo::http::header::CONTENT_DISPOSITION,
+        format!(
+            "attachment; filename=\"progress-report-{}-{}.pdf\"",
+            report.from, report.to
+        )
+        .parse()
+        .unwrap(),
+    );
+    res.write_body(report::to_pdf(&report, font)).ok();
+    Ok(())
+}
+
+#[derive(Deserialize, ToSchema, Debug)]
+pub struct CreateShareInput {
+    /// YYYY-MM, or leave empty with `from` and `to`
+    pub month: Option<String>,
+    /// YYYY-MM-DD
+    pub from: Option<String>,
+    /// YYYY-MM-DD
+    pub to: Option<String>,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct CreatedShare {
+    pub share: ReportShare,
+    /// Shown only once; the link is `/api/reports/shared/{token}`
+    pub token: String,
+    pub url: String,
+}
+
+/// Share a report as an HTML link that works without signing in
+///
+/// The days are fixed when the link is created. The link expires after
+/// `REPORT_SHARE_DAYS` days and can be revoked sooner.
+#[endpoint(tags("Learn"))]
+pub async fn create_share(
+    input: JsonBody<CreateShareInput>,
+    depot: &mut Depot,
+) -> JsonResult<CreatedShare> {
+    let user_id = depot.user_id()?;
+    let input = input.into_inner();
+    let period = parse_period(input.month, input.from, input.to)?;
+    let days = AppConfig::get().report.share_days;
+
+    let (share, token) =
+        with_conn(move |conn| share::create(conn, user_id, period, days, Utc::now()))
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to share progress report: {:?}", e);
+                StatusError::internal_server_error().brief("failed to share progress report")
+            })?;
+    json_ok(CreatedShare {
+        share,
+        url: format!("/api/reports/shared/{token}"),
+        token,
+    })
+}
+
+/// Shared report links of the current user that still work, newest first
+#[endpoint(tags("Learn"))]
+pub async fn list_shares(depot: &mut Depot) -> JsonResult<Vec<ReportShare>> {
+    let user_id = depot.user_id()?;
+    let shares = with_conn(move |conn| share::list(conn, user_id, Utc::now()))
+        .await
+        .map_err(|_| StatusError::internal_server_error().brief("failed to list shared reports"))?;
+    json_ok(shares)
+}
+
+/// Revoke a shared report link
+#[endpoint(tags("Learn"))]
+pub async fn revoke_share(id: PathParam<i64>, depot: &mut Depot) -> JsonResult<OkResponse> {
+    let user_id = depot.user_id()?;
+    let share_id = id.into_inner();
+    let revoked = with_conn(move |conn| share::revoke(conn, user_id, share_id, Utc::now()))
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to revoke shared report {}: {:?}", share_id, e);
+            StatusError::internal_server_error().brief("failed to revoke shared report")
+        })?;
+    if !revoked {
+        return Err(StatusError::not_found()
+            .brief("shared report not found")
+            .into());
+    }
+    ok_response()
+}
+
+/// A shared report as a standalone HTML page, no sign in needed
+#[handler]
+pub async fn get_shared_report(req: &mut Request, res: &mut Response) -> AppResult<()> {
+    let token: String = req
+        .param("token")
+        .ok_or_else(|| StatusError::bad_request().brief("missing token"))?;
+    let report = with_conn(move |conn| share::open(conn, &token, Utc::now()))
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to build shared progress report: {:?}", e);
+            StatusError::internal_server_error().brief("failed to build progress report")
+        })?
+        .ok_or_else(|| StatusError::not_found().brief("shared report not found or expired"))?;
+    render_html(&report, res);
+    Ok(())
+}
+
+async fn build_report(user_id: i64, period: ReportPeriod) -> Result<ProgressReport, StatusError> {
+    with_conn(move |conn| report::build(conn, user_id, period, Utc::now()))
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to build progress report: {:?}", e);
+            StatusError::internal_server_error().brief("failed to build progress report")
+        })
+}
+
+fn parse_period(
+    month: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+) -> Result<ReportPeriod, StatusError> {
+    let parse_date = |value: Option<String>| {
+        value
+            .map(|v| NaiveDate::parse_from_str(&v, "%Y-%m-%d"))
+            .transpose()
+            .map_err(|_| StatusError::bad_request().brief("invalid date format, use YYYY-MM-DD"))
+    };
+    let (from, to) = (parse_date(from)?, parse_date(to)?);
+    ReportPeriod::parse(month.as_deref(), from, to)
+        .map_err(|message| StatusError::bad_request().brief(message))
+}
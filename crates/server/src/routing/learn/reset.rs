@@ -1,15 +1,19 @@
-use diesel::prelude::*;
+use chrono::Utc;
+use salvo::oapi::extract::*;
 use salvo::prelude::*;
 use serde::Serialize;
 
-use crate::db::schema::*;
 use crate::db::with_conn;
-use crate::{AppResult, DepotExt};
+use crate::learn::reset::{self, ResetKind, RestoredReset};
+use crate::models::ResetSnapshot;
+use crate::{AppConfig, AppResult, DepotExt, JsonResult, json_ok};
 
 #[derive(Serialize)]
 pub struct ResetResponse {
     deleted_count: usize,
     table: String,
+    /// Where the rows went, restorable until it expires
+    snapshot: Option<ResetSnapshot>,
 }
 
 #[derive(Serialize)]
@@ -18,149 +22,98 @@ pub struct ResetAllResponse {
     total_deleted: usize,
 }
 
-#[handler]
-pub async fn reset_issue_words(depot: &mut Depot, res: &mut Response) -> AppResult<()> {
+/// Archive the current user's rows of a reset and render what was removed
+async fn archive_reset(kind: ResetKind, depot: &mut Depot, res: &mut Response) -> AppResult<()> {
     let user_id = depot.user_id()?;
-
-    let deleted_count = with_conn(move |conn| {
-        diesel::delete(learn_issue_words::table.filter(learn_issue_words::user_id.eq(user_id)))
-            .execute(conn)
-    })
-    .await
-    .map_err(|_| StatusError::internal_server_error().brief("failed to reset issue words"))?;
-
+    let restore_days = AppConfig::get().account.reset_restore_days;
+
+    let snapshot =
+        with_conn(move |conn| reset::archive(conn, user_id, kind, restore_days, Utc::now()))
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to reset {}: {:?}", kind.as_str(), e);
+                StatusError::internal_server_error().brief(format!(
+                    "failed to reset {}",
+                    kind.as_str().replace('_', " ")
+                ))
+            })?;
+
+    let table = kind.tables()[0];
+    let deleted_count = snapshot
+        .as_ref()
+        .and_then(|snapshot| snapshot.counts.get(table)?.as_u64())
+        .unwrap_or(0) as usize;
     res.render(Json(ResetResponse {
         deleted_count,
-        table: "learn_issue_words".to_string(),
+        table: table.to_string(),
+        snapshot,
     }));
     Ok(())
 }
 
 #[handler]
-pub async fn reset_chats(depot: &mut Depot, res: &mut Response) -> AppResult<()> {
-    let user_id = depot.user_id()?;
-
-    let deleted_count = with_conn(move |conn| {
-        diesel::delete(learn_chats::table.filter(learn_chats::user_id.eq(user_id))).execute(conn)
-    })
-    .await
-    .map_err(|_| StatusError::internal_server_error().brief("failed to reset chats"))?;
+pub async fn reset_issue_words(depot: &mut Depot, res: &mut Response) -> AppResult<()> {
+    archive_reset(ResetKind::IssueWords, depot, res).await
+}
 
-    res.render(Json(ResetResponse {
-        deleted_count,
-        table: "learn_chats".to_string(),
-    }));
-    Ok(())
+#[handler]
+pub async fn reset_chats(depot: &mut Depot, res: &mut Response) -> AppResult<()> {
+    archive_reset(ResetKind::Chats, depot, res).await
 }
 
 #[handler]
 pub async fn reset_write_practices(depot: &mut Depot, res: &mut Response) -> AppResult<()> {
-    let user_id = depot.user_id()?;
-
-    let deleted_count = with_conn(move |conn| {
-        diesel::delete(
-            learn_write_practices::table.filter(learn_write_practices::user_id.eq(user_id)),
-        )
-        .execute(conn)
-    })
-    .await
-    .map_err(|_| StatusError::internal_server_error().brief("failed to reset write practices"))?;
-
-    res.render(Json(ResetResponse {
-        deleted_count,
-        table: "learn_write_practices".to_string(),
-    }));
-    Ok(())
+    archive_reset(ResetKind::WritePractices, depot, res).await
 }
 
 #[handler]
 pub async fn reset_read_practices(depot: &mut Depot, res: &mut Response) -> AppResult<()> {
-    let user_id = depot.user_id()?;
-
-    let deleted_count = with_conn(move |conn| {
-        diesel::delete(
-            learn_read_practices::table.filter(learn_read_practices::user_id.eq(user_id)),
-        )
-        .execute(conn)
-    })
-    .await
-    .map_err(|_| StatusError::internal_server_error().brief("failed to reset read practices"))?;
-
-    res.render(Json(ResetResponse {
-        deleted_count,
-        table: "learn_read_practices".to_string(),
-    }));
-    Ok(())
+    archive_reset(ResetKind::ReadPractices, depot, res).await
 }
 
 #[handler]
 pub async fn reset_vocabulary(depot: &mut Depot, res: &mut Response) -> AppResult<()> {
-    let user_id = depot.user_id()?;
-
-    let deleted_count = with_conn(move |conn| {
-        diesel::delete(learn_vocabularies::table.filter(learn_vocabularies::user_id.eq(user_id)))
-            .execute(conn)
-    })
-    .await
-    .map_err(|_| StatusError::internal_server_error().brief("failed to reset vocabulary"))?;
-
-    res.render(Json(ResetResponse {
-        deleted_count,
-        table: "learn_vocabularies".to_string(),
-    }));
-    Ok(())
+    archive_reset(ResetKind::Vocabulary, depot, res).await
 }
 
 #[handler]
 pub async fn reset_daily_stats(depot: &mut Depot, res: &mut Response) -> AppResult<()> {
-    let user_id = depot.user_id()?;
-
-    let deleted_count = with_conn(move |conn| {
-        diesel::delete(learn_daily_stats::table.filter(learn_daily_stats::user_id.eq(user_id)))
-            .execute(conn)
-    })
-    .await
-    .map_err(|_| StatusError::internal_server_error().brief("failed to reset daily stats"))?;
-
-    res.render(Json(ResetResponse {
-        deleted_count,
-        table: "learn_daily_stats".to_string(),
-    }));
-    Ok(())
+    archive_reset(ResetKind::DailyStats, depot, res).await
 }
 
 #[handler]
 pub async fn reset_achievements(depot: &mut Depot, res: &mut Response) -> AppResult<()> {
-    let user_id = depot.user_id()?;
-
-    let deleted_count = with_conn(move |conn| {
-        diesel::delete(learn_achievements::table.filter(learn_achievements::user_id.eq(user_id)))
-            .execute(conn)
-    })
-    .await
-    .map_err(|_| StatusError::internal_server_error().brief("failed to reset achievements"))?;
-
-    res.render(Json(ResetResponse {
-        deleted_count,
-        table: "learn_achievements".to_string(),
-    }));
-    Ok(())
+    archive_reset(ResetKind::Achievements, depot, res).await
 }
 
 #[handler]
 pub async fn reset_suggestions(depot: &mut Depot, res: &mut Response) -> AppResult<()> {
-    let user_id = depot.user_id()?;
+    archive_reset(ResetKind::Suggestions, depot, res).await
+}
 
-    let deleted_count = with_conn(move |conn| {
-        diesel::delete(learn_suggestions::table.filter(learn_suggestions::user_id.eq(user_id)))
-            .execute(conn)
-    })
-    .await
-    .map_err(|_| StatusError::internal_server_error().brief("failed to reset suggestions"))?;
+/// Resets of the current user that can still be undone, newest first
+#[endpoint(tags("Learn"))]
+pub async fn list_resets(depot: &mut Depot) -> JsonResult<Vec<ResetSnapshot>> {
+    let user_id = depot.user_id()?;
+    let snapshots = with_conn(move |conn| reset::list(conn, user_id, Utc::now()))
+        .await
+        .map_err(|_| StatusError::internal_server_error().brief("failed to list resets"))?;
+    json_ok(snapshots)
+}
 
-    res.render(Json(ResetResponse {
-        deleted_count,
-        table: "learn_suggestions".to_string(),
-    }));
-    Ok(())
+/// Undo a reset, putting its rows back
+///
+/// Rows clashing with ones created since the reset are skipped.
+#[endpoint(tags("Learn"))]
+pub async fn restore_reset(id: PathParam<i64>, depot: &mut Depot) -> JsonResult<RestoredReset> {
+    let user_id = depot.user_id()?;
+    let snapshot_id = id.into_inner();
+    let restored = with_conn(move |conn| reset::restore(conn, user_id, snapshot_id, Utc::now()))
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to restore reset {}: {:?}", snapshot_id, e);
+            StatusError::internal_server_error().brief("failed to restore reset")
+        })?
+        .ok_or_else(|| StatusError::not_found().brief("reset not found or expired"))?;
+    json_ok(restored)
 }
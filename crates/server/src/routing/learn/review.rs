@@ -0,0 +1,97 @@
+use chrono::Utc;
+use diesel::prelude::*;
+use salvo::oapi::ToSchema;
+use salvo::oapi::extract::*;
+use salvo::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::config::AppConfig;
+use crate::db::with_conn;
+use crate::learn::achievement::{self, AchievementEvent};
+use crate::learn::review::{ReviewItemType, ReviewResult, grade_item};
+use crate::learn::scheduler::ReviewGrade;
+use crate::models::achievement::AchievementBadge;
+use crate::{DepotExt, JsonResult, json_ok};
+
+#[derive(Deserialize, ToSchema)]
+pub struct ReviewGradeRequest {
+    /// again | hard | good | easy
+    grade: ReviewGrade,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReviewGradeResponse {
+    #[serde(flatten)]
+    review: ReviewResult,
+    /// Achievements unlocked by this review
+    unlocked: Vec<AchievementBadge>,
+}
+
+/// Grade a review of a vocabulary word and reschedule it
+#[endpoint(tags("Learn"))]
+pub async fn review_vocabulary(
+    id: PathParam<i64>,
+    input: JsonBody<ReviewGradeRequest>,
+    depot: &mut Depot,
+) -> JsonResult<ReviewGradeResponse> {
+    let user_id = depot.user_id()?;
+    let result = grade(
+        user_id,
+        ReviewItemType::Vocabulary,
+        id.into_inner(),
+        input.grade,
+    )
+    .await?;
+    json_ok(result)
+}
+
+/// Grade a review of an issue word and reschedule it
+#[endpoint(tags("Learn"))]
+pub async fn review_issue_word(
+    id: PathParam<i64>,
+    input: JsonBody<ReviewGradeRequest>,
+    depot: &mut Depot,
+) -> JsonResult<ReviewGradeResponse> {
+    let user_id = depot.user_id()?;
+    let result = grade(
+        user_id,
+        ReviewItemType::IssueWord,
+        id.into_inner(),
+        input.grade,
+    )
+    .await?;
+    json_ok(result)
+}
+
+async fn grade(
+    user_id: i64,
+    item_type: ReviewItemType,
+    item_id: i64,
+    grade: ReviewGrade,
+) -> Result<ReviewGradeResponse, StatusError> {
+    with_conn(move |conn| {
+        let now = Utc::now();
+        let Some(review) = grade_item(
+            conn,
+            user_id,
+            item_type,
+            item_id,
+            grade,
+            now,
+            &AppConfig::get().scheduler,
+        )
+        .optional()?
+        else {
+            return Ok(None);
+        };
+        let unlocked =
+            achievement::evaluate_and_notify(conn, user_id, AchievementEvent::Studied, now)?;
+        Ok(Some(ReviewGradeResponse { review, unlocked }))
+    })
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to grade review: {:?}", e);
+        StatusError::internal_server_error().brief("failed to grade review")
+    })?
+    .ok_or_else(|| StatusError::not_found().brief("review item not found"))
+}
@@ -0,0 +1,105 @@
+use chrono::Utc;
+use diesel::prelude::*;
+use salvo::oapi::ToSchema;
+use salvo::oapi::extract::*;
+use salvo::prelude::*;
+use serde::Deserialize;
+
+use crate::config::AppConfig;
+use crate::db::with_conn;
+use crate::learn::local_time;
+use crate::learn::scheduler::ReviewGrade;
+use crate::learn::session::{self, AnswerResult, SessionView};
+use crate::{DepotExt, JsonResult, json_ok};
+
+const DEFAULT_SESSION_SIZE: i64 = 20;
+const MAX_SESSION_SIZE: i64 = 50;
+
+#[derive(Deserialize, ToSchema, Default)]
+pub struct CreateReviewSessionRequest {
+    /// Number of items, 20 by default and at most 50
+    size: Option<i64>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct AnswerItemRequest {
+    answer: String,
+    /// Self-assessment for a correct answer: hard | good | easy
+    grade: Option<ReviewGrade>,
+}
+
+/// Start today's review session, or continue the one already started
+///
+/// Answers 404 when nothing is due.
+#[endpoint(tags("Learn"))]
+pub async fn create_review_session(
+    input: JsonBody<CreateReviewSessionRequest>,
+    depot: &mut Depot,
+) -> JsonResult<SessionView> {
+    let user_id = depot.user_id()?;
+    let size = input
+        .size
+        .unwrap_or(DEFAULT_SESSION_SIZE)
+        .clamp(1, MAX_SESSION_SIZE);
+
+    let view = with_conn(move |conn| {
+        let now = Utc::now();
+        // A session per day as the learner sees it
+        let today = local_time::local_date(local_time::user_timezone(conn, user_id)?, now);
+        session::get_or_create_session(conn, user_id, size, today, now)
+    })
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to create review session: {:?}", e);
+        StatusError::internal_server_error().brief("failed to create review session")
+    })?
+    .ok_or_else(|| StatusError::not_found().brief("nothing is due for review"))?;
+    json_ok(view)
+}
+
+#[endpoint(tags("Learn"))]
+pub async fn get_review_session(id: PathParam<i64>, depot: &mut Depot) -> JsonResult<SessionView> {
+    let user_id = depot.user_id()?;
+    let session_id = id.into_inner();
+
+    let view = with_conn(move |conn| session::get_session(conn, user_id, session_id).optional())
+        .await
+        .map_err(|_| StatusError::internal_server_error().brief("failed to fetch review session"))?
+        .ok_or_else(|| StatusError::not_found().brief("review session not found"))?;
+    json_ok(view)
+}
+
+/// Check the answer to a session item and reschedule the word
+#[endpoint(tags("Learn"))]
+pub async fn answer_review_item(
+    id: PathParam<i64>,
+    item_id: PathParam<i64>,
+    input: JsonBody<AnswerItemRequest>,
+    depot: &mut Depot,
+) -> JsonResult<AnswerResult> {
+    let user_id = depot.user_id()?;
+    let session_id = id.into_inner();
+    let item_id = item_id.into_inner();
+    let AnswerItemRequest { answer, grade } = input.into_inner();
+
+    let result = with_conn(move |conn| {
+        session::answer_item(
+            conn,
+            user_id,
+            session_id,
+            item_id,
+            &answer,
+            grade,
+            Utc::now(),
+            &AppConfig::get().scheduler,
+        )
+        .optional()
+    })
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to answer review item: {:?}", e);
+        StatusError::internal_server_error().brief("failed to answer review item")
+    })?
+    .ok_or_else(|| StatusError::not_found().brief("review item not found"))?;
+    json_ok(result)
+}
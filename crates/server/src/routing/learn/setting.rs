@@ -0,0 +1,177 @@
+use chrono::Utc;
+use diesel::prelude::*;
+use salvo::oapi::ToSchema;
+use salvo::oapi::extract::*;
+use salvo::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::db::schema::*;
+use crate::db::with_conn;
+use crate::learn::leaderboard;
+use crate::learn::local_time::{parse_timezone, user_timezone};
+use crate::models::{NewUserProfile, UpdateUserProfile};
+use crate::services::language::LANGUAGES;
+use crate::services::{LanguagePair, find_language};
+use crate::{DepotExt, JsonResult, json_ok};
+
+/// Language the user can choose as native or target language
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LanguageOption {
+    pub code: String,
+    pub name: String,
+    pub native_name: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LearnSettings {
+    /// Language the user already speaks, used for translations and explanations
+    pub native_lang: String,
+    /// Language the user is learning
+    pub target_lang: String,
+    /// IANA time zone that decides where the user's days start
+    pub timezone: String,
+    /// Hidden from leaderboards and leagues
+    pub leaderboard_opt_out: bool,
+    /// All supported languages
+    pub languages: Vec<LanguageOption>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateLearnSettingsRequest {
+    pub native_lang: Option<String>,
+    pub target_lang: Option<String>,
+    /// IANA time zone, e.g. "Asia/Shanghai"
+    pub timezone: Option<String>,
+    pub leaderboard_opt_out: Option<bool>,
+}
+
+impl LearnSettings {
+    fn new(langs: LanguagePair, timezone: String, leaderboard_opt_out: bool) -> Self {
+        Self {
+            native_lang: langs.native,
+            target_lang: langs.target,
+            timezone,
+            leaderboard_opt_out,
+            languages: LANGUAGES
+                .iter()
+                .map(|l| LanguageOption {
+                    code: l.code.to_owned(),
+                    name: l.name.to_owned(),
+                    native_name: l.native_name.to_owned(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Load the native/target languages of a user, defaults when they have no profile yet
+pub async fn get_language_pair(user_id: i64) -> LanguagePair {
+    with_conn(move |conn| {
+        archive_user_profiles::table
+            .filter(archive_user_profiles::user_id.eq(user_id))
+            .select((
+                archive_user_profiles::target_lang,
+                archive_user_profiles::native_lang,
+            ))
+            .first::<(String, String)>(conn)
+            .optional()
+    })
+    .await
+    .map_err(|e| tracing::error!("Failed to load language settings: {:?}", e))
+    .ok()
+    .flatten()
+    .map(|(target, native)| LanguagePair::new(target, native))
+    .unwrap_or_default()
+}
+
+/// Time zone and leaderboard opt-out of a user
+async fn get_profile_settings(user_id: i64) -> Result<(String, bool), StatusError> {
+    with_conn(move |conn| {
+        let tz = user_timezone(conn, user_id)?;
+        let opt_out = archive_user_profiles::table
+            .filter(archive_user_profiles::user_id.eq(user_id))
+            .select(archive_user_profiles::leaderboard_opt_out)
+            .first::<bool>(conn)
+            .optional()?;
+        Ok::<_, diesel::result::Error>((tz.name().to_owned(), opt_out.unwrap_or(false)))
+    })
+    .await
+    .map_err(|_| StatusError::internal_server_error().brief("failed to load settings"))
+}
+
+/// Get the current user's learning languages, time zone and privacy
+#[endpoint(tags("Learn"))]
+pub async fn get_settings(depot: &mut Depot) -> JsonResult<LearnSettings> {
+    let user_id = depot.user_id()?;
+    let langs = get_language_pair(user_id).await;
+    let (timezone, leaderboard_opt_out) = get_profile_settings(user_id).await?;
+    json_ok(LearnSettings::new(langs, timezone, leaderboard_opt_out))
+}
+
+/// Update the current user's learning languages, time zone and privacy
+#[endpoint(tags("Learn"))]
+pub async fn update_settings(
+    input: JsonBody<UpdateLearnSettingsRequest>,
+    depot: &mut Depot,
+) -> JsonResult<LearnSettings> {
+    let user_id = depot.user_id()?;
+    let input = input.into_inner();
+
+    for code in [&input.native_lang, &input.target_lang]
+        .into_iter()
+        .flatten()
+    {
+        if find_language(code).is_none() {
+            return Err(StatusError::bad_request()
+                .brief(format!("unsupported language: {code}"))
+                .into());
+        }
+    }
+
+    if let Some(timezone) = &input.timezone
+        && parse_timezone(timezone).is_none()
+    {
+        return Err(StatusError::bad_request()
+            .brief(format!("unknown time zone: {timezone}"))
+            .into());
+    }
+
+    let current = get_language_pair(user_id).await;
+    let langs = LanguagePair::new(
+        input.target_lang.unwrap_or(current.target),
+        input.native_lang.unwrap_or(current.native),
+    );
+    if langs.target == langs.native {
+        return Err(StatusError::bad_request()
+            .brief("native and target language must differ")
+            .into());
+    }
+
+    let changes = UpdateUserProfile {
+        native_lang: Some(langs.native.clone()),
+        target_lang: Some(langs.target.clone()),
+        timezone: input.timezone,
+        leaderboard_opt_out: input.leaderboard_opt_out,
+        updated_at: Some(Utc::now()),
+        ..Default::default()
+    };
+    with_conn(move |conn| {
+        diesel::insert_into(archive_user_profiles::table)
+            .values(&NewUserProfile { user_id })
+            .on_conflict(archive_user_profiles::user_id)
+            .do_nothing()
+            .execute(conn)?;
+        diesel::update(archive_user_profiles::table)
+            .filter(archive_user_profiles::user_id.eq(user_id))
+            .set(&changes)
+            .execute(conn)
+    })
+    .await
+    .map_err(|_| StatusError::internal_server_error().brief("failed to update settings"))?;
+    if input.leaderboard_opt_out.is_some() {
+        leaderboard::invalidate();
+    }
+
+    let (timezone, leaderboard_opt_out) = get_profile_settings(user_id).await?;
+    json_ok(LearnSettings::new(langs, timezone, leaderboard_opt_out))
+}
@@ -0,0 +1,193 @@
+use std::path::PathBuf;
+
+use chrono::Utc;
+use diesel::prelude::*;
+use salvo::oapi::ToSchema;
+use salvo::oapi::extract::*;
+use salvo::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::config::AppConfig;
+use crate::db::schema::*;
+use crate::db::with_conn;
+use crate::learn::audio::save_audio_file;
+use crate::learn::shadowing::{
+    self, ShadowingDetails, ShadowingSource, ShadowingTrendPoint, TimedWord,
+};
+use crate::models::achievement::AchievementBadge;
+use crate::models::learn::{NewShadowingPractice, ShadowingPractice};
+use crate::routing::recording;
+use crate::{DepotExt, JsonResult, json_ok};
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateShadowingRequest {
+    source_type: ShadowingSource,
+    source_id: i64,
+    audio_base64: String,
+    /// wav by default
+    audio_format: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ShadowingResult {
+    #[serde(flatten)]
+    pub practice: ShadowingPractice,
+    /// Word timings of the reference audio
+    pub reference: Vec<TimedWord>,
+    /// Word timings of the recording
+    pub learner: Vec<TimedWord>,
+    pub comparison: ShadowingDetails,
+    pub unlocked: Vec<AchievementBadge>,
+}
+
+/// Score a recording made while shadowing a script turn or reading sentence
+#[endpoint(tags("Learn"))]
+pub async fn create_shadowing(
+    input: JsonBody<CreateShadowingRequest>,
+    depot: &mut Depot,
+) -> JsonResult<ShadowingResult> {
+    let user_id = depot.user_id()?;
+    let input = input.into_inner();
+
+    let audio_data = recording::decode_audio(&input.audio_base64)?;
+    let audio_format = recording::audio_format(input.audio_format)?;
+
+    let (source, source_id) = (input.source_type, input.source_id);
+    let (reference_text, reference_audio) =
+        with_conn(move |conn| shadowing::source_reference(conn, source, source_id))
+            .await
+            .map_err(|_| StatusError::internal_server_error().brief("failed to get source"))?
+            .ok_or_else(|| StatusError::not_found().brief("source not found"))?;
+    let reference_audio = reference_audio
+        .ok_or_else(|| StatusError::not_found().brief("no audio available for this source"))?;
+
+    let reference = reference_timings(reference_audio).await?;
+
+    let learner_result = recording::transcribe(audio_data.clone(), "en").await?;
+    let transcript = learner_result.text.trim().to_string();
+    if transcript.is_empty() {
+        return Err(StatusError::bad_request()
+            .brief("Could not transcribe audio - no speech detected")
+            .into());
+    }
+    let learner = shadowing::timed_words(learner_result.words.as_deref().unwrap_or_default());
+    let user_audio_path = save_audio_file(user_id, &audio_data, "shadow", &audio_format).await;
+
+    let scores = shadowing::score(&reference_text, &reference, &transcript, &learner);
+    let new_practice = NewShadowingPractice {
+        user_id,
+        source_type: source.as_str().to_owned(),
+        source_id,
+        reference_text,
+        user_audio_path,
+        transcript,
+        accuracy_score: scores.accuracy,
+        rhythm_score: scores.rhythm,
+        pause_score: scores.pause,
+        speed_ratio: scores.speed_ratio,
+        overall_score: scores.overall,
+        details: json!(scores.details),
+    };
+    let (practice, unlocked) =
+        with_conn(move |conn| shadowing::record(conn, source, &new_practice))
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to save shadowing practice: {:?}", e);
+                StatusError::internal_server_error().brief("failed to save shadowing practice")
+            })?;
+
+    json_ok(ShadowingResult {
+        practice,
+        reference,
+        learner,
+        comparison: scores.details,
+        unlocked,
+    })
+}
+
+/// Recent shadowing practices, optionally of one source
+#[endpoint(tags("Learn"))]
+pub async fn list_shadowing(
+    source_type: QueryParam<ShadowingSource, false>,
+    source_id: QueryParam<i64, false>,
+    limit: QueryParam<i64, false>,
+    depot: &mut Depot,
+) -> JsonResult<Vec<ShadowingPractice>> {
+    let user_id = depot.user_id()?;
+    let source_type = source_type.into_inner();
+    let source_id = source_id.into_inner();
+    let limit = limit.into_inner().unwrap_or(50).clamp(1, 200);
+
+    let practices = with_conn(move |conn| {
+        let mut query = learn_shadowing_practices::table
+            .filter(learn_shadowing_practices::user_id.eq(user_id))
+            .into_boxed();
+        if let Some(source_type) = source_type {
+            query = query.filter(learn_shadowing_practices::source_type.eq(source_type.as_str()));
+        }
+        if let Some(source_id) = source_id {
+            query = query.filter(learn_shadowing_practices::source_id.eq(source_id));
+        }
+        query
+            .order(learn_shadowing_practices::created_at.desc())
+            .limit(limit)
+            .load::<ShadowingPractice>(conn)
+    })
+    .await
+    .map_err(|_| {
+        StatusError::internal_server_error().brief("failed to list shadowing practices")
+    })?;
+    json_ok(practices)
+}
+
+/// Daily average scores over the last `days` days (30 by default)
+#[endpoint(tags("Learn"))]
+pub async fn shadowing_trend(
+    days: QueryParam<u64, false>,
+    depot: &mut Depot,
+) -> JsonResult<Vec<ShadowingTrendPoint>> {
+    let user_id = depot.user_id()?;
+    let days = days.into_inner().unwrap_or(30).clamp(1, 366);
+
+    let trend = with_conn(move |conn| shadowing::trend(conn, user_id, days, Utc::now()))
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to get shadowing trend: {:?}", e);
+            StatusError::internal_server_error().brief("failed to get shadowing trend")
+        })?;
+    json_ok(trend)
+}
+
+/// Word timings of a reference audio file, transcribed on first use
+async fn reference_timings(audio_path: String) -> Result<Vec<TimedWord>, StatusError> {
+    let path = audio_path.clone();
+    let cached = with_conn(move |conn| shadowing::reference_timings(conn, &path))
+        .await
+        .map_err(|_| StatusError::internal_server_error().brief("failed to get audio timings"))?;
+    if let Some(words) = cached {
+        return Ok(words);
+    }
+
+    let file_path = PathBuf::from(&AppConfig::get().space_path).join(&audio_path);
+    let audio_data = tokio::fs::read(&file_path).await.map_err(|e| {
+        tracing::warn!("Reference audio file {:?} unreadable: {:?}", file_path, e);
+        StatusError::not_found().brief("audio file not found")
+    })?;
+    let result = recording::transcribe(audio_data, "en").await?;
+    let words = shadowing::timed_words(result.words.as_deref().unwrap_or_default());
+    if words.is_empty() {
+        return Err(
+            StatusError::internal_server_error().brief("no word timings for the reference audio")
+        );
+    }
+
+    let transcript = result.text.trim().to_string();
+    let stored = words.clone();
+    with_conn(move |conn| {
+        shadowing::store_reference_timings(conn, &audio_path, &transcript, &stored)
+    })
+    .await
+    .map_err(|_| StatusError::internal_server_error().brief("failed to save audio timings"))?;
+    Ok(words)
+}
@@ -0,0 +1,56 @@
+use chrono::{NaiveDate, Utc};
+use salvo::oapi::extract::*;
+use salvo::prelude::*;
+
+use crate::config::AppConfig;
+use crate::db::with_conn;
+use crate::learn::streak::{self, StreakView};
+use crate::{DepotExt, JsonResult, json_ok};
+
+/// Longest calendar range returned at once
+const MAX_CALENDAR_DAYS: i64 = 366;
+
+/// Current streak with a calendar of studied and frozen days
+///
+/// `from` and `to` are local dates (YYYY-MM-DD), the last 30 days by default.
+#[endpoint(tags("Learn"))]
+pub async fn get_streak(
+    from: QueryParam<String, false>,
+    to: QueryParam<String, false>,
+    depot: &mut Depot,
+) -> JsonResult<StreakView> {
+    let user_id = depot.user_id()?;
+    let from = parse_date(from.into_inner())?;
+    let to = parse_date(to.into_inner())?;
+    if let (Some(from), Some(to)) = (from, to)
+        && (to < from || (to - from).num_days() >= MAX_CALENDAR_DAYS)
+    {
+        return Err(StatusError::bad_request()
+            .brief("invalid date range, at most 366 days")
+            .into());
+    }
+
+    let view = with_conn(move |conn| {
+        streak::streak_view(
+            conn,
+            user_id,
+            from,
+            to,
+            Utc::now(),
+            &AppConfig::get().streak,
+        )
+    })
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to load streak: {:?}", e);
+        StatusError::internal_server_error().brief("failed to load streak")
+    })?;
+    json_ok(view)
+}
+
+fn parse_date(value: Option<String>) -> Result<Option<NaiveDate>, StatusError> {
+    value
+        .map(|v| NaiveDate::parse_from_str(&v, "%Y-%m-%d"))
+        .transpose()
+        .map_err(|_| StatusError::bad_request().brief("invalid date format, use YYYY-MM-DD"))
+}
@@ -1,11 +1,15 @@
 use chrono::Utc;
 use diesel::prelude::*;
 use salvo::oapi::ToSchema;
+use salvo::oapi::extract::*;
 use salvo::prelude::*;
 use serde::{Deserialize, Serialize};
 
 use crate::db::schema::*;
 use crate::db::with_conn;
+use crate::learn::enrich::{self, WordDetails};
+use crate::learn::extract::{self, MAX_CONTEXT_CHARS, TextVocabulary};
+use crate::learn::stats::{self, Activity};
 use crate::models::learn::*;
 use crate::{AppResult, DepotExt, JsonResult, json_ok};
 
@@ -15,9 +19,49 @@ pub struct CreateVocabularyRequest {
     word_zh: Option<String>,
 }
 
+/// Longest text accepted for vocabulary extraction
+const MAX_TEXT_CHARS: usize = 50_000;
+/// Most words added at once
+const MAX_BULK_WORDS: usize = 200;
+
+#[derive(Deserialize, ToSchema)]
+pub struct ExtractVocabularyRequest {
+    text: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct BulkVocabularyWord {
+    word: String,
+    word_zh: Option<String>,
+    /// Sentence the word was met in
+    context: Option<String>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct BulkVocabularyRequest {
+    words: Vec<BulkVocabularyWord>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BulkVocabularyResponse {
+    /// Words that were not in the vocabulary yet
+    pub added: Vec<UserVocabulary>,
+    /// Words already in the vocabulary, left as they were
+    pub existing: Vec<String>,
+}
+
+/// A vocabulary word with the details of its dictionary entry
+#[derive(Serialize, ToSchema)]
+pub struct VocabularyItem {
+    #[serde(flatten)]
+    pub vocabulary: UserVocabulary,
+    #[serde(flatten)]
+    pub details: WordDetails,
+}
+
 #[derive(Serialize, ToSchema)]
 pub struct PaginatedVocabulary {
-    pub items: Vec<UserVocabulary>,
+    pub items: Vec<VocabularyItem>,
     pub total: i64,
     pub page: i64,
     pub per_page: i64,
@@ -31,7 +75,7 @@ pub async fn list_vocabulary(req: &mut Request, depot: &mut Depot) -> JsonResult
     let per_page = req.query::<i64>("per_page").unwrap_or(50).clamp(1, 200);
     let offset = (page - 1) * per_page;
 
-    let (items, total): (Vec<UserVocabulary>, i64) = with_conn(move |conn| {
+    let (items, total): (Vec<VocabularyItem>, i64) = with_conn(move |conn| {
         let mut query = learn_vocabularies::table
             .filter(learn_vocabularies::user_id.eq(user_id))
             .into_boxed();
@@ -62,6 +106,20 @@ pub async fn list_vocabulary(req: &mut Request, depot: &mut Depot) -> JsonResult
             .limit(per_page)
             .load::<UserVocabulary>(conn)?;
 
+        let word_ids: Vec<i64> = items.iter().filter_map(|v| v.word_id).collect();
+        let mut details = enrich::word_details(conn, &word_ids)?;
+        let items = items
+            .into_iter()
+            .map(|vocabulary| VocabularyItem {
+                details: vocabulary
+                    .word_id
+                    .and_then(|id| details.get_mut(&id))
+                    .map(std::mem::take)
+                    .unwrap_or_default(),
+                vocabulary,
+            })
+            .collect();
+
         Ok((items, total))
     })
     .await
@@ -98,20 +156,29 @@ pub async fn create_vocabulary(
         user_id,
         word: word.clone(),
         word_zh: word_zh.clone(),
+        context: None,
     };
 
     // Use upsert pattern: if word already exists for this user, just return the existing one
     let vocab: UserVocabulary = with_conn(move |conn| {
-        diesel::insert_into(learn_vocabularies::table)
+        let inserted = diesel::insert_into(learn_vocabularies::table)
             .values(&new_vocab)
             .on_conflict((learn_vocabularies::user_id, learn_vocabularies::word))
             .do_nothing()
             .execute(conn)?;
-
-        // Fetch the vocabulary (either newly created or existing)
-        learn_vocabularies::table
+        let existing = learn_vocabularies::table
             .filter(learn_vocabularies::user_id.eq(user_id))
             .filter(learn_vocabularies::word.eq(&word))
+            .first::<UserVocabulary>(conn)?;
+        if inserted == 0 {
+            return Ok(existing);
+        }
+
+        let now = Utc::now();
+        stats::record_activity(conn, user_id, Activity::NewWord, now)?;
+        enrich::enrich_vocabulary(conn, &[existing.id], now)?;
+        learn_vocabularies::table
+            .find(existing.id)
             .first::<UserVocabulary>(conn)
     })
     .await
@@ -170,10 +237,15 @@ pub async fn toggle_vocabulary(
                 user_id,
                 word: word.clone(),
                 word_zh: None,
+                context: None,
             };
-            diesel::insert_into(learn_vocabularies::table)
+            let id = diesel::insert_into(learn_vocabularies::table)
                 .values(&new_vocab)
-                .execute(conn)?;
+                .returning(learn_vocabularies::id)
+                .get_result::<i64>(conn)?;
+            let now = Utc::now();
+            stats::record_activity(conn, user_id, Activity::NewWord, now)?;
+            enrich::enrich_vocabulary(conn, &[id], now)?;
             Ok((true, word))
         }
     })
@@ -188,3 +260,113 @@ pub async fn toggle_vocabulary(
         added,
     })
 }
+
+/// Words of a pasted text classified as known, learning or new
+#[endpoint(tags("Learn"))]
+pub async fn extract_vocabulary(
+    input: JsonBody<ExtractVocabularyRequest>,
+    depot: &mut Depot,
+) -> JsonResult<TextVocabulary> {
+    let user_id = depot.user_id()?;
+    let text = input.into_inner().text;
+    if text.trim().is_empty() {
+        return Err(StatusError::bad_request().brief("text is required").into());
+    }
+    if text.chars().count() > MAX_TEXT_CHARS {
+        return Err(StatusError::bad_request()
+            .brief("text is too long, at most 50000 characters")
+            .into());
+    }
+
+    let vocabulary = with_conn(move |conn| extract::extract(conn, user_id, &text))
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to extract vocabulary: {:?}", e);
+            StatusError::internal_server_error().brief("failed to extract vocabulary")
+        })?;
+    json_ok(vocabulary)
+}
+
+/// Add several words at once, each with the sentence it was met in
+#[endpoint(tags("Learn"))]
+pub async fn bulk_add_vocabulary(
+    input: JsonBody<BulkVocabularyRequest>,
+    depot: &mut Depot,
+) -> JsonResult<BulkVocabularyResponse> {
+    let user_id = depot.user_id()?;
+    let words = input.into_inner().words;
+    if words.is_empty() || words.len() > MAX_BULK_WORDS {
+        return Err(StatusError::bad_request()
+            .brief("between 1 and 200 words are required")
+            .into());
+    }
+
+    let mut new_vocabs: Vec<NewUserVocabulary> = Vec::with_capacity(words.len());
+    for item in words {
+        let word = item.word.trim().to_lowercase();
+        if word.is_empty() {
+            return Err(StatusError::bad_request().brief("word is required").into());
+        }
+        if new_vocabs.iter().any(|v| v.word == word) {
+            continue;
+        }
+        new_vocabs.push(NewUserVocabulary {
+            user_id,
+            word,
+            word_zh: item.word_zh.filter(|w| !w.trim().is_empty()),
+            context: item
+                .context
+                .map(|c| extract::truncate(c.trim(), MAX_CONTEXT_CHARS))
+                .filter(|c| !c.is_empty()),
+        });
+    }
+
+    let response = with_conn(move |conn| {
+        conn.transaction(|conn| {
+            let now = Utc::now();
+            let mut added = Vec::new();
+            let mut existing = Vec::new();
+            for new_vocab in &new_vocabs {
+                let inserted = diesel::insert_into(learn_vocabularies::table)
+                    .values(new_vocab)
+                    .on_conflict((learn_vocabularies::user_id, learn_vocabularies::word))
+                    .do_nothing()
+                    .get_result::<UserVocabulary>(conn)
+                    .optional()?;
+                match inserted {
+                    Some(vocab) => {
+                        stats::record_activity(conn, user_id, Activity::NewWord, now)?;
+                        added.push(vocab);
+                    }
+                    None => {
+                        // Keep the first context of a word already in the vocabulary
+                        if let Some(context) = &new_vocab.context {
+                            diesel::update(
+                                learn_vocabularies::table
+                                    .filter(learn_vocabularies::user_id.eq(user_id))
+                                    .filter(learn_vocabularies::word.eq(&new_vocab.word))
+                                    .filter(learn_vocabularies::context.is_null()),
+                            )
+                            .set(learn_vocabularies::context.eq(context))
+                            .execute(conn)?;
+                        }
+                        existing.push(new_vocab.word.clone());
+                    }
+                }
+            }
+            let ids: Vec<i64> = added.iter().map(|v| v.id).collect();
+            enrich::enrich_vocabulary(conn, &ids, now)?;
+            let added = learn_vocabularies::table
+                .filter(learn_vocabularies::id.eq_any(&ids))
+                .order(learn_vocabularies::id.asc())
+                .load::<UserVocabulary>(conn)?;
+            Ok::<_, diesel::result::Error>(BulkVocabularyResponse { added, existing })
+        })
+    })
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to add vocabulary: {:?}", e);
+        StatusError::internal_server_error().brief("failed to add vocabulary")
+    })?;
+    json_ok(response)
+}
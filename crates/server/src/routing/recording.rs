@@ -0,0 +1,66 @@
+//! Recordings uploaded to the learning endpoints
+//!
+//! Clients send audio base64 encoded in the JSON body together with its file
+//! format; the audio is transcribed by the ASR of the configured provider.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use salvo::prelude::*;
+
+use crate::services::ai_provider::AsrResponse;
+use crate::services::create_provider_from_env;
+
+/// Decode a base64 recording, rejecting empty audio
+pub fn decode_audio(audio_base64: &str) -> Result<Vec<u8>, StatusError> {
+    let audio_data = BASE64
+        .decode(audio_base64)
+        .map_err(|_| StatusError::bad_request().brief("invalid base64 audio"))?;
+    if audio_data.is_empty() {
+        return Err(StatusError::bad_request().brief("audio data is empty"));
+    }
+    Ok(audio_data)
+}
+
+/// File extension the recording is saved with, wav by default
+///
+/// Only short alphanumeric formats are accepted since the format ends up in
+/// a file name.
+pub fn audio_format(format: Option<String>) -> Result<String, StatusError> {
+    let format = format.unwrap_or_else(|| "wav".to_string());
+    if format.is_empty() || format.len() > 8 || !format.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(StatusError::bad_request().brief("invalid audio format"));
+    }
+    Ok(format)
+}
+
+/// Transcribe a recording, `language` is the ASR hint such as "en"
+pub async fn transcribe(audio_data: Vec<u8>, language: &str) -> Result<AsrResponse, StatusError> {
+    let provider = create_provider_from_env()
+        .ok_or_else(|| StatusError::internal_server_error().brief("AI provider not configured"))?;
+    let asr = provider
+        .asr()
+        .ok_or_else(|| StatusError::internal_server_error().brief("ASR service not available"))?;
+    asr.transcribe(audio_data, Some(language))
+        .await
+        .map_err(|e| {
+            tracing::error!("{} ASR error: {:?}", provider.name(), e);
+            StatusError::internal_server_error().brief(e.to_string())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_audio_formats() {
+        assert_eq!(audio_format(None).unwrap(), "wav");
+        assert_eq!(audio_format(Some("webm".to_owned())).unwrap(), "webm");
+        assert!(audio_format(Some(String::new())).is_err());
+        assert!(audio_format(Some("../wav".to_owned())).is_err());
+        assert!(audio_format(Some("waveform1".to_owned())).is_err());
+        assert!(decode_audio("").is_err());
+        assert!(decode_audio("not base64!").is_err());
+        assert_eq!(decode_audio("AAE=").unwrap(), [0, 1]);
+    }
+}
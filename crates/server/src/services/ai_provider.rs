@@ -9,6 +9,8 @@ use async_trait::async_trait;
 use salvo::oapi::ToSchema;
 use serde::{Deserialize, Serialize};
 
+use super::language::LanguagePair;
+
 /// Error type for AI provider operations
 #[derive(Debug, thiserror::Error)]
 pub enum AiProviderError {
@@ -100,7 +102,8 @@ pub trait AsrService: Send + Sync {
     ///
     /// # Arguments
     /// * `audio_data` - Raw audio bytes (WAV or MP3 format)
-    /// * `language` - Optional language hint (e.g., "en", "zh", "auto")
+    /// * `language` - Optional language hint (e.g., "en", "zh", "auto"), or a
+    ///   comma separated list of candidates with the most likely first ("en,zh")
     async fn transcribe(
         &self,
         audio_data: Vec<u8>,
@@ -133,25 +136,104 @@ pub trait TtsService: Send + Sync {
     fn available_voices(&self) -> Vec<&'static str> {
         vec!["default"]
     }
+
+    /// Voice to use for text in the given language, `None` for the provider
+    /// default; fails for languages the provider can't speak
+    fn voice_for_language(&self, _language: &str) -> Result<Option<&'static str>, AiProviderError> {
+        Ok(None)
+    }
 }
 
-/// Structured AI response for English teaching
+/// Structured AI response for language tutoring
+///
+/// "target" is the language the user is learning, "native" the one they
+/// already speak, see [`LanguagePair`].
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StructuredChatResponse {
-    /// Language of user input: "en" | "zh" | "mix"
+    /// Language of user input: the target or native language code, or "mix"
     pub use_lang: String,
-    /// User text in English (original or translated)
-    pub original_en: String,
-    /// User text in Chinese (original or translated)
-    pub original_zh: String,
-    /// AI reply in English
-    pub reply_en: String,
-    /// AI reply in Chinese
-    pub reply_zh: String,
+    /// User text in the target language (original or translated)
+    pub original_target: String,
+    /// User text in the native language (original or translated)
+    pub original_native: String,
+    /// AI reply in the target language
+    pub reply_target: String,
+    /// AI reply translated to the native language
+    pub reply_native: String,
     /// Grammar/word choice issues found
     pub issues: Vec<TextIssue>,
 }
 
+impl StructuredChatResponse {
+    /// Parse the JSON object returned by a model
+    ///
+    /// Fences around the JSON are tolerated. If the content is not JSON at all
+    /// it is used as the target language reply and the user text is kept on
+    /// the side of the pair its script belongs to.
+    pub fn parse(content: &str, user_text: &str, langs: &LanguagePair) -> Self {
+        let json_str = content
+            .trim()
+            .trim_start_matches("```json")
+            .trim_start_matches("```")
+            .trim_end_matches("```")
+            .trim();
+
+        let user_lang = langs
+            .guess_lang(user_text)
+            .unwrap_or(&langs.target)
+            .to_owned();
+        let user_text_in = |lang: &str| {
+            if user_lang == lang {
+                user_text.to_owned()
+            } else {
+                String::new()
+            }
+        };
+
+        let structured: serde_json::Value = match serde_json::from_str(json_str) {
+            Ok(v) => v,
+            Err(e) => {
+                tracing::warn!(
+                    "Failed to parse structured response as JSON: {}, content: {}",
+                    e,
+                    content
+                );
+                return Self {
+                    original_target: user_text_in(&langs.target),
+                    original_native: user_text_in(&langs.native),
+                    use_lang: user_lang,
+                    reply_target: content.to_owned(),
+                    reply_native: String::new(),
+                    issues: vec![],
+                };
+            }
+        };
+
+        let field = |name: &str| {
+            structured[name]
+                .as_str()
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_owned())
+        };
+
+        let original_target =
+            field("original_target").unwrap_or_else(|| user_text_in(&langs.target));
+        let original_native =
+            field("original_native").unwrap_or_else(|| user_text_in(&langs.native));
+        let issues: Vec<TextIssue> =
+            serde_json::from_value(structured["issues"].clone()).unwrap_or_default();
+
+        Self {
+            use_lang: field("use_lang").unwrap_or_else(|| langs.target.clone()),
+            original_target,
+            original_native,
+            reply_target: field("reply_target").unwrap_or_default(),
+            reply_native: field("reply_native").unwrap_or_default(),
+            issues,
+        }
+    }
+}
+
 /// Text issue (grammar, word choice, or suggestion)
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct TextIssue {
@@ -162,10 +244,12 @@ pub struct TextIssue {
     pub original: String,
     /// Suggested correction
     pub suggested: String,
-    /// Explanation in English
-    pub description_en: String,
-    /// Explanation in Chinese
-    pub description_zh: String,
+    /// Explanation in the target language
+    #[serde(default)]
+    pub description_target: String,
+    /// Explanation in the native language
+    #[serde(default)]
+    pub description_native: String,
     /// Severity: low | medium | high
     pub severity: String,
     /// Start position in text (optional)
@@ -192,7 +276,7 @@ pub trait ChatService: Send + Sync {
         max_tokens: Option<u32>,
     ) -> Result<String, AiProviderError>;
 
-    /// Send chat completion with structured output (for English teaching)
+    /// Send chat completion with structured output (for language tutoring)
     ///
     /// This function combines user input analysis and AI reply generation in one call.
     /// It takes conversation history, analyzes only the last user message for issues,
@@ -202,11 +286,13 @@ pub trait ChatService: Send + Sync {
     /// * `messages` - Chat history (up to 100 recent messages for context)
     /// * `user_text` - The user's latest message text (to analyze for issues)
     /// * `system_prompt` - System prompt for the AI
+    /// * `langs` - Target and native language of the user
     async fn chat_structured(
         &self,
         messages: Vec<ChatMessage>,
         user_text: &str,
         system_prompt: &str,
+        langs: &LanguagePair,
     ) -> Result<StructuredChatResponse, AiProviderError>;
 }
 
@@ -347,3 +433,44 @@ impl ProviderConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_fenced_json() {
+        let content = r#"```json
+{"use_lang":"en","original_target":"I go there yesterday","original_native":"我昨天去了那里",
+ "reply_target":"Nice!","reply_native":"不错！",
+ "issues":[{"type":"grammar","original":"go","suggested":"went","severity":"low"}]}
+```"#;
+        let langs = LanguagePair::default();
+        let parsed = StructuredChatResponse::parse(content, "I go there yesterday", &langs);
+        assert_eq!(parsed.use_lang, "en");
+        assert_eq!(parsed.original_native, "我昨天去了那里");
+        assert_eq!(parsed.reply_native, "不错！");
+        assert_eq!(parsed.issues.len(), 1);
+        assert_eq!(parsed.issues[0].suggested, "went");
+    }
+
+    #[test]
+    fn keeps_plain_text_as_the_reply() {
+        let langs = LanguagePair::default();
+        let parsed = StructuredChatResponse::parse("Sounds fun!", "我去了公园", &langs);
+        assert_eq!(parsed.reply_target, "Sounds fun!");
+        assert_eq!(parsed.use_lang, "zh");
+        assert_eq!(parsed.original_native, "我去了公园");
+        assert_eq!(parsed.original_target, "");
+    }
+
+    #[test]
+    fn fills_missing_fields_from_the_user_text() {
+        let langs = LanguagePair::default();
+        let parsed = StructuredChatResponse::parse(r#"{"reply_target":"Hi"}"#, "hello", &langs);
+        assert_eq!(parsed.use_lang, "en");
+        assert_eq!(parsed.original_target, "hello");
+        assert_eq!(parsed.original_native, "");
+        assert!(parsed.issues.is_empty());
+    }
+}
@@ -10,6 +10,7 @@ use std::sync::Arc;
 use async_trait::async_trait;
 use outfox_doubao::Client as DoubaoSdkClient;
 use outfox_doubao::config::DoubaoConfig;
+use outfox_doubao::spec::asr::FlashRecognitionRequestArgs;
 use outfox_doubao::spec::chat::{
     ChatMessage as DoubaoChatMessage, CreateChatCompletionRequestArgs, ResponseFormat,
     ResponseFormatJsonSchema, ResponseFormatType,
@@ -20,8 +21,9 @@ use serde_json::json;
 
 use super::ai_provider::{
     AiProvider, AiProviderError, AsrResponse, AsrService, ChatMessage, ChatService,
-    StructuredChatResponse, TextIssue, TtsResponse, TtsService, WordTiming,
+    StructuredChatResponse, TtsResponse, TtsService, WordTiming,
 };
+use super::language::{LanguagePair, hinted_languages};
 
 const DEFAULT_CHAT_MODEL: &str = "doubao-1-5-pro-32k-250115";
 
@@ -102,20 +104,31 @@ impl AsrService for DoubaoClient {
     async fn transcribe(
         &self,
         audio_data: Vec<u8>,
-        _language: Option<&str>,
+        language: Option<&str>,
     ) -> Result<AsrResponse, AiProviderError> {
         let user_id = uuid::Uuid::new_v4().to_string();
 
+        let language = recognition_language(language);
         tracing::info!(
-            "Doubao ASR: transcribing {} bytes of audio",
-            audio_data.len()
+            "Doubao ASR: transcribing {} bytes of audio, language={:?}",
+            audio_data.len(),
+            language
         );
 
+        let mut request = FlashRecognitionRequestArgs::default();
+        request.audio_data(audio_data).user_id(user_id);
+        if let Some(language) = language {
+            request.language(language);
+        }
+        let request = request
+            .build()
+            .map_err(|e| AiProviderError::Config(e.to_string()))?;
+
         let response = self
             .client
             .asr()
             .recognition()
-            .flash_bytes(&audio_data, &user_id)
+            .flash(request)
             .await
             .map_err(|e| AiProviderError::Api(e.to_string()))?;
 
@@ -218,8 +231,31 @@ impl TtsService for DoubaoClient {
             "zh_male_wennuanahu_moon_bigtts",
             "zh_female_vv_uranus_bigtts",
             "en_male_adam_moon_bigtts",
+            "multi_female_shuangkuaisisi_moon_bigtts",
         ]
     }
+
+    fn voice_for_language(&self, language: &str) -> Result<Option<&'static str>, AiProviderError> {
+        match language {
+            // Bilingual voice, reads both English and Chinese naturally
+            "en" | "zh" => Ok(Some("zh_female_vv_uranus_bigtts")),
+            "ja" | "es" => Ok(Some("multi_female_shuangkuaisisi_moon_bigtts")),
+            _ => Err(AiProviderError::NotSupported(format!(
+                "Doubao has no voice for language {language}"
+            ))),
+        }
+    }
+}
+
+/// Locale to request from the flash recognition API, `None` to let it detect
+/// the language; its default model recognizes Chinese and English mixed, so a
+/// hint of both is left to it
+fn recognition_language(hint: Option<&str>) -> Option<&'static str> {
+    let languages = hinted_languages(hint);
+    if languages.len() > 1 && languages.iter().all(|l| matches!(l.code, "en" | "zh")) {
+        return None;
+    }
+    languages.first().map(|l| l.locale)
 }
 
 #[async_trait]
@@ -286,7 +322,10 @@ impl ChatService for DoubaoClient {
         messages: Vec<ChatMessage>,
         user_text: &str,
         system_prompt: &str,
+        langs: &LanguagePair,
     ) -> Result<StructuredChatResponse, AiProviderError> {
+        let target = langs.target_name();
+        let native = langs.native_name();
         let mut all_messages = vec![ChatMessage {
             role: "system".to_owned(),
             content: system_prompt.to_owned(),
@@ -312,23 +351,23 @@ impl ChatService for DoubaoClient {
             "properties": {
                 "use_lang": {
                     "type": "string",
-                    "description": "The language of the original text, either 'en' for English, 'zh' for Chinese, or 'mix' for mixed. ONLY contains issues if this value is 'en'."
+                    "description": format!("The language of the original text, either '{}' for {target}, '{}' for {native}, or 'mix' for mixed. ONLY contains issues if this value is '{}'.", langs.target, langs.native, langs.target)
                 },
-                "original_en": {
+                "original_target": {
                     "type": "string",
-                    "description": "The last user message or translation in English. If the user wrote in Chinese or mixed language, translate it to English here. ONLY English characters are allowed here; Chinese characters are not permitted."
+                    "description": format!("The last user message or translation in {target}. If the user wrote in {native} or mixed language, translate it to {target} here. ONLY {target} is allowed here.")
                 },
-                "original_zh": {
+                "original_native": {
                     "type": "string",
-                    "description": "The last user message or translation in Chinese. If the user wrote in English or mixed language, translate it to Chinese here. Chinese characters MUST BE present here."
+                    "description": format!("The last user message or translation in {native}. If the user wrote in {target} or mixed language, translate it to {native} here. It MUST be written in {native}.")
                 },
-                "reply_en": {
+                "reply_target": {
                     "type": "string",
-                    "description": "Your natural conversational response to the user in English. Keep it concise and encouraging."
+                    "description": format!("Your natural conversational response to the user in {target}. Keep it concise and encouraging.")
                 },
-                "reply_zh": {
+                "reply_native": {
                     "type": "string",
-                    "description": "Translation of your reply_en into Chinese."
+                    "description": format!("Translation of your reply_target into {native}.")
                 },
                 "issues": {
                     "type": "array",
@@ -349,13 +388,13 @@ impl ChatService for DoubaoClient {
                                 "type": "string",
                                 "description": "The corrected or better alternative"
                             },
-                            "description_en": {
+                            "description_target": {
                                 "type": "string",
-                                "description": "Explanation of the issue using simple English"
+                                "description": format!("Explanation of the issue using simple {target}")
                             },
-                            "description_zh": {
+                            "description_native": {
                                 "type": "string",
-                                "description": "Explanation of the issue using simple Chinese"
+                                "description": format!("Explanation of the issue using simple {native}")
                             },
                             "severity": {
                                 "type": "string",
@@ -371,12 +410,12 @@ impl ChatService for DoubaoClient {
                                 "description": "0-based character offset where issue ends, exclusive (null if unknown)"
                             }
                         },
-                        "required": ["type", "original", "suggested", "description_en", "description_zh", "severity"],
+                        "required": ["type", "original", "suggested", "description_target", "description_native", "severity"],
                         "additionalProperties": false
                     }
                 }
             },
-            "required": ["use_lang", "original_en", "original_zh", "reply_en", "reply_zh", "issues"],
+            "required": ["use_lang", "original_target", "original_native", "reply_target", "reply_native", "issues"],
             "additionalProperties": false
         });
         let request = CreateChatCompletionRequestArgs::default()
@@ -387,12 +426,12 @@ impl ChatService for DoubaoClient {
             .response_format(ResponseFormat {
                 format_type: ResponseFormatType::JsonObject,
                 json_schema: Some(ResponseFormatJsonSchema {
-                    name: "english_teacher_response".to_owned(),
+                    name: "language_tutor_response".to_owned(),
                     strict: Some(true),
                     schema: response_schema,
-                    description: Some(
-                        "Structured response for English learning with corrections".to_owned(),
-                    ),
+                    description: Some(format!(
+                        "Structured response for {target} learning with corrections"
+                    )),
                 }),
             })
             .build()
@@ -426,104 +465,23 @@ impl ChatService for DoubaoClient {
 
         tracing::debug!("Doubao Chat Structured response: {}", content);
 
-        // Parse JSON response
-        let json_str = content
-            .trim()
-            .trim_start_matches("```json")
-            .trim_start_matches("```")
-            .trim_end_matches("```")
-            .trim();
-
-        // Try to parse as JSON, with fallback for plain text responses
-        let structured: serde_json::Value = match serde_json::from_str(json_str) {
-            Ok(v) => v,
-            Err(e) => {
-                tracing::warn!(
-                    "Failed to parse structured response as JSON: {}, content: {}",
-                    e,
-                    content
-                );
-                // Fallback: detect if user_text is Chinese or English
-                let is_chinese = user_text
-                    .chars()
-                    .any(|c| c >= '\u{4e00}' && c <= '\u{9fff}');
-                return Ok(StructuredChatResponse {
-                    use_lang: if is_chinese { "zh" } else { "en" }.to_string(),
-                    original_en: if is_chinese {
-                        String::new()
-                    } else {
-                        user_text.to_string()
-                    },
-                    original_zh: if is_chinese {
-                        user_text.to_string()
-                    } else {
-                        String::new()
-                    },
-                    reply_en: content.clone(),
-                    reply_zh: String::new(),
-                    issues: vec![],
-                });
-            }
-        };
-
-        let use_lang = structured["use_lang"].as_str().unwrap_or("en").to_string();
-
-        // Detect if user_text is Chinese for fallback handling
-        let is_chinese = user_text
-            .chars()
-            .any(|c| c >= '\u{4e00}' && c <= '\u{9fff}');
-
-        let original_en = structured["original_en"]
-            .as_str()
-            .filter(|s| !s.is_empty())
-            .map(|s| s.to_string())
-            .unwrap_or_else(|| {
-                if is_chinese {
-                    String::new()
-                } else {
-                    user_text.to_string()
-                }
-            });
-        let original_zh = structured["original_zh"]
-            .as_str()
-            .filter(|s| !s.is_empty())
-            .map(|s| s.to_string())
-            .unwrap_or_else(|| {
-                if is_chinese {
-                    user_text.to_string()
-                } else {
-                    String::new()
-                }
-            });
-        let reply_en = structured["reply_en"].as_str().unwrap_or("").to_string();
-        let reply_zh = structured["reply_zh"].as_str().unwrap_or("").to_string();
+        let structured = StructuredChatResponse::parse(&content, user_text, langs);
 
         tracing::debug!(
-            "Doubao parsed: use_lang={}, original_en={}, original_zh={}, reply_en_len={}, reply_zh_len={}",
-            use_lang,
-            original_en,
-            original_zh,
-            reply_en.len(),
-            reply_zh.len()
+            "Doubao parsed: use_lang={}, original_target={}, original_native={}, reply_target_len={}, reply_native_len={}",
+            structured.use_lang,
+            structured.original_target,
+            structured.original_native,
+            structured.reply_target.len(),
+            structured.reply_native.len()
         );
-
-        let issues: Vec<TextIssue> =
-            serde_json::from_value(structured["issues"].clone()).unwrap_or_default();
-
         tracing::info!(
-            "Doubao Chat Structured: reply_en={} chars, issues={}",
-            reply_en.len(),
-            issues.len()
+            "Doubao Chat Structured: reply_target={} chars, issues={}",
+            structured.reply_target.len(),
+            structured.issues.len()
         );
 
-        Ok(StructuredChatResponse {
-            use_lang,
-            original_en,
-            original_zh,
-            reply_en,
-            reply_zh,
-            issues,
-        })
+        Ok(structured)
     }
 }
 
@@ -0,0 +1,277 @@
+//! Language metadata shared by the AI services
+//!
+//! Every learner has a native and a target language. Prompts, ASR hints and
+//! TTS voices look the languages up here instead of assuming English for
+//! Chinese speakers.
+
+use salvo::oapi::ToSchema;
+use serde::{Deserialize, Serialize};
+
+/// Writing system, used to guess which side of a pair a text is written in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Script {
+    Latin,
+    Han,
+    /// Kana mixed with Han characters
+    Japanese,
+    Hangul,
+    Cyrillic,
+}
+
+impl Script {
+    pub fn contains(self, c: char) -> bool {
+        let is_han = matches!(c, '\u{4e00}'..='\u{9fff}' | '\u{3400}'..='\u{4dbf}');
+        match self {
+            Script::Latin => c.is_ascii_alphabetic() || matches!(c, '\u{00c0}'..='\u{024f}'),
+            Script::Han => is_han,
+            Script::Japanese => is_han || matches!(c, '\u{3040}'..='\u{30ff}'),
+            Script::Hangul => matches!(c, '\u{ac00}'..='\u{d7af}' | '\u{1100}'..='\u{11ff}'),
+            Script::Cyrillic => matches!(c, '\u{0400}'..='\u{04ff}'),
+        }
+    }
+
+    /// Characters only this script uses, so a single one is enough to decide
+    fn is_distinctive(self, c: char) -> bool {
+        match self {
+            Script::Japanese => matches!(c, '\u{3040}'..='\u{30ff}'),
+            _ => self.contains(c),
+        }
+    }
+}
+
+/// A language learners can pick as native or target language
+#[derive(Debug)]
+pub struct Language {
+    /// ISO 639-1 code, also used as key in stored translations
+    pub code: &'static str,
+    /// Locale passed to speech recognition, e.g. "en-US"
+    pub locale: &'static str,
+    /// English name, used in prompts
+    pub name: &'static str,
+    /// Name in the language itself, for settings pages
+    pub native_name: &'static str,
+    pub script: Script,
+    /// Reply shown when the tutor fails to answer
+    pub apology: &'static str,
+}
+
+pub const LANGUAGES: &[Language] = &[
+    Language {
+        code: "en",
+        locale: "en-US",
+        name: "English",
+        native_name: "English",
+        script: Script::Latin,
+        apology: "I'm sorry, I couldn't process your input.",
+    },
+    Language {
+        code: "zh",
+        locale: "zh-CN",
+        name: "Chinese",
+        native_name: "中文",
+        script: Script::Han,
+        apology: "抱歉，我无法处理您的输入。",
+    },
+    Language {
+        code: "ja",
+        locale: "ja-JP",
+        name: "Japanese",
+        native_name: "日本語",
+        script: Script::Japanese,
+        apology: "申し訳ありません、入力を処理できませんでした。",
+    },
+    Language {
+        code: "ko",
+        locale: "ko-KR",
+        name: "Korean",
+        native_name: "한국어",
+        script: Script::Hangul,
+        apology: "죄송합니다. 입력을 처리할 수 없었습니다.",
+    },
+    Language {
+        code: "fr",
+        locale: "fr-FR",
+        name: "French",
+        native_name: "Français",
+        script: Script::Latin,
+        apology: "Désolé, je n'ai pas pu traiter votre message.",
+    },
+    Language {
+        code: "de",
+        locale: "de-DE",
+        name: "German",
+        native_name: "Deutsch",
+        script: Script::Latin,
+        apology: "Entschuldigung, ich konnte Ihre Eingabe nicht verarbeiten.",
+    },
+    Language {
+        code: "es",
+        locale: "es-ES",
+        name: "Spanish",
+        native_name: "Español",
+        script: Script::Latin,
+        apology: "Lo siento, no pude procesar tu mensaje.",
+    },
+    Language {
+        code: "it",
+        locale: "it-IT",
+        name: "Italian",
+        native_name: "Italiano",
+        script: Script::Latin,
+        apology: "Mi dispiace, non sono riuscito a elaborare il tuo messaggio.",
+    },
+    Language {
+        code: "pt",
+        locale: "pt-BR",
+        name: "Portuguese",
+        native_name: "Português",
+        script: Script::Latin,
+        apology: "Desculpe, não consegui processar sua mensagem.",
+    },
+    Language {
+        code: "ru",
+        locale: "ru-RU",
+        name: "Russian",
+        native_name: "Русский",
+        script: Script::Cyrillic,
+        apology: "Извините, я не смог обработать ваше сообщение.",
+    },
+];
+
+/// Find a supported language by its code
+pub fn find_language(code: &str) -> Option<&'static Language> {
+    LANGUAGES.iter().find(|l| l.code == code)
+}
+
+/// Languages of an ASR hint such as "en" or "en,zh", most likely first;
+/// "auto" and unknown codes are skipped
+pub fn hinted_languages(hint: Option<&str>) -> Vec<&'static Language> {
+    hint.into_iter()
+        .flat_map(|hint| hint.split(','))
+        .filter_map(|code| find_language(code.trim()))
+        .collect()
+}
+
+/// Native and target language of a learner
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct LanguagePair {
+    /// Language being learned, e.g. "en"
+    pub target: String,
+    /// Language the learner already speaks, e.g. "zh"
+    pub native: String,
+}
+
+impl Default for LanguagePair {
+    fn default() -> Self {
+        Self {
+            target: "en".to_owned(),
+            native: "zh".to_owned(),
+        }
+    }
+}
+
+impl LanguagePair {
+    pub fn new(target: impl Into<String>, native: impl Into<String>) -> Self {
+        Self {
+            target: target.into(),
+            native: native.into(),
+        }
+    }
+
+    /// English name of the target language, falls back to the code
+    pub fn target_name(&self) -> &str {
+        find_language(&self.target).map_or(self.target.as_str(), |l| l.name)
+    }
+
+    /// English name of the native language, falls back to the code
+    pub fn native_name(&self) -> &str {
+        find_language(&self.native).map_or(self.native.as_str(), |l| l.name)
+    }
+
+    /// Guess which language of the pair `text` is written in
+    ///
+    /// Only works when the two languages use different scripts; returns `None`
+    /// for pairs like French/English or when no letter is recognized.
+    pub fn guess_lang(&self, text: &str) -> Option<&str> {
+        let target = find_language(&self.target)?.script;
+        let native = find_language(&self.native)?.script;
+        if target == native {
+            return None;
+        }
+        // A letter only one of the scripts has decides, e.g. kana for Japanese
+        // against Chinese, which share the Han characters
+        let only =
+            |a: Script, b: Script| text.chars().any(|c| a.is_distinctive(c) && !b.contains(c));
+        if only(native, target) {
+            Some(self.native.as_str())
+        } else if only(target, native) {
+            Some(self.target.as_str())
+        } else if text.chars().any(|c| native.contains(c)) {
+            Some(self.native.as_str())
+        } else if text.chars().any(|c| target.contains(c)) {
+            Some(self.target.as_str())
+        } else {
+            None
+        }
+    }
+
+    /// Apology in the target language, in English when the language is unknown
+    pub fn target_apology(&self) -> &'static str {
+        find_language(&self.target).map_or(LANGUAGES[0].apology, |l| l.apology)
+    }
+
+    /// Apology in the native language, empty when the language is unknown
+    pub fn native_apology(&self) -> &'static str {
+        find_language(&self.native).map_or("", |l| l.apology)
+    }
+
+    /// ASR hint for free conversation: the learner may speak either language,
+    /// target first since that is what they are practicing
+    pub fn conversation_asr_hint(&self) -> String {
+        if self.target == self.native {
+            self.target.clone()
+        } else {
+            format!("{},{}", self.target, self.native)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guesses_the_side_by_script() {
+        let langs = LanguagePair::default();
+        assert_eq!(langs.guess_lang("I went to 北京"), Some("zh"));
+        assert_eq!(langs.guess_lang("I went home"), Some("en"));
+        assert_eq!(langs.guess_lang("123 !"), None);
+
+        let langs = LanguagePair::new("ja", "zh");
+        assert_eq!(langs.guess_lang("東京へ行きました"), Some("ja"));
+        assert_eq!(langs.guess_lang("我去了东京"), Some("zh"));
+
+        assert_eq!(LanguagePair::new("fr", "en").guess_lang("bonjour"), None);
+    }
+
+    #[test]
+    fn reads_asr_hints() {
+        let codes = |hint: Option<&str>| -> Vec<&'static str> {
+            hinted_languages(hint).iter().map(|l| l.code).collect()
+        };
+        assert_eq!(codes(Some("fr, zh")), ["fr", "zh"]);
+        assert_eq!(codes(Some("auto")), Vec::<&str>::new());
+        assert_eq!(codes(None), Vec::<&str>::new());
+        assert_eq!(hinted_languages(Some("ko"))[0].locale, "ko-KR");
+    }
+
+    #[test]
+    fn apologizes_in_the_pair_languages() {
+        let langs = LanguagePair::new("fr", "zh");
+        assert!(langs.target_apology().starts_with("Désolé"));
+        assert!(langs.native_apology().starts_with("抱歉"));
+        let unknown = LanguagePair::new("xx", "yy");
+        assert!(unknown.target_apology().starts_with("I'm sorry"));
+        assert_eq!(unknown.native_apology(), "");
+    }
+}
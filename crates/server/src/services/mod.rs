@@ -9,6 +9,7 @@
 
 pub mod ai_provider;
 pub mod doubao;
+pub mod language;
 pub mod zhipu;
 
 use std::sync::Arc;
@@ -19,6 +20,7 @@ pub use ai_provider::{
 };
 use async_trait::async_trait;
 pub use doubao::DoubaoClient;
+pub use language::{LanguagePair, find_language};
 pub use zhipu::ZhipuClient;
 
 /// Combined AI Provider that mixes services from different providers.
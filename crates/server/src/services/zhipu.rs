@@ -18,8 +18,9 @@ use outfox_zhipu::spec::tts::CreateSpeechRequest;
 
 use super::ai_provider::{
     AiProvider, AiProviderError, AsrResponse, AsrService, ChatMessage, ChatService,
-    StructuredChatResponse, TextIssue, TtsResponse, TtsService,
+    StructuredChatResponse, TtsResponse, TtsService,
 };
+use super::language::{LanguagePair, hinted_languages};
 
 const DEFAULT_CHAT_MODEL: &str = "glm-4-flash";
 
@@ -72,16 +73,18 @@ impl AsrService for ZhipuClient {
     async fn transcribe(
         &self,
         audio_data: Vec<u8>,
-        _language: Option<&str>,
+        language: Option<&str>,
     ) -> Result<AsrResponse, AiProviderError> {
         tracing::info!(
-            "Zhipu ASR: transcribing {} bytes of audio",
-            audio_data.len()
+            "Zhipu ASR: transcribing {} bytes of audio, language hint={:?}",
+            audio_data.len(),
+            language
         );
 
         let audio = AudioInput::from_bytes(audio_data, "audio.wav");
         let request = outfox_zhipu::spec::asr::CreateTranscriptionRequest {
             audio: Some(audio),
+            prompt: transcription_prompt(language),
             ..Default::default()
         };
 
@@ -106,6 +109,12 @@ impl AsrService for ZhipuClient {
     }
 }
 
+/// GLM-ASR has no language parameter, the hint goes into the context prompt
+fn transcription_prompt(hint: Option<&str>) -> Option<String> {
+    let names: Vec<&str> = hinted_languages(hint).iter().map(|l| l.name).collect();
+    (!names.is_empty()).then(|| format!("The speaker talks in {}.", names.join(" or ")))
+}
+
 #[async_trait]
 impl TtsService for ZhipuClient {
     async fn synthesize(
@@ -206,6 +215,7 @@ impl ChatService for ZhipuClient {
         messages: Vec<ChatMessage>,
         user_text: &str,
         system_prompt: &str,
+        langs: &LanguagePair,
     ) -> Result<StructuredChatResponse, AiProviderError> {
         let mut all_messages = vec![ChatMessage {
             role: "system".to_owned(),
@@ -246,105 +256,23 @@ impl ChatService for ZhipuClient {
 
         tracing::debug!("Zhipu Chat Structured response: {}", content);
 
-        // Parse JSON response
-        let json_str = content
-            .trim()
-            .trim_start_matches("```json")
-            .trim_start_matches("```")
-            .trim_end_matches("```")
-            .trim();
-
-        println!("Parsed JSON string: {}", json_str);
-
-        let structured: serde_json::Value = match serde_json::from_str(json_str) {
-            Ok(v) => v,
-            Err(e) => {
-                tracing::warn!(
-                    "Failed to parse structured response as JSON: {}, content: {}",
-                    e,
-                    content
-                );
-                // Fallback: detect if user_text is Chinese or English
-                let is_chinese = user_text
-                    .chars()
-                    .any(|c| c >= '\u{4e00}' && c <= '\u{9fff}');
-                return Ok(StructuredChatResponse {
-                    use_lang: if is_chinese { "zh" } else { "en" }.to_string(),
-                    original_en: if is_chinese {
-                        String::new()
-                    } else {
-                        user_text.to_string()
-                    },
-                    original_zh: if is_chinese {
-                        user_text.to_string()
-                    } else {
-                        String::new()
-                    },
-                    reply_en: content.clone(),
-                    reply_zh: String::new(),
-                    issues: vec![],
-                });
-            }
-        };
-
-        let use_lang = structured["use_lang"].as_str().unwrap_or("en").to_string();
-
-        // Detect if user_text is Chinese for fallback handling
-        let is_chinese = user_text
-            .chars()
-            .any(|c| c >= '\u{4e00}' && c <= '\u{9fff}');
-
-        let original_en = structured["original_en"]
-            .as_str()
-            .filter(|s| !s.is_empty())
-            .map(|s| s.to_string())
-            .unwrap_or_else(|| {
-                if is_chinese {
-                    String::new()
-                } else {
-                    user_text.to_string()
-                }
-            });
-        let original_zh = structured["original_zh"]
-            .as_str()
-            .filter(|s| !s.is_empty())
-            .map(|s| s.to_string())
-            .unwrap_or_else(|| {
-                if is_chinese {
-                    user_text.to_string()
-                } else {
-                    String::new()
-                }
-            });
-        let reply_en = structured["reply_en"].as_str().unwrap_or("").to_string();
-        let reply_zh = structured["reply_zh"].as_str().unwrap_or("").to_string();
+        let structured = StructuredChatResponse::parse(&content, user_text, langs);
 
         tracing::debug!(
-            "Zhipu parsed: use_lang={}, original_en={}, original_zh={}, reply_en_len={}, reply_zh_len={}",
-            use_lang,
-            original_en,
-            original_zh,
-            reply_en.len(),
-            reply_zh.len()
+            "Zhipu parsed: use_lang={}, original_target={}, original_native={}, reply_target_len={}, reply_native_len={}",
+            structured.use_lang,
+            structured.original_target,
+            structured.original_native,
+            structured.reply_target.len(),
+            structured.reply_native.len()
         );
-
-        let issues: Vec<TextIssue> =
-            serde_json::from_value(structured["issues"].clone()).unwrap_or_default();
-
         tracing::info!(
-            "Zhipu Chat Structured: reply_en={} chars, issues={}",
-            reply_en.len(),
-            issues.len()
+            "Zhipu Chat Structured: reply_target={} chars, issues={}",
+            structured.reply_target.len(),
+            structured.issues.len()
         );
 
-        Ok(StructuredChatResponse {
-            use_lang,
-            original_en,
-            original_zh,
-            reply_en,
-            reply_zh,
-            issues,
-        })
+        Ok(structured)
     }
 }
 
@@ -10,6 +10,8 @@ use crate::db::schema::*;
 use crate::models::{NewPassword, NewUser, User};
 use crate::{AppResult, diesel_exists};
 
+pub mod personal_data;
+
 #[derive(Debug, Deserialize)]
 pub struct JwtClaims {
     pub sub: String,
@@ -0,0 +1,568 @@
+//! Everything stored about a user: export and account deletion
+//!
+//! [`PERSONAL_TABLES`] and [`USER_DIRS`] list where a user's data lives. An
+//! export writes one JSON file per table and the user's files into a ZIP
+//! archive, built by a background job. Deletion waits out a grace period and
+//! then removes all rows in one transaction. The user's directories are moved
+//! aside first and only removed once the transaction has committed, or moved
+//! back if it fails, so rows and files go together. Shared rows that merely
+//! record the user as owner or author, listed in [`AUTHOR_COLUMNS`], are kept
+//! with the column set to NULL.
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Duration, Utc};
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Jsonb};
+use salvo::oapi::ToSchema;
+use serde::Serialize;
+use serde_json::{Value, json};
+
+use self::zip::ZipWriter;
+use crate::AppResult;
+use crate::db::schema::*;
+use crate::models::{AccountDeletion, DataExport};
+
+mod zip;
+
+/// Tables with rows of a user and the column pointing at the user
+///
+/// Rows are deleted in this order, so children come before their parents.
+pub const PERSONAL_TABLES: &[(&str, &str)] = &[
+    ("learn_review_session_items", "user_id"),
+    ("learn_review_sessions", "user_id"),
+    ("learn_quiz_questions", "user_id"),
+    ("learn_quizzes", "user_id"),
+    ("learn_placement_items", "user_id"),
+    ("learn_placement_tests", "user_id"),
+    ("learn_achievements", "user_id"),
+    ("learn_chat_issues", "user_id"),
+    ("learn_chat_turns", "user_id"),
+    ("learn_chats", "user_id"),
+    ("learn_daily_stats", "user_id"),
+    ("learn_goals", "user_id"),
+    ("learn_issue_words", "user_id"),
+    ("learn_practices", "user_id"),
+    ("learn_read_practices", "user_id"),
+    ("learn_read_progress", "user_id"),
+    ("learn_reset_snapshots", "user_id"),
+    ("learn_review_logs", "user_id"),
+    ("learn_script_progress", "user_id"),
+    ("learn_shadowing_practices", "user_id"),
+    ("learn_streak_freezes", "user_id"),
+    ("learn_suggestions", "user_id"),
+    ("learn_vocabularies", "user_id"),
+    ("learn_write_practices", "user_id"),
+    ("archive_league_members", "user_id"),
+    ("archive_user_achievements", "user_id"),
+    ("archive_user_profiles", "user_id"),
+    ("archive_user_xp_history", "user_id"),
+    ("asset_contexts", "user_id"),
+    ("dict_searched_words", "user_id"),
+    ("oauth_identities", "user_id"),
+    ("base_role_users", "user_id"),
+    ("base_data_exports", "user_id"),
+    ("base_users", "id"),
+];
+
+/// Tables deleted with the account but left out of exports, they hold
+/// credentials
+const SECRET_TABLES: &[(&str, &str)] = &[
+    ("auth_codes", "user_id"),
+    ("base_passwords", "user_id"),
+    ("learn_report_shares", "user_id"),
+];
+
+/// Columns naming a user on rows that are not theirs: roles, dictionary
+/// content and accounts they created or edited
+///
+/// Deleting these rows would take shared data with the account, so the
+/// column is set to NULL instead. They are not exported either.
+pub const AUTHOR_COLUMNS: &[(&str, &str)] = &[
+    ("base_roles", "owner_id"),
+    ("base_roles", "created_by"),
+    ("base_roles", "updated_by"),
+    ("base_users", "created_by"),
+    ("base_users", "updated_by"),
+    ("dict_dictionaries", "created_by"),
+    ("dict_dictionaries", "updated_by"),
+    ("dict_images", "created_by"),
+    ("dict_import_batches", "created_by"),
+    ("dict_words", "created_by"),
+    ("dict_words", "updated_by"),
+];
+
+/// Directories under the space path holding a user's files
+pub const USER_DIRS: &[&str] = &["learn/audios", "uploads/avatars", "exports"];
+
+/// Where directories wait while their rows are being deleted
+const DELETING_DIR: &str = "deleting";
+
+/// Rows and files of a user
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct DataInventory {
+    /// Row count per table
+    pub rows: BTreeMap<String, i64>,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+#[derive(QueryableByName)]
+struct JsonRows {
+    #[diesel(sql_type = Jsonb)]
+    rows: Value,
+}
+
+#[derive(QueryableByName)]
+struct RowCount {
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+/// Directory of a user under `base`, e.g. `learn/audios/42`
+fn user_dir(space_path: &Path, base: &str, user_id: i64) -> PathBuf {
+    space_path.join(base).join(user_id.to_string())
+}
+
+/// Files below `dir` with their path relative to it
+fn list_files(dir: &Path) -> Vec<(PathBuf, String)> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        let Ok(entries) = fs::read_dir(&current) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else if let Ok(relative) = path.strip_prefix(dir) {
+                let relative = relative.to_string_lossy().replace('\\', "/");
+                files.push((path, relative));
+            }
+        }
+    }
+    files.sort_by(|a, b| a.1.cmp(&b.1));
+    files
+}
+
+/// What would be removed with the account
+pub fn inventory(
+    conn: &mut PgConnection,
+    user_id: i64,
+    space_path: &Path,
+) -> QueryResult<DataInventory> {
+    let mut inventory = DataInventory::default();
+    for (table, column) in SECRET_TABLES.iter().chain(PERSONAL_TABLES) {
+        let count = diesel::sql_query(format!(
+            "SELECT COUNT(*) AS count FROM {table} WHERE {column} = $1"
+        ))
+        .bind::<BigInt, _>(user_id)
+        .get_result::<RowCount>(conn)?
+        .count;
+        if count > 0 {
+            inventory.rows.insert(table.to_string(), count);
+        }
+    }
+    for base in USER_DIRS {
+        for (path, _) in list_files(&user_dir(space_path, base, user_id)) {
+            inventory.files += 1;
+            inventory.bytes += fs::metadata(&path).map_or(0, |m| m.len());
+        }
+    }
+    Ok(inventory)
+}
+
+// ============================================================================
+// Export
+// ============================================================================
+
+/// Queue an export, or return the one already waiting
+pub fn request_export(conn: &mut PgConnection, user_id: i64) -> QueryResult<DataExport> {
+    let pending = base_data_exports::table
+        .filter(base_data_exports::user_id.eq(user_id))
+        .filter(base_data_exports::status.eq("pending"))
+        .first::<DataExport>(conn)
+        .optional()?;
+    if let Some(export) = pending {
+        return Ok(export);
+    }
+    diesel::insert_into(base_data_exports::table)
+        .values(base_data_exports::user_id.eq(user_id))
+        .get_result::<DataExport>(conn)
+}
+
+/// Build every pending export
+///
+/// Returns the number of exports processed; failures are recorded on the
+/// export rather than returned.
+pub fn process_exports(
+    conn: &mut PgConnection,
+    space_path: &Path,
+    ttl_days: i64,
+    now: DateTime<Utc>,
+) -> QueryResult<usize> {
+    let pending = base_data_exports::table
+        .filter(base_data_exports::status.eq("pending"))
+        .order(base_data_exports::created_at.asc())
+        .load::<DataExport>(conn)?;
+    for export in &pending {
+        let file_path = format!("exports/{}/colang-export-{}.zip", export.user_id, export.id);
+        let result = write_archive(conn, export.user_id, space_path, &file_path, now);
+        let finished_at = Utc::now();
+        let expires_at = finished_at + Duration::days(ttl_days);
+        match result {
+            Ok(size) => diesel::update(base_data_exports::table.find(export.id))
+                .set((
+                    base_data_exports::status.eq("ready"),
+                    base_data_exports::file_path.eq(Some(file_path.as_str())),
+                    base_data_exports::file_size.eq(Some(size as i64)),
+                    base_data_exports::finished_at.eq(Some(finished_at)),
+                    base_data_exports::expires_at.eq(Some(expires_at)),
+                ))
+                .execute(conn)?,
+            Err(e) => {
+                tracing::error!("Failed to export data of user {}: {}", export.user_id, e);
+                diesel::update(base_data_exports::table.find(export.id))
+                    .set((
+                        base_data_exports::status.eq("failed"),
+                        base_data_exports::error.eq(Some(e.to_string())),
+                        base_data_exports::finished_at.eq(Some(finished_at)),
+                    ))
+                    .execute(conn)?
+            }
+        };
+    }
+    Ok(pending.len())
+}
+
+/// Remove the archives of exports past their expiry
+pub fn expire_exports(
+    conn: &mut PgConnection,
+    space_path: &Path,
+    now: DateTime<Utc>,
+) -> QueryResult<usize> {
+    let expired = base_data_exports::table
+        .filter(base_data_exports::status.eq("ready"))
+        .filter(base_data_exports::expires_at.le(now))
+        .load::<DataExport>(conn)?;
+    for export in &expired {
+        if let Some(path) = &export.file_path
+            && let Err(e) = fs::remove_file(space_path.join(path))
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            tracing::warn!("Failed to remove expired export {}: {}", path, e);
+        }
+        diesel::update(base_data_exports::table.find(export.id))
+            .set((
+                base_data_exports::status.eq("expired"),
+                base_data_exports::file_path.eq(None::<String>),
+            ))
+            .execute(conn)?;
+    }
+    Ok(expired.len())
+}
+
+/// Write the archive of a user to `file_path` and return its size
+fn write_archive(
+    conn: &mut PgConnection,
+    user_id: i64,
+    space_path: &Path,
+    file_path: &str,
+    now: DateTime<Utc>,
+) -> AppResult<u64> {
+    let path = space_path.join(file_path);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    // Written next to its final name so a half-written archive is never served
+    let partial = path.with_extension("zip.part");
+    let result = write_entries(conn, user_id, space_path, &partial, now);
+    if result.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    result?;
+    fs::rename(&partial, &path)?;
+    Ok(fs::metadata(&path)?.len())
+}
+
+fn write_entries(
+    conn: &mut PgConnection,
+    user_id: i64,
+    space_path: &Path,
+    partial: &Path,
+    now: DateTime<Utc>,
+) -> AppResult<()> {
+    let mut zip = ZipWriter::new(BufWriter::new(File::create(partial)?), now.naive_utc());
+
+    let mut counts = BTreeMap::new();
+    conn.build_transaction().read_only().repeatable_read().run(|conn| {
+        for (table, column) in PERSONAL_TABLES {
+            let rows = diesel::sql_query(format!(
+                "SELECT COALESCE(jsonb_agg(t), '[]'::jsonb) AS rows FROM {table} t WHERE t.{column} = $1"
+            ))
+            .bind::<BigInt, _>(user_id)
+            .get_result::<JsonRows>(conn)?
+            .rows;
+            counts.insert(*table, rows.as_array().map_or(0, Vec::len));
+            zip.add(
+                &format!("data/{table}.json"),
+                &serde_json::to_vec_pretty(&rows)?,
+            )?;
+        }
+        Ok::<_, crate::AppError>(())
+    })?;
+
+    let mut files = 0;
+    for base in USER_DIRS.iter().filter(|base| **base != "exports") {
+        for (file, relative) in list_files(&user_dir(space_path, base, user_id)) {
+            zip.add(&format!("files/{base}/{relative}"), &fs::read(&file)?)?;
+            files += 1;
+        }
+    }
+
+    let manifest = json!({
+        "user_id": user_id,
+        "exported_at": now,
+        "tables": counts,
+        "files": files,
+    });
+    zip.add("manifest.json", &serde_json::to_vec_pretty(&manifest)?)?;
+    zip.finish()?.into_inner().map_err(|e| e.into_error())?;
+    Ok(())
+}
+
+// ============================================================================
+// Deletion
+// ============================================================================
+
+/// Deletion request of a user that is neither cancelled nor done
+pub fn pending_deletion(
+    conn: &mut PgConnection,
+    user_id: i64,
+) -> QueryResult<Option<AccountDeletion>> {
+    base_account_deletions::table
+        .filter(base_account_deletions::user_id.eq(user_id))
+        .filter(base_account_deletions::cancelled_at.is_null())
+        .filter(base_account_deletions::completed_at.is_null())
+        .first::<AccountDeletion>(conn)
+        .optional()
+}
+
+/// Schedule the deletion of an account after the grace period
+pub fn request_deletion(
+    conn: &mut PgConnection,
+    user_id: i64,
+    grace_days: i64,
+    now: DateTime<Utc>,
+) -> QueryResult<AccountDeletion> {
+    if let Some(deletion) = pending_deletion(conn, user_id)? {
+        return Ok(deletion);
+    }
+    diesel::insert_into(base_account_deletions::table)
+        .values((
+            base_account_deletions::user_id.eq(user_id),
+            base_account_deletions::requested_at.eq(now),
+            base_account_deletions::scheduled_for.eq(now + Duration::days(grace_days)),
+        ))
+        .get_result::<AccountDeletion>(conn)
+}
+
+/// Cancel a scheduled deletion, `None` when there is none
+pub fn cancel_deletion(
+    conn: &mut PgConnection,
+    user_id: i64,
+    now: DateTime<Utc>,
+) -> QueryResult<Option<AccountDeletion>> {
+    diesel::update(
+        base_account_deletions::table
+            .filter(base_account_deletions::user_id.eq(user_id))
+            .filter(base_account_deletions::cancelled_at.is_null())
+            .filter(base_account_deletions::completed_at.is_null()),
+    )
+    .set(base_account_deletions::cancelled_at.eq(Some(now)))
+    .get_result::<AccountDeletion>(conn)
+    .optional()
+}
+
+/// Delete the accounts whose grace period is over
+///
+/// Returns the number of accounts deleted; a failed deletion is logged and
+/// retried on the next run.
+pub fn purge_due_accounts(
+    conn: &mut PgConnection,
+    space_path: &Path,
+    now: DateTime<Utc>,
+) -> QueryResult<usize> {
+    let due = base_account_deletions::table
+        .filter(base_account_deletions::cancelled_at.is_null())
+        .filter(base_account_deletions::completed_at.is_null())
+        .filter(base_account_deletions::scheduled_for.le(now))
+        .load::<AccountDeletion>(conn)?;
+    let mut deleted = 0;
+    for deletion in due {
+        match delete_account(conn, &deletion, space_path, now) {
+            Ok(removed) => {
+                tracing::info!(
+                    "Deleted account {}: {} rows, {} files",
+                    deletion.user_id,
+                    removed.rows.values().sum::<i64>(),
+                    removed.files
+                );
+                deleted += 1;
+            }
+            Err(e) => tracing::error!("Failed to delete account {}: {}", deletion.user_id, e),
+        }
+    }
+    Ok(deleted)
+}
+
+/// Move staged directories back to where they were
+fn restore(staged: &[(PathBuf, PathBuf)]) {
+    for (original, moved) in staged {
+        if let Err(e) = fs::rename(moved, original) {
+            tracing::error!("Failed to restore {:?} to {:?}: {}", moved, original, e);
+        }
+    }
+}
+
+/// Remove all rows and files of the user of a deletion request
+fn delete_account(
+    conn: &mut PgConnection,
+    deletion: &AccountDeletion,
+    space_path: &Path,
+    now: DateTime<Utc>,
+) -> AppResult<DataInventory> {
+    let user_id = deletion.user_id;
+    let mut removed = DataInventory::default();
+
+    // Move the directories aside, remembering where they came from
+    let staging = space_path
+        .join(DELETING_DIR)
+        .join(format!("{user_id}-{}", deletion.id));
+    let mut staged: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for base in USER_DIRS {
+        let dir = user_dir(space_path, base, user_id);
+        if !dir.exists() {
+            continue;
+        }
+        for (path, _) in list_files(&dir) {
+            removed.files += 1;
+            removed.bytes += fs::metadata(&path).map_or(0, |m| m.len());
+        }
+        let moved = staging.join(base);
+        let result = moved
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::rename(&dir, &moved));
+        if let Err(e) = result {
+            restore(&staged);
+            return Err(e.into());
+        }
+        staged.push((dir, moved));
+    }
+
+    let result = conn.transaction(|conn| {
+        for (table, column) in AUTHOR_COLUMNS {
+            diesel::sql_query(format!(
+                "UPDATE {table} SET {column} = NULL WHERE {column} = $1"
+            ))
+            .bind::<BigInt, _>(user_id)
+            .execute(conn)?;
+        }
+        for (table, column) in SECRET_TABLES.iter().chain(PERSONAL_TABLES) {
+            let count = diesel::sql_query(format!("DELETE FROM {table} WHERE {column} = $1"))
+                .bind::<BigInt, _>(user_id)
+                .execute(conn)?;
+            if count > 0 {
+                removed.rows.insert(table.to_string(), count as i64);
+            }
+        }
+        diesel::update(base_account_deletions::table.find(deletion.id))
+            .set((
+                base_account_deletions::completed_at.eq(Some(now)),
+                base_account_deletions::removed.eq(Some(json!(removed))),
+            ))
+            .execute(conn)
+    });
+    if let Err(e) = result {
+        restore(&staged);
+        return Err(e.into());
+    }
+
+    if staging.exists()
+        && let Err(e) = fs::remove_dir_all(&staging)
+    {
+        // The rows are gone, the files can only be removed by hand now
+        tracing::error!(
+            "Failed to remove files of deleted account {}: {}",
+            user_id,
+            e
+        );
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_table_is_listed_once() {
+        let mut tables: Vec<&str> = SECRET_TABLES
+            .iter()
+            .chain(PERSONAL_TABLES)
+            .map(|(table, _)| *table)
+            .collect();
+        let count = tables.len();
+        tables.sort_unstable();
+        tables.dedup();
+        assert_eq!(tables.len(), count);
+        // The user row goes last so nothing is left pointing at a missing user
+        assert_eq!(PERSONAL_TABLES.last(), Some(&("base_users", "id")));
+    }
+
+    /// Columns of the schema holding a user id, as (table, column)
+    fn user_columns(schema: &str) -> Vec<(String, String)> {
+        let mut columns = Vec::new();
+        let mut table = None;
+        for line in schema.lines().map(str::trim) {
+            if let Some(header) = line.strip_suffix(" {")
+                && header.contains(" (")
+            {
+                table = header.split_whitespace().next();
+            } else if line == "}" {
+                table = None;
+            } else if let (Some(table), Some((column, _))) = (table, line.split_once(" -> "))
+                && matches!(column, "user_id" | "owner_id" | "created_by" | "updated_by")
+            {
+                columns.push((table.to_owned(), column.to_owned()));
+            }
+        }
+        columns
+    }
+
+    #[test]
+    fn every_user_column_is_handled() {
+        let columns = user_columns(include_str!("../db/schema.rs"));
+        assert!(columns.contains(&("learn_chats".to_owned(), "user_id".to_owned())));
+        for (table, column) in &columns {
+            let entry = (table.as_str(), column.as_str());
+            let handled = SECRET_TABLES.contains(&entry)
+                || PERSONAL_TABLES.contains(&entry)
+                || AUTHOR_COLUMNS.contains(&entry)
+                // The request is kept as the record of the deletion
+                || entry == ("base_account_deletions", "user_id");
+            assert!(
+                handled,
+                "{table}.{column} is left pointing at deleted users"
+            );
+        }
+        for entry in AUTHOR_COLUMNS {
+            assert!(columns.contains(&(entry.0.to_owned(), entry.1.to_owned())));
+        }
+    }
+}
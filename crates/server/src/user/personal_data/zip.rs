@@ -0,0 +1,193 @@
+//! Minimal ZIP writer storing files uncompressed
+//!
+//! Exports are JSON and already compressed audio, so entries are stored
+//! rather than deflated. Each entry is a local header followed by its bytes;
+//! the central directory at the end lists them. Archives are limited to the
+//! classic format: under 4 GiB and 65535 entries.
+
+use std::io::{self, Write};
+
+use chrono::{Datelike, NaiveDateTime, Timelike};
+
+/// UTF-8 file names
+const FLAG_UTF8: u16 = 1 << 11;
+/// ZIP 2.0, the first version with stored entries and directories
+const VERSION: u16 = 20;
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, byte| {
+        CRC_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+struct Entry {
+    name: String,
+    crc: u32,
+    size: u32,
+    offset: u32,
+}
+
+pub struct ZipWriter<W: Write> {
+    out: W,
+    offset: u64,
+    dos_time: u16,
+    dos_date: u16,
+    entries: Vec<Entry>,
+}
+
+fn too_large() -> io::Error {
+    io::Error::other("archive exceeds the ZIP size limits")
+}
+
+impl<W: Write> ZipWriter<W> {
+    /// Start an archive whose entries are all dated `modified`
+    pub fn new(out: W, modified: NaiveDateTime) -> Self {
+        // DOS dates start in 1980 and have a two second resolution
+        let year = modified.year().clamp(1980, 2107) as u16;
+        Self {
+            out,
+            offset: 0,
+            dos_time: ((modified.hour() as u16) << 11)
+                | ((modified.minute() as u16) << 5)
+                | (modified.second() as u16 / 2),
+            dos_date: ((year - 1980) << 9)
+                | ((modified.month() as u16) << 5)
+                | modified.day() as u16,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Add a file; `name` uses forward slashes for directories
+    pub fn add(&mut self, name: &str, data: &[u8]) -> io::Result<()> {
+        let size = u32::try_from(data.len()).map_err(|_| too_large())?;
+        let offset = u32::try_from(self.offset).map_err(|_| too_large())?;
+        if self.entries.len() == usize::from(u16::MAX) {
+            return Err(too_large());
+        }
+        let crc = crc32(data);
+
+        let mut header = Vec::with_capacity(30 + name.len());
+        header.extend_from_slice(&0x0403_4b50u32.to_le_bytes());
+        header.extend_from_slice(&VERSION.to_le_bytes());
+        header.extend_from_slice(&FLAG_UTF8.to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes()); // stored
+        header.extend_from_slice(&self.dos_time.to_le_bytes());
+        header.extend_from_slice(&self.dos_date.to_le_bytes());
+        header.extend_from_slice(&crc.to_le_bytes());
+        header.extend_from_slice(&size.to_le_bytes()); // compressed size
+        header.extend_from_slice(&size.to_le_bytes());
+        header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes()); // extra field length
+        header.extend_from_slice(name.as_bytes());
+        self.out.write_all(&header)?;
+        self.out.write_all(data)?;
+
+        self.offset += header.len() as u64 + data.len() as u64;
+        self.entries.push(Entry {
+            name: name.to_owned(),
+            crc,
+            size,
+            offset,
+        });
+        Ok(())
+    }
+
+    /// Write the central directory and return the underlying writer
+    pub fn finish(mut self) -> io::Result<W> {
+        let directory_offset = u32::try_from(self.offset).map_err(|_| too_large())?;
+        let mut directory = Vec::new();
+        for entry in &self.entries {
+            directory.extend_from_slice(&0x0201_4b50u32.to_le_bytes());
+            directory.extend_from_slice(&VERSION.to_le_bytes()); // made by
+            directory.extend_from_slice(&VERSION.to_le_bytes()); // needed
+            directory.extend_from_slice(&FLAG_UTF8.to_le_bytes());
+            directory.extend_from_slice(&0u16.to_le_bytes());
+            directory.extend_from_slice(&self.dos_time.to_le_bytes());
+            directory.extend_from_slice(&self.dos_date.to_le_bytes());
+            directory.extend_from_slice(&entry.crc.to_le_bytes());
+            directory.extend_from_slice(&entry.size.to_le_bytes());
+            directory.extend_from_slice(&entry.size.to_le_bytes());
+            directory.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+            // Extra field and comment lengths, disk number, internal and
+            // external attributes
+            directory.extend_from_slice(&[0u8; 12]);
+            directory.extend_from_slice(&entry.offset.to_le_bytes());
+            directory.extend_from_slice(entry.name.as_bytes());
+        }
+        let directory_size = u32::try_from(directory.len()).map_err(|_| too_large())?;
+        let count = self.entries.len() as u16;
+
+        directory.extend_from_slice(&0x0605_4b50u32.to_le_bytes());
+        directory.extend_from_slice(&[0u8; 4]); // disk numbers
+        directory.extend_from_slice(&count.to_le_bytes());
+        directory.extend_from_slice(&count.to_le_bytes());
+        directory.extend_from_slice(&directory_size.to_le_bytes());
+        directory.extend_from_slice(&directory_offset.to_le_bytes());
+        directory.extend_from_slice(&0u16.to_le_bytes()); // comment length
+        self.out.write_all(&directory)?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    #[test]
+    fn checksums_match_the_reference() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn writes_a_readable_directory() {
+        let modified = NaiveDate::from_ymd_opt(2024, 5, 17)
+            .unwrap()
+            .and_hms_opt(13, 45, 10)
+            .unwrap();
+        let mut zip = ZipWriter::new(Vec::new(), modified);
+        zip.add("a.json", b"[]").unwrap();
+        zip.add("audio/b.wav", b"RIFF").unwrap();
+        let bytes = zip.finish().unwrap();
+
+        let end = &bytes[bytes.len() - 22..];
+        assert_eq!(&end[..4], &0x0605_4b50u32.to_le_bytes());
+        assert_eq!(u16::from_le_bytes([end[10], end[11]]), 2);
+        let directory_offset = u32::from_le_bytes(end[16..20].try_into().unwrap()) as usize;
+        // Fixed part of a directory record is 46 bytes, then the name
+        for offset in [directory_offset, directory_offset + 46 + "a.json".len()] {
+            assert_eq!(&bytes[offset..offset + 4], &0x0201_4b50u32.to_le_bytes());
+        }
+        // The second entry starts after the first header and its data
+        let second = 30 + "a.json".len() + 2;
+        assert_eq!(&bytes[second..second + 4], &0x0403_4b50u32.to_le_bytes());
+        assert_eq!(&bytes[second + 30..second + 41], b"audio/b.wav");
+        // 2024-05-17 13:45:10
+        assert_eq!(&bytes[10..14], &[0xA5, 0x6D, 0xB1, 0x58]);
+    }
+}